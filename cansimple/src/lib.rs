@@ -1,6 +1,15 @@
 #![doc = include_str!("../README.md")]
 #![cfg_attr(not(test), no_std)]
 
+// the `Arbitrary` derive needs std, fuzzing runs with it anyway
+#[cfg(all(feature = "arbitrary", not(test)))]
+extern crate std;
+
+pub mod message;
+pub mod view;
+
+pub use message::{DecodeError, EncodeError, Message};
+
 /// 11-bit identifier mask.
 const ID_MASK: u16 = 0x7FF;
 
@@ -41,22 +50,203 @@ impl Id {
         (self.0 & 0x1F) as u8
     }
 
+    /// Command identifier as a [`Command`], [`None`] if unknown.
+    pub fn known_command(&self) -> Option<Command> {
+        Command::try_from(self.command()).ok()
+    }
+
     /// Node identifier.
     pub fn node(&self) -> u8 {
         (self.0 >> 5) as u8
     }
 }
 
+/// Any 11-bit identifier.
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Id {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Self(u.int_in_range(0..=ID_MASK)?))
+    }
+}
+
+/// ODrive CANSimple command.
+///
+/// [Reference](https://docs.odriverobotics.com/v/latest/manual/can-protocol.html#messages)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[repr(u8)]
+pub enum Command {
+    GetVersion = 0x00,
+    Heartbeat = 0x01,
+    Estop = 0x02,
+    GetError = 0x03,
+    RxSdo = 0x04,
+    TxSdo = 0x05,
+    Address = 0x06,
+    SetAxisState = 0x07,
+    GetEncoderEstimates = 0x09,
+    SetControllerMode = 0x0b,
+    SetInputPos = 0x0c,
+    SetInputVel = 0x0d,
+    SetInputTorque = 0x0e,
+    SetLimits = 0x0f,
+    SetTrajVelLimit = 0x11,
+    SetTrajAccelLimits = 0x12,
+    SetTrajInertia = 0x13,
+    GetIq = 0x14,
+    GetTemperature = 0x15,
+    Reboot = 0x16,
+    GetBusVoltageCurrent = 0x17,
+    ClearErrors = 0x18,
+    SetAbsolutePosition = 0x19,
+    SetPosGain = 0x1a,
+    SetVelGains = 0x1b,
+    GetTorques = 0x1c,
+    GetPowers = 0x1d,
+    EnterDfuMode = 0x1f,
+}
+
+impl TryFrom<u8> for Command {
+    type Error = u8;
+
+    /// Returns the value back if it is not a known command.
+    fn try_from(value: u8) -> Result<Self, u8> {
+        use Command::*;
+
+        Ok(match value {
+            0x00 => GetVersion,
+            0x01 => Heartbeat,
+            0x02 => Estop,
+            0x03 => GetError,
+            0x04 => RxSdo,
+            0x05 => TxSdo,
+            0x06 => Address,
+            0x07 => SetAxisState,
+            0x09 => GetEncoderEstimates,
+            0x0b => SetControllerMode,
+            0x0c => SetInputPos,
+            0x0d => SetInputVel,
+            0x0e => SetInputTorque,
+            0x0f => SetLimits,
+            0x11 => SetTrajVelLimit,
+            0x12 => SetTrajAccelLimits,
+            0x13 => SetTrajInertia,
+            0x14 => GetIq,
+            0x15 => GetTemperature,
+            0x16 => Reboot,
+            0x17 => GetBusVoltageCurrent,
+            0x18 => ClearErrors,
+            0x19 => SetAbsolutePosition,
+            0x1a => SetPosGain,
+            0x1b => SetVelGains,
+            0x1c => GetTorques,
+            0x1d => GetPowers,
+            0x1f => EnterDfuMode,
+            _ => return Err(value),
+        })
+    }
+}
+
+impl From<Command> for u8 {
+    fn from(command: Command) -> u8 {
+        command as u8
+    }
+}
+
+/// Acceptance filter of CANSimple identifiers.
+///
+/// An identifier is accepted when `id & mask == self.id & mask`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Filter {
+    pub id: Id,
+    /// 11-bit mask of the compared identifier bits.
+    pub mask: u16,
+}
+
+impl Filter {
+    /// Mask selecting the node bits.
+    const NODE_MASK: u16 = 0x7e0;
+    /// Mask selecting the command bits.
+    const COMMAND_MASK: u16 = 0x01f;
+
+    /// Accept every command from or to `node`.
+    ///
+    /// Will return [`None`] if `node` is > 63.
+    pub fn node(node: u8) -> Option<Self> {
+        Some(Self {
+            id: Id::new(node, 0)?,
+            mask: Self::NODE_MASK,
+        })
+    }
+
+    /// Accept `command` from or to any node.
+    pub fn command(command: Command) -> Self {
+        Self {
+            id: Id(command as u16),
+            mask: Self::COMMAND_MASK,
+        }
+    }
+
+    /// Accept `command` from or to `node` only.
+    ///
+    /// Will return [`None`] if `node` is > 63.
+    pub fn node_command(node: u8, command: Command) -> Option<Self> {
+        Some(Self {
+            id: Id::new(node, command.into())?,
+            mask: ID_MASK,
+        })
+    }
+
+    /// Accept SDO responses of any node.
+    pub fn sdo_responses() -> Self {
+        Self::command(Command::TxSdo)
+    }
+
+    /// Returns true if `id` passes this filter.
+    pub fn matches(&self, id: Id) -> bool {
+        (id.0 ^ self.id.0) & self.mask == 0
+    }
+}
+
 impl From<embedded_can::StandardId> for Id {
     fn from(value: embedded_can::StandardId) -> Self {
         Id(value.as_raw())
     }
 }
 
+impl TryFrom<embedded_can::Id> for Id {
+    type Error = embedded_can::ExtendedId;
+
+    /// Returns extended identifiers back, CANSimple only uses standard
+    /// identifiers.
+    fn try_from(value: embedded_can::Id) -> Result<Self, Self::Error> {
+        match value {
+            embedded_can::Id::Standard(id) => Ok(id.into()),
+            embedded_can::Id::Extended(id) => Err(id),
+        }
+    }
+}
+
+impl TryFrom<u16> for Id {
+    type Error = u16;
+
+    /// Returns the value back if it is larger than 11 bits, unlike
+    /// [`Id::from_raw`] which masks it.
+    fn try_from(value: u16) -> Result<Self, u16> {
+        if value <= ID_MASK {
+            Ok(Self(value))
+        } else {
+            Err(value)
+        }
+    }
+}
+
 impl From<Id> for embedded_can::StandardId {
     fn from(id: Id) -> Self {
-        // [`simplecan::Id`] ensures id is masked to the correct number of bits.
-        embedded_can::StandardId::new(id.as_raw()).unwrap()
+        // [`Id`] is masked to 11 bits so the fallback is never used.
+        embedded_can::StandardId::new(id.as_raw()).unwrap_or(embedded_can::StandardId::ZERO)
     }
 }
 
@@ -78,6 +268,55 @@ mod tests {
         assert_eq!(id.command(), 9);
     }
 
+    #[test]
+    fn commands() {
+        let id = Id::new(1, Command::SetInputPos.into()).unwrap();
+        assert_eq!(id.command(), 0x0c);
+        assert_eq!(id.known_command(), Some(Command::SetInputPos));
+        assert_eq!(Id::new(1, 0x08).unwrap().known_command(), None);
+        for raw in 0..32 {
+            if let Ok(command) = Command::try_from(raw) {
+                assert_eq!(u8::from(command), raw);
+            }
+        }
+    }
+
+    #[test]
+    fn filters() {
+        let node = Filter::node(3).unwrap();
+        assert_eq!((node.id.as_raw(), node.mask), (0x060, 0x7e0));
+        assert!(node.matches(Id::new(3, 0x01).unwrap()));
+        assert!(!node.matches(Id::new(4, 0x01).unwrap()));
+        assert_eq!(Filter::node(64), None);
+
+        let sdo = Filter::sdo_responses();
+        assert_eq!((sdo.id.as_raw(), sdo.mask), (0x005, 0x01f));
+        assert!(sdo.matches(Id::new(63, 0x05).unwrap()));
+        assert!(!sdo.matches(Id::new(63, 0x04).unwrap()));
+
+        let one = Filter::node_command(3, Command::Heartbeat).unwrap();
+        assert!(one.matches(Id::new(3, 0x01).unwrap()));
+        assert!(!one.matches(Id::new(2, 0x01).unwrap()));
+        assert!(!one.matches(Id::new(3, 0x02).unwrap()));
+    }
+
+    #[test]
+    fn embedded_can_interop() {
+        let id = Id::new(1, 9).unwrap();
+        let standard = embedded_can::StandardId::from(id);
+        assert_eq!(standard.as_raw(), 0x029);
+        assert_eq!(Id::try_from(embedded_can::Id::from(id)), Ok(id));
+
+        let extended = embedded_can::ExtendedId::new(0x029).unwrap();
+        assert_eq!(
+            Id::try_from(embedded_can::Id::Extended(extended)),
+            Err(extended)
+        );
+
+        assert_eq!(Id::try_from(0x7ff), Ok(Id::from_raw(0x7ff)));
+        assert_eq!(Id::try_from(0x800), Err(0x800));
+    }
+
     #[test]
     fn make_identifier() {
         let id = Id::new(1, 9).unwrap();
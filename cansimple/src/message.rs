@@ -0,0 +1,704 @@
+//! Typed ODrive messages.
+//!
+//! [`Message::decode`] parses the data of a frame by its command and
+//! [`Message::encode`] writes it. Remote
+//! frames carry no data and are requests for the message of their command.
+//! Fields are kept raw, errors and states as the integers on the wire.
+
+use crate::{Command, Id};
+use core::fmt;
+
+/// Data of an ODrive frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum Message {
+    GetVersion {
+        protocol_version: u8,
+        hw_version_major: u8,
+        hw_version_minor: u8,
+        hw_version_variant: u8,
+        fw_version_major: u8,
+        fw_version_minor: u8,
+        fw_version_revision: u8,
+        fw_version_unreleased: bool,
+    },
+    Heartbeat {
+        axis_error: u32,
+        axis_state: u8,
+        procedure_result: u8,
+        trajectory_done: bool,
+    },
+    Estop,
+    GetError {
+        active_errors: u32,
+        disarm_reason: u32,
+    },
+    /// SDO request, `opcode` 0 reads and 1 writes `value`.
+    RxSdo {
+        opcode: u8,
+        endpoint: u16,
+        value: [u8; 4],
+    },
+    /// SDO response.
+    TxSdo {
+        endpoint: u16,
+        value: [u8; 4],
+    },
+    Address {
+        node: u8,
+        /// 48-bit serial number.
+        serial_number: u64,
+    },
+    SetAxisState {
+        axis_requested_state: u32,
+    },
+    GetEncoderEstimates {
+        /// Position in rev.
+        position: f32,
+        /// Velocity in rev/s.
+        velocity: f32,
+    },
+    SetControllerMode {
+        control_mode: u32,
+        input_mode: u32,
+    },
+    SetInputPos {
+        /// Position in rev.
+        position: f32,
+        /// Velocity feed forward in 0.001 rev/s by default.
+        velocity_ff: i16,
+        /// Torque feed forward in 0.001 Nm by default.
+        torque_ff: i16,
+    },
+    SetInputVel {
+        /// Velocity in rev/s.
+        velocity: f32,
+        /// Torque feed forward in Nm.
+        torque_ff: f32,
+    },
+    SetInputTorque {
+        /// Torque in Nm.
+        torque: f32,
+    },
+    SetLimits {
+        /// Velocity limit in rev/s.
+        velocity: f32,
+        /// Current limit in amps.
+        current: f32,
+    },
+    SetTrajVelLimit {
+        /// Velocity limit in rev/s.
+        velocity: f32,
+    },
+    SetTrajAccelLimits {
+        /// Acceleration limit in rev/s^2.
+        acceleration: f32,
+        /// Deceleration limit in rev/s^2.
+        deceleration: f32,
+    },
+    SetTrajInertia {
+        /// Inertia in Nm/(rev/s^2).
+        inertia: f32,
+    },
+    GetIq {
+        /// Current setpoint in amps.
+        setpoint: f32,
+        /// Measured current in amps.
+        measured: f32,
+    },
+    GetTemperature {
+        /// FET temperature in °C.
+        fet: f32,
+        /// Motor temperature in °C.
+        motor: f32,
+    },
+    /// `action` 0 reboots, 1 saves the configuration, 2 erases it and 3
+    /// enters DFU mode.
+    Reboot {
+        action: u8,
+    },
+    GetBusVoltageCurrent {
+        /// Bus voltage in volts.
+        voltage: f32,
+        /// Bus current in amps.
+        current: f32,
+    },
+    ClearErrors {
+        identify: bool,
+    },
+    SetAbsolutePosition {
+        /// Position in rev.
+        position: f32,
+    },
+    SetPosGain {
+        /// Gain in (rev/s)/rev.
+        gain: f32,
+    },
+    SetVelGains {
+        /// Gain in Nm/(rev/s).
+        gain: f32,
+        /// Integrator gain in Nm/rev.
+        integrator_gain: f32,
+    },
+    GetTorques {
+        /// Torque target in Nm.
+        target: f32,
+        /// Torque estimate in Nm.
+        estimate: f32,
+    },
+    GetPowers {
+        /// Electrical power in watts.
+        electrical: f32,
+        /// Mechanical power in watts.
+        mechanical: f32,
+    },
+    EnterDfuMode,
+}
+
+/// Data that is not a [`Message`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DecodeError {
+    UnknownCommand(u8),
+    /// The data is shorter than the message.
+    TooShort {
+        expected: usize,
+        actual: usize,
+    },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::UnknownCommand(command) => write!(f, "unknown command {command:#04x}"),
+            DecodeError::TooShort { expected, actual } => {
+                write!(f, "message data too short: {actual} < {expected}")
+            }
+        }
+    }
+}
+
+impl core::error::Error for DecodeError {}
+
+/// Message that can not be encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum EncodeError {
+    /// The node is larger than 63.
+    InvalidNode(u8),
+    /// The buffer is shorter than the message.
+    BufferTooShort { expected: usize, actual: usize },
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodeError::InvalidNode(node) => write!(f, "node {node} out of range"),
+            EncodeError::BufferTooShort { expected, actual } => {
+                write!(f, "buffer too short: {actual} < {expected}")
+            }
+        }
+    }
+}
+
+impl core::error::Error for EncodeError {}
+
+/// Length of the data of `command` in bytes.
+pub(crate) fn data_len(command: Command) -> usize {
+    match command {
+        Command::Estop | Command::EnterDfuMode => 0,
+        Command::Reboot | Command::ClearErrors => 1,
+        Command::SetAxisState
+        | Command::SetInputTorque
+        | Command::SetTrajVelLimit
+        | Command::SetTrajInertia
+        | Command::SetAbsolutePosition
+        | Command::SetPosGain => 4,
+        Command::Address => 7,
+        _ => 8,
+    }
+}
+
+impl Message {
+    /// Decode the data of a frame with identifier `id`.
+    ///
+    /// Bytes beyond the message are ignored, as some gateways pad frames to
+    /// eight bytes.
+    pub fn decode(id: Id, data: &[u8]) -> Result<Self, DecodeError> {
+        let command = Command::try_from(id.command()).map_err(DecodeError::UnknownCommand)?;
+        let len = data_len(command);
+        if data.len() < len {
+            return Err(DecodeError::TooShort {
+                expected: len,
+                actual: data.len(),
+            });
+        }
+
+        let u32_at =
+            |at: usize| u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]);
+        let f32_at = |at: usize| f32::from_bits(u32_at(at));
+        let value = || [data[4], data[5], data[6], data[7]];
+        Ok(match command {
+            Command::GetVersion => Message::GetVersion {
+                protocol_version: data[0],
+                hw_version_major: data[1],
+                hw_version_minor: data[2],
+                hw_version_variant: data[3],
+                fw_version_major: data[4],
+                fw_version_minor: data[5],
+                fw_version_revision: data[6],
+                fw_version_unreleased: data[7] == 1,
+            },
+            Command::Heartbeat => Message::Heartbeat {
+                axis_error: u32_at(0),
+                axis_state: data[4],
+                procedure_result: data[5],
+                trajectory_done: data[6] != 0,
+            },
+            Command::Estop => Message::Estop,
+            Command::GetError => Message::GetError {
+                active_errors: u32_at(0),
+                disarm_reason: u32_at(4),
+            },
+            Command::RxSdo => Message::RxSdo {
+                opcode: data[0],
+                endpoint: u16::from_le_bytes([data[1], data[2]]),
+                value: value(),
+            },
+            Command::TxSdo => Message::TxSdo {
+                endpoint: u16::from_le_bytes([data[1], data[2]]),
+                value: value(),
+            },
+            Command::Address => {
+                let mut serial_number = [0; 8];
+                serial_number[..6].copy_from_slice(&data[1..7]);
+                Message::Address {
+                    node: data[0],
+                    serial_number: u64::from_le_bytes(serial_number),
+                }
+            }
+            Command::SetAxisState => Message::SetAxisState {
+                axis_requested_state: u32_at(0),
+            },
+            Command::GetEncoderEstimates => Message::GetEncoderEstimates {
+                position: f32_at(0),
+                velocity: f32_at(4),
+            },
+            Command::SetControllerMode => Message::SetControllerMode {
+                control_mode: u32_at(0),
+                input_mode: u32_at(4),
+            },
+            Command::SetInputPos => Message::SetInputPos {
+                position: f32_at(0),
+                velocity_ff: i16::from_le_bytes([data[4], data[5]]),
+                torque_ff: i16::from_le_bytes([data[6], data[7]]),
+            },
+            Command::SetInputVel => Message::SetInputVel {
+                velocity: f32_at(0),
+                torque_ff: f32_at(4),
+            },
+            Command::SetInputTorque => Message::SetInputTorque { torque: f32_at(0) },
+            Command::SetLimits => Message::SetLimits {
+                velocity: f32_at(0),
+                current: f32_at(4),
+            },
+            Command::SetTrajVelLimit => Message::SetTrajVelLimit {
+                velocity: f32_at(0),
+            },
+            Command::SetTrajAccelLimits => Message::SetTrajAccelLimits {
+                acceleration: f32_at(0),
+                deceleration: f32_at(4),
+            },
+            Command::SetTrajInertia => Message::SetTrajInertia { inertia: f32_at(0) },
+            Command::GetIq => Message::GetIq {
+                setpoint: f32_at(0),
+                measured: f32_at(4),
+            },
+            Command::GetTemperature => Message::GetTemperature {
+                fet: f32_at(0),
+                motor: f32_at(4),
+            },
+            Command::Reboot => Message::Reboot { action: data[0] },
+            Command::GetBusVoltageCurrent => Message::GetBusVoltageCurrent {
+                voltage: f32_at(0),
+                current: f32_at(4),
+            },
+            Command::ClearErrors => Message::ClearErrors {
+                identify: data[0] != 0,
+            },
+            Command::SetAbsolutePosition => Message::SetAbsolutePosition {
+                position: f32_at(0),
+            },
+            Command::SetPosGain => Message::SetPosGain { gain: f32_at(0) },
+            Command::SetVelGains => Message::SetVelGains {
+                gain: f32_at(0),
+                integrator_gain: f32_at(4),
+            },
+            Command::GetTorques => Message::GetTorques {
+                target: f32_at(0),
+                estimate: f32_at(4),
+            },
+            Command::GetPowers => Message::GetPowers {
+                electrical: f32_at(0),
+                mechanical: f32_at(4),
+            },
+            Command::EnterDfuMode => Message::EnterDfuMode,
+        })
+    }
+
+    /// Encode the message for `node` into `buf`, returning the identifier
+    /// and the length of the data.
+    ///
+    /// Fails if `node` is larger than 63 or `buf` is shorter than
+    /// [`Message::data_len`].
+    pub fn encode(&self, node: u8, buf: &mut [u8]) -> Result<(Id, usize), EncodeError> {
+        let command = self.command();
+        let id = Id::new(node, command.into()).ok_or(EncodeError::InvalidNode(node))?;
+        let len = data_len(command);
+        let actual = buf.len();
+        let data = buf.get_mut(..len).ok_or(EncodeError::BufferTooShort {
+            expected: len,
+            actual,
+        })?;
+        data.fill(0);
+
+        let mut put = |at: usize, bytes: &[u8]| data[at..at + bytes.len()].copy_from_slice(bytes);
+        match *self {
+            Message::GetVersion {
+                protocol_version,
+                hw_version_major,
+                hw_version_minor,
+                hw_version_variant,
+                fw_version_major,
+                fw_version_minor,
+                fw_version_revision,
+                fw_version_unreleased,
+            } => put(
+                0,
+                &[
+                    protocol_version,
+                    hw_version_major,
+                    hw_version_minor,
+                    hw_version_variant,
+                    fw_version_major,
+                    fw_version_minor,
+                    fw_version_revision,
+                    fw_version_unreleased as u8,
+                ],
+            ),
+            Message::Heartbeat {
+                axis_error,
+                axis_state,
+                procedure_result,
+                trajectory_done,
+            } => {
+                put(0, &axis_error.to_le_bytes());
+                put(4, &[axis_state, procedure_result, trajectory_done as u8]);
+            }
+            Message::Estop | Message::EnterDfuMode => {}
+            Message::GetError {
+                active_errors,
+                disarm_reason,
+            } => {
+                put(0, &active_errors.to_le_bytes());
+                put(4, &disarm_reason.to_le_bytes());
+            }
+            Message::RxSdo {
+                opcode,
+                endpoint,
+                value,
+            } => {
+                put(0, &[opcode]);
+                put(1, &endpoint.to_le_bytes());
+                put(4, &value);
+            }
+            Message::TxSdo { endpoint, value } => {
+                put(1, &endpoint.to_le_bytes());
+                put(4, &value);
+            }
+            Message::Address {
+                node,
+                serial_number,
+            } => {
+                put(0, &[node]);
+                put(1, &serial_number.to_le_bytes()[..6]);
+            }
+            Message::SetAxisState {
+                axis_requested_state,
+            } => put(0, &axis_requested_state.to_le_bytes()),
+            Message::SetControllerMode {
+                control_mode,
+                input_mode,
+            } => {
+                put(0, &control_mode.to_le_bytes());
+                put(4, &input_mode.to_le_bytes());
+            }
+            Message::SetInputPos {
+                position,
+                velocity_ff,
+                torque_ff,
+            } => {
+                put(0, &position.to_le_bytes());
+                put(4, &velocity_ff.to_le_bytes());
+                put(6, &torque_ff.to_le_bytes());
+            }
+            Message::SetInputTorque { torque: a }
+            | Message::SetTrajVelLimit { velocity: a }
+            | Message::SetTrajInertia { inertia: a }
+            | Message::SetAbsolutePosition { position: a }
+            | Message::SetPosGain { gain: a } => put(0, &a.to_le_bytes()),
+            Message::GetEncoderEstimates {
+                position: a,
+                velocity: b,
+            }
+            | Message::SetInputVel {
+                velocity: a,
+                torque_ff: b,
+            }
+            | Message::SetLimits {
+                velocity: a,
+                current: b,
+            }
+            | Message::SetTrajAccelLimits {
+                acceleration: a,
+                deceleration: b,
+            }
+            | Message::GetIq {
+                setpoint: a,
+                measured: b,
+            }
+            | Message::GetTemperature { fet: a, motor: b }
+            | Message::GetBusVoltageCurrent {
+                voltage: a,
+                current: b,
+            }
+            | Message::SetVelGains {
+                gain: a,
+                integrator_gain: b,
+            }
+            | Message::GetTorques {
+                target: a,
+                estimate: b,
+            }
+            | Message::GetPowers {
+                electrical: a,
+                mechanical: b,
+            } => {
+                put(0, &a.to_le_bytes());
+                put(4, &b.to_le_bytes());
+            }
+            Message::Reboot { action } => put(0, &[action]),
+            Message::ClearErrors { identify } => put(0, &[identify as u8]),
+        }
+        Ok((id, len))
+    }
+
+    /// Length of the data of the message in bytes.
+    pub fn data_len(&self) -> usize {
+        data_len(self.command())
+    }
+
+    /// Command the message is sent with.
+    pub fn command(&self) -> Command {
+        match self {
+            Message::GetVersion { .. } => Command::GetVersion,
+            Message::Heartbeat { .. } => Command::Heartbeat,
+            Message::Estop => Command::Estop,
+            Message::GetError { .. } => Command::GetError,
+            Message::RxSdo { .. } => Command::RxSdo,
+            Message::TxSdo { .. } => Command::TxSdo,
+            Message::Address { .. } => Command::Address,
+            Message::SetAxisState { .. } => Command::SetAxisState,
+            Message::GetEncoderEstimates { .. } => Command::GetEncoderEstimates,
+            Message::SetControllerMode { .. } => Command::SetControllerMode,
+            Message::SetInputPos { .. } => Command::SetInputPos,
+            Message::SetInputVel { .. } => Command::SetInputVel,
+            Message::SetInputTorque { .. } => Command::SetInputTorque,
+            Message::SetLimits { .. } => Command::SetLimits,
+            Message::SetTrajVelLimit { .. } => Command::SetTrajVelLimit,
+            Message::SetTrajAccelLimits { .. } => Command::SetTrajAccelLimits,
+            Message::SetTrajInertia { .. } => Command::SetTrajInertia,
+            Message::GetIq { .. } => Command::GetIq,
+            Message::GetTemperature { .. } => Command::GetTemperature,
+            Message::Reboot { .. } => Command::Reboot,
+            Message::GetBusVoltageCurrent { .. } => Command::GetBusVoltageCurrent,
+            Message::ClearErrors { .. } => Command::ClearErrors,
+            Message::SetAbsolutePosition { .. } => Command::SetAbsolutePosition,
+            Message::SetPosGain { .. } => Command::SetPosGain,
+            Message::SetVelGains { .. } => Command::SetVelGains,
+            Message::GetTorques { .. } => Command::GetTorques,
+            Message::GetPowers { .. } => Command::GetPowers,
+            Message::EnterDfuMode => Command::EnterDfuMode,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(command: Command) -> Id {
+        Id::new(1, command.into()).unwrap()
+    }
+
+    #[test]
+    fn decodes() {
+        let mut data = [0; 8];
+        data[..4].copy_from_slice(&0x200u32.to_le_bytes());
+        data[4] = 8;
+        data[6] = 1;
+        assert_eq!(
+            Message::decode(id(Command::Heartbeat), &data),
+            Ok(Message::Heartbeat {
+                axis_error: 0x200,
+                axis_state: 8,
+                procedure_result: 0,
+                trajectory_done: true,
+            })
+        );
+
+        let mut data = [0; 8];
+        data[..4].copy_from_slice(&1.5f32.to_le_bytes());
+        data[4..6].copy_from_slice(&(-2i16).to_le_bytes());
+        data[6..].copy_from_slice(&3i16.to_le_bytes());
+        let message = Message::decode(id(Command::SetInputPos), &data).unwrap();
+        assert_eq!(
+            message,
+            Message::SetInputPos {
+                position: 1.5,
+                velocity_ff: -2,
+                torque_ff: 3,
+            }
+        );
+        assert_eq!(message.command(), Command::SetInputPos);
+
+        let data = [1, 0x12, 0x34, 0, 7, 0, 0, 0];
+        assert_eq!(
+            Message::decode(id(Command::RxSdo), &data),
+            Ok(Message::RxSdo {
+                opcode: 1,
+                endpoint: 0x3412,
+                value: [7, 0, 0, 0],
+            })
+        );
+
+        let data = [5, 1, 2, 3, 4, 5, 6];
+        assert_eq!(
+            Message::decode(id(Command::Address), &data),
+            Ok(Message::Address {
+                node: 5,
+                serial_number: 0x0605_0403_0201,
+            })
+        );
+
+        // padded by a gateway
+        assert_eq!(
+            Message::decode(id(Command::ClearErrors), &[1, 0, 0, 0, 0, 0, 0, 0]),
+            Ok(Message::ClearErrors { identify: true })
+        );
+        assert_eq!(Message::decode(id(Command::Estop), &[]), Ok(Message::Estop));
+    }
+
+    #[test]
+    fn round_trips() {
+        let messages = [
+            Message::GetVersion {
+                protocol_version: 2,
+                hw_version_major: 4,
+                hw_version_minor: 4,
+                hw_version_variant: 58,
+                fw_version_major: 0,
+                fw_version_minor: 6,
+                fw_version_revision: 8,
+                fw_version_unreleased: true,
+            },
+            Message::Heartbeat {
+                axis_error: 0x200,
+                axis_state: 8,
+                procedure_result: 1,
+                trajectory_done: true,
+            },
+            Message::Estop,
+            Message::TxSdo {
+                endpoint: 0x1234,
+                value: [1, 2, 3, 4],
+            },
+            Message::Address {
+                node: 5,
+                serial_number: 0x3937_3434_3538,
+            },
+            Message::SetAxisState {
+                axis_requested_state: 8,
+            },
+            Message::SetInputPos {
+                position: -1.25,
+                velocity_ff: 100,
+                torque_ff: -50,
+            },
+            Message::SetTrajInertia { inertia: 0.5 },
+            Message::GetTemperature {
+                fet: 40.5,
+                motor: 35.0,
+            },
+            Message::Reboot { action: 1 },
+            Message::ClearErrors { identify: false },
+            Message::GetPowers {
+                electrical: 12.0,
+                mechanical: 10.5,
+            },
+        ];
+        for message in messages {
+            let mut buf = [0xff; 8];
+            let (id, len) = message.encode(3, &mut buf).unwrap();
+            assert_eq!(id.node(), 3);
+            assert_eq!(id.known_command(), Some(message.command()));
+            assert_eq!(len, message.data_len());
+            assert_eq!(Message::decode(id, &buf[..len]), Ok(message));
+        }
+
+        let mut buf = [0xff; 8];
+        let (_, len) = Message::SetLimits {
+            velocity: 2.0,
+            current: 10.0,
+        }
+        .encode(1, &mut buf)
+        .unwrap();
+        assert_eq!(buf[..len], [0, 0, 0, 0x40, 0, 0, 0x20, 0x41]);
+    }
+
+    #[test]
+    fn rejects_encoding() {
+        let message = Message::GetIq {
+            setpoint: 1.0,
+            measured: 2.0,
+        };
+        assert_eq!(
+            message.encode(64, &mut [0; 8]),
+            Err(EncodeError::InvalidNode(64))
+        );
+        assert_eq!(
+            message.encode(1, &mut [0; 4]),
+            Err(EncodeError::BufferTooShort {
+                expected: 8,
+                actual: 4
+            })
+        );
+        assert_eq!(Message::Estop.encode(1, &mut []).map(|(_, len)| len), Ok(0));
+    }
+
+    #[test]
+    fn rejects() {
+        assert_eq!(
+            Message::decode(Id::new(1, 0x08).unwrap(), &[0; 8]),
+            Err(DecodeError::UnknownCommand(0x08))
+        );
+        assert_eq!(
+            Message::decode(id(Command::GetIq), &[0; 4]),
+            Err(DecodeError::TooShort {
+                expected: 8,
+                actual: 4
+            })
+        );
+    }
+}
@@ -0,0 +1,190 @@
+//! Borrowed message views.
+//!
+//! Views interpret frame data in place and only read a field when its getter
+//! is called, for example in interrupt handlers:
+//!
+//! ```
+//! use cansimple::view::HeartbeatRef;
+//!
+//! let data = [0x00, 0x02, 0x00, 0x00, 0x08, 0x00, 0x01, 0x00];
+//! let heartbeat = HeartbeatRef::new(&data).unwrap();
+//! assert_eq!(heartbeat.axis_error(), 0x200);
+//! assert_eq!(heartbeat.axis_state(), 8);
+//! ```
+
+use crate::message::data_len;
+use crate::{Command, DecodeError};
+
+fn u16_at(data: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([data[at], data[at + 1]])
+}
+
+fn u32_at(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+}
+
+fn f32_at(data: &[u8], at: usize) -> f32 {
+    f32::from_bits(u32_at(data, at))
+}
+
+macro_rules! views {
+    ($(
+        $(#[$doc:meta])*
+        $name:ident($command:ident) {
+            $($(#[$field_doc:meta])* $field:ident: $ty:ty = |$data:ident| $get:expr;)*
+        }
+    )*) => {$(
+        $(#[$doc])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+        pub struct $name<'a>(&'a [u8]);
+
+        impl<'a> $name<'a> {
+            /// View `data`, which may be longer than the message.
+            pub fn new(data: &'a [u8]) -> Result<Self, DecodeError> {
+                let expected = data_len(Command::$command);
+                if data.len() < expected {
+                    return Err(DecodeError::TooShort {
+                        expected,
+                        actual: data.len(),
+                    });
+                }
+                Ok(Self(data))
+            }
+
+            /// Data of the message.
+            pub fn data(&self) -> &'a [u8] {
+                self.0
+            }
+
+            $(
+                $(#[$field_doc])*
+                pub fn $field(&self) -> $ty {
+                    let $data = self.0;
+                    $get
+                }
+            )*
+        }
+    )*};
+}
+
+views! {
+    /// View of a [`Message::GetVersion`](crate::Message::GetVersion).
+    VersionRef(GetVersion) {
+        protocol_version: u8 = |d| d[0];
+        hw_version_major: u8 = |d| d[1];
+        hw_version_minor: u8 = |d| d[2];
+        hw_version_variant: u8 = |d| d[3];
+        fw_version_major: u8 = |d| d[4];
+        fw_version_minor: u8 = |d| d[5];
+        fw_version_revision: u8 = |d| d[6];
+        fw_version_unreleased: bool = |d| d[7] == 1;
+    }
+
+    /// View of a [`Message::Heartbeat`](crate::Message::Heartbeat).
+    HeartbeatRef(Heartbeat) {
+        axis_error: u32 = |d| u32_at(d, 0);
+        axis_state: u8 = |d| d[4];
+        procedure_result: u8 = |d| d[5];
+        trajectory_done: bool = |d| d[6] != 0;
+    }
+
+    /// View of a [`Message::GetError`](crate::Message::GetError).
+    ErrorRef(GetError) {
+        active_errors: u32 = |d| u32_at(d, 0);
+        disarm_reason: u32 = |d| u32_at(d, 4);
+    }
+
+    /// View of a [`Message::TxSdo`](crate::Message::TxSdo).
+    TxSdoRef(TxSdo) {
+        endpoint: u16 = |d| u16_at(d, 1);
+        value: [u8; 4] = |d| [d[4], d[5], d[6], d[7]];
+    }
+
+    /// View of a [`Message::GetEncoderEstimates`](crate::Message::GetEncoderEstimates).
+    EncoderEstimatesRef(GetEncoderEstimates) {
+        /// Position in rev.
+        position: f32 = |d| f32_at(d, 0);
+        /// Velocity in rev/s.
+        velocity: f32 = |d| f32_at(d, 4);
+    }
+
+    /// View of a [`Message::GetIq`](crate::Message::GetIq).
+    IqRef(GetIq) {
+        /// Current setpoint in amps.
+        setpoint: f32 = |d| f32_at(d, 0);
+        /// Measured current in amps.
+        measured: f32 = |d| f32_at(d, 4);
+    }
+
+    /// View of a [`Message::GetTemperature`](crate::Message::GetTemperature).
+    TemperatureRef(GetTemperature) {
+        /// FET temperature in °C.
+        fet: f32 = |d| f32_at(d, 0);
+        /// Motor temperature in °C.
+        motor: f32 = |d| f32_at(d, 4);
+    }
+
+    /// View of a [`Message::GetBusVoltageCurrent`](crate::Message::GetBusVoltageCurrent).
+    BusVoltageCurrentRef(GetBusVoltageCurrent) {
+        /// Bus voltage in volts.
+        voltage: f32 = |d| f32_at(d, 0);
+        /// Bus current in amps.
+        current: f32 = |d| f32_at(d, 4);
+    }
+
+    /// View of a [`Message::GetTorques`](crate::Message::GetTorques).
+    TorquesRef(GetTorques) {
+        /// Torque target in Nm.
+        target: f32 = |d| f32_at(d, 0);
+        /// Torque estimate in Nm.
+        estimate: f32 = |d| f32_at(d, 4);
+    }
+
+    /// View of a [`Message::GetPowers`](crate::Message::GetPowers).
+    PowersRef(GetPowers) {
+        /// Electrical power in watts.
+        electrical: f32 = |d| f32_at(d, 0);
+        /// Mechanical power in watts.
+        mechanical: f32 = |d| f32_at(d, 4);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Id, Message};
+
+    #[test]
+    fn views() {
+        let message = Message::GetTemperature {
+            fet: 40.5,
+            motor: 35.0,
+        };
+        let mut buf = [0; 8];
+        let (_, len) = message.encode(1, &mut buf).unwrap();
+        let temperature = TemperatureRef::new(&buf[..len]).unwrap();
+        assert_eq!(temperature.fet(), 40.5);
+        assert_eq!(temperature.motor(), 35.0);
+        assert_eq!(temperature.data(), &buf);
+
+        let data = [0, 0x34, 0x12, 0, 1, 2, 3, 4];
+        let sdo = TxSdoRef::new(&data).unwrap();
+        assert_eq!(
+            Message::decode(Id::new(1, Command::TxSdo.into()).unwrap(), &data),
+            Ok(Message::TxSdo {
+                endpoint: sdo.endpoint(),
+                value: sdo.value(),
+            })
+        );
+        assert_eq!(sdo.endpoint(), 0x1234);
+
+        assert_eq!(
+            HeartbeatRef::new(&[0; 7]),
+            Err(DecodeError::TooShort {
+                expected: 8,
+                actual: 7
+            })
+        );
+    }
+}
@@ -0,0 +1,87 @@
+#![no_main]
+
+use arbitrary::Arbitrary;
+use cansimple::{Id, Message};
+use libfuzzer_sys::fuzz_target;
+use odrive::protocol::{self, Frame, Heartbeat, TelemetryMessage, Value, Version};
+
+#[derive(Debug, Arbitrary)]
+enum Input {
+    /// Frame data decoded as every message.
+    Data { id: Id, data: Vec<u8>, fd: bool },
+    /// Messages, values and telemetry encoded and decoded again.
+    Message { node: u8, message: Message },
+    Value(Value),
+    Heartbeat(Heartbeat),
+    Version(Version),
+}
+
+fuzz_target!(|input: Input| match input {
+    Input::Data { id, data, fd } => decode(id, &data, fd),
+    Input::Message { node, message } => {
+        let mut buf = [0; 8];
+        let (id, len) = message.encode(node & 0x3f, &mut buf).unwrap();
+        let decoded = Message::decode(id, &buf[..len]).unwrap();
+        // compared encoded, fields such as NaN floats do not equal themselves
+        let mut again = [0; 8];
+        assert_eq!(decoded.encode(node & 0x3f, &mut again), Ok((id, len)));
+        assert_eq!(buf, again);
+    }
+    Input::Value(value) => {
+        let bytes = value.to_le_bytes();
+        assert_eq!(Value::from_le_bytes(value.kind(), bytes).to_le_bytes(), bytes);
+        if let Ok(frame) = protocol::sdo_write(1, 3, value) {
+            let (endpoint, written) = protocol::decode_sdo_request(frame.data()).unwrap();
+            assert_eq!(endpoint, 3);
+            assert_eq!(written.unwrap(), bytes);
+        }
+    }
+    Input::Heartbeat(heartbeat) => {
+        let data = protocol::encode_heartbeat(&heartbeat);
+        assert_eq!(protocol::decode_heartbeat(&data).unwrap(), heartbeat);
+    }
+    Input::Version(version) => {
+        let data = protocol::encode_version(&version);
+        assert_eq!(protocol::decode_version(&data).unwrap(), version);
+    }
+});
+
+fn decode(id: Id, data: &[u8], fd: bool) {
+    let _ = Message::decode(id, data);
+    let _ = protocol::decode_version(data);
+    let _ = protocol::decode_heartbeat(data);
+    let _ = protocol::decode_error(data);
+    let _ = protocol::decode_sdo_value(data);
+    let _ = protocol::decode_sdo_request(data);
+    let _ = protocol::decode_encoder_estimates(data);
+    let _ = protocol::decode_iq(data);
+    let _ = protocol::decode_temperature(data);
+    let _ = protocol::decode_bus_voltage_current(data);
+    let _ = protocol::decode_torques(data);
+    let _ = protocol::decode_powers(data);
+    let _ = protocol::decode_set_axis_state(data);
+    let _ = protocol::decode_set_controller_mode(data);
+    let _ = protocol::decode_set_input_position(data);
+    let _ = protocol::decode_set_input_velocity(data);
+    let _ = protocol::decode_set_input_torque(data);
+    let _ = protocol::decode_set_limits(data);
+    let _ = protocol::decode_set_trajectory_velocity_limit(data);
+    let _ = protocol::decode_set_trajectory_acceleration_limit(data);
+    let _ = protocol::decode_set_trajectory_inertia(data);
+    let _ = protocol::decode_clear_errors(data);
+    let _ = protocol::decode_set_absolute_position(data);
+    let _ = protocol::decode_set_position_gain(data);
+    let _ = protocol::decode_set_velocity_gains(data);
+    let _ = protocol::legacy::decode_motor_error(data);
+    let _ = protocol::legacy::decode_encoder_error(data);
+    let _ = protocol::legacy::decode_sensorless_error(data);
+    let _ = protocol::legacy::decode_controller_error(data);
+
+    let frame = Frame::new(id, data).or_else(|| Frame::new_fd(id, data, fd));
+    if let Some(frame) = frame {
+        let _ = TelemetryMessage::decode(&frame);
+        if let Ok(request) = protocol::sdo_read(1, 3) {
+            let _ = request.response(&frame);
+        }
+    }
+}
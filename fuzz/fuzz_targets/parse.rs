@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use odrive::middleware::{candump::parse_frame, session::Session};
+use odrive::mock::scenario::Scenario;
+
+fuzz_target!(|text: &str| {
+    let _ = parse_frame(text);
+    let _ = text.parse::<Session>();
+    let _ = text.parse::<Scenario>();
+});
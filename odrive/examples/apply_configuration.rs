@@ -1,13 +1,16 @@
-use odrive::{can::ODrive, flat_endpoints::FlatEndpoints};
+use odrive::{
+    can::ODrive,
+    flat_endpoints::{FlatEndpoints, Verify},
+};
 use serde_json::json;
 use socketcan::tokio::CanSocket;
-use std::io;
+use std::{error::Error, io};
 
 #[tokio::main]
-async fn main() -> io::Result<()> {
+async fn main() -> Result<(), Box<dyn Error>> {
     // Setup the ODrive connection
     let socket = CanSocket::open("can0")?;
-    let odrive = ODrive::new(socket, 1);
+    let odrive = ODrive::new(socket, 1)?;
 
     // Get our endpoints reference
     let file = std::fs::File::open("examples/endpoints.json")?;
@@ -21,8 +24,19 @@ async fn main() -> io::Result<()> {
         "can.config.protocol": 1, // set to cansimple
     });
 
-    odrive.apply_configuration(&endpoints, &config).await?;
-    println!("Configuration applied");
+    let report = odrive
+        .apply_configuration(&endpoints, &config, Verify::READ_BACK)
+        .await?;
+    for (key, err) in &report.failed {
+        println!("Failed to apply {key}: {err}");
+    }
+    for (key, mismatch) in &report.mismatched {
+        println!(
+            "{key} reads {:?} after writing {:?}",
+            mismatch.read, mismatch.written
+        );
+    }
+    println!("Configuration applied: {} keys", report.applied.len());
 
     Ok(())
 }
@@ -0,0 +1,18 @@
+use odrive::blocking::ODrive;
+use socketcan::{CanSocket, Socket};
+
+fn main() -> odrive::Result<()> {
+    // Setup the ODrive connection
+    let socket = CanSocket::open("can0")?;
+    let odrive = ODrive::new(socket, 1)?;
+
+    // Get the version information
+    let version = odrive.get_version()?;
+    println!("{:?}", version);
+
+    // Bus voltage and current
+    let bus = odrive.get_bus_voltage_current()?;
+    println!("{:?}", bus);
+
+    Ok(())
+}
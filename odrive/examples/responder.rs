@@ -0,0 +1,35 @@
+use odrive::mock::{
+    MockODrive,
+    scenario::{Responder, Scenario},
+};
+use socketcan::tokio::CanSocket;
+use std::{io, sync::Arc};
+
+/// Answer the driver on a virtual CAN interface.
+///
+/// Usage: `responder <interface> <scenario> [node...]`, frames the scenario
+/// does not cover are answered by mock ODrives with the given node IDs.
+#[tokio::main]
+async fn main() -> io::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let (Some(interface), Some(path)) = (args.next(), args.next()) else {
+        eprintln!("usage: responder <interface> <scenario> [node...]");
+        std::process::exit(2);
+    };
+
+    let scenario: Scenario = std::fs::read_to_string(path)?
+        .parse()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    let mut responder = Responder::new(scenario);
+    for node in args {
+        let node = node
+            .parse()
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid node ID"))?;
+        responder = responder.with_node(Arc::new(MockODrive::new(node)));
+    }
+
+    // Setup with `ip link add dev vcan0 type vcan && ip link set up vcan0`
+    let socket = CanSocket::open(&interface)?;
+    println!("Answering on {interface}");
+    responder.serve(&socket).await
+}
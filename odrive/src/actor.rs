@@ -0,0 +1,176 @@
+//! # Background IO task
+//!
+//! [`spawn`] moves a transport into background tasks and returns an
+//! [`ODriveHandle`]. The handle is an [`ODrive`] whose transport sends frames
+//! to the background task over a channel and receives the frames it read.
+//! Handles are cheap to clone and can be moved into other tasks, every clone
+//! shares the same connection.
+//!
+//! ```no_run
+//! # #[tokio::main]
+//! # async fn main() {
+//! let socket = socketcan::tokio::CanSocket::open("can0").unwrap();
+//! let odrive = odrive::actor::spawn(socket, 1).unwrap();
+//!
+//! tokio::spawn({
+//!     let odrive = odrive.clone();
+//!     async move { odrive.get_temperature().await }
+//! });
+//! let bus = odrive.get_bus_voltage_current().await.unwrap();
+//! # }
+//! ```
+//!
+//! The background tasks run until every handle has been dropped or reading
+//! from the transport fails. Received frames are buffered until a request or
+//! [`ODrive::listen`] takes them, frames received while the buffer is full
+//! are dropped.
+
+use crate::can::ODrive;
+use crate::transport::{BusError, BusState, Frame, TokioTimer, Transport};
+use std::{io, sync::Arc};
+use tokio::sync::{Mutex, mpsc, oneshot};
+
+/// Number of received frames buffered for the handles.
+const BUFFER: usize = 256;
+
+/// ODrive driven by a background task, see [`spawn`].
+pub type ODriveHandle = ODrive<Channel>;
+
+/// Frame to write and where to report the result.
+struct Write {
+    frame: Frame,
+    result: oneshot::Sender<io::Result<()>>,
+}
+
+/// Transport connected to the background task.
+pub struct Channel {
+    writes: mpsc::UnboundedSender<Write>,
+    frames: Mutex<mpsc::Receiver<io::Result<Frame>>>,
+}
+
+impl Transport for Channel {
+    type Timer = TokioTimer;
+
+    async fn write_frame(&self, frame: &Frame) -> io::Result<()> {
+        let (result, done) = oneshot::channel();
+        self.writes
+            .send(Write {
+                frame: *frame,
+                result,
+            })
+            .map_err(|_| stopped())?;
+        done.await.map_err(|_| stopped())?
+    }
+
+    async fn read_frame(&self) -> io::Result<Frame> {
+        self.frames.lock().await.recv().await.ok_or_else(stopped)?
+    }
+}
+
+/// Error returned once the background task has stopped.
+fn stopped() -> io::Error {
+    io::Error::new(io::ErrorKind::NotConnected, "ODrive IO task stopped")
+}
+
+/// Move `transport` into background tasks and return a handle for `axis`.
+///
+/// Must be called from within a tokio runtime.
+pub fn spawn<T>(transport: T, axis: u8) -> crate::Result<ODriveHandle>
+where
+    T: Transport + Send + Sync + 'static,
+{
+    let runtime = tokio::runtime::Handle::try_current().map_err(io::Error::other)?;
+    let transport = Arc::new(transport);
+    let (writes, mut pending_writes) = mpsc::unbounded_channel::<Write>();
+    let (frames, received) = mpsc::channel(BUFFER);
+
+    runtime.spawn({
+        let transport = transport.clone();
+        async move {
+            while let Some(write) = pending_writes.recv().await {
+                let _ = write.result.send(transport.write_frame(&write.frame).await);
+            }
+        }
+    });
+
+    runtime.spawn(async move {
+        loop {
+            let result = transport.read_frame().await;
+            let fatal = result.as_ref().is_err_and(|err| {
+                BusError::from_error(err).is_none() && BusState::from_error(err).is_none()
+            });
+
+            match frames.try_send(result) {
+                Err(mpsc::error::TrySendError::Closed(_)) => return,
+                // nobody is reading, drop the frame
+                Err(mpsc::error::TrySendError::Full(_)) | Ok(()) => {}
+            }
+            if fatal {
+                trace_event!(warn, "actor read task stopped after a read error");
+                return;
+            }
+        }
+    });
+
+    ODrive::new(
+        Channel {
+            writes,
+            frames: Mutex::new(received),
+        },
+        axis,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol;
+
+    /// Transport answering every remote frame with a data frame.
+    struct Echo {
+        tx: mpsc::UnboundedSender<Frame>,
+        rx: Mutex<mpsc::UnboundedReceiver<Frame>>,
+    }
+
+    impl Transport for Echo {
+        type Timer = TokioTimer;
+
+        async fn write_frame(&self, frame: &Frame) -> io::Result<()> {
+            let response = Frame::new(frame.id(), &[0; 8]).unwrap();
+            self.tx.send(response).map_err(|_| stopped())
+        }
+
+        async fn read_frame(&self) -> io::Result<Frame> {
+            self.rx.lock().await.recv().await.ok_or_else(stopped)
+        }
+    }
+
+    #[tokio::test]
+    async fn shared_handles() {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let odrive = spawn(
+            Echo {
+                tx,
+                rx: Mutex::new(rx),
+            },
+            1,
+        )
+        .unwrap();
+
+        let other = odrive.clone();
+        let iq = tokio::spawn(async move { other.get_iq().await });
+        odrive.get_temperature().await.unwrap();
+        iq.await.unwrap().unwrap();
+        odrive.send(protocol::estop(1).unwrap()).await.unwrap();
+    }
+
+    #[test]
+    fn requires_runtime() {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let echo = Echo {
+            tx,
+            rx: Mutex::new(rx),
+        };
+        assert!(spawn(echo, 1).is_err());
+    }
+}
@@ -0,0 +1,343 @@
+//! # Telemetry alarms
+//!
+//! Monitors turn cyclic telemetry into events whenever a value crosses a
+//! warning or critical threshold. A value has to move back past a threshold
+//! by the hysteresis before the level is lowered again, so values hovering
+//! around a threshold do not flap.
+//!
+//! - [`TemperatureMonitor`] watches the FET and motor temperatures.
+//! - [`BusVoltageMonitor`] watches for undervoltage and overvoltage of the
+//!   supply, which can be reacted to before the drive faults.
+//!
+//! Monitors are fed with their `update` method or turned into an
+//! [`ODrive::on_frame`](crate::can::ODrive::on_frame) subscriber:
+//!
+//! ```no_run
+//! # tokio_test::block_on(async {
+//! use odrive::alarm::{Level, TemperatureMonitor, Thresholds};
+//! use odrive::can::ODrive;
+//!
+//! let monitor = TemperatureMonitor::new(
+//!     Thresholds::new(80.0, 100.0, 5.0),
+//!     Thresholds::new(70.0, 90.0, 5.0),
+//! );
+//! let socket = socketcan::tokio::CanSocket::open("can0").unwrap();
+//! let odrive = ODrive::new(socket, 1)
+//!     .unwrap()
+//!     .on_frame(monitor.into_callback(|event| {
+//!         if event.level == Level::Critical {
+//!             eprintln!("{:?} temperature critical", event.sensor);
+//!         }
+//!     }));
+//! odrive.listen().await.unwrap();
+//! # });
+//! ```
+
+use crate::protocol::{BusVoltageCurrent, Frame, TelemetryMessage, Temperature};
+use std::sync::{Mutex, PoisonError};
+
+/// Severity of a monitored value.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    #[default]
+    Normal,
+    Warning,
+    Critical,
+}
+
+/// Limits of a monitored value.
+///
+/// Limits of values that must not fall too low, like the undervoltage
+/// limits, have the critical below the warning threshold.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Thresholds {
+    /// Value from which on the level is [`Level::Warning`].
+    pub warning: f32,
+    /// Value from which on the level is [`Level::Critical`].
+    pub critical: f32,
+    /// Distance back past a threshold before the level is lowered again.
+    pub hysteresis: f32,
+}
+
+impl Thresholds {
+    pub fn new(warning: f32, critical: f32, hysteresis: f32) -> Self {
+        Self {
+            warning,
+            critical,
+            hysteresis,
+        }
+    }
+}
+
+/// Level of one value.
+#[derive(Debug, Clone, Copy)]
+struct Alarm {
+    thresholds: Thresholds,
+    level: Level,
+    /// -1 if low values are alarming.
+    sign: f32,
+}
+
+impl Alarm {
+    fn new(thresholds: Thresholds) -> Self {
+        Self {
+            thresholds,
+            level: Level::Normal,
+            sign: 1.0,
+        }
+    }
+
+    /// Alarm for values that must not fall too low.
+    fn falling(thresholds: Thresholds) -> Self {
+        Self {
+            sign: -1.0,
+            ..Self::new(thresholds)
+        }
+    }
+
+    /// Update with a new value, returns the level if it changed.
+    fn update(&mut self, value: f32) -> Option<Level> {
+        let Thresholds {
+            warning,
+            critical,
+            hysteresis,
+        } = self.thresholds;
+        // thresholds of the current level and below are lowered
+        let reached = |level: Level, threshold: f32| {
+            let threshold = if self.level >= level {
+                threshold - self.sign * hysteresis
+            } else {
+                threshold
+            };
+            value * self.sign >= threshold * self.sign
+        };
+
+        let level = if reached(Level::Critical, critical) {
+            Level::Critical
+        } else if reached(Level::Warning, warning) {
+            Level::Warning
+        } else {
+            Level::Normal
+        };
+
+        if level == self.level {
+            return None;
+        }
+        self.level = level;
+        Some(level)
+    }
+}
+
+/// Temperature sensor of an axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sensor {
+    Fet,
+    Motor,
+}
+
+/// A temperature changed its [`Level`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TemperatureEvent {
+    pub sensor: Sensor,
+    pub level: Level,
+    /// Temperature that caused the change.
+    pub temperature: f32,
+}
+
+/// Watches the FET and motor temperatures of an axis.
+#[derive(Debug, Clone)]
+pub struct TemperatureMonitor {
+    fet: Alarm,
+    motor: Alarm,
+}
+
+impl TemperatureMonitor {
+    pub fn new(fet: Thresholds, motor: Thresholds) -> Self {
+        Self {
+            fet: Alarm::new(fet),
+            motor: Alarm::new(motor),
+        }
+    }
+
+    /// Feed a temperature reading, returns the resulting events.
+    pub fn update(&mut self, temperature: &Temperature) -> impl Iterator<Item = TemperatureEvent> {
+        let fet = self
+            .fet
+            .update(temperature.fet)
+            .map(|level| TemperatureEvent {
+                sensor: Sensor::Fet,
+                level,
+                temperature: temperature.fet,
+            });
+        let motor = self
+            .motor
+            .update(temperature.motor)
+            .map(|level| TemperatureEvent {
+                sensor: Sensor::Motor,
+                level,
+                temperature: temperature.motor,
+            });
+        fet.into_iter().chain(motor)
+    }
+
+    /// Frame subscriber calling `on_event` for every event.
+    pub fn into_callback(
+        self,
+        on_event: impl Fn(TemperatureEvent) + Send + Sync + 'static,
+    ) -> impl Fn(&Frame) + Send + Sync + 'static {
+        subscriber(self, on_event, |monitor, message| match message {
+            TelemetryMessage::Temperature(temperature) => monitor.update(temperature).collect(),
+            _ => Vec::new(),
+        })
+    }
+}
+
+/// Limit of the bus voltage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoltageLimit {
+    Under,
+    Over,
+}
+
+/// The bus voltage changed its [`Level`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VoltageEvent {
+    pub limit: VoltageLimit,
+    pub level: Level,
+    /// Voltage that caused the change.
+    pub voltage: f32,
+}
+
+/// Watches the bus voltage of an ODrive.
+#[derive(Debug, Clone)]
+pub struct BusVoltageMonitor {
+    under: Alarm,
+    over: Alarm,
+}
+
+impl BusVoltageMonitor {
+    /// Thresholds of `under` are reached when the voltage falls below them.
+    pub fn new(under: Thresholds, over: Thresholds) -> Self {
+        Self {
+            under: Alarm::falling(under),
+            over: Alarm::new(over),
+        }
+    }
+
+    /// Feed a bus reading, returns the resulting events.
+    pub fn update(&mut self, bus: &BusVoltageCurrent) -> impl Iterator<Item = VoltageEvent> {
+        let event = |limit| {
+            move |level| VoltageEvent {
+                limit,
+                level,
+                voltage: bus.voltage,
+            }
+        };
+        let under = self
+            .under
+            .update(bus.voltage)
+            .map(event(VoltageLimit::Under));
+        let over = self.over.update(bus.voltage).map(event(VoltageLimit::Over));
+        under.into_iter().chain(over)
+    }
+
+    /// Frame subscriber calling `on_event` for every event.
+    pub fn into_callback(
+        self,
+        on_event: impl Fn(VoltageEvent) + Send + Sync + 'static,
+    ) -> impl Fn(&Frame) + Send + Sync + 'static {
+        subscriber(self, on_event, |monitor, message| match message {
+            TelemetryMessage::BusVoltageCurrent(bus) => monitor.update(bus).collect(),
+            _ => Vec::new(),
+        })
+    }
+}
+
+/// Frame subscriber feeding decoded telemetry to `monitor`.
+fn subscriber<M: Send + 'static, E>(
+    monitor: M,
+    on_event: impl Fn(E) + Send + Sync + 'static,
+    update: impl Fn(&mut M, &TelemetryMessage) -> Vec<E> + Send + Sync + 'static,
+) -> impl Fn(&Frame) + Send + Sync + 'static {
+    let monitor = Mutex::new(monitor);
+    move |frame| {
+        if let Some(Ok(message)) = TelemetryMessage::decode(frame) {
+            // do not hold the lock while calling back
+            let events = update(
+                &mut monitor.lock().unwrap_or_else(PoisonError::into_inner),
+                &message,
+            );
+            events.into_iter().for_each(&on_event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hysteresis() {
+        let mut alarm = Alarm::new(Thresholds::new(80.0, 100.0, 5.0));
+        let levels: Vec<_> = [79.0, 80.0, 78.0, 74.0, 101.0, 97.0, 94.0, f32::NAN]
+            .into_iter()
+            .map(|value| alarm.update(value))
+            .collect();
+        assert_eq!(
+            levels,
+            [
+                None,
+                Some(Level::Warning),
+                None,
+                Some(Level::Normal),
+                Some(Level::Critical),
+                None,
+                Some(Level::Warning),
+                Some(Level::Normal),
+            ]
+        );
+    }
+
+    #[test]
+    fn temperature_events() {
+        let mut monitor = TemperatureMonitor::new(
+            Thresholds::new(80.0, 100.0, 5.0),
+            Thresholds::new(70.0, 90.0, 5.0),
+        );
+        let events: Vec<_> = monitor
+            .update(&Temperature {
+                fet: 85.0,
+                motor: 95.0,
+            })
+            .collect();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].sensor, Sensor::Fet);
+        assert_eq!(events[0].level, Level::Warning);
+        assert_eq!(events[1].level, Level::Critical);
+    }
+
+    #[test]
+    fn undervoltage() {
+        let mut monitor = BusVoltageMonitor::new(
+            Thresholds::new(20.0, 18.0, 1.0),
+            Thresholds::new(52.0, 56.0, 1.0),
+        );
+        let mut levels = [24.0, 19.5, 20.5, 17.0, 21.5, 53.0]
+            .into_iter()
+            .flat_map(|voltage| {
+                monitor
+                    .update(&BusVoltageCurrent {
+                        voltage,
+                        current: 0.0,
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .map(|event| (event.limit, event.level));
+
+        assert_eq!(levels.next(), Some((VoltageLimit::Under, Level::Warning)));
+        assert_eq!(levels.next(), Some((VoltageLimit::Under, Level::Critical)));
+        assert_eq!(levels.next(), Some((VoltageLimit::Under, Level::Normal)));
+        assert_eq!(levels.next(), Some((VoltageLimit::Over, Level::Warning)));
+        assert_eq!(levels.next(), None);
+    }
+}
@@ -0,0 +1,1590 @@
+//! # Blocking CAN interface for ODrives
+//!
+//! Mirrors the API of [`crate::can`] for use without an async runtime. The
+//! driver is generic over a blocking [`Transport`] which is implemented for
+//! `socketcan::CanSocket` and `socketcan::CanFdSocket` on Linux.
+
+use crate::cache::TelemetryCache;
+use crate::can::Snapshot;
+#[cfg(feature = "flat-endpoints")]
+use crate::flat_endpoints::{
+    Access, ApplyReport, Change, Difference, Endpoint, FlatEndpoints, Profiles, RestoreReport,
+    Verify,
+};
+use crate::middleware::{Chain, Middleware};
+use crate::pending::{Key, Pending, Reader, Registration, Subscribers, Subscription, Turn};
+use crate::protocol::{
+    self, BusVoltageCurrent, EncoderEstimate, Error, Filter, Frame, FrameFormat, Power, ReadMode,
+    Request, SdoType, TelemetryMessage, Temperature, Torque, Validation, Value, ValueKind, Version,
+};
+use crate::stats::Stats;
+use crate::transport::{BusError, BusState, ErrorCounts};
+use crate::{AxisState, ControlMode, InputMode, Result};
+use std::{
+    collections::VecDeque,
+    io,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+/// Blocking CAN bus access.
+pub trait Transport {
+    /// Write a frame to the bus.
+    fn write_frame(&self, frame: &Frame) -> io::Result<()>;
+
+    /// Read the next frame from the bus.
+    ///
+    /// Frames that cannot be represented by [`Frame`], such as error frames,
+    /// are skipped. Controller state changes and bus errors are reported as
+    /// an error carrying a [`BusState`] or
+    /// [`BusError`].
+    fn read_frame(&self) -> io::Result<Frame>;
+
+    /// Only receive frames matching at least one of `filters`.
+    ///
+    /// An empty slice accepts every frame. Transports without hardware
+    /// filtering ignore the filters.
+    fn set_filters(&self, filters: &[Filter]) -> io::Result<()> {
+        let _ = filters;
+        Ok(())
+    }
+
+    /// Read the next frame, waiting at most `timeout`.
+    ///
+    /// Returns [`None`] if no frame was received in time. The default
+    /// implementation ignores the timeout.
+    fn read_frame_timeout(&self, timeout: Duration) -> io::Result<Option<Frame>> {
+        let _ = timeout;
+        self.read_frame().map(Some)
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Transport for socketcan::CanSocket {
+    fn write_frame(&self, frame: &Frame) -> io::Result<()> {
+        socketcan::Socket::write_frame(self, &socketcan::CanFrame::try_from(frame)?)
+    }
+
+    fn read_frame(&self) -> io::Result<Frame> {
+        loop {
+            match socketcan::Socket::read_frame(self)? {
+                socketcan::CanFrame::Error(error) => {
+                    return Err(crate::transport::socketcan_error(error));
+                }
+                frame => {
+                    if let Ok(frame) = Frame::try_from(frame) {
+                        return Ok(frame);
+                    }
+                }
+            }
+        }
+    }
+
+    fn set_filters(&self, filters: &[Filter]) -> io::Result<()> {
+        crate::transport::set_socketcan_filters(self, filters)
+    }
+
+    fn read_frame_timeout(&self, timeout: Duration) -> io::Result<Option<Frame>> {
+        socketcan_read_timeout(self, timeout)
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Transport for socketcan::CanFdSocket {
+    fn write_frame(&self, frame: &Frame) -> io::Result<()> {
+        socketcan::Socket::write_frame(self, &socketcan::CanAnyFrame::try_from(frame)?)
+    }
+
+    fn read_frame(&self) -> io::Result<Frame> {
+        loop {
+            match socketcan::Socket::read_frame(self)? {
+                socketcan::CanAnyFrame::Error(error) => {
+                    return Err(crate::transport::socketcan_error(error));
+                }
+                frame => {
+                    if let Ok(frame) = Frame::try_from(frame) {
+                        return Ok(frame);
+                    }
+                }
+            }
+        }
+    }
+
+    fn set_filters(&self, filters: &[Filter]) -> io::Result<()> {
+        crate::transport::set_socketcan_filters(self, filters)
+    }
+
+    fn read_frame_timeout(&self, timeout: Duration) -> io::Result<Option<Frame>> {
+        socketcan_read_timeout(self, timeout)
+    }
+}
+
+/// Read from a SocketCAN socket with a read timeout.
+#[cfg(target_os = "linux")]
+fn socketcan_read_timeout<S>(socket: &S, timeout: Duration) -> io::Result<Option<Frame>>
+where
+    S: Transport + socketcan::Socket,
+{
+    // a zero `SO_RCVTIMEO` blocks forever, and shorter timeouts round to it
+    if timeout < Duration::from_micros(1) {
+        return Ok(None);
+    }
+    socket.set_read_timeout(timeout)?;
+    let result = Transport::read_frame(socket);
+    socket.set_read_timeout(None)?;
+
+    match result {
+        Ok(frame) => Ok(Some(frame)),
+        Err(err)
+            if matches!(
+                err.kind(),
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+            ) =>
+        {
+            Ok(None)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+type BusStateCallback = Arc<dyn Fn(BusState) + Send + Sync>;
+type BusErrorCallback = Arc<dyn Fn(BusError) + Send + Sync>;
+type RecoveryCallback = Arc<dyn Fn() -> io::Result<()> + Send + Sync>;
+
+/// Default time to wait for a response.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Number of SDO reads the configuration helpers such as
+/// [`ODrive::backup_configuration`] keep in flight, see
+/// [`ODrive::sdo_read_many`].
+#[cfg(feature = "flat-endpoints")]
+pub const SDO_WINDOW: usize = 8;
+
+/// Time [`ODrive::restore_configuration`] waits for the drive to reboot.
+#[cfg(feature = "flat-endpoints")]
+pub const REBOOT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Gap in the heartbeats that [`ODrive::restore_configuration`] takes as
+/// the drive rebooting.
+#[cfg(feature = "flat-endpoints")]
+pub const REBOOT_SILENCE: Duration = Duration::from_millis(500);
+
+/// ODrive driver.
+///
+/// Generic over the blocking [`Transport`] used to access the CAN bus.
+///
+/// # Sharing
+///
+/// Cloning is cheap, clones share the transport, callbacks and error counts.
+/// A driver can be used from several threads at once, either cloned or behind an
+/// [`Arc`]. Requests register the response they are waiting for before they
+/// are sent. Only one waiting request reads from the transport at a time and
+/// routes every received frame to the request waiting for it, so concurrent
+/// requests never receive each other's responses. Frames nobody waits for go
+/// to the [`ODrive::on_frame`] subscribers.
+pub struct ODrive<T> {
+    interface: Arc<T>,
+    axis: u8,
+    format: FrameFormat,
+    validation: Validation,
+    read_mode: ReadMode,
+    timeout: Option<Duration>,
+    on_bus_state: Option<BusStateCallback>,
+    on_bus_error: Option<BusErrorCallback>,
+    recovery: Option<RecoveryCallback>,
+    subscribers: Arc<Subscribers>,
+    middleware: Arc<Chain>,
+    pending: Arc<Pending>,
+    cache: Arc<TelemetryCache>,
+    /// Records telemetry into `cache` while any clone sharing it is alive.
+    cache_subscription: Arc<Subscription>,
+    retries: u32,
+    /// Read back every SDO write if set, see [`Self::with_write_verification`].
+    write_tolerance: Option<f64>,
+    /// Firmware commands are checked against, see [`Self::with_firmware`].
+    firmware: Option<(u8, u8, u8)>,
+    stats: Arc<Mutex<Stats>>,
+    error_counts: Arc<Mutex<ErrorCounts>>,
+}
+
+impl<T> Clone for ODrive<T> {
+    fn clone(&self) -> Self {
+        Self {
+            interface: self.interface.clone(),
+            axis: self.axis,
+            format: self.format,
+            validation: self.validation,
+            read_mode: self.read_mode.clone(),
+            timeout: self.timeout,
+            on_bus_state: self.on_bus_state.clone(),
+            on_bus_error: self.on_bus_error.clone(),
+            recovery: self.recovery.clone(),
+            subscribers: self.subscribers.clone(),
+            middleware: self.middleware.clone(),
+            pending: self.pending.clone(),
+            cache: self.cache.clone(),
+            cache_subscription: self.cache_subscription.clone(),
+            retries: self.retries,
+            write_tolerance: self.write_tolerance,
+            firmware: self.firmware,
+            stats: self.stats.clone(),
+            error_counts: self.error_counts.clone(),
+        }
+    }
+}
+
+impl<T: Transport> ODrive<T> {
+    /// Creates a new ODrive interface.
+    ///
+    /// Installs a [`Filter::node`] filter on the transport so only frames
+    /// for `axis` are received. Failing to install the filter is not an
+    /// error since it only reduces load, see [`ODrive::set_filters`].
+    ///
+    /// Fails with [`crate::Error::InvalidNode`] if `axis` is larger than 63.
+    pub fn new(interface: T, axis: u8) -> Result<Self> {
+        let filter = Filter::node(axis)?;
+        let _ = interface.set_filters(&[filter]);
+        Ok(Self::with_shared(
+            Arc::new(interface),
+            axis,
+            Arc::default(),
+            Arc::default(),
+            Arc::default(),
+        ))
+    }
+
+    /// Driver for `axis` sharing a transport with other drivers.
+    fn with_shared(
+        interface: Arc<T>,
+        axis: u8,
+        pending: Arc<Pending>,
+        subscribers: Arc<Subscribers>,
+        middleware: Arc<Chain>,
+    ) -> Self {
+        let cache = Arc::new(TelemetryCache::default());
+        let latest = Arc::downgrade(&cache);
+        let cache_subscription = Arc::new(subscribers.subscribe(
+            Some(axis),
+            Arc::new(move |frame: &Frame| {
+                if let Some(cache) = latest.upgrade() {
+                    cache.record(frame, Instant::now());
+                }
+            }),
+        ));
+
+        Self {
+            interface,
+            axis,
+            format: FrameFormat::Classic,
+            validation: Validation::Strict,
+            read_mode: ReadMode::Remote,
+            timeout: Some(DEFAULT_TIMEOUT),
+            on_bus_state: None,
+            on_bus_error: None,
+            recovery: None,
+            subscribers,
+            middleware,
+            pending,
+            cache,
+            cache_subscription,
+            retries: 0,
+            write_tolerance: None,
+            firmware: None,
+            stats: Arc::default(),
+            error_counts: Arc::new(Mutex::new(ErrorCounts::default())),
+        }
+    }
+
+    /// Call `callback` whenever the transport reports a [`BusState`]
+    /// change while waiting for a response.
+    pub fn on_bus_state(mut self, callback: impl Fn(BusState) + Send + Sync + 'static) -> Self {
+        self.on_bus_state = Some(Arc::new(callback));
+        self
+    }
+
+    /// Call `callback` for every [`BusError`] received while waiting for a
+    /// response.
+    ///
+    /// Forward the errors into a channel to consume them as a stream.
+    pub fn on_bus_error(mut self, callback: impl Fn(BusError) + Send + Sync + 'static) -> Self {
+        self.on_bus_error = Some(Arc::new(callback));
+        self
+    }
+
+    /// Call `callback` for every received frame that is not the response to
+    /// a request, such as heartbeats and cyclic telemetry.
+    ///
+    /// Only frames from or to `axis` are passed to `callback`. Can be called
+    /// several times to add more subscribers. Frames are only received while
+    /// a request is waiting for its response or while [`ODrive::listen`]
+    /// runs.
+    pub fn on_frame(self, callback: impl Fn(&Frame) + Send + Sync + 'static) -> Self {
+        let _ = self.subscribers.add(Some(self.axis), Arc::new(callback));
+        self
+    }
+
+    /// Pass every written and read frame through `middleware`.
+    ///
+    /// The middleware is shared with all drivers using the same transport,
+    /// see [`crate::middleware`].
+    pub fn with_middleware(self, middleware: impl Middleware + 'static) -> Self {
+        self.middleware.add(Arc::new(middleware));
+        self
+    }
+
+    /// Most recent telemetry received from the axis.
+    pub fn cache(&self) -> &TelemetryCache {
+        &self.cache
+    }
+
+    /// Link statistics so far.
+    pub fn stats(&self) -> Stats {
+        self.stats
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    fn count(&self, update: impl FnOnce(&mut Stats)) {
+        update(&mut self.stats.lock().unwrap_or_else(PoisonError::into_inner));
+    }
+
+    /// Number of bus errors received so far.
+    pub fn error_counts(&self) -> ErrorCounts {
+        *self
+            .error_counts
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Call `restart` when the bus goes [`BusState::BusOff`].
+    ///
+    /// The request that observed the bus off condition still fails, later
+    /// requests succeed once the controller has recovered. On Linux
+    /// [`restart_interface`](crate::transport::restart_interface) restarts a
+    /// SocketCAN interface. Without recovery the bus has to be restarted by
+    /// the user.
+    pub fn with_bus_recovery(
+        mut self,
+        restart: impl Fn() -> io::Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        self.recovery = Some(Arc::new(restart));
+        self
+    }
+
+    /// Handle an error returned while reading.
+    ///
+    /// Bus errors and bus state changes other than bus off are reported and
+    /// otherwise ignored, all other errors are returned.
+    fn read_error(&self, err: io::Error) -> Result<()> {
+        if let Some(error) = BusError::from_error(&err) {
+            trace_event!(debug, node = self.axis, %error, "bus error");
+            self.error_counts
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .record(error);
+            if let Some(callback) = &self.on_bus_error {
+                callback(error);
+            }
+            return Ok(());
+        }
+
+        let Some(state) = BusState::from_error(&err) else {
+            trace_event!(warn, node = self.axis, %err, "read failed");
+            return Err(err.into());
+        };
+        trace_event!(warn, node = self.axis, %state, "bus state changed");
+
+        if let Some(callback) = &self.on_bus_state {
+            callback(state);
+        }
+
+        if state != BusState::BusOff {
+            return Ok(());
+        }
+
+        if let Some(restart) = &self.recovery {
+            restart()?;
+        }
+        Err(err.into())
+    }
+
+    /// Replace the receive filters of the transport.
+    ///
+    /// An empty slice disables filtering. Filters are shared by everything
+    /// using the same transport.
+    pub fn set_filters(&self, filters: &[Filter]) -> Result<()> {
+        Ok(self.interface.set_filters(filters)?)
+    }
+
+    /// Wait at most `timeout` for each response, or forever if [`None`].
+    ///
+    /// Defaults to [`DEFAULT_TIMEOUT`]. Requests that time out fail with
+    /// [`crate::Error::Timeout`].
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Copy of this driver using `timeout` for its requests.
+    ///
+    /// Overrides the timeout for individual calls:
+    ///
+    /// ```ignore
+    /// let version = odrive.timeout(Duration::from_millis(50)).get_version()?;
+    /// ```
+    pub fn timeout(&self, timeout: Duration) -> Self {
+        self.clone().with_timeout(Some(timeout))
+    }
+
+    /// Check the length of responses according to `validation`.
+    ///
+    /// Defaults to [`Validation::Strict`].
+    pub fn with_validation(mut self, validation: Validation) -> Self {
+        self.validation = validation;
+        self
+    }
+
+    /// Send requests again up to `retries` times if they time out.
+    ///
+    /// Defaults to no retries.
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Read every endpoint written with [`Self::sdo_write`] back and fail
+    /// with [`crate::Error::WriteMismatch`] if it does not match, floats
+    /// within `tolerance`, see [`Value::approx_eq`].
+    ///
+    /// The ODrive silently clamps or rejects some values. Defaults to
+    /// [`None`], writing without reading back. Endpoints known to be
+    /// write-only and function calls are never read back.
+    pub fn with_write_verification(mut self, tolerance: Option<f64>) -> Self {
+        self.write_tolerance = tolerance;
+        self
+    }
+
+    /// Fail with [`crate::Error::UnsupportedByFirmware`] instead of timing
+    /// out when sending commands that `firmware` does not support, see
+    /// [`protocol::supported_since`].
+    ///
+    /// Defaults to [`None`], sending every command. Pass the firmware
+    /// reported by [`Self::get_version`] to enable the check:
+    ///
+    /// ```ignore
+    /// let version = odrive.get_version()?;
+    /// let odrive = odrive.with_firmware(Some(version.firmware()));
+    /// ```
+    pub fn with_firmware(mut self, firmware: Option<(u8, u8, u8)>) -> Self {
+        self.firmware = firmware;
+        self
+    }
+
+    /// Query messages without remote frames according to `mode`.
+    ///
+    /// Defaults to [`ReadMode::Remote`].
+    pub fn with_read_mode(mut self, mode: ReadMode) -> Self {
+        self.read_mode = mode;
+        self
+    }
+
+    /// Use `format` for all transmitted frames.
+    ///
+    /// Defaults to [`FrameFormat::Classic`]. Requests are sent as remote
+    /// frames regardless of the format since CAN FD has no remote frames.
+    pub fn with_frame_format(mut self, format: FrameFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    fn check_supported(&self, frame: &Frame) -> Result<()> {
+        match self.firmware {
+            Some(firmware) => protocol::check_supported(frame, firmware),
+            None => Ok(()),
+        }
+    }
+
+    /// Send a frame without waiting for a response.
+    fn send(&self, frame: Frame) -> Result<()> {
+        self.check_supported(&frame)?;
+        if let Some(frame) = self.middleware.outgoing(frame.with_format(self.format)) {
+            self.interface.write_frame(&frame)?;
+            trace_event!(trace, node = self.axis, id = ?frame.id(), data = ?frame.data(), "frame sent");
+            self.count(|stats| stats.frames_sent += 1);
+        }
+        Ok(())
+    }
+
+    /// Send a request and wait for its response.
+    fn request<M>(&self, request: Request<M>) -> Result<M> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!(
+            "request",
+            node = self.axis,
+            command = request.response_id().command()
+        )
+        .entered();
+        self.check_supported(request.frame())?;
+        let request = request.with_validation(self.validation);
+        let mut retries = 0;
+        loop {
+            let result = self.request_once(&request);
+            if !matches!(result, Err(crate::Error::Timeout)) {
+                return result;
+            }
+
+            self.count(|stats| stats.timeouts += 1);
+            trace_event!(warn, retries, "request timed out");
+            if retries == self.retries {
+                return result;
+            }
+            retries += 1;
+            self.count(|stats| stats.retries += 1);
+        }
+    }
+
+    fn request_once<M>(&self, request: &Request<M>) -> Result<M> {
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        if !request.frame().is_remote_frame() {
+            return self.exchange(request, true, deadline);
+        }
+
+        match &self.read_mode {
+            ReadMode::Remote => self.exchange(request, true, deadline),
+            ReadMode::Telemetry { max_age } => {
+                let command = request.response_id().command();
+                if let Some(latest) = self.cache.frame(command)
+                    && latest.age() <= *max_age
+                    && let Some(response) = request.response(&latest.value)
+                {
+                    return response;
+                }
+                self.exchange(request, false, deadline)
+            }
+            #[cfg(feature = "flat-endpoints")]
+            ReadMode::Sdo(endpoints) => {
+                let command = request.response_id().command();
+                let names = protocol::sdo_endpoints(command).ok_or_else(|| {
+                    crate::Error::Config(format!("no SDO endpoints for command {command}"))
+                })?;
+
+                let mut data = [0; 8];
+                for (name, value) in names.iter().zip(data.chunks_exact_mut(4)) {
+                    let (endpoint, _) = endpoints.resolve(name, Access::Read)?;
+                    let read = protocol::sdo_read(self.axis, endpoint)?;
+                    value.copy_from_slice(&self.exchange(&read, true, deadline)?);
+                }
+                request.decode_data(&data)
+            }
+        }
+    }
+
+    /// Wait for the response to `request`, after sending it if `write`.
+    fn exchange<M>(
+        &self,
+        request: &Request<M>,
+        write: bool,
+        deadline: Option<Instant>,
+    ) -> Result<M> {
+        let registration = self.pending.register(Some(Key::of(request)));
+        let sent = Instant::now();
+        if write {
+            self.send(*request.frame())?;
+        }
+        self.response(&registration, request, write.then_some(sent), deadline)
+    }
+
+    /// Wait for the response to `request` registered as `registration`,
+    /// sent at `sent` if it was sent.
+    fn response<M>(
+        &self,
+        registration: &Registration<'_>,
+        request: &Request<M>,
+        sent: Option<Instant>,
+        deadline: Option<Instant>,
+    ) -> Result<M> {
+        loop {
+            match registration.wait_turn(deadline) {
+                None => return Err(crate::Error::Timeout),
+                Some(Turn::Frame(frame)) => match request.response(&frame) {
+                    Some(response) => {
+                        self.record_response(request, sent.map(|sent| sent.elapsed()), &response);
+                        self.cache.record(&frame, Instant::now());
+                        return response;
+                    }
+                    None => self.dispatch(&frame),
+                },
+                Some(Turn::Read(reader)) => {
+                    let result = match deadline {
+                        Some(deadline) => {
+                            let remaining = deadline.saturating_duration_since(Instant::now());
+                            self.interface.read_frame_timeout(remaining)
+                        }
+                        None => self.interface.read_frame().map(Some),
+                    };
+                    match result {
+                        Ok(Some(frame)) => self.receive(reader, Ok(frame))?,
+                        Ok(None) => return Err(crate::Error::Timeout),
+                        Err(err) => self.receive(reader, Err(err))?,
+                    }
+                }
+            }
+        }
+    }
+
+    /// Count a response that took `latency` after sending the request.
+    fn record_response<M>(
+        &self,
+        request: &Request<M>,
+        latency: Option<Duration>,
+        response: &Result<M>,
+    ) {
+        #[cfg(feature = "tracing")]
+        match response {
+            Ok(_) => tracing::debug!(?latency, "response received"),
+            Err(error) => tracing::warn!(%error, "invalid response"),
+        }
+        self.count(|stats| {
+            if response.is_err() {
+                stats.decode_failures += 1;
+            }
+            if let Some(latency) = latency {
+                stats.record_latency(request.response_id().command(), latency);
+            }
+        });
+    }
+
+    /// Route a received frame to a waiting request or the subscribers.
+    ///
+    /// The frame is routed before the reader role is released, so a request
+    /// taking over the role cannot miss its response.
+    fn receive(&self, reader: Reader<'_>, result: io::Result<Frame>) -> Result<()> {
+        if result.is_ok() {
+            self.count(|stats| stats.frames_received += 1);
+        }
+        #[cfg(feature = "tracing")]
+        if let Ok(frame) = &result {
+            tracing::trace!(id = ?frame.id(), data = ?frame.data(), "frame received");
+        }
+        let result = result.map(|frame| {
+            self.middleware
+                .incoming(frame)
+                .and_then(|frame| self.pending.route(frame))
+        });
+        drop(reader);
+        match result {
+            Ok(Some(frame)) => {
+                self.dispatch(&frame);
+                Ok(())
+            }
+            Ok(None) => Ok(()),
+            Err(err) => self.read_error(err),
+        }
+    }
+
+    /// Pass a frame that is not a response to the subscribers.
+    fn dispatch(&self, frame: &Frame) {
+        self.subscribers.dispatch(frame);
+    }
+
+    /// Receive frames and pass them to the [`ODrive::on_frame`] subscribers
+    /// until reading fails.
+    ///
+    /// Responses received while listening are routed to the waiting
+    /// requests.
+    pub fn listen(&self) -> Result<()> {
+        let registration = self.pending.register(None);
+        loop {
+            if let Some(Turn::Read(reader)) = registration.wait_turn(None) {
+                let result = self.interface.read_frame();
+                self.receive(reader, result)?;
+            }
+        }
+    }
+
+    /// Split into a sending and a receiving half.
+    ///
+    /// The sending half is the driver itself, the [`Receiver`] yields every
+    /// received frame that is not the response to a request. Both can be
+    /// used from different threads at the same time.
+    pub fn split(self) -> (Self, Receiver<T>) {
+        let inbox = Arc::new(Mutex::new(VecDeque::new()));
+        let buffered = inbox.clone();
+        let callback = Arc::new(move |frame: &Frame| {
+            let mut frames = buffered.lock().unwrap_or_else(PoisonError::into_inner);
+            if frames.len() == INBOX_CAPACITY {
+                frames.pop_front();
+            }
+            frames.push_back(*frame);
+        });
+        let subscriber = self.subscribers.add(Some(self.axis), callback);
+
+        let receiver = Receiver {
+            odrive: self.clone(),
+            inbox,
+            subscriber,
+        };
+        (self, receiver)
+    }
+
+    /// Iterator over the cyclic messages received from the axis.
+    ///
+    /// Frames that are not telemetry or fail to decode are skipped. The
+    /// iterator ends when reading from the transport fails.
+    pub fn telemetry(&self) -> Telemetry<T> {
+        Telemetry {
+            receiver: self.clone().split().1,
+            done: false,
+        }
+    }
+
+    /// Wait for a heartbeat reporting the trajectory as done.
+    ///
+    /// Only heartbeats received after the call are considered. A heartbeat
+    /// the ODrive sent before it started the last commanded trajectory may
+    /// still report the previous one as done.
+    pub fn trajectory_done(&self) -> Result<()> {
+        let (_, receiver) = self.clone().split();
+        loop {
+            let frame = receiver.recv()?;
+            if let Some(Ok(TelemetryMessage::Heartbeat(heartbeat))) =
+                TelemetryMessage::decode(&frame)
+                && heartbeat.trajectory_done
+            {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Get version information.
+    pub fn get_version(&self) -> Result<Version> {
+        self.request(protocol::get_version(self.axis)?)
+    }
+
+    /// Get version information, failing with
+    /// [`crate::Error::FirmwareTooOld`] if the firmware is older than
+    /// `major.minor.revision`.
+    pub fn require_firmware(&self, major: u8, minor: u8, revision: u8) -> Result<Version> {
+        let version = self.get_version()?;
+        version.require_firmware(major, minor, revision)?;
+        Ok(version)
+    }
+
+    /// Cause the axis to disarm.
+    pub fn estop(&self) -> Result<()> {
+        self.send(protocol::estop(self.axis)?)
+    }
+
+    /// Get errors.
+    pub fn get_error(&self) -> Result<Error> {
+        self.request(protocol::get_error(self.axis)?)
+    }
+
+    /// Write an arbitrary parameter.
+    ///
+    /// Takes a [`Value`] or a primitive such as `1.5f32`. Fails with
+    /// [`crate::Error::ValueTooWide`] for 64-bit values.
+    pub fn sdo_write(&self, endpoint: u16, value: impl Into<Value>) -> Result<()> {
+        self.write_value(endpoint, value.into(), true)
+    }
+
+    /// Write `value`, reading it back if `readable` and write verification
+    /// is enabled.
+    fn write_value(&self, endpoint: u16, value: Value, readable: bool) -> Result<()> {
+        self.send(protocol::sdo_write(self.axis, endpoint, value)?)?;
+        if let Some(tolerance) = self.write_tolerance
+            && readable
+        {
+            let read = self.sdo_read(endpoint, value.kind())?;
+            if !value.approx_eq(&read, tolerance) {
+                return Err(crate::Error::WriteMismatch {
+                    endpoint,
+                    written: value,
+                    read,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Read an arbitrary parameter.
+    ///
+    /// Fails with [`crate::Error::ValueTooWide`] for 64-bit kinds.
+    pub fn sdo_read(&self, endpoint: u16, kind: ValueKind) -> Result<Value> {
+        protocol::check_sdo_kind(kind)?;
+        let data = self.request(protocol::sdo_read(self.axis, endpoint)?)?;
+        Ok(Value::from_le_bytes(kind, data))
+    }
+
+    /// Read several arbitrary parameters, keeping up to `window` requests
+    /// in flight.
+    ///
+    /// Sends the next read without waiting for the previous response,
+    /// responses are matched by endpoint. The results are in the order of
+    /// `reads`, a failed read does not stop the others.
+    pub fn sdo_read_many(&self, reads: &[(u16, ValueKind)], window: usize) -> Vec<Result<Value>> {
+        let window = window.max(1);
+        let mut results = Vec::with_capacity(reads.len());
+        let mut in_flight = VecDeque::with_capacity(window);
+        let mut next = reads.iter();
+        loop {
+            while in_flight.len() < window
+                && let Some(&(endpoint, kind)) = next.next()
+            {
+                let sent = protocol::check_sdo_kind(kind)
+                    .and_then(|()| protocol::sdo_read(self.axis, endpoint))
+                    .and_then(|read| {
+                        let read = read.with_validation(self.validation);
+                        let registration = self.pending.register(Some(Key::of(&read)));
+                        let sent = Instant::now();
+                        self.send(*read.frame())?;
+                        Ok((read, registration, sent))
+                    });
+                in_flight.push_back((endpoint, kind, sent));
+            }
+            let Some((endpoint, kind, sent)) = in_flight.pop_front() else {
+                return results;
+            };
+
+            let first = sent.and_then(|(read, registration, sent)| {
+                let deadline = self.timeout.map(|timeout| sent + timeout);
+                self.response(&registration, &read, Some(sent), deadline)
+            });
+            let result = match first {
+                // fall back to a read with retries
+                Err(crate::Error::Timeout) if self.retries > 0 => {
+                    self.count(|stats| stats.timeouts += 1);
+                    self.sdo_read(endpoint, kind)
+                }
+                Err(err) => Err(err),
+                Ok(data) => Ok(Value::from_le_bytes(kind, data)),
+            };
+            results.push(result);
+        }
+    }
+
+    /// Read an arbitrary parameter of type `V`.
+    ///
+    /// ```ignore
+    /// let limit = odrive.sdo_read_as::<f32>(endpoint)?;
+    /// ```
+    pub fn sdo_read_as<V: SdoType>(&self, endpoint: u16) -> Result<V> {
+        let value = self.sdo_read(endpoint, V::KIND)?;
+        V::from_value(value).ok_or_else(|| crate::Error::Protocol("SDO value kind".into()))
+    }
+
+    /// Read the endpoint `name` as the kind listed in `endpoints`.
+    ///
+    /// Fails with [`crate::Error::Config`] if the endpoint does not exist
+    /// or is write-only.
+    #[cfg(feature = "flat-endpoints")]
+    pub fn read_named(&self, endpoints: &FlatEndpoints, name: &str) -> Result<Value> {
+        let (endpoint, kind) = endpoints.resolve(name, Access::Read)?;
+        self.sdo_read(endpoint, kind)
+    }
+
+    /// Write `value` to the endpoint `name`.
+    ///
+    /// Fails with [`crate::Error::Config`] if the endpoint does not exist,
+    /// is read-only or `value` is not of its kind.
+    #[cfg(feature = "flat-endpoints")]
+    pub fn write_named(&self, endpoints: &FlatEndpoints, name: &str, value: Value) -> Result<()> {
+        let endpoint = endpoints.resolve_write(name, &value)?;
+        let readable = endpoints.access(name).is_none_or(Access::is_readable);
+        self.write_value(endpoint, value, readable)
+    }
+
+    /// Call the firmware function `name` with `args`, returns its outputs.
+    ///
+    /// Writes each argument to its input endpoint, then writes the function
+    /// endpoint to call it and reads the output endpoints. Fails with
+    /// [`crate::Error::Config`] if the function does not exist or `args`
+    /// do not match its inputs.
+    #[cfg(feature = "flat-endpoints")]
+    pub fn call_function(
+        &self,
+        endpoints: &FlatEndpoints,
+        name: &str,
+        args: &[Value],
+    ) -> Result<Vec<Value>> {
+        let function = endpoints.resolve_call(name, args)?;
+        for (input, arg) in function.inputs.iter().zip(args) {
+            self.sdo_write(input.id, *arg)?;
+        }
+        self.write_value(function.id, Value::U32(0), false)?;
+
+        let mut outputs = Vec::with_capacity(function.outputs.len());
+        for output in &function.outputs {
+            outputs.push(self.sdo_read(output.id, output.kind)?);
+        }
+        Ok(outputs)
+    }
+
+    /// Read a typed endpoint, see [`FlatEndpoints::typed`].
+    #[cfg(feature = "flat-endpoints")]
+    pub fn read_endpoint<V: SdoType>(&self, endpoint: &Endpoint<V>) -> Result<V> {
+        endpoint.check(Access::Read)?;
+        self.sdo_read_as(endpoint.id())
+    }
+
+    /// Write a typed endpoint, see [`FlatEndpoints::typed`].
+    #[cfg(feature = "flat-endpoints")]
+    pub fn write_endpoint<V: SdoType>(&self, endpoint: &Endpoint<V>, value: V) -> Result<()> {
+        endpoint.check(Access::Write)?;
+        let readable = endpoint.access().is_readable();
+        self.write_value(endpoint.id(), value.into(), readable)
+    }
+
+    /// Change the axis state.
+    pub fn set_axis_state(&self, state: AxisState) -> Result<()> {
+        self.send(protocol::set_axis_state(self.axis, state)?)
+    }
+
+    /// Get the encoder estimates.
+    pub fn get_encoder_estimates(&self) -> Result<EncoderEstimate> {
+        self.request(protocol::get_encoder_estimates(self.axis)?)
+    }
+
+    /// Set the control loop mode.
+    pub fn set_controller_mode(
+        &self,
+        control_mode: ControlMode,
+        input_mode: InputMode,
+    ) -> Result<()> {
+        self.send(protocol::set_controller_mode(
+            self.axis,
+            control_mode,
+            input_mode,
+        )?)
+    }
+
+    /// Set input position.
+    ///
+    /// - `position` rev.
+    /// - `velocity` 0.001 rev/s (default).
+    /// - `torque` 0.001 Nm (default).
+    ///
+    /// Velocity and torque scale is configurable.
+    pub fn set_input_position(&self, position: f32, velocity: i16, torque: i16) -> Result<()> {
+        self.send(protocol::set_input_position(
+            self.axis, position, velocity, torque,
+        )?)
+    }
+
+    /// Set input velocity.
+    ///
+    /// - `velocity` rev/s.
+    /// - `torque` Nm.
+    pub fn set_input_velocity(&self, velocity: f32, torque: f32) -> Result<()> {
+        self.send(protocol::set_input_velocity(self.axis, velocity, torque)?)
+    }
+
+    /// Set input torque.
+    ///
+    /// - `torque` Nm.
+    pub fn set_input_torque(&self, torque: f32) -> Result<()> {
+        self.send(protocol::set_input_torque(self.axis, torque)?)
+    }
+
+    /// Set limits.
+    ///
+    /// - `velocity` limit rev/s.
+    /// - `current` limit amps.
+    pub fn set_limits(&self, velocity: f32, current: f32) -> Result<()> {
+        self.send(protocol::set_limits(self.axis, velocity, current)?)
+    }
+
+    /// Set trajectory velocity limit.
+    ///
+    /// `velocity` limit rev/s.
+    pub fn set_trajectory_velocity_limit(&self, velocity: f32) -> Result<()> {
+        self.send(protocol::set_trajectory_velocity_limit(
+            self.axis, velocity,
+        )?)
+    }
+
+    /// Set trajectory acceleration limits.
+    ///
+    /// `acceleration` limit rev/s^2.
+    /// `deceleration` limit rev/s^2.
+    pub fn set_trajectory_acceleration_limit(
+        &self,
+        acceleration: f32,
+        deceleration: f32,
+    ) -> Result<()> {
+        self.send(protocol::set_trajectory_acceleration_limit(
+            self.axis,
+            acceleration,
+            deceleration,
+        )?)
+    }
+
+    /// Set trajectory inertia.
+    ///
+    /// `inertia` Nm/(rev/s^2).
+    pub fn set_trajectory_inertia(&self, inertia: f32) -> Result<()> {
+        self.send(protocol::set_trajectory_inertia(self.axis, inertia)?)
+    }
+
+    /// Get motor current.
+    ///
+    /// Response: (setpoint, measured)
+    pub fn get_iq(&self) -> Result<(f32, f32)> {
+        self.request(protocol::get_iq(self.axis)?)
+    }
+
+    /// Get temperature.
+    pub fn get_temperature(&self) -> Result<Temperature> {
+        self.request(protocol::get_temperature(self.axis)?)
+    }
+
+    /// Reboot the device.
+    pub fn reboot(&self) -> Result<()> {
+        self.send(protocol::reboot(self.axis)?)
+    }
+
+    /// Get bus voltage and current.
+    pub fn get_bus_voltage_current(&self) -> Result<BusVoltageCurrent> {
+        self.request(protocol::get_bus_voltage_current(self.axis)?)
+    }
+
+    /// Save configuration.
+    pub fn save_configuration(&self) -> Result<()> {
+        self.send(protocol::save_configuration(self.axis)?)
+    }
+
+    /// Erase configuration.
+    pub fn erase_configuration(&self) -> Result<()> {
+        self.send(protocol::erase_configuration(self.axis)?)
+    }
+
+    /// Enter DFU mode 2.
+    pub fn enter_dfu_mode2(&self) -> Result<()> {
+        self.send(protocol::enter_dfu_mode2(self.axis)?)
+    }
+
+    /// Clear disarm reason and procedure result.
+    pub fn clear_errors(&self, identify: bool) -> Result<()> {
+        self.send(protocol::clear_errors(self.axis, identify)?)
+    }
+
+    /// Set the absolute position estimate.
+    ///
+    /// - `position` rev.
+    pub fn set_absolute_position(&self, position: f32) -> Result<()> {
+        self.send(protocol::set_absolute_position(self.axis, position)?)
+    }
+
+    /// Set position gain.
+    ///
+    /// - `gain` (rev/s)/rev.
+    pub fn set_position_gain(&self, gain: f32) -> Result<()> {
+        self.send(protocol::set_position_gain(self.axis, gain)?)
+    }
+
+    /// Set velocity gains.
+    ///
+    /// - `gain` Nm/(rev/s).
+    /// - `integrator_gain` Nm/rev.
+    pub fn set_velocity_gains(&self, gain: f32, integrator_gain: f32) -> Result<()> {
+        self.send(protocol::set_velocity_gains(
+            self.axis,
+            gain,
+            integrator_gain,
+        )?)
+    }
+
+    /// Get torque values.
+    pub fn get_torques(&self) -> Result<Torque> {
+        self.request(protocol::get_torques(self.axis)?)
+    }
+
+    /// Get power values.
+    pub fn get_powers(&self) -> Result<Power> {
+        self.request(protocol::get_powers(self.axis)?)
+    }
+
+    /// Get all telemetry values, one request after the other.
+    pub fn snapshot(&self) -> Result<Snapshot> {
+        Ok(Snapshot {
+            encoder: self.get_encoder_estimates()?,
+            iq: self.get_iq()?,
+            temperature: self.get_temperature()?,
+            bus: self.get_bus_voltage_current()?,
+            torques: self.get_torques()?,
+            powers: self.get_powers()?,
+            captured: Instant::now(),
+        })
+    }
+
+    /// Read every read-write endpoint into a configuration, an object of
+    /// endpoint names and values that
+    /// [`apply_configuration`](Self::apply_configuration) writes back, for
+    /// example to clone a tuned drive onto a spare.
+    ///
+    /// The reads are sent in batches of [`SDO_WINDOW`] without waiting
+    /// for each response, `progress` is called with the number of endpoints
+    /// read and the total after every batch.
+    /// See [`flat_endpoints::config_to_toml`](crate::flat_endpoints::config_to_toml)
+    /// to store it as TOML.
+    #[cfg(feature = "flat-endpoints")]
+    pub fn backup_configuration(
+        &self,
+        endpoints: &FlatEndpoints,
+        mut progress: impl FnMut(usize, usize),
+    ) -> Result<serde_json::Value> {
+        let configuration = endpoints.configuration_endpoints();
+        let total = configuration.len();
+        let mut backup = serde_json::Map::with_capacity(total);
+        for batch in configuration.chunks(SDO_WINDOW) {
+            let reads: Vec<_> = batch
+                .iter()
+                .map(|&(_, endpoint, kind)| (endpoint, kind))
+                .collect();
+            let values = self.sdo_read_many(&reads, SDO_WINDOW);
+            for (&(name, ..), value) in batch.iter().zip(values) {
+                backup.insert(name.to_owned(), value?.to_json());
+            }
+            progress(backup.len(), total);
+        }
+        Ok(backup.into())
+    }
+
+    /// Apply the configuration of profile `name` in `profiles`.
+    ///
+    /// Like [`Self::apply_configuration`], but the current values are read
+    /// first and written back if any write fails, so the drive is not left
+    /// between two profiles. Write-only endpoints cannot be restored.
+    #[cfg(feature = "flat-endpoints")]
+    pub fn apply_profile(
+        &self,
+        endpoints: &FlatEndpoints,
+        profiles: &Profiles,
+        name: &str,
+    ) -> Result<ApplyReport> {
+        let config = profiles.resolve(name)?;
+        let mut previous = Vec::new();
+        for (_, endpoint, value, access) in endpoints.resolve_configuration(config)? {
+            if access.is_readable() {
+                previous.push((endpoint, self.sdo_read(endpoint, value.kind())?));
+            }
+        }
+
+        let report = self.apply_configuration(endpoints, config, Verify::None)?;
+        if !report.failed.is_empty() {
+            for (endpoint, value) in previous {
+                self.sdo_write(endpoint, value)?;
+            }
+        }
+        Ok(report)
+    }
+
+    /// Entries of `config` the device does not match, for detecting drift
+    /// or checking a drive before closed loop control.
+    ///
+    /// Floats match within `tolerance` relative to the expected value, other
+    /// kinds only if equal. Write-only entries cannot be read and are not
+    /// compared.
+    #[cfg(feature = "flat-endpoints")]
+    pub fn diff_configuration(
+        &self,
+        endpoints: &FlatEndpoints,
+        config: &serde_json::Value,
+        tolerance: f64,
+    ) -> Result<Vec<Difference>> {
+        let entries: Vec<_> = endpoints
+            .resolve_configuration(config)?
+            .into_iter()
+            .filter(|&(.., access)| access.is_readable())
+            .collect();
+        let reads: Vec<_> = entries
+            .iter()
+            .map(|&(_, endpoint, expected, _)| (endpoint, expected.kind()))
+            .collect();
+        let values = self.sdo_read_many(&reads, SDO_WINDOW);
+
+        let mut differences = Vec::new();
+        for ((key, _, expected, _), actual) in entries.into_iter().zip(values) {
+            differences.extend(Difference::new(key, expected, actual?, tolerance));
+        }
+        Ok(differences)
+    }
+
+    /// Entries of `config` that differ from the device, without writing
+    /// anything.
+    ///
+    /// Reads the current value of every entry, write-only entries are
+    /// always listed. Fails like [`Self::apply_configuration`] if `config`
+    /// cannot be applied.
+    #[cfg(feature = "flat-endpoints")]
+    pub fn dry_run_configuration(
+        &self,
+        endpoints: &FlatEndpoints,
+        config: &serde_json::Value,
+    ) -> Result<Vec<Change>> {
+        let mut changes = Vec::new();
+        for (key, endpoint, desired, access) in endpoints.resolve_configuration(config)? {
+            let current = if access.is_readable() {
+                Some(self.sdo_read(endpoint, desired.kind())?)
+            } else {
+                None
+            };
+            changes.extend(Change::new(key, current, desired));
+        }
+        Ok(changes)
+    }
+
+    /// Write every entry of `config`, an object of endpoint names and
+    /// values, and read them back according to `verify`.
+    ///
+    /// Fails with [`crate::Error::Config`] without writing anything if an
+    /// entry does not exist, is read-only or its value does not fit the
+    /// endpoint. Failed writes and read backs do not stop the others, they
+    /// are listed in the returned [`ApplyReport`].
+    #[cfg(feature = "flat-endpoints")]
+    pub fn apply_configuration(
+        &self,
+        endpoints: &FlatEndpoints,
+        config: &serde_json::Value,
+        verify: Verify,
+    ) -> Result<ApplyReport> {
+        let writes = endpoints.resolve_configuration(config)?;
+
+        let mut report = ApplyReport::default();
+        let mut written = Vec::with_capacity(writes.len());
+        for (key, endpoint, value, access) in writes {
+            match self.write_value(endpoint, value, access.is_readable()) {
+                Ok(()) => written.push((key, endpoint, value, access)),
+                Err(err) => report.failed.push((key, err)),
+            }
+        }
+
+        // read back once everything is written, so later writes that
+        // change earlier endpoints are caught too
+        for (key, endpoint, value, access) in written {
+            if verify == Verify::None || !access.is_readable() {
+                report.applied.push(key);
+                continue;
+            }
+            let read = self.sdo_read(endpoint, value.kind());
+            report.check(key, value, read, verify);
+        }
+
+        Ok(report)
+    }
+
+    /// Restore a configuration such as a backup, see
+    /// [`Self::backup_configuration`].
+    ///
+    /// Writes `config`, saves it and waits up to [`REBOOT_TIMEOUT`] for the
+    /// drive to reboot, that is for its heartbeats to stop for
+    /// [`REBOOT_SILENCE`] and resume. Then reads every entry again. The
+    /// configuration is not saved if any write failed. Entries that differ
+    /// after the reboot, such as values clamped by the firmware, are listed
+    /// in the report, write-only entries cannot be checked.
+    #[cfg(feature = "flat-endpoints")]
+    pub fn restore_configuration(
+        &self,
+        endpoints: &FlatEndpoints,
+        config: &serde_json::Value,
+    ) -> Result<RestoreReport> {
+        let apply = self.apply_configuration(endpoints, config, Verify::None)?;
+        if !apply.failed.is_empty() {
+            return Ok(RestoreReport {
+                apply,
+                saved: false,
+                residual: Vec::new(),
+            });
+        }
+
+        // subscribe first so the heartbeat after the reboot is not missed
+        let (_, receiver) = self.clone().split();
+        self.save_configuration()?;
+        let heartbeat = |until: Instant| -> Result<bool> {
+            loop {
+                let remaining = until.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    return Ok(false);
+                }
+                let Some(frame) = receiver.recv_timeout(remaining)? else {
+                    return Ok(false);
+                };
+                if let Some(Ok(TelemetryMessage::Heartbeat(_))) = TelemetryMessage::decode(&frame) {
+                    return Ok(true);
+                }
+            }
+        };
+        let deadline = Instant::now() + REBOOT_TIMEOUT;
+        // heartbeats sent before the reset may still arrive
+        while heartbeat((Instant::now() + REBOOT_SILENCE).min(deadline))? {
+            if Instant::now() >= deadline {
+                return Err(crate::Error::Timeout);
+            }
+        }
+        if !heartbeat(deadline)? {
+            return Err(crate::Error::Timeout);
+        }
+
+        // the drive may still be starting up, retry reads that time out
+        let mut retries = 0;
+        let changes = loop {
+            match self.dry_run_configuration(endpoints, config) {
+                Err(crate::Error::Timeout) if retries < self.retries.max(2) => retries += 1,
+                result => break result?,
+            }
+        };
+        Ok(RestoreReport {
+            apply,
+            saved: true,
+            residual: changes
+                .into_iter()
+                .filter(|change| change.current.is_some())
+                .collect(),
+        })
+    }
+}
+
+/// Several ODrives sharing one transport.
+///
+/// Every axis is a separate [`ODrive`] driver, received frames are routed to
+/// the driver of their node whichever driver read them.
+///
+/// ```no_run
+/// use odrive::blocking::Bus;
+/// use socketcan::Socket;
+///
+/// let socket = socketcan::CanSocket::open("can0").unwrap();
+/// let bus = Bus::new(socket);
+/// let left = bus.axis(1).unwrap();
+/// let right = bus.axis(2).unwrap();
+///
+/// let left_iq = left.get_iq()?;
+/// let right_iq = right.get_iq()?;
+/// # Ok::<(), odrive::Error>(())
+/// ```
+pub struct Bus<T> {
+    interface: Arc<T>,
+    pending: Arc<Pending>,
+    subscribers: Arc<Subscribers>,
+    middleware: Arc<Chain>,
+    filters: Mutex<Vec<Filter>>,
+}
+
+impl<T: Transport> Bus<T> {
+    /// Share `interface` between several ODrives.
+    pub fn new(interface: T) -> Self {
+        Self {
+            interface: Arc::new(interface),
+            pending: Arc::default(),
+            subscribers: Arc::default(),
+            middleware: Arc::default(),
+            filters: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Driver for `axis` on this bus.
+    ///
+    /// Adds a [`Filter::node`] filter for `axis` to the transport, failing
+    /// to install it is not an error. Fails with
+    /// [`crate::Error::InvalidNode`] if `axis` is larger than 63.
+    pub fn axis(&self, axis: u8) -> Result<ODrive<T>> {
+        let filter = Filter::node(axis)?;
+        let mut filters = self.filters.lock().unwrap_or_else(PoisonError::into_inner);
+        if !filters.contains(&filter) {
+            filters.push(filter);
+            let _ = self.interface.set_filters(&filters);
+        }
+
+        Ok(ODrive::with_shared(
+            self.interface.clone(),
+            axis,
+            self.pending.clone(),
+            self.subscribers.clone(),
+            self.middleware.clone(),
+        ))
+    }
+
+    /// Call `callback` for every received frame that is not the response to
+    /// a request, whichever node it belongs to.
+    pub fn on_frame(self, callback: impl Fn(&Frame) + Send + Sync + 'static) -> Self {
+        let _ = self.subscribers.add(None, Arc::new(callback));
+        self
+    }
+
+    /// Pass every frame written or read by the drivers of this bus through
+    /// `middleware`, see [`crate::middleware`].
+    pub fn with_middleware(self, middleware: impl Middleware + 'static) -> Self {
+        self.middleware.add(Arc::new(middleware));
+        self
+    }
+}
+
+/// Number of frames buffered for a [`Receiver`] before the oldest is dropped.
+const INBOX_CAPACITY: usize = 256;
+
+/// Receiving half of a split [`ODrive`], see [`ODrive::split`].
+pub struct Receiver<T> {
+    odrive: ODrive<T>,
+    subscriber: u64,
+    inbox: Arc<Mutex<VecDeque<Frame>>>,
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.odrive.subscribers.remove(self.subscriber);
+    }
+}
+
+impl<T: Transport> Receiver<T> {
+    /// Receive the next frame that is not the response to a request.
+    ///
+    /// Frames received by the sending half are buffered, the oldest are
+    /// dropped if they are not taken in time.
+    pub fn recv(&self) -> Result<Frame> {
+        let registration = self.odrive.pending.register(None);
+        loop {
+            if let Some(frame) = self.pop() {
+                return Ok(frame);
+            }
+            if let Some(Turn::Read(reader)) = registration.wait_turn(None) {
+                // a frame may have been buffered while waiting
+                if let Some(frame) = self.pop() {
+                    return Ok(frame);
+                }
+                let result = self.odrive.interface.read_frame();
+                self.odrive.receive(reader, result)?;
+            }
+        }
+    }
+
+    /// Like [`Self::recv`], but returns [`None`] if no frame was received
+    /// within `timeout`.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<Option<Frame>> {
+        let deadline = Instant::now() + timeout;
+        let registration = self.odrive.pending.register(None);
+        loop {
+            if let Some(frame) = self.pop() {
+                return Ok(Some(frame));
+            }
+            let Some(turn) = registration.wait_turn(Some(deadline)) else {
+                return Ok(None);
+            };
+            if let Turn::Read(reader) = turn {
+                if let Some(frame) = self.pop() {
+                    return Ok(Some(frame));
+                }
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                match self.odrive.interface.read_frame_timeout(remaining) {
+                    Ok(Some(frame)) => self.odrive.receive(reader, Ok(frame))?,
+                    Ok(None) => return Ok(None),
+                    Err(err) => self.odrive.receive(reader, Err(err))?,
+                }
+            }
+        }
+    }
+
+    fn pop(&self) -> Option<Frame> {
+        self.inbox
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pop_front()
+    }
+}
+
+/// Decoded telemetry of an axis, see [`ODrive::telemetry`].
+pub struct Telemetry<T> {
+    receiver: Receiver<T>,
+    done: bool,
+}
+
+impl<T: Transport> Iterator for Telemetry<T> {
+    type Item = TelemetryMessage;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            match self.receiver.recv() {
+                Ok(frame) => {
+                    if let Some(Ok(message)) = TelemetryMessage::decode(&frame) {
+                        return Some(message);
+                    }
+                }
+                Err(_) => self.done = true,
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Drive that ignores every request and keeps sending heartbeats.
+    #[cfg(feature = "flat-endpoints")]
+    struct Heartbeating(crate::mock::MockODrive);
+
+    #[cfg(feature = "flat-endpoints")]
+    impl Transport for Heartbeating {
+        fn write_frame(&self, _: &Frame) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn read_frame(&self) -> io::Result<Frame> {
+            std::thread::sleep(Duration::from_millis(50));
+            self.0.heartbeat().map_err(io::Error::other)
+        }
+    }
+
+    #[cfg(feature = "flat-endpoints")]
+    #[test]
+    fn restore_times_out_without_reboot() {
+        let endpoints = FlatEndpoints::from_json(serde_json::json!({"endpoints": {
+            "axis0.requested_state": {"id": 9, "type": "uint32", "access": "w"}
+        }}))
+        .unwrap();
+        let odrive = ODrive::new(Heartbeating(crate::mock::MockODrive::new(1)), 1).unwrap();
+
+        let start = Instant::now();
+        let backup = serde_json::json!({"axis0.requested_state": 1});
+        assert!(matches!(
+            odrive.restore_configuration(&endpoints, &backup),
+            Err(crate::Error::Timeout)
+        ));
+        assert!(start.elapsed() >= REBOOT_TIMEOUT);
+        assert!(start.elapsed() < REBOOT_TIMEOUT + Duration::from_secs(1));
+    }
+
+    #[test]
+    fn dropped_drivers_unsubscribe() {
+        let bus = Bus::new(crate::mock::MockBus::new());
+        let subscribers = bus.subscribers.clone();
+        let odrive = bus.axis(1).unwrap();
+        let other = odrive.clone();
+        for _ in 0..3 {
+            drop(bus.axis(2).unwrap());
+        }
+        drop(odrive);
+        assert_eq!(subscribers.len(), 1);
+        drop(other);
+        assert_eq!(subscribers.len(), 0);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn zero_timeout_does_not_block() {
+        use socketcan::{CanAddr, Socket};
+
+        // bound to every interface, skipped where the kernel has no CAN
+        let Ok(socket) = socketcan::CanSocket::open_addr(&CanAddr::new(0)) else {
+            return;
+        };
+        for timeout in [Duration::ZERO, Duration::from_nanos(999)] {
+            assert!(
+                Transport::read_frame_timeout(&socket, timeout)
+                    .unwrap()
+                    .is_none()
+            );
+        }
+    }
+}
@@ -0,0 +1,196 @@
+//! # Telemetry cache
+//!
+//! Every driver keeps the most recent value of each cyclic message received
+//! from its axis, see [`ODrive::cache`](crate::can::ODrive::cache). Control
+//! loops can read the freshest value without a round trip on the bus:
+//!
+//! ```no_run
+//! # tokio_test::block_on(async {
+//! use odrive::can::ODrive;
+//! use std::time::Duration;
+//!
+//! let socket = socketcan::tokio::CanSocket::open("can0").unwrap();
+//! let odrive = ODrive::new(socket, 1).unwrap();
+//! tokio::spawn({
+//!     let odrive = odrive.clone();
+//!     async move { odrive.listen().await }
+//! });
+//!
+//! if let Some(estimate) = odrive.cache().latest_encoder_estimate()
+//!     && estimate.age() < Duration::from_millis(10)
+//! {
+//!     println!("position {}", estimate.value.position);
+//! }
+//! # });
+//! ```
+//!
+//! Responses to requests also update the cache. Otherwise it is only
+//! updated while frames are received, that is while a request is waiting
+//! for its response or the driver is listening.
+
+use crate::protocol::{
+    BusVoltageCurrent, EncoderEstimate, Frame, Heartbeat, Power, TelemetryMessage, Temperature,
+    Torque,
+};
+use cansimple::Id;
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+/// Value with the time it was received.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Timestamped<T> {
+    pub value: T,
+    pub received: Instant,
+}
+
+impl<T> Timestamped<T> {
+    /// Time since the value was received.
+    pub fn age(&self) -> Duration {
+        self.age_at(Instant::now())
+    }
+
+    /// Time from receiving the value to `now`, for clocks other than the
+    /// system's, see [`Timer::now`](crate::transport::Timer::now).
+    pub fn age_at(&self, now: Instant) -> Duration {
+        now.saturating_duration_since(self.received)
+    }
+}
+
+fn stamp<T>(value: T, received: Instant) -> Option<Timestamped<T>> {
+    Some(Timestamped { value, received })
+}
+
+#[derive(Debug, Default)]
+struct Latest {
+    heartbeat: Option<Timestamped<Heartbeat>>,
+    encoder_estimate: Option<Timestamped<EncoderEstimate>>,
+    iq: Option<Timestamped<(f32, f32)>>,
+    temperature: Option<Timestamped<Temperature>>,
+    bus_voltage_current: Option<Timestamped<BusVoltageCurrent>>,
+    torques: Option<Timestamped<Torque>>,
+    powers: Option<Timestamped<Power>>,
+    frames: HashMap<u8, Timestamped<Frame>>,
+}
+
+/// Most recent telemetry of an axis.
+#[derive(Debug, Default)]
+pub struct TelemetryCache {
+    latest: Mutex<Latest>,
+}
+
+impl TelemetryCache {
+    fn lock(&self) -> MutexGuard<'_, Latest> {
+        self.latest.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Store `frame` if it is a telemetry message received at `received`.
+    pub(crate) fn record(&self, frame: &Frame, received: Instant) {
+        let embedded_can::Id::Standard(id) = frame.id() else {
+            return;
+        };
+        if frame.is_remote_frame() {
+            return;
+        }
+        let mut latest = self.lock();
+        latest.frames.insert(
+            Id::from(id).command(),
+            Timestamped {
+                value: *frame,
+                received,
+            },
+        );
+
+        let Some(Ok(message)) = TelemetryMessage::decode(frame) else {
+            return;
+        };
+        match message {
+            TelemetryMessage::Heartbeat(value) => latest.heartbeat = stamp(value, received),
+            TelemetryMessage::EncoderEstimates(value) => {
+                latest.encoder_estimate = stamp(value, received)
+            }
+            TelemetryMessage::Iq { setpoint, measured } => {
+                latest.iq = stamp((setpoint, measured), received)
+            }
+            TelemetryMessage::Temperature(value) => latest.temperature = stamp(value, received),
+            TelemetryMessage::BusVoltageCurrent(value) => {
+                latest.bus_voltage_current = stamp(value, received)
+            }
+            TelemetryMessage::Torques(value) => latest.torques = stamp(value, received),
+            TelemetryMessage::Powers(value) => latest.powers = stamp(value, received),
+        }
+    }
+
+    /// Last data frame of `command`.
+    pub(crate) fn frame(&self, command: u8) -> Option<Timestamped<Frame>> {
+        self.lock().frames.get(&command).copied()
+    }
+
+    /// Last heartbeat.
+    pub fn latest_heartbeat(&self) -> Option<Timestamped<Heartbeat>> {
+        self.lock().heartbeat
+    }
+
+    /// Last encoder estimates.
+    pub fn latest_encoder_estimate(&self) -> Option<Timestamped<EncoderEstimate>> {
+        self.lock().encoder_estimate
+    }
+
+    /// Last Iq setpoint and measurement.
+    pub fn latest_iq(&self) -> Option<Timestamped<(f32, f32)>> {
+        self.lock().iq
+    }
+
+    /// Last temperatures.
+    pub fn latest_temperature(&self) -> Option<Timestamped<Temperature>> {
+        self.lock().temperature
+    }
+
+    /// Last bus voltage and current.
+    pub fn latest_bus_voltage_current(&self) -> Option<Timestamped<BusVoltageCurrent>> {
+        self.lock().bus_voltage_current
+    }
+
+    /// Last torque target and estimate.
+    pub fn latest_torques(&self) -> Option<Timestamped<Torque>> {
+        self.lock().torques
+    }
+
+    /// Last electrical and mechanical power.
+    pub fn latest_powers(&self) -> Option<Timestamped<Power>> {
+        self.lock().powers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_latest() {
+        let cache = TelemetryCache::default();
+        let id = |command| Id::new(1, command).unwrap();
+        let temperature = |fet: f32| {
+            let mut data = [0; 8];
+            data[..4].copy_from_slice(&fet.to_le_bytes());
+            Frame::new(id(0x15), &data).unwrap()
+        };
+
+        assert!(cache.latest_temperature().is_none());
+        let received = Instant::now();
+        cache.record(&temperature(30.0), received);
+        cache.record(&temperature(40.0), received);
+        cache.record(&Frame::new(id(0x05), &[0; 8]).unwrap(), received);
+
+        let latest = cache.latest_temperature().unwrap();
+        assert_eq!(latest.value.fet, 40.0);
+        assert!(latest.age() < Duration::from_secs(60));
+        assert_eq!(
+            latest.age_at(received + Duration::from_millis(5)),
+            Duration::from_millis(5)
+        );
+        assert!(cache.latest_heartbeat().is_none());
+    }
+}
@@ -1,202 +1,840 @@
 //! # CAN interface for ODrives
 
+use crate::cache::TelemetryCache;
 #[cfg(feature = "flat-endpoints")]
-use crate::flat_endpoints::FlatEndpoints;
-use crate::{AxisErrors, AxisState, ControlMode, InputMode};
-use cansimple::Id;
-use embedded_can::Frame;
-use socketcan::{CanFrame, tokio::CanSocket};
-use std::io;
+use crate::flat_endpoints::{
+    Access, ApplyReport, Change, Difference, Endpoint, FlatEndpoints, Profiles, RestoreReport,
+    Verify,
+};
+use crate::middleware::{Chain, Middleware};
+use crate::pending::{Key, Pending, Reader, Subscribers, Subscription, Turn};
+use crate::protocol::{self, Request};
+pub use crate::protocol::{
+    BusVoltageCurrent, EncoderEstimate, Error, FrameFormat, Heartbeat, Power, ReadMode, SdoType,
+    TelemetryMessage, Temperature, Torque, Validation, Value, ValueKind, Version,
+};
+use crate::stats::Stats;
+use crate::transport::{
+    BusError, BusState, ErrorCounts, Filter, Frame, Timer, Transport, timeout as timeout_after,
+};
+use crate::{AxisState, ControlMode, InputMode, Result};
+use std::{
+    collections::VecDeque,
+    future::poll_fn,
+    io,
+    pin::{Pin, pin},
+    sync::{Arc, Mutex, PoisonError},
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant},
+};
+
+type BusStateCallback = Arc<dyn Fn(BusState) + Send + Sync>;
+type BusErrorCallback = Arc<dyn Fn(BusError) + Send + Sync>;
+type RecoveryCallback = Arc<dyn Fn() -> io::Result<()> + Send + Sync>;
+
+/// Default time to wait for a response.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Time [`ODrive::restore_configuration`] waits for the drive to reboot.
+#[cfg(feature = "flat-endpoints")]
+pub const REBOOT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Gap in the heartbeats that [`ODrive::restore_configuration`] takes as
+/// the drive rebooting.
+#[cfg(feature = "flat-endpoints")]
+pub const REBOOT_SILENCE: Duration = Duration::from_millis(500);
+
+/// Number of SDO reads the configuration helpers such as
+/// [`ODrive::backup_configuration`] keep in flight, see
+/// [`ODrive::sdo_read_many`].
+#[cfg(feature = "flat-endpoints")]
+pub const SDO_WINDOW: usize = 8;
 
 /// ODrive driver.
 ///
-/// Implemented using a tokio-async CAN socket.
-pub struct ODrive {
-    interface: CanSocket,
+/// Generic over the [`Transport`] used to access the CAN bus.
+///
+/// # Sharing
+///
+/// Cloning is cheap, clones share the transport, callbacks and error counts.
+/// A driver can be used from several tasks at once, either cloned or behind an
+/// [`Arc`]. Requests register the response they are waiting for before they
+/// are sent. Only one waiting request reads from the transport at a time and
+/// routes every received frame to the request waiting for it, so concurrent
+/// requests never receive each other's responses. Frames nobody waits for go
+/// to the [`ODrive::on_frame`] subscribers.
+///
+/// A request that is the current reader must keep being polled, a future
+/// that is neither polled nor dropped stalls the other requests until their
+/// timeouts expire.
+pub struct ODrive<T> {
+    interface: Arc<T>,
     axis: u8,
+    format: FrameFormat,
+    validation: Validation,
+    read_mode: ReadMode,
+    timeout: Option<Duration>,
+    on_bus_state: Option<BusStateCallback>,
+    on_bus_error: Option<BusErrorCallback>,
+    recovery: Option<RecoveryCallback>,
+    subscribers: Arc<Subscribers>,
+    middleware: Arc<Chain>,
+    pending: Arc<Pending>,
+    cache: Arc<TelemetryCache>,
+    /// Records telemetry into `cache` while any clone sharing it is alive.
+    cache_subscription: Arc<Subscription>,
+    retries: u32,
+    /// Read back every SDO write if set, see [`Self::with_write_verification`].
+    write_tolerance: Option<f64>,
+    /// Firmware commands are checked against, see [`Self::with_firmware`].
+    firmware: Option<(u8, u8, u8)>,
+    stats: Arc<Mutex<Stats>>,
+    error_counts: Arc<Mutex<ErrorCounts>>,
 }
 
-impl ODrive {
+impl<T> Clone for ODrive<T> {
+    fn clone(&self) -> Self {
+        Self {
+            interface: self.interface.clone(),
+            axis: self.axis,
+            format: self.format,
+            validation: self.validation,
+            read_mode: self.read_mode.clone(),
+            timeout: self.timeout,
+            on_bus_state: self.on_bus_state.clone(),
+            on_bus_error: self.on_bus_error.clone(),
+            recovery: self.recovery.clone(),
+            subscribers: self.subscribers.clone(),
+            middleware: self.middleware.clone(),
+            pending: self.pending.clone(),
+            cache: self.cache.clone(),
+            cache_subscription: self.cache_subscription.clone(),
+            retries: self.retries,
+            write_tolerance: self.write_tolerance,
+            firmware: self.firmware,
+            stats: self.stats.clone(),
+            error_counts: self.error_counts.clone(),
+        }
+    }
+}
+
+impl<T: Transport> ODrive<T> {
     /// Creates a new ODrive interface.
-    pub fn new(interface: CanSocket, axis: u8) -> Self {
-        Self { interface, axis }
+    ///
+    /// Installs a [`Filter::node`] filter on the transport so only frames
+    /// for `axis` are received. Failing to install the filter is not an
+    /// error since it only reduces load, see [`ODrive::set_filters`].
+    ///
+    /// Fails with [`crate::Error::InvalidNode`] if `axis` is larger than 63.
+    pub fn new(interface: T, axis: u8) -> Result<Self> {
+        let filter = Filter::node(axis)?;
+        let _ = interface.set_filters(&[filter]);
+        Ok(Self::with_shared(
+            Arc::new(interface),
+            axis,
+            Arc::default(),
+            Arc::default(),
+            Arc::default(),
+        ))
     }
 
-    /// Get version information.
-    pub async fn get_version(&self) -> io::Result<Version> {
-        let id = Id::new(self.axis, 0x00).unwrap();
+    /// Driver for `axis` sharing a transport with other drivers.
+    fn with_shared(
+        interface: Arc<T>,
+        axis: u8,
+        pending: Arc<Pending>,
+        subscribers: Arc<Subscribers>,
+        middleware: Arc<Chain>,
+    ) -> Self {
+        let cache = Arc::new(TelemetryCache::default());
+        let latest = Arc::downgrade(&cache);
+        let cache_subscription = Arc::new(subscribers.subscribe(
+            Some(axis),
+            Arc::new(move |frame: &Frame| {
+                if let Some(cache) = latest.upgrade() {
+                    cache.record(frame, T::Timer::now());
+                }
+            }),
+        ));
+
+        Self {
+            interface,
+            axis,
+            format: FrameFormat::Classic,
+            validation: Validation::Strict,
+            read_mode: ReadMode::Remote,
+            timeout: Some(DEFAULT_TIMEOUT),
+            on_bus_state: None,
+            on_bus_error: None,
+            recovery: None,
+            subscribers,
+            middleware,
+            pending,
+            cache,
+            cache_subscription,
+            retries: 0,
+            write_tolerance: None,
+            firmware: None,
+            stats: Arc::default(),
+            error_counts: Arc::new(Mutex::new(ErrorCounts::default())),
+        }
+    }
 
-        // request the message with an rtr frame
-        self.interface
-            .write_frame(CanFrame::new_remote(id, 0).unwrap())
-            .await?;
+    /// Call `callback` whenever the transport reports a [`BusState`]
+    /// change while waiting for a response.
+    pub fn on_bus_state(mut self, callback: impl Fn(BusState) + Send + Sync + 'static) -> Self {
+        self.on_bus_state = Some(Arc::new(callback));
+        self
+    }
 
-        let frame = loop {
-            let frame = self.interface.read_frame().await?;
-            if frame.id() == id.into() {
-                break frame;
-            }
-        };
+    /// Call `callback` for every [`BusError`] received while waiting for a
+    /// response.
+    ///
+    /// Forward the errors into a channel to consume them as a stream.
+    pub fn on_bus_error(mut self, callback: impl Fn(BusError) + Send + Sync + 'static) -> Self {
+        self.on_bus_error = Some(Arc::new(callback));
+        self
+    }
 
-        if frame.data().len() != 8 {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                format!("Frame data length invalid: {} != 8", frame.data().len()),
-            ));
-        }
+    /// Call `callback` for every received frame that is not the response to
+    /// a request, such as heartbeats and cyclic telemetry.
+    ///
+    /// Only frames from or to `axis` are passed to `callback`. Can be called
+    /// several times to add more subscribers. Frames are only received while
+    /// a request is waiting for its response or while [`ODrive::listen`]
+    /// runs.
+    pub fn on_frame(self, callback: impl Fn(&Frame) + Send + Sync + 'static) -> Self {
+        let _ = self.subscribers.add(Some(self.axis), Arc::new(callback));
+        self
+    }
 
-        Ok(Version {
-            protocol_version: frame.data()[0],
-            hw_version_major: frame.data()[1],
-            hw_version_minor: frame.data()[2],
-            hw_version_variant: frame.data()[3],
-            fw_version_major: frame.data()[4],
-            fw_version_minor: frame.data()[5],
-            fw_version_revision: frame.data()[6],
-            fw_version_unreleased: frame.data()[7] == 1,
-        })
+    /// Pass every written and read frame through `middleware`.
+    ///
+    /// The middleware is shared with all drivers using the same transport,
+    /// see [`crate::middleware`].
+    pub fn with_middleware(self, middleware: impl Middleware + 'static) -> Self {
+        self.middleware.add(Arc::new(middleware));
+        self
     }
 
-    /// Cause the axis to disarm.
-    pub async fn estop(&self) -> io::Result<()> {
-        let frame = CanFrame::new(Id::new(self.axis, 0x02).unwrap(), &[]).unwrap();
-        self.interface.write_frame(frame).await
+    /// Most recent telemetry received from the axis.
+    pub fn cache(&self) -> &TelemetryCache {
+        &self.cache
     }
 
-    /// Get errors.
-    pub async fn get_error(&self) -> io::Result<Error> {
-        let id = Id::new(self.axis, 0x03).unwrap();
+    /// Link statistics so far.
+    pub fn stats(&self) -> Stats {
+        self.stats
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
 
-        // request the message with an rtr frame
-        self.interface
-            .write_frame(CanFrame::new_remote(id, 0).unwrap())
-            .await?;
+    fn count(&self, update: impl FnOnce(&mut Stats)) {
+        update(&mut self.stats.lock().unwrap_or_else(PoisonError::into_inner));
+    }
 
-        let frame = loop {
-            let frame = self.interface.read_frame().await?;
-            if frame.id() == id.into() {
-                break frame;
+    /// Number of bus errors received so far.
+    pub fn error_counts(&self) -> ErrorCounts {
+        *self
+            .error_counts
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Call `restart` when the bus goes [`BusState::BusOff`].
+    ///
+    /// The request that observed the bus off condition still fails, later
+    /// requests succeed once the controller has recovered. On Linux
+    /// [`restart_interface`](crate::transport::restart_interface) restarts a
+    /// SocketCAN interface. Without recovery the bus has to be restarted by
+    /// the user.
+    pub fn with_bus_recovery(
+        mut self,
+        restart: impl Fn() -> io::Result<()> + Send + Sync + 'static,
+    ) -> Self {
+        self.recovery = Some(Arc::new(restart));
+        self
+    }
+
+    /// Handle an error returned while reading.
+    ///
+    /// Bus errors and bus state changes other than bus off are reported and
+    /// otherwise ignored, all other errors are returned.
+    fn read_error(&self, err: io::Error) -> Result<()> {
+        if let Some(error) = BusError::from_error(&err) {
+            trace_event!(debug, node = self.axis, %error, "bus error");
+            self.error_counts
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .record(error);
+            if let Some(callback) = &self.on_bus_error {
+                callback(error);
             }
+            return Ok(());
+        }
+
+        let Some(state) = BusState::from_error(&err) else {
+            trace_event!(warn, node = self.axis, %err, "read failed");
+            return Err(err.into());
         };
+        trace_event!(warn, node = self.axis, %state, "bus state changed");
 
-        if frame.data().len() != 8 {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                format!("Frame data length invalid: {} != 8", frame.data().len()),
-            ));
+        if let Some(callback) = &self.on_bus_state {
+            callback(state);
         }
 
-        let data = frame.data();
+        if state != BusState::BusOff {
+            return Ok(());
+        }
 
-        Ok(Error {
-            active_errors: AxisErrors::from_bits_retain(u32::from_le_bytes([
-                data[0], data[1], data[2], data[3],
-            ])),
-            disarm_reason: AxisErrors::from_bits_retain(u32::from_le_bytes([
-                data[4], data[5], data[6], data[7],
-            ])),
-        })
+        if let Some(restart) = &self.recovery {
+            restart()?;
+        }
+        Err(err.into())
     }
 
-    /// Write an arbitrary parameter.
-    pub async fn sdo_write(&self, endpoint: u16, value: Value) -> io::Result<()> {
-        let id = Id::new(self.axis, 0x04).unwrap();
+    /// Replace the receive filters of the transport.
+    ///
+    /// An empty slice disables filtering. Filters are shared by everything
+    /// using the same transport.
+    pub fn set_filters(&self, filters: &[Filter]) -> Result<()> {
+        Ok(self.interface.set_filters(filters)?)
+    }
 
-        let mut data = vec![];
-        data.push(1); // opcode = write
-        data.extend(endpoint.to_le_bytes());
-        data.push(0); // reserved
-        data.extend(value.to_le_bytes());
+    /// Wait at most `timeout` for each response, or forever if [`None`].
+    ///
+    /// Defaults to [`DEFAULT_TIMEOUT`]. Requests that time out fail with
+    /// [`crate::Error::Timeout`].
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
 
-        self.interface
-            .write_frame(CanFrame::new(id, &data).unwrap())
-            .await
+    /// Copy of this driver using `timeout` for its requests.
+    ///
+    /// Overrides the timeout for individual calls:
+    ///
+    /// ```ignore
+    /// let version = odrive.timeout(Duration::from_millis(50)).get_version().await?;
+    /// ```
+    pub fn timeout(&self, timeout: Duration) -> Self {
+        self.clone().with_timeout(Some(timeout))
     }
 
-    /// Read an arbitrary parameter.
-    pub async fn sdo_read(&self, endpoint: u16, kind: ValueKind) -> io::Result<Value> {
-        let id = Id::new(self.axis, 0x04).unwrap();
+    /// Check the length of responses according to `validation`.
+    ///
+    /// Defaults to [`Validation::Strict`].
+    pub fn with_validation(mut self, validation: Validation) -> Self {
+        self.validation = validation;
+        self
+    }
 
-        let mut data = vec![];
-        data.push(0); // opcode = read
-        data.extend(endpoint.to_le_bytes());
-        data.push(0); // reserved
-        data.extend(0_u32.to_le_bytes());
+    /// Send requests again up to `retries` times if they time out.
+    ///
+    /// Defaults to no retries.
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
 
-        self.interface
-            .write_frame(CanFrame::new(id, &data).unwrap())
-            .await?;
+    /// Read every endpoint written with [`Self::sdo_write`] back and fail
+    /// with [`crate::Error::WriteMismatch`] if it does not match, floats
+    /// within `tolerance`, see [`Value::approx_eq`].
+    ///
+    /// The ODrive silently clamps or rejects some values. Defaults to
+    /// [`None`], writing without reading back. Endpoints known to be
+    /// write-only and function calls are never read back.
+    pub fn with_write_verification(mut self, tolerance: Option<f64>) -> Self {
+        self.write_tolerance = tolerance;
+        self
+    }
 
-        let id = Id::new(self.axis, 0x05).unwrap();
+    /// Fail with [`crate::Error::UnsupportedByFirmware`] instead of timing
+    /// out when sending commands that `firmware` does not support, see
+    /// [`protocol::supported_since`].
+    ///
+    /// Defaults to [`None`], sending every command. Pass the firmware
+    /// reported by [`Self::get_version`] to enable the check:
+    ///
+    /// ```ignore
+    /// let version = odrive.get_version().await?;
+    /// let odrive = odrive.with_firmware(Some(version.firmware()));
+    /// ```
+    pub fn with_firmware(mut self, firmware: Option<(u8, u8, u8)>) -> Self {
+        self.firmware = firmware;
+        self
+    }
 
-        let frame = loop {
-            let frame = self.interface.read_frame().await?;
-            if frame.data().len() != 8 {
-                continue;
+    /// Query messages without remote frames according to `mode`.
+    ///
+    /// Defaults to [`ReadMode::Remote`].
+    pub fn with_read_mode(mut self, mode: ReadMode) -> Self {
+        self.read_mode = mode;
+        self
+    }
+
+    /// Use `format` for all transmitted frames.
+    ///
+    /// Defaults to [`FrameFormat::Classic`]. Requests are sent as remote
+    /// frames regardless of the format since CAN FD has no remote frames.
+    pub fn with_frame_format(mut self, format: FrameFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Node of the axis.
+    pub(crate) fn axis(&self) -> u8 {
+        self.axis
+    }
+
+    fn check_supported(&self, frame: &Frame) -> Result<()> {
+        match self.firmware {
+            Some(firmware) => protocol::check_supported(frame, firmware),
+            None => Ok(()),
+        }
+    }
+
+    /// Send a frame without waiting for a response.
+    pub(crate) async fn send(&self, frame: Frame) -> Result<()> {
+        self.check_supported(&frame)?;
+        if let Some(frame) = self.middleware.outgoing(frame.with_format(self.format)) {
+            self.interface.write_frame(&frame).await?;
+            trace_event!(trace, node = self.axis, id = ?frame.id(), data = ?frame.data(), "frame sent");
+            self.count(|stats| stats.frames_sent += 1);
+        }
+        Ok(())
+    }
+
+    /// Send a request and wait for its response.
+    async fn request<M>(&self, request: Request<M>) -> Result<M> {
+        #[cfg(feature = "tracing")]
+        let span = tracing::debug_span!(
+            "request",
+            node = self.axis,
+            command = request.response_id().command()
+        );
+        let response = self.request_retrying(request);
+        #[cfg(feature = "tracing")]
+        let response = tracing::Instrument::instrument(response, span);
+        response.await
+    }
+
+    async fn request_retrying<M>(&self, request: Request<M>) -> Result<M> {
+        self.check_supported(request.frame())?;
+        let request = request.with_validation(self.validation);
+        let mut retries = 0;
+        loop {
+            let result = match self.timeout {
+                Some(timeout) => timeout_after::<T::Timer, _>(timeout, self.request_once(&request))
+                    .await
+                    .unwrap_or(Err(crate::Error::Timeout)),
+                None => self.request_once(&request).await,
+            };
+            if !matches!(result, Err(crate::Error::Timeout)) {
+                return result;
+            }
+
+            self.count(|stats| stats.timeouts += 1);
+            trace_event!(warn, retries, "request timed out");
+            if retries == self.retries {
+                return result;
             }
-            if frame.id() == id.into() {
-                let rx_endpoint = u16::from_le_bytes([frame.data()[1], frame.data()[2]]);
-                if rx_endpoint == endpoint {
-                    break frame;
+            retries += 1;
+            self.count(|stats| stats.retries += 1);
+        }
+    }
+
+    async fn request_once<M>(&self, request: &Request<M>) -> Result<M> {
+        if !request.frame().is_remote_frame() {
+            return self.exchange(request, true).await;
+        }
+
+        match &self.read_mode {
+            ReadMode::Remote => self.exchange(request, true).await,
+            ReadMode::Telemetry { max_age } => {
+                let command = request.response_id().command();
+                if let Some(latest) = self.cache.frame(command)
+                    && latest.age_at(T::Timer::now()) <= *max_age
+                    && let Some(response) = request.response(&latest.value)
+                {
+                    return response;
                 }
+                self.exchange(request, false).await
             }
-        };
+            #[cfg(feature = "flat-endpoints")]
+            ReadMode::Sdo(endpoints) => {
+                let command = request.response_id().command();
+                let names = protocol::sdo_endpoints(command).ok_or_else(|| {
+                    crate::Error::Config(format!("no SDO endpoints for command {command}"))
+                })?;
+
+                let mut data = [0; 8];
+                for (name, value) in names.iter().zip(data.chunks_exact_mut(4)) {
+                    let (endpoint, _) = endpoints.resolve(name, Access::Read)?;
+                    let read = protocol::sdo_read(self.axis, endpoint)?;
+                    value.copy_from_slice(&self.exchange(&read, true).await?);
+                }
 
-        let data = &frame.data()[4..8];
-
-        Ok(match kind {
-            ValueKind::Bool => Value::Bool(data[0] == 1),
-            ValueKind::U8 => Value::U8(data[0]),
-            ValueKind::I8 => Value::I8(i8::from_le_bytes([data[0]])),
-            ValueKind::U16 => Value::U16(u16::from_le_bytes([data[0], data[1]])),
-            ValueKind::I16 => Value::I16(i16::from_le_bytes([data[0], data[1]])),
-            ValueKind::U32 => Value::U32(u32::from_le_bytes([data[0], data[1], data[2], data[3]])),
-            ValueKind::I32 => Value::I32(i32::from_le_bytes([data[0], data[1], data[2], data[3]])),
-            ValueKind::Float => {
-                Value::Float(f32::from_le_bytes([data[0], data[1], data[2], data[3]]))
+                request.decode_data(&data)
             }
-        })
+        }
     }
 
-    /// Change the axis state.
-    pub async fn set_axis_state(&self, state: AxisState) -> io::Result<()> {
-        let frame = CanFrame::new(
-            Id::new(self.axis, 0x07).unwrap(),
-            &(state as u32).to_le_bytes(),
-        )
-        .unwrap();
-        self.interface.write_frame(frame).await
+    /// Wait for the response to `request`, after sending it if `write`.
+    async fn exchange<M>(&self, request: &Request<M>, write: bool) -> Result<M> {
+        let registration = self.pending.register(Some(Key::of(request)));
+        let sent = T::Timer::now();
+        if write {
+            self.send(*request.frame()).await?;
+        }
+
+        loop {
+            match poll_fn(|cx| registration.poll_turn(cx)).await {
+                Turn::Frame(frame) => match request.response(&frame) {
+                    Some(response) => {
+                        let received = T::Timer::now();
+                        let latency = received.saturating_duration_since(sent);
+                        self.record_response(request, write.then_some(latency), &response);
+                        self.cache.record(&frame, received);
+                        return response;
+                    }
+                    None => self.dispatch(&frame),
+                },
+                Turn::Read(reader) => {
+                    let result = self.interface.read_frame().await;
+                    self.receive(reader, result)?;
+                }
+            }
+        }
     }
 
-    /// Get the encoder estimates.
-    pub async fn get_encoder_estimates(&self) -> io::Result<EncoderEstimate> {
-        let id = Id::new(self.axis, 0x09).unwrap();
+    /// Count a response that took `latency` after sending the request.
+    fn record_response<M>(
+        &self,
+        request: &Request<M>,
+        latency: Option<Duration>,
+        response: &Result<M>,
+    ) {
+        #[cfg(feature = "tracing")]
+        match response {
+            Ok(_) => tracing::debug!(?latency, "response received"),
+            Err(error) => tracing::warn!(%error, "invalid response"),
+        }
+        self.count(|stats| {
+            if response.is_err() {
+                stats.decode_failures += 1;
+            }
+            if let Some(latency) = latency {
+                stats.record_latency(request.response_id().command(), latency);
+            }
+        });
+    }
 
-        // request the message with an rtr frame
-        self.interface
-            .write_frame(CanFrame::new_remote(id, 0).unwrap())
-            .await?;
+    /// Route a received frame to a waiting request or the subscribers.
+    ///
+    /// The frame is routed before the reader role is released, so a request
+    /// taking over the role cannot miss its response.
+    fn receive(&self, reader: Reader<'_>, result: io::Result<Frame>) -> Result<()> {
+        if result.is_ok() {
+            self.count(|stats| stats.frames_received += 1);
+        }
+        #[cfg(feature = "tracing")]
+        if let Ok(frame) = &result {
+            tracing::trace!(id = ?frame.id(), data = ?frame.data(), "frame received");
+        }
+        let result = result.map(|frame| {
+            self.middleware
+                .incoming(frame)
+                .and_then(|frame| self.pending.route(frame))
+        });
+        drop(reader);
+        match result {
+            Ok(Some(frame)) => {
+                self.dispatch(&frame);
+                Ok(())
+            }
+            Ok(None) => Ok(()),
+            Err(err) => self.read_error(err),
+        }
+    }
 
-        let frame = loop {
-            let frame = self.interface.read_frame().await?;
-            if frame.id() == id.into() {
-                break frame;
+    /// Pass a frame that is not a response to the subscribers.
+    fn dispatch(&self, frame: &Frame) {
+        self.subscribers.dispatch(frame);
+    }
+
+    /// Receive frames and pass them to the [`ODrive::on_frame`] subscribers
+    /// until reading fails.
+    ///
+    /// Responses received while listening are routed to the waiting
+    /// requests.
+    pub async fn listen(&self) -> Result<()> {
+        let registration = self.pending.register(None);
+        loop {
+            if let Turn::Read(reader) = poll_fn(|cx| registration.poll_turn(cx)).await {
+                let result = self.interface.read_frame().await;
+                self.receive(reader, result)?;
             }
+        }
+    }
+
+    /// Split into a sending and a receiving half.
+    ///
+    /// The sending half is the driver itself, the [`Receiver`] yields every
+    /// received frame that is not the response to a request. Both can be
+    /// used from different tasks at the same time.
+    pub fn split(self) -> (Self, Receiver<T>) {
+        let inbox = Arc::new(Mutex::new(Inbox::default()));
+        let buffered = inbox.clone();
+        let callback = Arc::new(move |frame: &Frame| {
+            buffered
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(*frame);
+        });
+        let subscriber = self.subscribers.add(Some(self.axis), callback);
+
+        let receiver = Receiver {
+            odrive: self.clone(),
+            inbox,
+            subscriber,
         };
+        (self, receiver)
+    }
 
-        if frame.data().len() != 8 {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                format!("Frame data length invalid: {} != 8", frame.data().len()),
-            ));
+    /// Stream of the cyclic messages received from the axis.
+    ///
+    /// Frames that are not telemetry or fail to decode are skipped. The
+    /// stream ends when reading from the transport fails.
+    pub fn telemetry(&self) -> Telemetry<T>
+    where
+        T: Send + Sync + 'static,
+    {
+        Telemetry {
+            receiver: Arc::new(self.clone().split().1),
+            next: None,
+            done: false,
+        }
+    }
+
+    /// Wait for a heartbeat reporting the trajectory as done.
+    ///
+    /// Only heartbeats received after the call are considered. A heartbeat
+    /// the ODrive sent before it started the last commanded trajectory may
+    /// still report the previous one as done.
+    pub async fn trajectory_done(&self) -> Result<()> {
+        let (_, receiver) = self.clone().split();
+        loop {
+            let frame = receiver.recv().await?;
+            if let Some(Ok(TelemetryMessage::Heartbeat(heartbeat))) =
+                TelemetryMessage::decode(&frame)
+                && heartbeat.trajectory_done
+            {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Get version information.
+    pub async fn get_version(&self) -> Result<Version> {
+        self.request(protocol::get_version(self.axis)?).await
+    }
+
+    /// Get version information, failing with
+    /// [`crate::Error::FirmwareTooOld`] if the firmware is older than
+    /// `major.minor.revision`.
+    pub async fn require_firmware(&self, major: u8, minor: u8, revision: u8) -> Result<Version> {
+        let version = self.get_version().await?;
+        version.require_firmware(major, minor, revision)?;
+        Ok(version)
+    }
+
+    /// Cause the axis to disarm.
+    pub async fn estop(&self) -> Result<()> {
+        self.send(protocol::estop(self.axis)?).await
+    }
+
+    /// Get errors.
+    pub async fn get_error(&self) -> Result<Error> {
+        self.request(protocol::get_error(self.axis)?).await
+    }
+
+    /// Write an arbitrary parameter.
+    ///
+    /// Takes a [`Value`] or a primitive such as `1.5f32`. Fails with
+    /// [`crate::Error::ValueTooWide`] for 64-bit values.
+    pub async fn sdo_write(&self, endpoint: u16, value: impl Into<Value>) -> Result<()> {
+        self.write_value(endpoint, value.into(), true).await
+    }
+
+    /// Write `value`, reading it back if `readable` and write verification
+    /// is enabled.
+    async fn write_value(&self, endpoint: u16, value: Value, readable: bool) -> Result<()> {
+        self.send(protocol::sdo_write(self.axis, endpoint, value)?)
+            .await?;
+        if let Some(tolerance) = self.write_tolerance
+            && readable
+        {
+            let read = self.sdo_read(endpoint, value.kind()).await?;
+            if !value.approx_eq(&read, tolerance) {
+                return Err(crate::Error::WriteMismatch {
+                    endpoint,
+                    written: value,
+                    read,
+                });
+            }
         }
+        Ok(())
+    }
 
-        let data = frame.data();
+    /// Read an arbitrary parameter.
+    ///
+    /// Fails with [`crate::Error::ValueTooWide`] for 64-bit kinds.
+    pub async fn sdo_read(&self, endpoint: u16, kind: ValueKind) -> Result<Value> {
+        protocol::check_sdo_kind(kind)?;
+        let data = self
+            .request(protocol::sdo_read(self.axis, endpoint)?)
+            .await?;
+        Ok(Value::from_le_bytes(kind, data))
+    }
 
-        Ok(EncoderEstimate {
-            position: f32::from_le_bytes([data[0], data[1], data[2], data[3]]),
-            velocity: f32::from_le_bytes([data[4], data[5], data[6], data[7]]),
+    /// Read several arbitrary parameters, keeping up to `window` requests
+    /// in flight.
+    ///
+    /// Sends the next read without waiting for the previous response,
+    /// responses are matched by endpoint. The results are in the order of
+    /// `reads`, a failed read does not stop the others.
+    pub async fn sdo_read_many(
+        &self,
+        reads: &[(u16, ValueKind)],
+        window: usize,
+    ) -> Vec<Result<Value>> {
+        let window = window.max(1);
+        let mut results: Vec<_> = reads.iter().map(|_| None).collect();
+        let mut in_flight = Vec::with_capacity(window);
+        let mut next = reads.iter().enumerate();
+        poll_fn(|cx| {
+            loop {
+                while in_flight.len() < window
+                    && let Some((index, &(endpoint, kind))) = next.next()
+                {
+                    in_flight.push((index, Box::pin(self.sdo_read(endpoint, kind))));
+                }
+                let mut completed = false;
+                in_flight.retain_mut(|(index, read)| match read.as_mut().poll(cx) {
+                    Poll::Ready(result) => {
+                        results[*index] = Some(result);
+                        completed = true;
+                        false
+                    }
+                    Poll::Pending => true,
+                });
+                if in_flight.is_empty() {
+                    return Poll::Ready(());
+                }
+                if !completed {
+                    return Poll::Pending;
+                }
+            }
         })
+        .await;
+        results
+            .into_iter()
+            .map(|result| {
+                result
+                    .unwrap_or_else(|| Err(crate::Error::Protocol("read did not complete".into())))
+            })
+            .collect()
+    }
+
+    /// Read an arbitrary parameter of type `V`.
+    ///
+    /// ```ignore
+    /// let limit = odrive.sdo_read_as::<f32>(endpoint).await?;
+    /// ```
+    pub async fn sdo_read_as<V: SdoType>(&self, endpoint: u16) -> Result<V> {
+        let value = self.sdo_read(endpoint, V::KIND).await?;
+        V::from_value(value).ok_or_else(|| crate::Error::Protocol("SDO value kind".into()))
+    }
+
+    /// Read the endpoint `name` as the kind listed in `endpoints`.
+    ///
+    /// Fails with [`crate::Error::Config`] if the endpoint does not exist
+    /// or is write-only.
+    #[cfg(feature = "flat-endpoints")]
+    pub async fn read_named(&self, endpoints: &FlatEndpoints, name: &str) -> Result<Value> {
+        let (endpoint, kind) = endpoints.resolve(name, Access::Read)?;
+        self.sdo_read(endpoint, kind).await
+    }
+
+    /// Write `value` to the endpoint `name`.
+    ///
+    /// Fails with [`crate::Error::Config`] if the endpoint does not exist,
+    /// is read-only or `value` is not of its kind.
+    #[cfg(feature = "flat-endpoints")]
+    pub async fn write_named(
+        &self,
+        endpoints: &FlatEndpoints,
+        name: &str,
+        value: Value,
+    ) -> Result<()> {
+        let endpoint = endpoints.resolve_write(name, &value)?;
+        let readable = endpoints.access(name).is_none_or(Access::is_readable);
+        self.write_value(endpoint, value, readable).await
+    }
+
+    /// Call the firmware function `name` with `args`, returns its outputs.
+    ///
+    /// Writes each argument to its input endpoint, then writes the function
+    /// endpoint to call it and reads the output endpoints. Fails with
+    /// [`crate::Error::Config`] if the function does not exist or `args`
+    /// do not match its inputs.
+    #[cfg(feature = "flat-endpoints")]
+    pub async fn call_function(
+        &self,
+        endpoints: &FlatEndpoints,
+        name: &str,
+        args: &[Value],
+    ) -> Result<Vec<Value>> {
+        let function = endpoints.resolve_call(name, args)?;
+        for (input, arg) in function.inputs.iter().zip(args) {
+            self.sdo_write(input.id, *arg).await?;
+        }
+        self.write_value(function.id, Value::U32(0), false).await?;
+
+        let mut outputs = Vec::with_capacity(function.outputs.len());
+        for output in &function.outputs {
+            outputs.push(self.sdo_read(output.id, output.kind).await?);
+        }
+        Ok(outputs)
+    }
+
+    /// Read a typed endpoint, see [`FlatEndpoints::typed`].
+    #[cfg(feature = "flat-endpoints")]
+    pub async fn read_endpoint<V: SdoType>(&self, endpoint: &Endpoint<V>) -> Result<V> {
+        endpoint.check(Access::Read)?;
+        self.sdo_read_as(endpoint.id()).await
+    }
+
+    /// Write a typed endpoint, see [`FlatEndpoints::typed`].
+    #[cfg(feature = "flat-endpoints")]
+    pub async fn write_endpoint<V: SdoType>(&self, endpoint: &Endpoint<V>, value: V) -> Result<()> {
+        endpoint.check(Access::Write)?;
+        let readable = endpoint.access().is_readable();
+        self.write_value(endpoint.id(), value.into(), readable)
+            .await
+    }
+
+    /// Change the axis state.
+    pub async fn set_axis_state(&self, state: AxisState) -> Result<()> {
+        self.send(protocol::set_axis_state(self.axis, state)?).await
+    }
+
+    /// Get the encoder estimates.
+    pub async fn get_encoder_estimates(&self) -> Result<EncoderEstimate> {
+        self.request(protocol::get_encoder_estimates(self.axis)?)
+            .await
     }
 
     /// Set the control loop mode.
@@ -204,12 +842,13 @@ impl ODrive {
         &self,
         control_mode: ControlMode,
         input_mode: InputMode,
-    ) -> io::Result<()> {
-        let mut data = vec![];
-        data.extend((control_mode as u32).to_le_bytes());
-        data.extend((input_mode as u32).to_le_bytes());
-        let frame = CanFrame::new(Id::new(self.axis, 0x0b).unwrap(), &data).unwrap();
-        self.interface.write_frame(frame).await
+    ) -> Result<()> {
+        self.send(protocol::set_controller_mode(
+            self.axis,
+            control_mode,
+            input_mode,
+        )?)
+        .await
     }
 
     /// Set input position.
@@ -224,57 +863,47 @@ impl ODrive {
         position: f32,
         velocity: i16,
         torque: i16,
-    ) -> io::Result<()> {
-        let mut data = vec![];
-        data.extend(position.to_le_bytes());
-        data.extend(velocity.to_le_bytes());
-        data.extend(torque.to_le_bytes());
-        let frame = CanFrame::new(Id::new(self.axis, 0x0c).unwrap(), &data).unwrap();
-        self.interface.write_frame(frame).await
+    ) -> Result<()> {
+        self.send(protocol::set_input_position(
+            self.axis, position, velocity, torque,
+        )?)
+        .await
     }
 
     /// Set input velocity.
     ///
     /// - `velocity` rev/s.
     /// - `torque` Nm.
-    pub async fn set_input_velocity(&self, velocity: f32, torque: f32) -> io::Result<()> {
-        let mut data = vec![];
-        data.extend(velocity.to_le_bytes());
-        data.extend(torque.to_le_bytes());
-        let frame = CanFrame::new(Id::new(self.axis, 0x0d).unwrap(), &data).unwrap();
-        self.interface.write_frame(frame).await
+    pub async fn set_input_velocity(&self, velocity: f32, torque: f32) -> Result<()> {
+        self.send(protocol::set_input_velocity(self.axis, velocity, torque)?)
+            .await
     }
 
     /// Set input torque.
     ///
     /// - `torque` Nm.
-    pub async fn set_input_torque(&self, torque: f32) -> io::Result<()> {
-        let mut data = vec![];
-        data.extend(torque.to_le_bytes());
-        let frame = CanFrame::new(Id::new(self.axis, 0x0e).unwrap(), &data).unwrap();
-        self.interface.write_frame(frame).await
+    pub async fn set_input_torque(&self, torque: f32) -> Result<()> {
+        self.send(protocol::set_input_torque(self.axis, torque)?)
+            .await
     }
 
     /// Set limits.
     ///
     /// - `velocity` limit rev/s.
     /// - `current` limit amps.
-    pub async fn set_limits(&self, velocity: f32, current: f32) -> io::Result<()> {
-        let mut data = vec![];
-        data.extend(velocity.to_le_bytes());
-        data.extend(current.to_le_bytes());
-        let frame = CanFrame::new(Id::new(self.axis, 0x0f).unwrap(), &data).unwrap();
-        self.interface.write_frame(frame).await
+    pub async fn set_limits(&self, velocity: f32, current: f32) -> Result<()> {
+        self.send(protocol::set_limits(self.axis, velocity, current)?)
+            .await
     }
 
     /// Set trajectory velocity limit.
     ///
     /// `velocity` limit rev/s.
-    pub async fn set_trajectory_velocity_limit(&self, velocity: f32) -> io::Result<()> {
-        let mut data = vec![];
-        data.extend(velocity.to_le_bytes());
-        let frame = CanFrame::new(Id::new(self.axis, 0x11).unwrap(), &data).unwrap();
-        self.interface.write_frame(frame).await
+    pub async fn set_trajectory_velocity_limit(&self, velocity: f32) -> Result<()> {
+        self.send(protocol::set_trajectory_velocity_limit(
+            self.axis, velocity,
+        )?)
+        .await
     }
 
     /// Set trajectory acceleration limits.
@@ -285,428 +914,582 @@ impl ODrive {
         &self,
         acceleration: f32,
         deceleration: f32,
-    ) -> io::Result<()> {
-        let mut data = vec![];
-        data.extend(acceleration.to_le_bytes());
-        data.extend(deceleration.to_le_bytes());
-        let frame = CanFrame::new(Id::new(self.axis, 0x12).unwrap(), &data).unwrap();
-        self.interface.write_frame(frame).await
+    ) -> Result<()> {
+        self.send(protocol::set_trajectory_acceleration_limit(
+            self.axis,
+            acceleration,
+            deceleration,
+        )?)
+        .await
     }
 
     /// Set trajectory inertia.
     ///
     /// `inertia` Nm/(rev/s^2).
-    pub async fn set_trajectory_inertia(&self, inertia: f32) -> io::Result<()> {
-        let mut data = vec![];
-        data.extend(inertia.to_le_bytes());
-        let frame = CanFrame::new(Id::new(self.axis, 0x13).unwrap(), &data).unwrap();
-        self.interface.write_frame(frame).await
+    pub async fn set_trajectory_inertia(&self, inertia: f32) -> Result<()> {
+        self.send(protocol::set_trajectory_inertia(self.axis, inertia)?)
+            .await
     }
 
     /// Get motor current.
     ///
     /// Response: (setpoint, measured)
-    pub async fn get_iq(&self) -> io::Result<(f32, f32)> {
-        let id = Id::new(self.axis, 0x14).unwrap();
-
-        // request the message with an rtr frame
-        self.interface
-            .write_frame(CanFrame::new_remote(id, 0).unwrap())
-            .await?;
-
-        let frame = loop {
-            let frame = self.interface.read_frame().await?;
-            if frame.id() == id.into() {
-                break frame;
-            }
-        };
-
-        if frame.data().len() != 8 {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                format!("Frame data length invalid: {} != 8", frame.data().len()),
-            ));
-        }
-
-        let data = frame.data();
-
-        Ok((
-            f32::from_le_bytes([data[0], data[1], data[2], data[3]]),
-            f32::from_le_bytes([data[4], data[5], data[6], data[7]]),
-        ))
+    pub async fn get_iq(&self) -> Result<(f32, f32)> {
+        self.request(protocol::get_iq(self.axis)?).await
     }
 
     /// Get temperature.
-    pub async fn get_temperature(&self) -> io::Result<Temperature> {
-        let id = Id::new(self.axis, 0x15).unwrap();
-
-        // request the message with an rtr frame
-        self.interface
-            .write_frame(CanFrame::new_remote(id, 0).unwrap())
-            .await?;
-
-        let frame = loop {
-            let frame = self.interface.read_frame().await?;
-            if frame.id() == id.into() {
-                break frame;
-            }
-        };
-
-        if frame.data().len() != 8 {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                format!("Frame data length invalid: {} != 8", frame.data().len()),
-            ));
-        }
-
-        let data = frame.data();
-
-        Ok(Temperature {
-            fet: f32::from_le_bytes([data[0], data[1], data[2], data[3]]),
-            motor: f32::from_le_bytes([data[4], data[5], data[6], data[7]]),
-        })
+    pub async fn get_temperature(&self) -> Result<Temperature> {
+        self.request(protocol::get_temperature(self.axis)?).await
     }
 
     /// Reboot the device.
-    pub async fn reboot(&self) -> io::Result<()> {
-        let frame = CanFrame::new(Id::new(self.axis, 0x16).unwrap(), &[0]).unwrap();
-        self.interface.write_frame(frame).await
+    pub async fn reboot(&self) -> Result<()> {
+        self.send(protocol::reboot(self.axis)?).await
     }
 
     /// Get bus voltage and current.
-    pub async fn get_bus_voltage_current(&self) -> io::Result<BusVoltageCurrent> {
-        let id = Id::new(self.axis, 0x17).unwrap();
-
-        // request the message with an rtr frame
-        self.interface
-            .write_frame(CanFrame::new_remote(id, 0).unwrap())
-            .await?;
-
-        let frame = loop {
-            let frame = self.interface.read_frame().await?;
-            if frame.id() == id.into() {
-                break frame;
-            }
-        };
-
-        if frame.data().len() != 8 {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                format!("Frame data length invalid: {} != 8", frame.data().len()),
-            ));
-        }
-
-        let data = frame.data();
-
-        Ok(BusVoltageCurrent {
-            voltage: f32::from_le_bytes([data[0], data[1], data[2], data[3]]),
-            current: f32::from_le_bytes([data[4], data[5], data[6], data[7]]),
-        })
+    pub async fn get_bus_voltage_current(&self) -> Result<BusVoltageCurrent> {
+        self.request(protocol::get_bus_voltage_current(self.axis)?)
+            .await
     }
 
     /// Save configuration.
-    pub async fn save_configuration(&self) -> io::Result<()> {
-        let frame = CanFrame::new(Id::new(self.axis, 0x16).unwrap(), &[1]).unwrap();
-        self.interface.write_frame(frame).await
+    pub async fn save_configuration(&self) -> Result<()> {
+        self.send(protocol::save_configuration(self.axis)?).await
     }
 
     /// Erase configuration.
-    pub async fn erase_configuration(&self) -> io::Result<()> {
-        let frame = CanFrame::new(Id::new(self.axis, 0x16).unwrap(), &[2]).unwrap();
-        self.interface.write_frame(frame).await
+    pub async fn erase_configuration(&self) -> Result<()> {
+        self.send(protocol::erase_configuration(self.axis)?).await
     }
 
     /// Enter DFU mode 2.
-    pub async fn enter_dfu_mode2(&self) -> io::Result<()> {
-        let frame = CanFrame::new(Id::new(self.axis, 0x16).unwrap(), &[3]).unwrap();
-        self.interface.write_frame(frame).await
+    pub async fn enter_dfu_mode2(&self) -> Result<()> {
+        self.send(protocol::enter_dfu_mode2(self.axis)?).await
     }
 
     /// Clear disarm reason and procedure result.
-    pub async fn clear_errors(&self, identify: bool) -> io::Result<()> {
-        let frame = CanFrame::new(Id::new(self.axis, 0x18).unwrap(), &[identify as u8]).unwrap();
-        self.interface.write_frame(frame).await
+    pub async fn clear_errors(&self, identify: bool) -> Result<()> {
+        self.send(protocol::clear_errors(self.axis, identify)?)
+            .await
     }
 
     /// Set the absolute position estimate.
     ///
     /// - `position` rev.
-    pub async fn set_absolute_position(&self, position: f32) -> io::Result<()> {
-        let frame =
-            CanFrame::new(Id::new(self.axis, 0x19).unwrap(), &position.to_le_bytes()).unwrap();
-        self.interface.write_frame(frame).await
+    pub async fn set_absolute_position(&self, position: f32) -> Result<()> {
+        self.send(protocol::set_absolute_position(self.axis, position)?)
+            .await
     }
 
     /// Set position gain.
     ///
     /// - `gain` (rev/s)/rev.
-    pub async fn set_position_gain(&self, gain: f32) -> io::Result<()> {
-        let frame = CanFrame::new(Id::new(self.axis, 0x1a).unwrap(), &gain.to_le_bytes()).unwrap();
-        self.interface.write_frame(frame).await
+    pub async fn set_position_gain(&self, gain: f32) -> Result<()> {
+        self.send(protocol::set_position_gain(self.axis, gain)?)
+            .await
     }
 
     /// Set velocity gains.
     ///
     /// - `gain` Nm/(rev/s).
     /// - `integrator_gain` Nm/rev.
-    pub async fn set_velocity_gains(&self, gain: f32, integrator_gain: f32) -> io::Result<()> {
-        let mut data = vec![];
-        data.extend(gain.to_le_bytes());
-        data.extend(integrator_gain.to_le_bytes());
-        let frame = CanFrame::new(Id::new(self.axis, 0x1b).unwrap(), &data).unwrap();
-        self.interface.write_frame(frame).await
+    pub async fn set_velocity_gains(&self, gain: f32, integrator_gain: f32) -> Result<()> {
+        self.send(protocol::set_velocity_gains(
+            self.axis,
+            gain,
+            integrator_gain,
+        )?)
+        .await
     }
 
     /// Get torque values.
-    pub async fn get_torques(&self) -> io::Result<Torque> {
-        let id = Id::new(self.axis, 0x1c).unwrap();
+    pub async fn get_torques(&self) -> Result<Torque> {
+        self.request(protocol::get_torques(self.axis)?).await
+    }
 
-        // request the message with an rtr frame
-        self.interface
-            .write_frame(CanFrame::new_remote(id, 0).unwrap())
-            .await?;
+    /// Get power values.
+    pub async fn get_powers(&self) -> Result<Power> {
+        self.request(protocol::get_powers(self.axis)?).await
+    }
 
-        let frame = loop {
-            let frame = self.interface.read_frame().await?;
-            if frame.id() == id.into() {
-                break frame;
+    /// Get all telemetry values at once.
+    ///
+    /// The requests are sent without waiting for the previous responses, so
+    /// the snapshot takes about one round trip instead of one per value.
+    pub async fn snapshot(&self) -> Result<Snapshot> {
+        let mut encoder = pin!(self.get_encoder_estimates());
+        let mut iq = pin!(self.get_iq());
+        let mut temperature = pin!(self.get_temperature());
+        let mut bus = pin!(self.get_bus_voltage_current());
+        let mut torques = pin!(self.get_torques());
+        let mut powers = pin!(self.get_powers());
+        let mut outputs = (None, None, None, None, None, None);
+
+        poll_fn(|cx| {
+            let done = [
+                poll_into(encoder.as_mut(), &mut outputs.0, cx),
+                poll_into(iq.as_mut(), &mut outputs.1, cx),
+                poll_into(temperature.as_mut(), &mut outputs.2, cx),
+                poll_into(bus.as_mut(), &mut outputs.3, cx),
+                poll_into(torques.as_mut(), &mut outputs.4, cx),
+                poll_into(powers.as_mut(), &mut outputs.5, cx),
+            ];
+            if done.iter().all(|done| *done) {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
             }
-        };
+        })
+        .await;
+
+        let missing = || crate::Error::Protocol("request did not complete".into());
+        Ok(Snapshot {
+            encoder: outputs.0.ok_or_else(missing)??,
+            iq: outputs.1.ok_or_else(missing)??,
+            temperature: outputs.2.ok_or_else(missing)??,
+            bus: outputs.3.ok_or_else(missing)??,
+            torques: outputs.4.ok_or_else(missing)??,
+            powers: outputs.5.ok_or_else(missing)??,
+            captured: T::Timer::now(),
+        })
+    }
 
-        if frame.data().len() != 8 {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                format!("Frame data length invalid: {} != 8", frame.data().len()),
-            ));
+    /// Read every read-write endpoint into a configuration, an object of
+    /// endpoint names and values that
+    /// [`apply_configuration`](Self::apply_configuration) writes back, for
+    /// example to clone a tuned drive onto a spare.
+    ///
+    /// The reads are sent in batches of [`SDO_WINDOW`] without waiting
+    /// for each response, `progress` is called with the number of endpoints
+    /// read and the total after every batch.
+    /// See [`flat_endpoints::config_to_toml`](crate::flat_endpoints::config_to_toml)
+    /// to store it as TOML.
+    #[cfg(feature = "flat-endpoints")]
+    pub async fn backup_configuration(
+        &self,
+        endpoints: &FlatEndpoints,
+        mut progress: impl FnMut(usize, usize),
+    ) -> Result<serde_json::Value> {
+        let configuration = endpoints.configuration_endpoints();
+        let total = configuration.len();
+        let mut backup = serde_json::Map::with_capacity(total);
+        for batch in configuration.chunks(SDO_WINDOW) {
+            let reads: Vec<_> = batch
+                .iter()
+                .map(|&(_, endpoint, kind)| (endpoint, kind))
+                .collect();
+            let values = self.sdo_read_many(&reads, SDO_WINDOW).await;
+            for (&(name, ..), value) in batch.iter().zip(values) {
+                backup.insert(name.to_owned(), value?.to_json());
+            }
+            progress(backup.len(), total);
         }
-
-        let data = frame.data();
-
-        Ok(Torque {
-            target: f32::from_le_bytes([data[0], data[1], data[2], data[3]]),
-            estimate: f32::from_le_bytes([data[4], data[5], data[6], data[7]]),
-        })
+        Ok(backup.into())
     }
 
-    /// Get power values.
-    pub async fn get_powers(&self) -> io::Result<Power> {
-        let id = Id::new(self.axis, 0x1d).unwrap();
+    /// Apply the configuration of profile `name` in `profiles`.
+    ///
+    /// Like [`Self::apply_configuration`], but the current values are read
+    /// first and written back if any write fails, so the drive is not left
+    /// between two profiles. Write-only endpoints cannot be restored.
+    #[cfg(feature = "flat-endpoints")]
+    pub async fn apply_profile(
+        &self,
+        endpoints: &FlatEndpoints,
+        profiles: &Profiles,
+        name: &str,
+    ) -> Result<ApplyReport> {
+        let config = profiles.resolve(name)?;
+        let mut previous = Vec::new();
+        for (_, endpoint, value, access) in endpoints.resolve_configuration(config)? {
+            if access.is_readable() {
+                previous.push((endpoint, self.sdo_read(endpoint, value.kind()).await?));
+            }
+        }
 
-        // request the message with an rtr frame
-        self.interface
-            .write_frame(CanFrame::new_remote(id, 0).unwrap())
+        let report = self
+            .apply_configuration(endpoints, config, Verify::None)
             .await?;
-
-        let frame = loop {
-            let frame = self.interface.read_frame().await?;
-            if frame.id() == id.into() {
-                break frame;
+        if !report.failed.is_empty() {
+            for (endpoint, value) in previous {
+                self.sdo_write(endpoint, value).await?;
             }
-        };
-
-        if frame.data().len() != 8 {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                format!("Frame data length invalid: {} != 8", frame.data().len()),
-            ));
         }
+        Ok(report)
+    }
 
-        let data = frame.data();
+    /// Entries of `config` the device does not match, for detecting drift
+    /// or checking a drive before closed loop control.
+    ///
+    /// Floats match within `tolerance` relative to the expected value, other
+    /// kinds only if equal. Write-only entries cannot be read and are not
+    /// compared.
+    #[cfg(feature = "flat-endpoints")]
+    pub async fn diff_configuration(
+        &self,
+        endpoints: &FlatEndpoints,
+        config: &serde_json::Value,
+        tolerance: f64,
+    ) -> Result<Vec<Difference>> {
+        let entries: Vec<_> = endpoints
+            .resolve_configuration(config)?
+            .into_iter()
+            .filter(|&(.., access)| access.is_readable())
+            .collect();
+        let reads: Vec<_> = entries
+            .iter()
+            .map(|&(_, endpoint, expected, _)| (endpoint, expected.kind()))
+            .collect();
+        let values = self.sdo_read_many(&reads, SDO_WINDOW).await;
+
+        let mut differences = Vec::new();
+        for ((key, _, expected, _), actual) in entries.into_iter().zip(values) {
+            differences.extend(Difference::new(key, expected, actual?, tolerance));
+        }
+        Ok(differences)
+    }
 
-        Ok(Power {
-            electrical: f32::from_le_bytes([data[0], data[1], data[2], data[3]]),
-            mechanical: f32::from_le_bytes([data[4], data[5], data[6], data[7]]),
-        })
+    /// Entries of `config` that differ from the device, without writing
+    /// anything.
+    ///
+    /// Reads the current value of every entry, write-only entries are
+    /// always listed. Fails like [`Self::apply_configuration`] if `config`
+    /// cannot be applied.
+    #[cfg(feature = "flat-endpoints")]
+    pub async fn dry_run_configuration(
+        &self,
+        endpoints: &FlatEndpoints,
+        config: &serde_json::Value,
+    ) -> Result<Vec<Change>> {
+        let mut changes = Vec::new();
+        for (key, endpoint, desired, access) in endpoints.resolve_configuration(config)? {
+            let current = if access.is_readable() {
+                Some(self.sdo_read(endpoint, desired.kind()).await?)
+            } else {
+                None
+            };
+            changes.extend(Change::new(key, current, desired));
+        }
+        Ok(changes)
     }
 
+    /// Write every entry of `config`, an object of endpoint names and
+    /// values, and read them back according to `verify`.
+    ///
+    /// Fails with [`crate::Error::Config`] without writing anything if an
+    /// entry does not exist, is read-only or its value does not fit the
+    /// endpoint. Failed writes and read backs do not stop the others, they
+    /// are listed in the returned [`ApplyReport`].
     #[cfg(feature = "flat-endpoints")]
     pub async fn apply_configuration(
         &self,
         endpoints: &FlatEndpoints,
         config: &serde_json::Value,
-    ) -> io::Result<()> {
-        let Some(items) = config.as_object() else {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidInput,
-                "Expected object",
-            ));
-        };
+        verify: Verify,
+    ) -> Result<ApplyReport> {
+        let writes = endpoints.resolve_configuration(config)?;
+
+        let mut report = ApplyReport::default();
+        let mut written = Vec::with_capacity(writes.len());
+        for (key, endpoint, value, access) in writes {
+            match self
+                .write_value(endpoint, value, access.is_readable())
+                .await
+            {
+                Ok(()) => written.push((key, endpoint, value, access)),
+                Err(err) => report.failed.push((key, err)),
+            }
+        }
 
-        for (key, value) in items.iter() {
-            let Some((id, kind)) = endpoints.get(key) else {
-                return Err(io::Error::new(
-                    io::ErrorKind::InvalidInput,
-                    "Configuration endpoint not found in flat endpoints",
-                ));
-            };
+        // read back once everything is written, so later writes that
+        // change earlier endpoints are caught too
+        for (key, endpoint, value, access) in written {
+            if verify == Verify::None || !access.is_readable() {
+                report.applied.push(key);
+                continue;
+            }
+            let read = self.sdo_read(endpoint, value.kind()).await;
+            report.check(key, value, read, verify);
+        }
 
-            let Some(value) = Value::try_from_json(value, kind) else {
-                return Err(io::Error::new(
-                    io::ErrorKind::InvalidInput,
-                    "Configuration value not able to be converted into an SDO value",
-                ));
-            };
+        Ok(report)
+    }
 
-            let endpoint = u16::try_from(id).map_err(|_| {
-                io::Error::new(
-                    io::ErrorKind::InvalidInput,
-                    "Endpoint ID out of range for u16",
-                )
-            })?;
-            self.sdo_write(endpoint, value).await?;
+    /// Restore a configuration such as a backup, see
+    /// [`Self::backup_configuration`].
+    ///
+    /// Writes `config`, saves it and waits up to [`REBOOT_TIMEOUT`] for the
+    /// drive to reboot, that is for its heartbeats to stop for
+    /// [`REBOOT_SILENCE`] and resume. Then reads every entry again. The
+    /// configuration is not saved if any write failed. Entries that differ
+    /// after the reboot, such as values clamped by the firmware, are listed
+    /// in the report, write-only entries cannot be checked.
+    #[cfg(feature = "flat-endpoints")]
+    pub async fn restore_configuration(
+        &self,
+        endpoints: &FlatEndpoints,
+        config: &serde_json::Value,
+    ) -> Result<RestoreReport> {
+        let apply = self
+            .apply_configuration(endpoints, config, Verify::None)
+            .await?;
+        if !apply.failed.is_empty() {
+            return Ok(RestoreReport {
+                apply,
+                saved: false,
+                residual: Vec::new(),
+            });
         }
 
-        Ok(())
+        // subscribe first so the heartbeat after the reboot is not missed
+        let (_, receiver) = self.clone().split();
+        self.save_configuration().await?;
+        let heartbeat = || async {
+            loop {
+                let frame = receiver.recv().await?;
+                if let Some(Ok(TelemetryMessage::Heartbeat(_))) = TelemetryMessage::decode(&frame) {
+                    return Ok(());
+                }
+            }
+        };
+        let rebooted = async {
+            // heartbeats sent before the reset may still arrive
+            while timeout_after::<T::Timer, _>(REBOOT_SILENCE, heartbeat())
+                .await
+                .transpose()?
+                .is_some()
+            {}
+            heartbeat().await
+        };
+        timeout_after::<T::Timer, _>(REBOOT_TIMEOUT, rebooted)
+            .await
+            .unwrap_or(Err(crate::Error::Timeout))?;
+
+        // the drive may still be starting up, retry reads that time out
+        let mut retries = 0;
+        let changes = loop {
+            match self.dry_run_configuration(endpoints, config).await {
+                Err(crate::Error::Timeout) if retries < self.retries.max(2) => retries += 1,
+                result => break result?,
+            }
+        };
+        Ok(RestoreReport {
+            apply,
+            saved: true,
+            residual: changes
+                .into_iter()
+                .filter(|change| change.current.is_some())
+                .collect(),
+        })
     }
 }
 
-/// Version information.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct Version {
-    pub protocol_version: u8,
-    pub hw_version_major: u8,
-    pub hw_version_minor: u8,
-    pub hw_version_variant: u8,
-    pub fw_version_major: u8,
-    pub fw_version_minor: u8,
-    pub fw_version_revision: u8,
-    pub fw_version_unreleased: bool,
-}
-
-/// Error message.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct Error {
-    /// Active errors
-    pub active_errors: AxisErrors,
-    /// Reason for disarm
-    pub disarm_reason: AxisErrors,
+/// Poll `future` unless it already completed into `output`.
+///
+/// Returns whether the future has completed.
+fn poll_into<F: Future>(
+    future: Pin<&mut F>,
+    output: &mut Option<F::Output>,
+    cx: &mut Context<'_>,
+) -> bool {
+    if output.is_none()
+        && let Poll::Ready(value) = future.poll(cx)
+    {
+        *output = Some(value);
+    }
+    output.is_some()
 }
 
-/// Encoder estimates.
+/// Telemetry values of an axis, see [`ODrive::snapshot`].
 #[derive(Debug, Clone, Copy)]
-pub struct EncoderEstimate {
-    /// Position estimate in revolutions
-    pub position: f32,
-    /// Velocity estimate in rev/s
-    pub velocity: f32,
+pub struct Snapshot {
+    pub encoder: EncoderEstimate,
+    /// Iq setpoint and measurement.
+    pub iq: (f32, f32),
+    pub temperature: Temperature,
+    pub bus: BusVoltageCurrent,
+    pub torques: Torque,
+    pub powers: Power,
+    /// When the last response was received.
+    pub captured: Instant,
 }
 
-/// Temperature message.
-#[derive(Debug, Clone, Copy)]
-pub struct Temperature {
-    /// FET temperature
-    pub fet: f32,
-    /// Motor temperature
-    pub motor: f32,
+/// Several ODrives sharing one transport.
+///
+/// Every axis is a separate [`ODrive`] driver, received frames are routed to
+/// the driver of their node whichever driver read them.
+///
+/// ```no_run
+/// # tokio_test::block_on(async {
+/// use odrive::can::Bus;
+///
+/// let socket = socketcan::tokio::CanSocket::open("can0").unwrap();
+/// let bus = Bus::new(socket);
+/// let left = bus.axis(1).unwrap();
+/// let right = bus.axis(2).unwrap();
+///
+/// let (left, right) = tokio::join!(left.get_iq(), right.get_iq());
+/// # });
+/// ```
+pub struct Bus<T> {
+    interface: Arc<T>,
+    pending: Arc<Pending>,
+    subscribers: Arc<Subscribers>,
+    middleware: Arc<Chain>,
+    filters: Mutex<Vec<Filter>>,
 }
 
-/// Bus voltage and current.
-#[derive(Debug, Clone, Copy)]
-pub struct BusVoltageCurrent {
-    /// Bus voltage in volts
-    pub voltage: f32,
-    /// Bus current in amps
-    pub current: f32,
+impl<T: Transport> Bus<T> {
+    /// Share `interface` between several ODrives.
+    pub fn new(interface: T) -> Self {
+        Self {
+            interface: Arc::new(interface),
+            pending: Arc::default(),
+            subscribers: Arc::default(),
+            middleware: Arc::default(),
+            filters: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Driver for `axis` on this bus.
+    ///
+    /// Adds a [`Filter::node`] filter for `axis` to the transport, failing
+    /// to install it is not an error. Fails with
+    /// [`crate::Error::InvalidNode`] if `axis` is larger than 63.
+    pub fn axis(&self, axis: u8) -> Result<ODrive<T>> {
+        let filter = Filter::node(axis)?;
+        let mut filters = self.filters.lock().unwrap_or_else(PoisonError::into_inner);
+        if !filters.contains(&filter) {
+            filters.push(filter);
+            let _ = self.interface.set_filters(&filters);
+        }
+
+        Ok(ODrive::with_shared(
+            self.interface.clone(),
+            axis,
+            self.pending.clone(),
+            self.subscribers.clone(),
+            self.middleware.clone(),
+        ))
+    }
+
+    /// Call `callback` for every received frame that is not the response to
+    /// a request, whichever node it belongs to.
+    pub fn on_frame(self, callback: impl Fn(&Frame) + Send + Sync + 'static) -> Self {
+        let _ = self.subscribers.add(None, Arc::new(callback));
+        self
+    }
+
+    /// Pass every frame written or read by the drivers of this bus through
+    /// `middleware`, see [`crate::middleware`].
+    pub fn with_middleware(self, middleware: impl Middleware + 'static) -> Self {
+        self.middleware.add(Arc::new(middleware));
+        self
+    }
 }
 
-/// Torque values
-#[derive(Debug, Clone, Copy)]
-pub struct Torque {
-    /// Torque target in Nm
-    pub target: f32,
-    /// Torque estimate in Nm
-    pub estimate: f32,
+/// Frames buffered for a [`Receiver`].
+#[derive(Default)]
+struct Inbox {
+    frames: VecDeque<Frame>,
+    waker: Option<Waker>,
 }
 
-/// Power values.
-#[derive(Debug, Clone, Copy)]
-pub struct Power {
-    /// Electrical power in watts
-    pub electrical: f32,
-    /// Mechanical power in watts
-    pub mechanical: f32,
+impl Inbox {
+    /// Number of frames buffered before the oldest is dropped.
+    const CAPACITY: usize = 256;
+
+    fn push(&mut self, frame: Frame) {
+        if self.frames.len() == Self::CAPACITY {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(frame);
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
 }
 
-/// Arbitrary parameter value.
-#[derive(Debug, Clone, Copy)]
-pub enum Value {
-    Bool(bool),
-    U8(u8),
-    I8(i8),
-    U16(u16),
-    I16(i16),
-    U32(u32),
-    I32(i32),
-    Float(f32),
+/// Receiving half of a split [`ODrive`], see [`ODrive::split`].
+pub struct Receiver<T> {
+    odrive: ODrive<T>,
+    subscriber: u64,
+    inbox: Arc<Mutex<Inbox>>,
 }
 
-impl Value {
-    /// Convert to a const length slice.
-    ///
-    /// Any unused bytes will be zero.
-    pub fn to_le_bytes(&self) -> [u8; 4] {
-        match *self {
-            Self::Bool(b) => [b as u8, 0, 0, 0],
-            Self::U8(u) => [u, 0, 0, 0],
-            Self::I8(i) => [i.to_le_bytes()[0], 0, 0, 0],
-            Self::U16(u) => [u.to_le_bytes()[0], u.to_le_bytes()[1], 0, 0],
-            Self::I16(i) => [i.to_le_bytes()[0], i.to_le_bytes()[1], 0, 0],
-            Self::U32(u) => u.to_le_bytes(),
-            Self::I32(i) => i.to_le_bytes(),
-            Self::Float(f) => f.to_le_bytes(),
-        }
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.odrive.subscribers.remove(self.subscriber);
     }
+}
 
-    #[cfg(feature = "flat-endpoints")]
-    pub fn try_from_json(value: &serde_json::Value, kind: ValueKind) -> Option<Self> {
-        match kind {
-            ValueKind::Bool => value.as_bool().map(Self::Bool),
-            ValueKind::U8 => value.as_u64().map(|b| Self::U8(b as u8)),
-            ValueKind::I8 => value.as_i64().map(|b| Self::I8(b as i8)),
-            ValueKind::U16 => value.as_u64().map(|b| Self::U16(b as u16)),
-            ValueKind::I16 => value.as_i64().map(|b| Self::I16(b as i16)),
-            ValueKind::U32 => value.as_u64().map(|b| Self::U32(b as u32)),
-            ValueKind::I32 => value.as_i64().map(|b| Self::I32(b as i32)),
-            ValueKind::Float => value
-                .as_number()
-                .and_then(|n| n.as_f64())
-                .map(|f| Self::Float(f as f32)),
+impl<T: Transport> Receiver<T> {
+    /// Receive the next frame that is not the response to a request.
+    ///
+    /// Frames received by the sending half are buffered, the oldest are
+    /// dropped if they are not taken in time.
+    pub async fn recv(&self) -> Result<Frame> {
+        let registration = self.odrive.pending.register(None);
+        loop {
+            let reader = poll_fn(|cx| {
+                let mut inbox = self.inbox.lock().unwrap_or_else(PoisonError::into_inner);
+                if let Some(frame) = inbox.frames.pop_front() {
+                    return Poll::Ready(Err(frame));
+                }
+                inbox.waker = Some(cx.waker().clone());
+                drop(inbox);
+                registration.poll_turn(cx).map(Ok)
+            })
+            .await;
+
+            match reader {
+                Err(frame) => return Ok(frame),
+                Ok(Turn::Read(reader)) => {
+                    let result = self.odrive.interface.read_frame().await;
+                    self.odrive.receive(reader, result)?;
+                }
+                Ok(Turn::Frame(_)) => {}
+            }
         }
     }
 }
 
-/// Arbitrary parameter value kind.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum ValueKind {
-    Bool,
-    U8,
-    I8,
-    U16,
-    I16,
-    U32,
-    I32,
-    Float,
-}
+type Recv = Pin<Box<dyn Future<Output = Result<Frame>> + Send>>;
 
-impl TryFrom<&serde_json::Value> for ValueKind {
-    type Error = ();
+/// Decoded telemetry of an axis, see [`ODrive::telemetry`].
+pub struct Telemetry<T> {
+    receiver: Arc<Receiver<T>>,
+    next: Option<Recv>,
+    done: bool,
+}
 
-    fn try_from(value: &serde_json::Value) -> Result<Self, Self::Error> {
-        let Some(string) = value.as_str() else {
-            return Err(());
-        };
+impl<T: Transport + Send + Sync + 'static> futures_core::Stream for Telemetry<T> {
+    type Item = TelemetryMessage;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        while !this.done {
+            let next = this.next.get_or_insert_with(|| {
+                let receiver = this.receiver.clone();
+                Box::pin(async move { receiver.recv().await })
+            });
+            let Poll::Ready(result) = next.as_mut().poll(cx) else {
+                return Poll::Pending;
+            };
+            this.next = None;
 
-        Ok(match string {
-            "bool" => Self::Bool,
-            "uint8" => Self::U8,
-            "int8" => Self::I8,
-            "uint16" => Self::U16,
-            "int16" => Self::I16,
-            "uint32" => Self::U32,
-            "int32" => Self::I32,
-            "float" => Self::Float,
-            _ => return Err(()),
-        })
+            match result {
+                Ok(frame) => {
+                    if let Some(Ok(message)) = TelemetryMessage::decode(&frame) {
+                        return Poll::Ready(Some(message));
+                    }
+                }
+                Err(_) => this.done = true,
+            }
+        }
+        Poll::Ready(None)
     }
 }
 
@@ -714,22 +1497,473 @@ impl TryFrom<&serde_json::Value> for ValueKind {
 mod tests {
     use super::*;
 
+    struct NoDelay;
+
+    impl Timer for NoDelay {
+        async fn sleep(_: Duration) {}
+    }
+
+    /// Transport for an absent node, nothing is ever received.
+    struct Silent;
+
+    impl Transport for Silent {
+        type Timer = NoDelay;
+
+        async fn write_frame(&self, _: &Frame) -> io::Result<()> {
+            Ok(())
+        }
+
+        async fn read_frame(&self) -> io::Result<Frame> {
+            std::future::pending().await
+        }
+    }
+
     #[test]
-    fn value_to_bytes() {
-        let value = Value::Float(1.234);
-        assert_eq!(value.to_le_bytes(), [0xb6, 0xf3, 0x9d, 0x3f]);
+    fn request_timeout() {
+        let odrive = ODrive::new(Silent, 1).unwrap();
+        let result = tokio_test::block_on(odrive.get_version());
+        assert!(matches!(result, Err(crate::Error::Timeout)));
+
+        let result = tokio_test::block_on(odrive.timeout(Duration::from_millis(10)).get_iq());
+        assert!(matches!(result, Err(crate::Error::Timeout)));
     }
 
     #[test]
-    #[cfg(feature = "flat-endpoints")]
-    fn value_from_json() {
-        Value::try_from_json(&serde_json::json!(true), ValueKind::Bool).unwrap();
-        Value::try_from_json(&serde_json::json!(13), ValueKind::U8).unwrap();
-        Value::try_from_json(&serde_json::json!(-13), ValueKind::I8).unwrap();
-        Value::try_from_json(&serde_json::json!(13), ValueKind::U16).unwrap();
-        Value::try_from_json(&serde_json::json!(-13), ValueKind::I16).unwrap();
-        Value::try_from_json(&serde_json::json!(13), ValueKind::U32).unwrap();
-        Value::try_from_json(&serde_json::json!(-13), ValueKind::I32).unwrap();
-        Value::try_from_json(&serde_json::json!(0.0), ValueKind::Float).unwrap();
+    fn retries_counted() {
+        let odrive = ODrive::new(Silent, 1).unwrap().with_retries(2);
+        let result = tokio_test::block_on(odrive.get_version());
+        assert!(matches!(result, Err(crate::Error::Timeout)));
+
+        let stats = odrive.stats();
+        assert_eq!(stats.frames_sent, 3);
+        assert_eq!(stats.timeouts, 3);
+        assert_eq!(stats.retries, 2);
+    }
+
+    /// Transport delivering the given frames in reverse order.
+    struct Chatty(Mutex<Vec<Frame>>);
+
+    impl Transport for Chatty {
+        type Timer = NoDelay;
+
+        async fn write_frame(&self, _: &Frame) -> io::Result<()> {
+            Ok(())
+        }
+
+        async fn read_frame(&self) -> io::Result<Frame> {
+            tokio::task::yield_now().await;
+            self.0
+                .lock()
+                .unwrap()
+                .pop()
+                .ok_or(io::ErrorKind::BrokenPipe.into())
+        }
+    }
+
+    #[test]
+    fn unmatched_frames_dispatched() {
+        let id = |command| cansimple::Id::new(1, command).unwrap();
+        let frames = vec![
+            Frame::new(id(0x14), &[0; 8]).unwrap(),
+            Frame::new(id(0x01), &[0; 8]).unwrap(),
+        ];
+        let received = Arc::new(Mutex::new(vec![]));
+        let log = received.clone();
+        let odrive = ODrive::new(Chatty(Mutex::new(frames)), 1)
+            .unwrap()
+            .with_timeout(None)
+            .on_frame(move |frame| log.lock().unwrap().push(*frame));
+
+        tokio_test::block_on(odrive.get_iq()).unwrap();
+        assert_eq!(received.lock().unwrap().len(), 1);
+        assert_eq!(received.lock().unwrap()[0].id(), id(0x01).into());
+        assert!(odrive.cache().latest_heartbeat().is_some());
+        assert!(odrive.cache().latest_iq().is_some());
+
+        let stats = odrive.stats();
+        assert_eq!(stats.frames_sent, 1);
+        assert_eq!(stats.frames_received, 2);
+        assert_eq!(stats.latency[&0x14].count, 1);
+    }
+
+    #[test]
+    fn middleware_drops_frames() {
+        struct DropHeartbeats;
+
+        impl crate::middleware::Middleware for DropHeartbeats {
+            fn incoming(&self, frame: Frame) -> Option<Frame> {
+                (frame.id() != cansimple::Id::new(1, 0x01).unwrap().into()).then_some(frame)
+            }
+        }
+
+        let id = |command| cansimple::Id::new(1, command).unwrap();
+        let frames = vec![
+            Frame::new(id(0x14), &[0; 8]).unwrap(),
+            Frame::new(id(0x01), &[0; 8]).unwrap(),
+        ];
+        let received = Arc::new(Mutex::new(vec![]));
+        let log = received.clone();
+        let odrive = ODrive::new(Chatty(Mutex::new(frames)), 1)
+            .unwrap()
+            .with_timeout(None)
+            .with_middleware(DropHeartbeats)
+            .on_frame(move |frame| log.lock().unwrap().push(*frame));
+
+        tokio_test::block_on(odrive.get_iq()).unwrap();
+        assert!(received.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn dropped_frames_not_counted() {
+        struct DropAll;
+
+        impl crate::middleware::Middleware for DropAll {
+            fn outgoing(&self, _: Frame) -> Option<Frame> {
+                None
+            }
+        }
+
+        let odrive = ODrive::new(Silent, 1).unwrap().with_middleware(DropAll);
+        let result = tokio_test::block_on(odrive.get_version());
+        assert!(matches!(result, Err(crate::Error::Timeout)));
+        assert_eq!(odrive.stats().frames_sent, 0);
+    }
+
+    #[test]
+    fn telemetry_read_mode() {
+        let id = |command| cansimple::Id::new(1, command).unwrap();
+        let frames = vec![Frame::new(id(0x14), &[0; 8]).unwrap()];
+        let odrive = ODrive::new(Chatty(Mutex::new(frames)), 1)
+            .unwrap()
+            .with_timeout(None)
+            .with_read_mode(ReadMode::Telemetry {
+                max_age: Duration::from_secs(60),
+            });
+
+        // waits for the broadcast, then answers from the cache
+        tokio_test::block_on(odrive.get_iq()).unwrap();
+        tokio_test::block_on(odrive.get_iq()).unwrap();
+        assert!(tokio_test::block_on(odrive.get_temperature()).is_err());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(start_paused = true)]
+    async fn virtual_time() {
+        use crate::mock::MockBus;
+        use crate::transport::TokioTimer;
+
+        let bus = MockBus::<TokioTimer>::default();
+        let id = |command| cansimple::Id::new(1, command).unwrap();
+        bus.push(Frame::new(id(0x14), &[0; 8]).unwrap());
+        let odrive = ODrive::new(bus, 1).unwrap();
+
+        let started = TokioTimer::now();
+        assert!(matches!(
+            odrive.get_version().await,
+            Err(crate::Error::Timeout)
+        ));
+        let elapsed = TokioTimer::now() - started;
+        assert!((DEFAULT_TIMEOUT..DEFAULT_TIMEOUT * 2).contains(&elapsed));
+
+        // the broadcast received while waiting is stale by now
+        let odrive = odrive.with_read_mode(ReadMode::Telemetry {
+            max_age: Duration::from_millis(100),
+        });
+        assert!(odrive.cache().latest_iq().is_some());
+        assert!(matches!(odrive.get_iq().await, Err(crate::Error::Timeout)));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(start_paused = true)]
+    async fn sdo_read_many() {
+        use crate::mock::{MockBus, MockODrive};
+        use crate::transport::TokioTimer;
+
+        let node = Arc::new(MockODrive::new(1));
+        node.update(|state| {
+            state.endpoints.insert(1, 24.5f32.to_le_bytes());
+            state.endpoints.insert(2, 7u32.to_le_bytes());
+            state.endpoints.insert(3, 1u32.to_le_bytes());
+        });
+        let bus = MockBus::<TokioTimer>::default().with_node(node);
+        let odrive = ODrive::new(bus, 1).unwrap();
+
+        let reads = [
+            (3, ValueKind::U64),
+            (99, ValueKind::U32),
+            (1, ValueKind::Float),
+            (2, ValueKind::U8),
+        ];
+        let values = odrive.sdo_read_many(&reads, 2).await;
+        assert!(matches!(
+            values[0],
+            Err(crate::Error::ValueTooWide(ValueKind::U64))
+        ));
+        assert!(matches!(values[1], Err(crate::Error::Timeout)));
+        assert!(matches!(values[2], Ok(Value::Float(24.5))));
+        assert!(matches!(values[3], Ok(Value::U8(7))));
+        assert!(odrive.sdo_read_many(&[], 2).await.is_empty());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(start_paused = true)]
+    async fn write_verification() {
+        use crate::mock::{MockBus, MockODrive};
+        use crate::transport::TokioTimer;
+
+        /// Drops writes to endpoint 2 as if the ODrive rejected them.
+        struct RejectWrites;
+
+        impl crate::middleware::Middleware for RejectWrites {
+            fn outgoing(&self, frame: Frame) -> Option<Frame> {
+                (frame.data()[..3] != [1, 2, 0]).then_some(frame)
+            }
+        }
+
+        let node = Arc::new(MockODrive::new(1));
+        node.update(|state| {
+            state.endpoints.insert(1, 0f32.to_le_bytes());
+            state.endpoints.insert(2, 5u32.to_le_bytes());
+        });
+        let bus = MockBus::<TokioTimer>::default().with_node(node);
+        let odrive = ODrive::new(bus, 1)
+            .unwrap()
+            .with_middleware(RejectWrites)
+            .with_write_verification(Some(1e-6));
+
+        odrive.sdo_write(1, 2.5f32).await.unwrap();
+        assert!(matches!(
+            odrive.sdo_write(2, 7u32).await,
+            Err(crate::Error::WriteMismatch {
+                endpoint: 2,
+                written: Value::U32(7),
+                read: Value::U32(5),
+            })
+        ));
+
+        let odrive = odrive.with_write_verification(None);
+        odrive.sdo_write(2, 7u32).await.unwrap();
+    }
+
+    #[test]
+    fn firmware_gating() {
+        use crate::mock::{MockBus, MockODrive};
+
+        let node = Arc::new(MockODrive::new(1));
+        node.update(|state| state.version.fw_version_revision = 8);
+        let bus = MockBus::new().with_node(node);
+        let odrive = ODrive::new(bus, 1).unwrap();
+
+        tokio_test::block_on(async {
+            let version = odrive.get_version().await.unwrap();
+            let odrive = odrive.with_firmware(Some(version.firmware()));
+            assert!(matches!(
+                odrive.get_powers().await,
+                Err(crate::Error::UnsupportedByFirmware {
+                    required: (0, 6, 9),
+                    actual: (0, 6, 8),
+                    ..
+                })
+            ));
+            assert!(odrive.get_torques().await.is_ok());
+        });
+    }
+
+    #[test]
+    fn trajectory_done() {
+        let id = |command| cansimple::Id::new(1, command).unwrap();
+        let frames = vec![
+            Frame::new(id(0x01), &[0, 0, 0, 0, 1, 0, 1, 0]).unwrap(),
+            Frame::new(id(0x09), &[0; 8]).unwrap(),
+            Frame::new(id(0x01), &[0, 0, 0, 0, 1, 0, 0, 0]).unwrap(),
+        ];
+        let odrive = ODrive::new(Chatty(Mutex::new(frames)), 1).unwrap();
+
+        tokio_test::block_on(odrive.trajectory_done()).unwrap();
+        assert!(tokio_test::block_on(odrive.trajectory_done()).is_err());
+    }
+
+    #[test]
+    fn snapshot() {
+        let id = |command| cansimple::Id::new(1, command).unwrap();
+        let frames = [0x1d, 0x1c, 0x17, 0x15, 0x14, 0x09]
+            .map(|command| Frame::new(id(command), &[0; 8]).unwrap())
+            .to_vec();
+        let odrive = ODrive::new(Chatty(Mutex::new(frames)), 1)
+            .unwrap()
+            .with_timeout(None);
+
+        let snapshot = tokio_test::block_on(odrive.snapshot()).unwrap();
+        assert_eq!(snapshot.bus.voltage, 0.0);
+        assert!(tokio_test::block_on(odrive.snapshot()).is_err());
+    }
+
+    #[test]
+    fn concurrent_requests() {
+        let id = |command| cansimple::Id::new(1, command).unwrap();
+        let frames = vec![
+            Frame::new(id(0x14), &[0; 8]).unwrap(),
+            Frame::new(id(0x15), &[0; 8]).unwrap(),
+        ];
+        let odrive = ODrive::new(Chatty(Mutex::new(frames)), 1)
+            .unwrap()
+            .with_timeout(None);
+
+        let (iq, temperature) =
+            tokio_test::block_on(async { tokio::join!(odrive.get_iq(), odrive.get_temperature()) });
+        assert!(iq.is_ok());
+        assert!(temperature.is_ok());
+    }
+
+    #[test]
+    fn split_halves() {
+        let id = |command| cansimple::Id::new(1, command).unwrap();
+        let frames = vec![
+            Frame::new(id(0x01), &[0; 8]).unwrap(),
+            Frame::new(id(0x14), &[0; 8]).unwrap(),
+            Frame::new(id(0x09), &[0; 8]).unwrap(),
+        ];
+        let odrive = ODrive::new(Chatty(Mutex::new(frames)), 1)
+            .unwrap()
+            .with_timeout(None);
+        let (tx, rx) = odrive.split();
+
+        tokio_test::block_on(async {
+            let (telemetry, iq) = tokio::join!(rx.recv(), tx.get_iq());
+            assert_eq!(telemetry.unwrap().id(), id(0x09).into());
+            assert!(iq.is_ok());
+            assert_eq!(rx.recv().await.unwrap().id(), id(0x01).into());
+        });
+    }
+
+    #[test]
+    fn telemetry_stream() {
+        use futures_core::Stream;
+
+        let id = |command| cansimple::Id::new(1, command).unwrap();
+        let frames = vec![
+            Frame::new(id(0x15), &[0; 8]).unwrap(),
+            Frame::new(id(0x05), &[0; 8]).unwrap(),
+            Frame::new(id(0x01), &[0; 8]).unwrap(),
+        ];
+        let odrive = ODrive::new(Chatty(Mutex::new(frames)), 1).unwrap();
+        let mut telemetry = odrive.telemetry();
+        let messages = tokio_test::block_on(async {
+            let mut messages = vec![];
+            while let Some(message) = poll_fn(|cx| Pin::new(&mut telemetry).poll_next(cx)).await {
+                messages.push(message);
+            }
+            messages
+        });
+
+        assert!(matches!(
+            messages[..],
+            [
+                TelemetryMessage::Heartbeat(_),
+                TelemetryMessage::Temperature(_)
+            ]
+        ));
+    }
+
+    #[test]
+    fn dropped_drivers_unsubscribe() {
+        let bus = Bus::new(Silent);
+        let subscribers = bus.subscribers.clone();
+        let odrive = bus.axis(1).unwrap();
+        let other = odrive.clone();
+        assert_eq!(subscribers.len(), 1);
+        for _ in 0..3 {
+            drop(bus.axis(2).unwrap());
+        }
+        drop(odrive);
+        assert_eq!(subscribers.len(), 1);
+        drop(other);
+        assert_eq!(subscribers.len(), 0);
+    }
+
+    #[test]
+    fn bus_demultiplexes() {
+        let id = |node, command| cansimple::Id::new(node, command).unwrap();
+        let frames = vec![
+            Frame::new(id(1, 0x14), &[0; 8]).unwrap(),
+            Frame::new(id(1, 0x01), &[0; 8]).unwrap(),
+            Frame::new(id(2, 0x14), &[0; 8]).unwrap(),
+        ];
+        let bus = Bus::new(Chatty(Mutex::new(frames)));
+        let heartbeats = Arc::new(Mutex::new(vec![]));
+        let log = heartbeats.clone();
+        let left = bus.axis(1).unwrap().with_timeout(None);
+        let right = bus
+            .axis(2)
+            .unwrap()
+            .with_timeout(None)
+            .on_frame(move |frame| log.lock().unwrap().push(*frame));
+
+        let (left, right) =
+            tokio_test::block_on(async { tokio::join!(left.get_iq(), right.get_iq()) });
+        assert!(left.is_ok() && right.is_ok());
+        // the heartbeat of node 1 is not passed to the subscriber of node 2
+        assert!(heartbeats.lock().unwrap().is_empty());
+    }
+
+    /// Transport answering every remote frame, from another thread.
+    struct Echo {
+        tx: tokio::sync::mpsc::UnboundedSender<Frame>,
+        rx: tokio::sync::Mutex<tokio::sync::mpsc::UnboundedReceiver<Frame>>,
+    }
+
+    impl Transport for Echo {
+        type Timer = NoDelay;
+
+        async fn write_frame(&self, frame: &Frame) -> io::Result<()> {
+            let response = Frame::new(frame.id(), &[0; 8]).unwrap();
+            self.tx
+                .send(response)
+                .map_err(|_| io::ErrorKind::BrokenPipe.into())
+        }
+
+        async fn read_frame(&self) -> io::Result<Frame> {
+            let frame = self.rx.lock().await.recv().await;
+            frame.ok_or(io::ErrorKind::BrokenPipe.into())
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn shared_between_tasks() {
+        fn shareable<T: Send + Sync + Clone>(_: &T) {}
+
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        let echo = Echo {
+            tx,
+            rx: tokio::sync::Mutex::new(rx),
+        };
+        let odrive = Arc::new(ODrive::new(echo, 1).unwrap().with_timeout(None));
+        shareable(&odrive);
+
+        let tasks: Vec<_> = (0..16)
+            .map(|i| {
+                let odrive = odrive.clone();
+                tokio::spawn(async move {
+                    for _ in 0..20 {
+                        match i % 3 {
+                            0 => drop(odrive.get_iq().await.unwrap()),
+                            1 => drop(odrive.get_temperature().await.unwrap()),
+                            _ => drop(odrive.get_torques().await.unwrap()),
+                        }
+                    }
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+    }
+
+    #[test]
+    fn invalid_axis() {
+        assert!(matches!(
+            ODrive::new(Silent, 64),
+            Err(crate::Error::InvalidNode(64))
+        ));
     }
 }
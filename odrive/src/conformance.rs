@@ -0,0 +1,247 @@
+//! # Conformance suite
+//!
+//! [`Conformance`] runs every command of the driver against a drive and
+//! reports which ones passed, to validate a firmware upgrade against this
+//! crate quickly:
+//!
+//! ```no_run
+//! # tokio_test::block_on(async {
+//! use odrive::{can::ODrive, conformance::Conformance};
+//!
+//! let socket = socketcan::tokio::CanSocket::open("can0").unwrap();
+//! let odrive = ODrive::new(socket, 1).unwrap();
+//! let report = Conformance::new().run(&odrive).await;
+//! print!("{report}");
+//! assert!(report.is_ok());
+//! # });
+//! ```
+//!
+//! The axis is kept idle so the motor does not move, but the setters change
+//! the configuration in RAM. The suite ends by rebooting the drive, which
+//! restores the saved configuration. The configuration is never saved or
+//! erased.
+//!
+//! The `hw` integration test runs the suite on real hardware, see the
+//! README.
+
+use crate::can::ODrive;
+use crate::protocol::{TelemetryMessage, ValueKind};
+use crate::transport::{Transport, timeout};
+use crate::{AxisState, ControlMode, InputMode};
+use std::{fmt, time::Duration};
+
+/// Time to wait for a heartbeat, ten times the default heartbeat period.
+const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Result of a single command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    pub command: &'static str,
+    /// Error message if the command failed.
+    pub result: Result<(), String>,
+}
+
+/// Outcomes of a [`Conformance`] run, in the order the commands were sent.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    pub outcomes: Vec<Outcome>,
+}
+
+impl Report {
+    /// Whether all commands passed.
+    pub fn is_ok(&self) -> bool {
+        self.outcomes.iter().all(|outcome| outcome.result.is_ok())
+    }
+
+    /// Commands that failed.
+    pub fn failed(&self) -> impl Iterator<Item = &Outcome> {
+        self.outcomes
+            .iter()
+            .filter(|outcome| outcome.result.is_err())
+    }
+
+    fn record<V>(&mut self, command: &'static str, result: crate::Result<V>) {
+        self.outcomes.push(Outcome {
+            command,
+            result: result.map(|_| ()).map_err(|err| err.to_string()),
+        });
+    }
+}
+
+impl fmt::Display for Report {
+    /// One line per command, followed by a summary.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for outcome in &self.outcomes {
+            match &outcome.result {
+                Ok(()) => writeln!(f, "pass {}", outcome.command)?,
+                Err(err) => writeln!(f, "FAIL {}: {err}", outcome.command)?,
+            }
+        }
+        let failed = self.failed().count();
+        writeln!(
+            f,
+            "{} passed, {failed} failed",
+            self.outcomes.len() - failed
+        )
+    }
+}
+
+/// Command matrix run against a drive.
+#[derive(Debug, Clone, Default)]
+pub struct Conformance {
+    sdo: Option<(u16, ValueKind)>,
+}
+
+impl Conformance {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also read `endpoint` with SDO.
+    ///
+    /// Endpoint IDs depend on the firmware, so SDO is skipped by default.
+    pub fn with_sdo(mut self, endpoint: u16, kind: ValueKind) -> Self {
+        self.sdo = Some((endpoint, kind));
+        self
+    }
+
+    /// Send every command to `odrive`.
+    ///
+    /// Requests pass if the drive answers in time with a valid response.
+    /// Commands without response pass if they were sent, then the drive is
+    /// checked to still answer and to report no errors.
+    pub async fn run<T: Transport>(&self, odrive: &ODrive<T>) -> Report {
+        let mut report = Report::default();
+
+        report.record("heartbeat", heartbeat(odrive).await);
+        report.record("get_version", odrive.get_version().await);
+        report.record("get_error", odrive.get_error().await);
+        report.record(
+            "get_encoder_estimates",
+            odrive.get_encoder_estimates().await,
+        );
+        report.record("get_iq", odrive.get_iq().await);
+        report.record("get_temperature", odrive.get_temperature().await);
+        report.record(
+            "get_bus_voltage_current",
+            odrive.get_bus_voltage_current().await,
+        );
+        report.record("get_torques", odrive.get_torques().await);
+        report.record("get_powers", odrive.get_powers().await);
+        if let Some((endpoint, kind)) = self.sdo {
+            report.record("sdo_read", odrive.sdo_read(endpoint, kind).await);
+        }
+
+        // the ODrive's default configuration
+        report.record(
+            "set_axis_state",
+            odrive.set_axis_state(AxisState::Idle).await,
+        );
+        report.record(
+            "set_controller_mode",
+            odrive
+                .set_controller_mode(ControlMode::PositionControl, InputMode::Passthrough)
+                .await,
+        );
+        report.record(
+            "set_input_position",
+            odrive.set_input_position(0.0, 0, 0).await,
+        );
+        report.record(
+            "set_input_velocity",
+            odrive.set_input_velocity(0.0, 0.0).await,
+        );
+        report.record("set_input_torque", odrive.set_input_torque(0.0).await);
+        report.record("set_limits", odrive.set_limits(2.0, 10.0).await);
+        report.record(
+            "set_trajectory_velocity_limit",
+            odrive.set_trajectory_velocity_limit(2.0).await,
+        );
+        report.record(
+            "set_trajectory_acceleration_limit",
+            odrive.set_trajectory_acceleration_limit(0.5, 0.5).await,
+        );
+        report.record(
+            "set_trajectory_inertia",
+            odrive.set_trajectory_inertia(0.0).await,
+        );
+        report.record("set_position_gain", odrive.set_position_gain(20.0).await);
+        report.record(
+            "set_velocity_gains",
+            odrive.set_velocity_gains(0.16, 0.32).await,
+        );
+        report.record(
+            "set_absolute_position",
+            odrive.set_absolute_position(0.0).await,
+        );
+        report.record("estop", odrive.estop().await);
+        report.record("clear_errors", clear_errors(odrive).await);
+
+        report.record("reboot", reboot(odrive).await);
+        report
+    }
+}
+
+/// Wait for a heartbeat of `odrive`.
+async fn heartbeat<T: Transport>(odrive: &ODrive<T>) -> crate::Result<()> {
+    let (_, receiver) = odrive.clone().split();
+    let heartbeat = async {
+        loop {
+            let frame = receiver.recv().await?;
+            if let Some(Ok(TelemetryMessage::Heartbeat(_))) = TelemetryMessage::decode(&frame) {
+                return Ok(());
+            }
+        }
+    };
+    timeout::<T::Timer, _>(HEARTBEAT_TIMEOUT, heartbeat)
+        .await
+        .unwrap_or(Err(crate::Error::Timeout))
+}
+
+/// Clear the errors from the previous estop and check they are gone.
+async fn clear_errors<T: Transport>(odrive: &ODrive<T>) -> crate::Result<()> {
+    odrive.clear_errors(false).await?;
+    let error = odrive.get_error().await?;
+    if !error.active_errors.is_empty() {
+        return Err(crate::Error::Device(error.active_errors));
+    }
+    Ok(())
+}
+
+/// Reboot and wait for the drive to come back.
+async fn reboot<T: Transport>(odrive: &ODrive<T>) -> crate::Result<()> {
+    odrive.reboot().await?;
+    heartbeat(odrive).await
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use super::*;
+    use crate::mock::{MockBus, MockODrive};
+    use crate::transport::TokioTimer;
+    use std::sync::Arc;
+
+    #[tokio::test(start_paused = true)]
+    async fn runs_matrix() {
+        let node = Arc::new(MockODrive::new(1));
+        node.update(|state| {
+            state.endpoints.insert(0x10, 7u32.to_le_bytes());
+        });
+        let bus = MockBus::<TokioTimer>::default().with_node(node.clone());
+        bus.heartbeats();
+        let odrive = ODrive::new(bus, 1).unwrap();
+        let report = Conformance::new()
+            .with_sdo(0x10, ValueKind::U32)
+            .run(&odrive)
+            .await;
+        assert!(report.is_ok(), "{report}");
+        assert_eq!(report.outcomes.len(), 25);
+        assert!(node.state().active_errors.is_empty());
+
+        // nothing answers, only the commands without response pass
+        let odrive = ODrive::new(MockBus::<TokioTimer>::default(), 2).unwrap();
+        let report = Conformance::new().run(&odrive).await;
+        assert_eq!(report.failed().count(), 11);
+        assert!(report.to_string().ends_with("13 passed, 11 failed\n"));
+    }
+}
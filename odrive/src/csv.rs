@@ -0,0 +1,198 @@
+//! # CSV telemetry logging
+//!
+//! [`CsvLogger`] appends the latest encoder estimates, currents,
+//! temperatures and bus readings of every node to a CSV file at a fixed
+//! rate, for quick analysis of a test run in a spreadsheet.
+//!
+//! ```no_run
+//! # tokio_test::block_on(async {
+//! use odrive::{can::ODrive, csv::CsvLogger};
+//! use std::{fs::File, sync::Arc, time::Duration};
+//!
+//! let file = File::create("run.csv").unwrap();
+//! let logger = Arc::new(CsvLogger::new(file, Duration::from_millis(100)).unwrap());
+//! let socket = socketcan::tokio::CanSocket::open("can0").unwrap();
+//! let odrive = ODrive::new(socket, 1)
+//!     .unwrap()
+//!     .on_frame(logger.subscriber());
+//! odrive.listen().await.unwrap();
+//! # });
+//! ```
+//!
+//! Rows are written when telemetry is received, so the rate is only kept
+//! while the ODrive broadcasts at least as fast. Cells of messages not
+//! received yet are left empty, later rows repeat the last value.
+
+use crate::protocol::{Frame, TelemetryMessage};
+use cansimple::Id;
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    io::{self, Write},
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Logged fields as message name, field name and column.
+const COLUMNS: [(&str, &str, &str); 8] = [
+    ("encoder_estimates", "position", "position"),
+    ("encoder_estimates", "velocity", "velocity"),
+    ("iq", "setpoint", "iq_setpoint"),
+    ("iq", "measured", "iq_measured"),
+    ("temperature", "fet", "fet_temperature"),
+    ("temperature", "motor", "motor_temperature"),
+    ("bus_voltage_current", "voltage", "bus_voltage"),
+    ("bus_voltage_current", "current", "bus_current"),
+];
+
+#[derive(Default)]
+struct Row {
+    values: [Option<f64>; COLUMNS.len()],
+    written: Option<SystemTime>,
+}
+
+struct Inner<W> {
+    out: W,
+    rows: BTreeMap<u8, Row>,
+}
+
+/// Logs telemetry to a CSV file.
+pub struct CsvLogger<W> {
+    inner: Mutex<Inner<W>>,
+    interval: Duration,
+}
+
+impl<W: Write + Send> CsvLogger<W> {
+    /// Log to `out`, writing a row per node at most every `interval`.
+    ///
+    /// Writes the header row. An `interval` of zero writes a row for every
+    /// received message.
+    pub fn new(mut out: W, interval: Duration) -> io::Result<Self> {
+        let header: Vec<_> = COLUMNS.iter().map(|(.., column)| *column).collect();
+        writeln!(out, "time,node,{}", header.join(","))?;
+        Ok(Self {
+            inner: Mutex::new(Inner {
+                out,
+                rows: BTreeMap::new(),
+            }),
+            interval,
+        })
+    }
+
+    /// Record `message` of `node` received at `time`, writes a row if the
+    /// interval has passed.
+    pub fn record(&self, node: u8, message: &TelemetryMessage, time: SystemTime) -> io::Result<()> {
+        let mut inner = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+        let row = inner.rows.entry(node).or_default();
+        let mut logged = false;
+        for (field, value) in message.fields() {
+            if let Some(column) = COLUMNS
+                .iter()
+                .position(|(name, name_field, _)| *name == message.name() && *name_field == field)
+            {
+                row.values[column] = Some(value);
+                logged = true;
+            }
+        }
+        let due = row.written.is_none_or(|written| {
+            time.duration_since(written).unwrap_or_default() >= self.interval
+        });
+        if !logged || !due {
+            return Ok(());
+        }
+        row.written = Some(time);
+
+        let time = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let mut line = format!("{}.{:06},{node}", time.as_secs(), time.subsec_micros());
+        for value in row.values {
+            line.push(',');
+            if let Some(value) = value {
+                let _ = write!(line, "{value}");
+            }
+        }
+        line.push('\n');
+        inner.out.write_all(line.as_bytes())
+    }
+
+    /// Record `frame` received now if it is a telemetry message.
+    pub fn record_frame(&self, frame: &Frame) -> io::Result<()> {
+        let embedded_can::Id::Standard(id) = frame.id() else {
+            return Ok(());
+        };
+        match TelemetryMessage::decode(frame) {
+            Some(Ok(message)) => self.record(Id::from(id).node(), &message, SystemTime::now()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Frame subscriber logging the received telemetry.
+    ///
+    /// To be passed to [`Bus::on_frame`](crate::can::Bus::on_frame) or
+    /// [`ODrive::on_frame`](crate::can::ODrive::on_frame). Write errors are
+    /// ignored.
+    pub fn subscriber(self: &Arc<Self>) -> impl Fn(&Frame) + Send + Sync + 'static
+    where
+        W: 'static,
+    {
+        let logger = Arc::downgrade(self);
+        move |frame| {
+            if let Some(logger) = logger.upgrade() {
+                let _ = logger.record_frame(frame);
+            }
+        }
+    }
+
+    /// Flush the underlying writer.
+    pub fn flush(&self) -> io::Result<()> {
+        self.inner
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .out
+            .flush()
+    }
+
+    /// The underlying writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+            .out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{BusVoltageCurrent, Temperature};
+
+    #[test]
+    fn rows_at_interval() {
+        let logger = CsvLogger::new(Vec::new(), Duration::from_millis(100)).unwrap();
+        let at = |ms| UNIX_EPOCH + Duration::from_millis(ms);
+        let temperature = TelemetryMessage::Temperature(Temperature {
+            fet: 40.5,
+            motor: 30.0,
+        });
+        let bus = TelemetryMessage::BusVoltageCurrent(BusVoltageCurrent {
+            voltage: 24.0,
+            current: 1.5,
+        });
+
+        logger.record(1, &temperature, at(1000)).unwrap();
+        logger.record(1, &bus, at(1050)).unwrap();
+        logger.record(1, &bus, at(1100)).unwrap();
+        logger.record(2, &bus, at(1100)).unwrap();
+
+        let csv = String::from_utf8(logger.into_inner()).unwrap();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "time,node,position,velocity,iq_setpoint,iq_measured,fet_temperature,motor_temperature,bus_voltage,bus_current",
+                "1.000000,1,,,,,40.5,30,,",
+                "1.100000,1,,,,,40.5,30,24,1.5",
+                "1.100000,2,,,,,,,24,1.5",
+            ]
+        );
+    }
+}
@@ -0,0 +1,240 @@
+//! # Error diagnostics
+//!
+//! Likely causes and suggested actions for [`AxisErrors`] and failed
+//! [`ProcedureResult`]s, following the troubleshooting guide of the
+//! official documentation. The [`Hint`]s are plain data for host UIs to
+//! guide operators:
+//!
+//! ```
+//! use odrive::AxisErrors;
+//! use odrive::diagnostics;
+//!
+//! for hint in diagnostics::axis_errors(AxisErrors::DC_BUS_UNDER_VOLTAGE) {
+//!     println!("{}: {}, {}", hint.name, hint.cause, hint.action);
+//! }
+//! ```
+
+use crate::{AxisErrors, ProcedureResult};
+use std::fmt;
+
+/// Cause of an error and what to do about it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hint {
+    /// Official name, such as `DC_BUS_UNDER_VOLTAGE`.
+    pub name: &'static str,
+    /// Likely cause.
+    pub cause: &'static str,
+    /// Suggested action.
+    pub action: &'static str,
+}
+
+impl fmt::Display for Hint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}, {}", self.name, self.cause, self.action)
+    }
+}
+
+/// Suggested action for each axis error flag.
+const AXIS_ACTIONS: [(AxisErrors, &str); 22] = [
+    (
+        AxisErrors::INITIALIZING,
+        "wait for the ODrive to finish starting up",
+    ),
+    (
+        AxisErrors::SYSTEM_LEVEL,
+        "reboot the ODrive and report the firmware version if it persists",
+    ),
+    (
+        AxisErrors::TIMING_ERROR,
+        "lower the message rates and reboot the ODrive",
+    ),
+    (
+        AxisErrors::MISSING_ESTIMATE,
+        "check the encoder wiring and run the encoder calibration",
+    ),
+    (
+        AxisErrors::BAD_CONFNIG,
+        "check the motor and encoder configuration for missing values",
+    ),
+    (
+        AxisErrors::DRV_FAULT,
+        "check the motor wiring for shorts and the supply for voltage dips",
+    ),
+    (
+        AxisErrors::MISSING_INPUT,
+        "send a setpoint before entering closed loop control",
+    ),
+    (
+        AxisErrors::DC_BUS_OVER_VOLTAGE,
+        "connect a brake resistor or lower the regenerative current limit",
+    ),
+    (
+        AxisErrors::DC_BUS_UNDER_VOLTAGE,
+        "check the power supply capacity and wiring or lower the current limits",
+    ),
+    (
+        AxisErrors::DC_BUS_OVER_CURRENT,
+        "raise the DC current limit or lower the motor current limits",
+    ),
+    (
+        AxisErrors::DC_BUS_OVER_REGEN_CURRENT,
+        "connect a brake resistor or lower the deceleration",
+    ),
+    (
+        AxisErrors::CURRENT_LIMIT_VIOLATION,
+        "raise the hard current limit or lower the gains and current limit",
+    ),
+    (
+        AxisErrors::MOTOR_OVER_TEMP,
+        "let the motor cool down and lower the current or improve cooling",
+    ),
+    (
+        AxisErrors::INVERTER_OVER_TEMP,
+        "let the ODrive cool down and improve its airflow",
+    ),
+    (
+        AxisErrors::VELOCITY_LIMIT_VIOLATION,
+        "raise the velocity limit tolerance or lower the velocity gain",
+    ),
+    (
+        AxisErrors::POSITION_LIMIT_VIOLATION,
+        "move the axis back into range and check the position limits",
+    ),
+    (
+        AxisErrors::WATCHDOG_TIMER_EXPIRED,
+        "feed the watchdog more often or raise its timeout",
+    ),
+    (
+        AxisErrors::ESTOP_REQUESTED,
+        "clear the emergency stop source, then clear the errors",
+    ),
+    (
+        AxisErrors::SPINOUT_DETECTED,
+        "check the encoder mounting and run the offset calibration again",
+    ),
+    (
+        AxisErrors::BRAKE_RESISTOR_DISARMED,
+        "resolve the other active errors, then clear the errors",
+    ),
+    (
+        AxisErrors::THERMISTOR_DISCONNECTED,
+        "check the thermistor wiring or disable the motor thermistor",
+    ),
+    (
+        AxisErrors::CALIBRATION_ERROR,
+        "check the procedure result of the failed calibration",
+    ),
+];
+
+/// Hint for every set flag, undocumented flags excepted.
+pub fn axis_errors(errors: AxisErrors) -> Vec<Hint> {
+    AXIS_ACTIONS
+        .iter()
+        .filter(|(flag, _)| errors.contains(*flag))
+        .filter_map(|(flag, action)| {
+            let (name, cause) = flag.descriptions().next()?;
+            Some(Hint {
+                name,
+                cause,
+                action,
+            })
+        })
+        .collect()
+}
+
+/// Hint for a failed procedure, [`None`] for [`ProcedureResult::Success`]
+/// and [`ProcedureResult::Busy`].
+pub fn procedure_result(result: ProcedureResult) -> Option<Hint> {
+    use ProcedureResult::*;
+
+    let (cause, action) = match result {
+        Success | Busy => return None,
+        Cancelled => (
+            "the procedure was cancelled by a state request",
+            "start the procedure again and wait for it to finish",
+        ),
+        Disarmed => (
+            "a fault disarmed the axis during the procedure",
+            "check the active errors and disarm reason",
+        ),
+        NoResponse => (
+            "a component such as the encoder did not respond",
+            "check the encoder wiring and configuration",
+        ),
+        PolePairCprMismatch => (
+            "the measured rotation does not match the pole pairs and counts per revolution",
+            "check the configured pole pairs and encoder resolution",
+        ),
+        PhaseResistanceOutOfRange => (
+            "the measured phase resistance is implausible",
+            "check the motor wiring and the calibration current and voltage",
+        ),
+        PhaseInductanceOutOfRange => (
+            "the measured phase inductance is implausible",
+            "check the motor wiring and the calibration voltage",
+        ),
+        UnbalancedPhases => (
+            "the phase resistances differ too much",
+            "check the motor connectors and cables for a bad contact",
+        ),
+        InvalidMotorType => (
+            "the configured motor type is not valid",
+            "set a supported motor type",
+        ),
+        IllegalHallState => (
+            "too many invalid hall sensor states were seen",
+            "check the hall sensor wiring and supply and add filter capacitors",
+        ),
+        Timeout => (
+            "the procedure did not finish in time",
+            "check that the motor can turn freely and the calibration settings",
+        ),
+        HomingWithoutEndstop => (
+            "homing was requested without an enabled endstop",
+            "enable and configure the minimum endstop",
+        ),
+        InvalidState => (
+            "the requested axis state does not exist",
+            "request a state supported by the firmware",
+        ),
+        NotCalibrated => (
+            "the axis is not calibrated",
+            "run the full calibration sequence first",
+        ),
+        NotConverging => (
+            "the calibration did not converge",
+            "check the mechanical load and the calibration settings",
+        ),
+    };
+    Some(Hint {
+        name: result.name(),
+        cause,
+        action,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hints() {
+        let hints = axis_errors(AxisErrors::DC_BUS_OVER_VOLTAGE | AxisErrors::MOTOR_OVER_TEMP);
+        assert_eq!(hints.len(), 2);
+        assert_eq!(hints[0].name, "DC_BUS_OVER_VOLTAGE");
+        assert!(hints[0].action.contains("brake resistor"));
+        assert_eq!(hints[1].name, "MOTOR_OVER_TEMP");
+        assert!(axis_errors(AxisErrors::from_bits_retain(0x80)).is_empty());
+
+        // every documented flag has an action
+        assert_eq!(
+            axis_errors(AxisErrors::all()).len(),
+            AxisErrors::all().descriptions().count()
+        );
+
+        assert_eq!(procedure_result(ProcedureResult::Success), None);
+        let hint = procedure_result(ProcedureResult::UnbalancedPhases).unwrap();
+        assert_eq!(hint.name, "UNBALANCED_PHASES");
+        assert!(hint.to_string().starts_with("UNBALANCED_PHASES: "));
+    }
+}
@@ -0,0 +1,101 @@
+//! # Errors
+
+use crate::AxisErrors;
+use crate::protocol::{Value, ValueKind};
+use std::io;
+
+/// Result type of the drivers and protocol decoders.
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Error returned by the ODrive drivers.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// Accessing the CAN bus failed.
+    #[error("transport error: {0}")]
+    Transport(#[source] io::Error),
+    /// The CAN controller went bus off.
+    #[error("CAN bus off")]
+    BusOff,
+    /// No response was received in time.
+    #[error("request timed out")]
+    Timeout,
+    /// A node or axis identifier is larger than 63.
+    #[error("node id {0} out of range")]
+    InvalidNode(u8),
+    /// A response frame had an unexpected data length.
+    #[error("frame data length invalid: {actual} != {expected}")]
+    InvalidLength { expected: usize, actual: usize },
+    /// A response was well formed but not valid for the request.
+    #[error("protocol mismatch: {0}")]
+    Protocol(String),
+    /// The ODrive reported active errors.
+    #[error("device fault: {0}")]
+    Device(AxisErrors),
+    /// A configuration could not be applied.
+    #[error("invalid configuration: {0}")]
+    Config(String),
+    /// The endpoints file does not match the firmware of the ODrive.
+    #[error("endpoints file CRC {expected:#06x} does not match firmware CRC {actual:#06x}")]
+    EndpointsMismatch { expected: u16, actual: u16 },
+    /// An endpoint read back another value than written, for example
+    /// because the ODrive clamped it.
+    #[error("endpoint {endpoint} reads {read:?} after writing {written:?}")]
+    WriteMismatch {
+        endpoint: u16,
+        written: Value,
+        read: Value,
+    },
+    /// The value kind is wider than the 4 value bytes of an SDO transfer.
+    #[error("{0} values do not fit an SDO transfer")]
+    ValueTooWide(ValueKind),
+    /// The firmware of the ODrive is older than required.
+    #[error(
+        "firmware {}.{}.{} is older than required {}.{}.{}",
+        actual.0, actual.1, actual.2, required.0, required.1, required.2
+    )]
+    FirmwareTooOld {
+        required: (u8, u8, u8),
+        actual: (u8, u8, u8),
+    },
+    /// The firmware of the ODrive does not support the command, see
+    /// [`protocol::supported_since`](crate::protocol::supported_since).
+    #[error(
+        "command {command:#04x} requires firmware {}.{}.{}, found {}.{}.{}",
+        required.0, required.1, required.2, actual.0, actual.1, actual.2
+    )]
+    UnsupportedByFirmware {
+        command: u8,
+        required: (u8, u8, u8),
+        actual: (u8, u8, u8),
+    },
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        #[cfg(feature = "can")]
+        if crate::transport::BusState::from_error(&err) == Some(crate::transport::BusState::BusOff)
+        {
+            return Error::BusOff;
+        }
+
+        Error::Transport(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn io_error_conversion() {
+        let err = Error::from(io::Error::other("cable unplugged"));
+        assert!(matches!(err, Error::Transport(_)));
+
+        #[cfg(feature = "can")]
+        assert!(matches!(
+            Error::from(crate::transport::BusState::BusOff.into_error()),
+            Error::BusOff
+        ));
+    }
+}
@@ -10,47 +10,1205 @@
 //!
 //! This module is enabled with the `flat-endpoints` feature which will also
 //! bring in `serde_json` which is used to parse the endpoints file.
+//!
+//! Each endpoint is read-only, write-only or read-write. Writing a read-only
+//! endpoint is silently ignored by the ODrive, so the drivers refuse to
+//! write endpoints by name unless their [`Access`] allows it.
+//!
+//! [`FlatEndpoints::typed`] binds an endpoint to a Rust type once, reads and
+//! writes through the returned [`Endpoint`] cannot use the wrong type:
+//!
+//! ```no_run
+//! # tokio_test::block_on(async {
+//! use odrive::{can::ODrive, flat_endpoints::FlatEndpoints};
+//!
+//! let json = serde_json::from_str(&std::fs::read_to_string("flat_endpoints.json").unwrap());
+//! let endpoints = FlatEndpoints::from_json(json.unwrap()).unwrap();
+//! let vel_limit = endpoints
+//!     .typed::<f32>("axis0.controller.config.vel_limit")
+//!     .unwrap();
+//!
+//! let socket = socketcan::tokio::CanSocket::open("can0").unwrap();
+//! let odrive = ODrive::new(socket, 1).unwrap();
+//! let limit = odrive.read_endpoint(&vel_limit).await.unwrap();
+//! odrive.write_endpoint(&vel_limit, limit / 2.0).await.unwrap();
+//! # });
+//! ```
+//!
+//! [`ODrive::read_named`](crate::can::ODrive::read_named) and
+//! [`ODrive::write_named`](crate::can::ODrive::write_named) look the
+//! endpoint up on every call instead.
+//!
+//! Endpoint IDs change between firmware versions and using the wrong file
+//! writes to the wrong parameters, [`FlatEndpoints::verify`] checks the file
+//! against the firmware first. [`FlatEndpoints::download`] fetches the file
+//! of the firmware running on an ODrive and verifies it.
+//!
+//! Releases can also be downloaded once and cached, see
+//! [`FlatEndpoints::fetch`]. The crate does not embed endpoints files, an
+//! application targeting known releases can embed their files itself with
+//! `include_str!` and [`FlatEndpoints::from_json`].
+//!
+//! [`codegen`] generates typed accessors from an endpoints file at build
+//! time.
+//!
+//! [`migration`] carries stored configurations across firmware releases
+//! that rename endpoints.
+
+pub mod codegen;
+pub mod migration;
+
+use crate::protocol::{SdoType, Value, ValueKind, Version};
+use crate::transport::Transport;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    marker::PhantomData,
+};
 
-use crate::can::ValueKind;
-use std::collections::HashMap;
+/// Access mode of an endpoint, `"r"`, `"w"` or `"rw"` in the JSON.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl Access {
+    pub fn is_readable(self) -> bool {
+        matches!(self, Access::Read | Access::ReadWrite)
+    }
+
+    pub fn is_writable(self) -> bool {
+        matches!(self, Access::Write | Access::ReadWrite)
+    }
+}
+
+impl TryFrom<&serde_json::Value> for Access {
+    type Error = ();
+
+    fn try_from(value: &serde_json::Value) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            Some("r") => Ok(Self::Read),
+            Some("w") => Ok(Self::Write),
+            Some("rw") => Ok(Self::ReadWrite),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Endpoint bound to the Rust type of its values, see
+/// [`FlatEndpoints::typed`].
+pub struct Endpoint<T> {
+    id: u16,
+    access: Access,
+    ty: PhantomData<fn() -> T>,
+}
+
+impl<T> Endpoint<T> {
+    /// Endpoint `id` without looking it up, for generated code, see
+    /// [`codegen`]. The type and access are not checked against the
+    /// firmware.
+    pub const fn new(id: u16, access: Access) -> Self {
+        Self {
+            id,
+            access,
+            ty: PhantomData,
+        }
+    }
+
+    /// SDO endpoint ID.
+    pub fn id(&self) -> u16 {
+        self.id
+    }
+
+    pub fn access(&self) -> Access {
+        self.access
+    }
+
+    /// Fails with [`crate::Error::Config`] unless `required` access is
+    /// allowed.
+    pub(crate) fn check(&self, required: Access) -> crate::Result<()> {
+        check_access(&format_args!("ID {}", self.id), self.access, required)
+    }
+}
+
+impl<T> Clone for Endpoint<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Endpoint<T> {}
+
+impl<T> fmt::Debug for Endpoint<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Endpoint")
+            .field("id", &self.id)
+            .field("access", &self.access)
+            .field("type", &std::any::type_name::<T>())
+            .finish()
+    }
+}
+
+/// `value` as written in the JSON, without quotes if it is a string.
+fn json_string(value: &serde_json::Value) -> String {
+    value
+        .as_str()
+        .map_or_else(|| value.to_string(), str::to_owned)
+}
+
+/// Fails with [`crate::Error::Config`] if `access` lacks `required`.
+fn check_access(name: &dyn fmt::Display, access: Access, required: Access) -> crate::Result<()> {
+    if required.is_readable() && !access.is_readable() {
+        return Err(crate::Error::Config(format!(
+            "Configuration endpoint {name} is write-only"
+        )));
+    }
+    if required.is_writable() && !access.is_writable() {
+        return Err(crate::Error::Config(format!(
+            "Configuration endpoint {name} is read-only"
+        )));
+    }
+    Ok(())
+}
+
+/// Direct child of a namespace, see [`FlatEndpoints::children`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Entry<'a> {
+    /// Namespace containing further entries, such as `config`.
+    Namespace(&'a str),
+    Endpoint(&'a str),
+}
+
+impl<'a> Entry<'a> {
+    /// Name relative to the namespace listed.
+    pub fn name(&self) -> &'a str {
+        match self {
+            Entry::Namespace(name) | Entry::Endpoint(name) => name,
+        }
+    }
+}
+
+/// Whether `name` matches `pattern`, where `*` matches any sequence
+/// including dots and `?` any single character.
+fn glob(pattern: &str, name: &str) -> bool {
+    let (pattern, name) = (pattern.as_bytes(), name.as_bytes());
+    let (mut p, mut n) = (0, 0);
+    // position after the last `*` and the name position it matched up to
+    let mut star = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                star = Some((p + 1, n));
+                p += 1;
+            }
+            Some(&c) if c == b'?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((after, matched)) => {
+                    p = after;
+                    n = matched + 1;
+                    star = Some((after, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// Node of the endpoints tree, see [`FlatEndpoints::tree`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EndpointNode {
+    /// Dotted path from the root, empty for the root.
+    pub path: String,
+    /// ID, type and access of the endpoint at this path, if there is one.
+    pub endpoint: Option<(u64, ValueKind, Access)>,
+    /// Nodes below this one, by name.
+    pub children: BTreeMap<String, EndpointNode>,
+}
+
+impl EndpointNode {
+    /// Last segment of the path.
+    pub fn name(&self) -> &str {
+        self.path.rsplit('.').next().unwrap_or_default()
+    }
+
+    /// Node at the dotted `path` below this one.
+    pub fn get(&self, path: &str) -> Option<&EndpointNode> {
+        if path.is_empty() {
+            return Some(self);
+        }
+        path.split('.')
+            .try_fold(self, |node, name| node.children.get(name))
+    }
+
+    /// Whether this node is a namespace rather than an endpoint.
+    pub fn is_namespace(&self) -> bool {
+        !self.children.is_empty()
+    }
+
+    /// This node and all nodes below it, depth first in name order.
+    pub fn walk(&self) -> Vec<&EndpointNode> {
+        let mut nodes = vec![self];
+        for child in self.children.values() {
+            nodes.extend(child.walk());
+        }
+        nodes
+    }
+}
+
+/// Why an entry of an endpoints file was skipped, see [`ParseError`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum SkipReason {
+    #[error("missing type")]
+    MissingType,
+    /// Type string that is not a known value type, function or string.
+    #[error("unknown type {0}")]
+    UnknownType(String),
+    #[error("missing id")]
+    MissingId,
+    #[error("id {0} out of range")]
+    IdOutOfRange(u64),
+    #[error("invalid access {0}")]
+    InvalidAccess(String),
+    /// Function with malformed inputs or outputs.
+    #[error("invalid function")]
+    InvalidFunction,
+}
+
+/// Entry of an endpoints file that could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Skipped {
+    pub name: String,
+    pub reason: SkipReason,
+}
+
+/// Error parsing an endpoints file, see [`FlatEndpoints::from_json`].
+#[derive(Debug, thiserror::Error)]
+pub enum ParseError {
+    /// The file has no `endpoints` object, it is likely not an endpoints
+    /// file at all.
+    #[error("flat endpoints file has no endpoints")]
+    MissingEndpoints,
+    /// Some entries could not be parsed, sorted by name.
+    #[error(
+        "{} invalid flat endpoints, first {}: {}",
+        skipped.len(),
+        skipped[0].name,
+        skipped[0].reason
+    )]
+    Invalid {
+        /// The entries that could be parsed.
+        endpoints: Box<FlatEndpoints>,
+        skipped: Vec<Skipped>,
+    },
+}
+
+impl ParseError {
+    /// Entries that could not be parsed.
+    pub fn skipped(&self) -> &[Skipped] {
+        match self {
+            ParseError::MissingEndpoints => &[],
+            ParseError::Invalid { skipped, .. } => skipped,
+        }
+    }
+
+    /// The entries that could be parsed, to knowingly use an incomplete
+    /// file.
+    pub fn into_partial(self) -> Option<FlatEndpoints> {
+        match self {
+            ParseError::MissingEndpoints => None,
+            ParseError::Invalid { endpoints, .. } => Some(*endpoints),
+        }
+    }
+}
+
+impl From<ParseError> for crate::Error {
+    fn from(err: ParseError) -> Self {
+        crate::Error::Config(err.to_string())
+    }
+}
+
+/// Endpoint whose value type changed, see [`EndpointsDiff`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Retyped {
+    pub name: String,
+    pub old: ValueKind,
+    pub new: ValueKind,
+}
+
+/// Difference between two endpoints files, see [`FlatEndpoints::diff`].
+///
+/// All lists are sorted by name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EndpointsDiff {
+    /// Endpoints only in the newer file.
+    pub added: Vec<String>,
+    /// Endpoints only in the older file.
+    pub removed: Vec<String>,
+    pub retyped: Vec<Retyped>,
+}
+
+impl EndpointsDiff {
+    /// Whether both files have the same endpoints with the same types.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.retyped.is_empty()
+    }
+}
+
+/// Configuration as TOML, with one quoted key per endpoint.
+///
+/// `config` is an object of endpoint names and values, such as a backup,
+/// see [`ODrive::backup_configuration`](crate::can::ODrive::backup_configuration).
+#[cfg(feature = "toml")]
+pub fn config_to_toml(config: &serde_json::Value) -> crate::Result<String> {
+    toml::to_string(config).map_err(|err| crate::Error::Config(format!("TOML: {err}")))
+}
+
+/// Configuration from TOML, the inverse of [`config_to_toml`].
+///
+/// Tables and unquoted dotted keys are flattened into endpoint names, so
+/// `[axis0.controller.config]` followed by `vel_limit = 10.0` is the same as
+/// `"axis0.controller.config.vel_limit" = 10.0`.
+#[cfg(feature = "toml")]
+pub fn config_from_toml(toml: &str) -> crate::Result<serde_json::Value> {
+    let mut config = serde_json::Map::new();
+    flatten("", parse_toml(toml)?, &mut config);
+    Ok(config.into())
+}
+
+#[cfg(feature = "toml")]
+fn parse_toml(toml: &str) -> crate::Result<serde_json::Value> {
+    let table: toml::Table =
+        toml::from_str(toml).map_err(|err| crate::Error::Config(format!("TOML: {err}")))?;
+    serde_json::to_value(table).map_err(|err| crate::Error::Config(format!("TOML: {err}")))
+}
+
+/// Insert the entries of nested objects in `value` into `config` with
+/// their names joined by dots.
+fn flatten(
+    prefix: &str,
+    value: serde_json::Value,
+    config: &mut serde_json::Map<String, serde_json::Value>,
+) {
+    match value {
+        serde_json::Value::Object(entries) => {
+            for (name, value) in entries {
+                let name = match prefix {
+                    "" => name,
+                    _ => format!("{prefix}.{name}"),
+                };
+                flatten(&name, value, config);
+            }
+        }
+        value => {
+            config.insert(prefix.to_owned(), value);
+        }
+    }
+}
+
+/// Configuration with the variables of `vars` substituted, so one template
+/// can commission several slightly different axes.
+///
+/// `${name}` in keys and string values is replaced by the variable `name`.
+/// A value that is just a reference takes the variable as is, so numbers
+/// stay numbers:
+///
+/// ```
+/// use odrive::flat_endpoints::render_template;
+/// use serde_json::json;
+///
+/// let template = json!({
+///     "axis0.config.can.node_id": "${node_id}",
+///     "axis0.config.motor.current_soft_max": "${current_limit}",
+///     "axis${axis}.controller.config.vel_limit": 10.0,
+/// });
+/// let vars = json!({"node_id": 3, "current_limit": 20.0, "axis": 0});
+/// assert_eq!(
+///     render_template(&template, &vars).unwrap(),
+///     json!({
+///         "axis0.config.can.node_id": 3,
+///         "axis0.config.motor.current_soft_max": 20.0,
+///         "axis0.controller.config.vel_limit": 10.0,
+///     })
+/// );
+/// ```
+///
+/// Fails with [`crate::Error::Config`] if a variable is not in `vars` or a
+/// reference is not closed.
+pub fn render_template(
+    template: &serde_json::Value,
+    vars: &serde_json::Value,
+) -> crate::Result<serde_json::Value> {
+    let Some(entries) = template.as_object() else {
+        return Err(crate::Error::Config("Expected object".into()));
+    };
+    let mut config = serde_json::Map::with_capacity(entries.len());
+    for (key, value) in entries {
+        let value = match value {
+            serde_json::Value::String(value) => match reference(value) {
+                Some(name) => variable(vars, name)?.clone(),
+                None => substitute(value, vars)?.into(),
+            },
+            value => value.clone(),
+        };
+        config.insert(substitute(key, vars)?, value);
+    }
+    Ok(config.into())
+}
+
+/// Name of the variable if `value` is a single `${name}` reference.
+fn reference(value: &str) -> Option<&str> {
+    let name = value.strip_prefix("${")?.strip_suffix('}')?;
+    (!name.contains(['$', '{', '}'])).then_some(name)
+}
+
+fn variable<'a>(vars: &'a serde_json::Value, name: &str) -> crate::Result<&'a serde_json::Value> {
+    vars.get(name)
+        .ok_or_else(|| crate::Error::Config(format!("Template variable {name} not defined")))
+}
+
+/// `text` with every `${name}` reference replaced by the variable.
+fn substitute(text: &str, vars: &serde_json::Value) -> crate::Result<String> {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        output.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('}') else {
+            return Err(crate::Error::Config(format!(
+                "Unclosed template variable in {text}"
+            )));
+        };
+        output.push_str(&json_string(variable(vars, &rest[start + 2..start + end])?));
+        rest = &rest[start + end + 1..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// Named configurations, such as `calibration` or `low-power`, see
+/// [`ODrive::apply_profile`](crate::can::ODrive::apply_profile).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Profiles {
+    profiles: BTreeMap<String, serde_json::Value>,
+}
+
+impl Profiles {
+    /// Profiles from an object with one configuration object per profile.
+    ///
+    /// Nested objects are flattened into endpoint names like
+    /// [`config_from_toml`] does.
+    pub fn from_json(input: serde_json::Value) -> crate::Result<Self> {
+        let serde_json::Value::Object(input) = input else {
+            return Err(crate::Error::Config("Expected object of profiles".into()));
+        };
+        let mut profiles = BTreeMap::new();
+        for (name, profile) in input {
+            if !profile.is_object() {
+                return Err(crate::Error::Config(format!(
+                    "Profile {name} is not an object"
+                )));
+            }
+            let mut config = serde_json::Map::new();
+            flatten("", profile, &mut config);
+            profiles.insert(name, config.into());
+        }
+        Ok(Self { profiles })
+    }
+
+    /// Profiles from TOML with one table per profile:
+    ///
+    /// ```toml
+    /// [low-power]
+    /// "axis0.config.motor.current_soft_max" = 5.0
+    ///
+    /// [performance.axis0.config.motor]
+    /// current_soft_max = 40.0
+    /// ```
+    #[cfg(feature = "toml")]
+    pub fn from_toml(toml: &str) -> crate::Result<Self> {
+        Self::from_json(parse_toml(toml)?)
+    }
+
+    /// Configuration of profile `name`.
+    pub fn get(&self, name: &str) -> Option<&serde_json::Value> {
+        self.profiles.get(name)
+    }
+
+    /// Names of the profiles, sorted.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.profiles.keys().map(String::as_str)
+    }
+
+    pub(crate) fn resolve(&self, name: &str) -> crate::Result<&serde_json::Value> {
+        self.get(name)
+            .ok_or_else(|| crate::Error::Config(format!("Profile {name} not found")))
+    }
+}
+
+/// Read-back check of `apply_configuration`, see
+/// [`ODrive::apply_configuration`](crate::can::ODrive::apply_configuration).
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Verify {
+    /// Write without reading back.
+    #[default]
+    None,
+    /// Read every written endpoint back once all are written, write-only
+    /// endpoints excepted.
+    ///
+    /// Floats match within `tolerance`, other kinds only if equal, see
+    /// [`Value::approx_eq`].
+    ReadBack { tolerance: f64 },
+}
+
+impl Verify {
+    /// Read back with a tolerance covering float rounding.
+    pub const READ_BACK: Self = Verify::ReadBack { tolerance: 1e-6 };
+
+    fn matches(&self, written: &Value, read: &Value) -> bool {
+        match self {
+            Verify::None => true,
+            Verify::ReadBack { tolerance } => written.approx_eq(read, *tolerance),
+        }
+    }
+}
+
+/// Endpoint read back with another value than written, see [`ApplyReport`].
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mismatch {
+    pub written: Value,
+    pub read: Value,
+}
+
+/// Outcome of `apply_configuration` per key, see
+/// [`ODrive::apply_configuration`](crate::can::ODrive::apply_configuration).
+#[derive(Debug, Default)]
+pub struct ApplyReport {
+    /// Keys written, and read back if verified.
+    pub applied: Vec<String>,
+    /// Keys whose write or read back failed.
+    pub failed: Vec<(String, crate::Error)>,
+    pub mismatched: Vec<(String, Mismatch)>,
+}
+
+impl ApplyReport {
+    /// Whether every key was applied.
+    pub fn is_ok(&self) -> bool {
+        self.failed.is_empty() && self.mismatched.is_empty()
+    }
+
+    /// Record the read back of `key`.
+    pub(crate) fn check(
+        &mut self,
+        key: String,
+        written: Value,
+        read: crate::Result<Value>,
+        verify: Verify,
+    ) {
+        match read {
+            Ok(read) if verify.matches(&written, &read) => self.applied.push(key),
+            Ok(read) => self.mismatched.push((key, Mismatch { written, read })),
+            Err(err) => self.failed.push((key, err)),
+        }
+    }
+}
+
+/// Entry of a configuration that differs from the device, see
+/// [`ODrive::dry_run_configuration`](crate::can::ODrive::dry_run_configuration).
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Change {
+    pub key: String,
+    /// Value on the device, unknown for write-only endpoints.
+    pub current: Option<Value>,
+    pub desired: Value,
+}
+
+impl Change {
+    /// Change of `key` unless `current` already equals `desired`.
+    pub(crate) fn new(key: String, current: Option<Value>, desired: Value) -> Option<Self> {
+        if current.is_some_and(|current| desired.approx_eq(&current, 0.0)) {
+            return None;
+        }
+        Some(Self {
+            key,
+            current,
+            desired,
+        })
+    }
+}
+
+/// Entry of a configuration the device does not match, see
+/// [`ODrive::diff_configuration`](crate::can::ODrive::diff_configuration).
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Difference {
+    pub key: String,
+    /// Value in the configuration.
+    pub expected: Value,
+    /// Value on the device.
+    pub actual: Value,
+}
+
+impl Difference {
+    /// Difference of `key` unless `actual` matches `expected` within
+    /// `tolerance`, see [`Value::approx_eq`].
+    pub(crate) fn new(key: String, expected: Value, actual: Value, tolerance: f64) -> Option<Self> {
+        if expected.approx_eq(&actual, tolerance) {
+            return None;
+        }
+        Some(Self {
+            key,
+            expected,
+            actual,
+        })
+    }
+}
+
+/// Outcome of restoring a configuration, see
+/// [`ODrive::restore_configuration`](crate::can::ODrive::restore_configuration).
+#[derive(Debug, Default)]
+pub struct RestoreReport {
+    /// Outcome of writing the configuration, not read back.
+    pub apply: ApplyReport,
+    /// Whether the configuration was saved, only if every write succeeded.
+    pub saved: bool,
+    /// Entries that differ from the configuration after the reboot.
+    pub residual: Vec<Change>,
+}
+
+impl RestoreReport {
+    /// Whether the configuration was saved and reads back unchanged.
+    pub fn is_ok(&self) -> bool {
+        self.saved && self.residual.is_empty()
+    }
+}
+
+/// Argument or return value of a [`Function`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Argument {
+    pub name: String,
+    /// Endpoint the value is written to or read from.
+    pub id: u16,
+    pub kind: ValueKind,
+}
+
+impl Argument {
+    fn from_json(input: &serde_json::Value) -> Option<Self> {
+        Some(Self {
+            name: input.get("name")?.as_str()?.to_owned(),
+            id: u16::try_from(input.get("id")?.as_u64()?).ok()?,
+            kind: ValueKind::try_from(input.get("type")?).ok()?,
+        })
+    }
+}
+
+/// Firmware function, such as `save_configuration`.
+///
+/// Called by writing the inputs to their endpoints, then writing the
+/// function endpoint and finally reading the outputs, see
+/// [`ODrive::call_function`](crate::can::ODrive::call_function).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Function {
+    pub id: u16,
+    pub inputs: Vec<Argument>,
+    pub outputs: Vec<Argument>,
+}
+
+impl Function {
+    fn from_json(input: &serde_json::Value) -> Option<Self> {
+        let arguments = |key| {
+            input.get(key).map_or(Some(Vec::new()), |arguments| {
+                arguments
+                    .as_array()?
+                    .iter()
+                    .map(Argument::from_json)
+                    .collect()
+            })
+        };
+        Some(Self {
+            id: u16::try_from(input.get("id")?.as_u64()?).ok()?,
+            inputs: arguments("inputs")?,
+            outputs: arguments("outputs")?,
+        })
+    }
+}
+
+/// Endpoint holding the CRC of the firmware's endpoint schema.
+pub const JSON_CRC_ENDPOINT: u16 = 0;
 
 /// Flattened endpoints store.
 #[derive(Debug, Clone)]
-pub struct FlatEndpoints(HashMap<String, (u64, ValueKind)>);
+pub struct FlatEndpoints {
+    json_crc: Option<u16>,
+    endpoints: HashMap<String, (u64, ValueKind)>,
+    access: HashMap<String, Access>,
+    functions: HashMap<String, Function>,
+}
 
 impl FlatEndpoints {
-    pub fn from_json(input: serde_json::Value) -> Option<Self> {
-        let endpoints = input.get("endpoints").and_then(|ep| ep.as_object())?;
+    /// Endpoints without an access mode are assumed to be read-write.
+    ///
+    /// Fails with [`ParseError::Invalid`] listing every entry that could not
+    /// be parsed, such as entries of unknown type or without an ID, so a
+    /// truncated or wrong file is not mistaken for a complete one.
+    pub fn from_json(input: serde_json::Value) -> Result<Self, ParseError> {
+        let endpoints = input
+            .get("endpoints")
+            .and_then(|ep| ep.as_object())
+            .ok_or(ParseError::MissingEndpoints)?;
 
         let mut map = HashMap::new();
+        let mut access = HashMap::new();
+        let mut functions = HashMap::new();
+        let mut skipped = Vec::new();
 
         for (name, ep) in endpoints.iter() {
+            let mut skip = |reason| {
+                skipped.push(Skipped {
+                    name: name.to_owned(),
+                    reason,
+                })
+            };
             let Some(kind) = ep.get("type") else {
+                skip(SkipReason::MissingType);
                 continue;
             };
-            let Ok(kind) = ValueKind::try_from(kind) else {
+            let Some(id) = ep.get("id").and_then(|i| i.as_u64()) else {
+                skip(SkipReason::MissingId);
                 continue;
             };
-            let Some(id) = ep.get("id").and_then(|i| i.as_u64()) else {
+            if kind == "function" {
+                match Function::from_json(ep) {
+                    Some(function) => {
+                        functions.insert(name.to_owned(), function);
+                    }
+                    None => skip(SkipReason::InvalidFunction),
+                }
+                continue;
+            }
+            // SDO transfers only carry values of up to 4 bytes, strings can
+            // not be read over CAN
+            if kind == "string" {
+                if u16::try_from(id).is_err() {
+                    skip(SkipReason::IdOutOfRange(id));
+                }
+                continue;
+            }
+            let Ok(kind) = ValueKind::try_from(kind) else {
+                skip(SkipReason::UnknownType(json_string(kind)));
                 continue;
             };
 
+            let mode = match ep.get("access") {
+                None => Access::ReadWrite,
+                Some(mode) => match Access::try_from(mode) {
+                    Ok(mode) => mode,
+                    Err(()) => {
+                        skip(SkipReason::InvalidAccess(json_string(mode)));
+                        continue;
+                    }
+                },
+            };
+
             map.insert(name.to_owned(), (id, kind));
+            access.insert(name.to_owned(), mode);
         }
 
-        Some(Self(map))
+        let endpoints = Self {
+            json_crc: input
+                .get("json_crc")
+                .and_then(|crc| crc.as_u64())
+                .and_then(|crc| u16::try_from(crc).ok()),
+            endpoints: map,
+            access,
+            functions,
+        };
+        if !skipped.is_empty() {
+            skipped.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+            return Err(ParseError::Invalid {
+                endpoints: Box::new(endpoints),
+                skipped,
+            });
+        }
+        Ok(endpoints)
     }
 
     /// Get a flattened endpoint from its name.
     ///
     /// Returns (id, type).
     pub fn get(&self, name: &str) -> Option<(u64, ValueKind)> {
-        self.0.get(name).copied()
+        self.endpoints.get(name).copied()
+    }
+
+    /// Access mode of an endpoint from its name.
+    pub fn access(&self, name: &str) -> Option<Access> {
+        self.access.get(name).copied()
+    }
+
+    /// Get a function from its name.
+    pub fn function(&self, name: &str) -> Option<&Function> {
+        self.functions.get(name)
+    }
+
+    /// Endpoints of the firmware `version` reports, cached in `cache_dir`.
+    ///
+    /// The crate does not include an HTTP client. `download` fetches the
+    /// `flat_endpoints.json` of the release with one of the caller's
+    /// choosing, for example from the firmware downloads page, and is only
+    /// called if the release is not cached yet. The cache is keyed by
+    /// hardware and firmware version, development builds are cached apart
+    /// from the release they precede:
+    ///
+    /// ```no_run
+    /// # tokio_test::block_on(async {
+    /// use odrive::{can::ODrive, flat_endpoints::FlatEndpoints};
+    ///
+    /// let socket = socketcan::tokio::CanSocket::open("can0").unwrap();
+    /// let odrive = ODrive::new(socket, 1).unwrap();
+    /// # let http_get = |_: &odrive::protocol::Version| -> std::io::Result<Vec<u8>> { unimplemented!() };
+    /// let version = odrive.get_version().await.unwrap();
+    /// let endpoints =
+    ///     FlatEndpoints::fetch(&version, "endpoints-cache".as_ref(), http_get).unwrap();
+    /// # });
+    /// ```
+    pub fn fetch(
+        version: &Version,
+        cache_dir: &std::path::Path,
+        download: impl FnOnce(&Version) -> std::io::Result<Vec<u8>>,
+    ) -> crate::Result<Self> {
+        let path = cache_dir.join(format!(
+            "flat_endpoints-hw{}.{}.{}-fw{}.{}.{}{}.json",
+            version.hw_version_major,
+            version.hw_version_minor,
+            version.hw_version_variant,
+            version.fw_version_major,
+            version.fw_version_minor,
+            version.fw_version_revision,
+            if version.fw_version_unreleased {
+                "-unreleased"
+            } else {
+                ""
+            },
+        ));
+        let json = match std::fs::read(&path) {
+            Ok(json) => json,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                let json = download(version)?;
+                // validate before caching
+                Self::from_slice(&json)?;
+                std::fs::create_dir_all(cache_dir)?;
+                std::fs::write(&path, &json)?;
+                json
+            }
+            Err(err) => return Err(err.into()),
+        };
+        Self::from_slice(&json)
+    }
+
+    fn from_slice(json: &[u8]) -> crate::Result<Self> {
+        let json = serde_json::from_slice(json)
+            .map_err(|err| crate::Error::Config(format!("invalid flat endpoints file: {err}")))?;
+        Ok(Self::from_json(json)?)
+    }
+
+    /// Endpoints of the firmware running on `odrive`, see [`Self::fetch`].
+    ///
+    /// Reads the version of `odrive` to pick the release, then checks the
+    /// file against the firmware with [`Self::verify`] if it lists its
+    /// `json_crc`.
+    pub async fn download<T: Transport>(
+        odrive: &crate::can::ODrive<T>,
+        cache_dir: &std::path::Path,
+        download: impl FnOnce(&Version) -> std::io::Result<Vec<u8>>,
+    ) -> crate::Result<Self> {
+        let version = odrive.get_version().await?;
+        let endpoints = Self::fetch(&version, cache_dir, download)?;
+        if endpoints.json_crc.is_some() {
+            endpoints.verify(odrive).await?;
+        }
+        Ok(endpoints)
+    }
+
+    /// Blocking [`Self::download`].
+    #[cfg(feature = "blocking")]
+    pub fn download_blocking<T: crate::blocking::Transport>(
+        odrive: &crate::blocking::ODrive<T>,
+        cache_dir: &std::path::Path,
+        download: impl FnOnce(&Version) -> std::io::Result<Vec<u8>>,
+    ) -> crate::Result<Self> {
+        let version = odrive.get_version()?;
+        let endpoints = Self::fetch(&version, cache_dir, download)?;
+        if endpoints.json_crc.is_some() {
+            endpoints.verify_blocking(odrive)?;
+        }
+        Ok(endpoints)
+    }
+
+    /// CRC of the endpoint schema this file describes, if listed.
+    pub fn json_crc(&self) -> Option<u16> {
+        self.json_crc
+    }
+
+    /// Check that the endpoint schema of `odrive` matches this file.
+    ///
+    /// Fails with [`crate::Error::EndpointsMismatch`] if the CRCs differ and
+    /// with [`crate::Error::Config`] if the file does not list its CRC.
+    pub async fn verify<T: Transport>(&self, odrive: &crate::can::ODrive<T>) -> crate::Result<()> {
+        let expected = self.expected_crc()?;
+        let actual = odrive.sdo_read_as::<u16>(JSON_CRC_ENDPOINT).await?;
+        Self::compare_crc(expected, actual)
+    }
+
+    /// Blocking [`Self::verify`].
+    #[cfg(feature = "blocking")]
+    pub fn verify_blocking<T: crate::blocking::Transport>(
+        &self,
+        odrive: &crate::blocking::ODrive<T>,
+    ) -> crate::Result<()> {
+        let expected = self.expected_crc()?;
+        let actual = odrive.sdo_read_as::<u16>(JSON_CRC_ENDPOINT)?;
+        Self::compare_crc(expected, actual)
+    }
+
+    fn expected_crc(&self) -> crate::Result<u16> {
+        self.json_crc
+            .ok_or_else(|| crate::Error::Config("flat endpoints do not list json_crc".into()))
+    }
+
+    fn compare_crc(expected: u16, actual: u16) -> crate::Result<()> {
+        if expected != actual {
+            return Err(crate::Error::EndpointsMismatch { expected, actual });
+        }
+        Ok(())
+    }
+
+    /// Access the map of functions.
+    pub fn functions(&self) -> &HashMap<String, Function> {
+        &self.functions
+    }
+
+    /// Function `name`, checking that `args` match its inputs.
+    pub(crate) fn resolve_call(&self, name: &str, args: &[Value]) -> crate::Result<&Function> {
+        let Some(function) = self.function(name) else {
+            return Err(crate::Error::Config(format!(
+                "Function {name} not found in flat endpoints"
+            )));
+        };
+        if args.len() != function.inputs.len() {
+            return Err(crate::Error::Config(format!(
+                "Function {name} takes {} arguments, not {}",
+                function.inputs.len(),
+                args.len()
+            )));
+        }
+        for (input, arg) in function.inputs.iter().zip(args) {
+            if input.kind != arg.kind() {
+                return Err(crate::Error::Config(format!(
+                    "Argument {} of function {name} is {:?}, not {:?}",
+                    input.name,
+                    input.kind,
+                    arg.kind()
+                )));
+            }
+        }
+        Ok(function)
     }
 
     /// Access the map of endpoints.
     pub fn endpoints(&self) -> &HashMap<String, (u64, ValueKind)> {
-        &self.0
+        &self.endpoints
+    }
+
+    /// Names matching `pattern`, sorted.
+    ///
+    /// `*` matches any sequence including dots and `?` any single
+    /// character, so `axis0.controller.config.*` finds all endpoints below
+    /// the controller configuration.
+    pub fn find(&self, pattern: &str) -> Vec<&str> {
+        let mut names: Vec<_> = self
+            .endpoints
+            .keys()
+            .map(String::as_str)
+            .filter(|name| glob(pattern, name))
+            .collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Endpoints and namespaces directly below `namespace`, sorted by name.
+    ///
+    /// The empty namespace lists the top level.
+    pub fn children(&self, namespace: &str) -> Vec<Entry<'_>> {
+        let mut children = BTreeMap::new();
+        for name in self.endpoints.keys() {
+            let rest = if namespace.is_empty() {
+                name.as_str()
+            } else {
+                match name
+                    .strip_prefix(namespace)
+                    .and_then(|rest| rest.strip_prefix('.'))
+                {
+                    Some(rest) => rest,
+                    None => continue,
+                }
+            };
+            let entry = match rest.split_once('.') {
+                Some((child, _)) => Entry::Namespace(child),
+                None => Entry::Endpoint(rest),
+            };
+            children.insert(entry.name(), entry);
+        }
+        children.into_values().collect()
+    }
+
+    /// All endpoints as a tree following their dotted names.
+    pub fn tree(&self) -> EndpointNode {
+        let mut root = EndpointNode::default();
+        for (name, &(id, kind)) in &self.endpoints {
+            let mut node = &mut root;
+            for (end, _) in name.match_indices('.').chain([(name.len(), "")]) {
+                let path = &name[..end];
+                let segment = path.rsplit('.').next().unwrap_or_default();
+                node = node
+                    .children
+                    .entry(segment.to_owned())
+                    .or_insert_with(|| EndpointNode {
+                        path: path.to_owned(),
+                        ..EndpointNode::default()
+                    });
+            }
+            let access = self.access(name).unwrap_or(Access::ReadWrite);
+            node.endpoint = Some((id, kind, access));
+        }
+        root
+    }
+
+    /// Endpoints added, removed and retyped in `newer`, for example the
+    /// file of the firmware being upgraded to.
+    ///
+    /// Endpoints whose ID changed but not their type are not reported,
+    /// configuration is stored by name.
+    pub fn diff(&self, newer: &FlatEndpoints) -> EndpointsDiff {
+        let mut diff = EndpointsDiff::default();
+        for (name, &(_, old)) in &self.endpoints {
+            match newer.get(name) {
+                None => diff.removed.push(name.to_owned()),
+                Some((_, new)) if new != old => diff.retyped.push(Retyped {
+                    name: name.to_owned(),
+                    old,
+                    new,
+                }),
+                Some(_) => {}
+            }
+        }
+        diff.added = newer
+            .endpoints
+            .keys()
+            .filter(|name| !self.endpoints.contains_key(*name))
+            .cloned()
+            .collect();
+        diff.added.sort_unstable();
+        diff.removed.sort_unstable();
+        diff.retyped.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+        diff
+    }
+
+    /// SDO endpoint ID and type of `name`, checking that `required` access
+    /// is allowed.
+    pub(crate) fn resolve(&self, name: &str, required: Access) -> crate::Result<(u16, ValueKind)> {
+        let (id, kind, access) = self.lookup(name)?;
+        check_access(&name, access, required)?;
+        Ok((id, kind))
+    }
+
+    fn lookup(&self, name: &str) -> crate::Result<(u16, ValueKind, Access)> {
+        let Some((id, kind)) = self.get(name) else {
+            return Err(crate::Error::Config(format!(
+                "Configuration endpoint {name} not found in flat endpoints"
+            )));
+        };
+        let id = u16::try_from(id)
+            .map_err(|_| crate::Error::Config(format!("Endpoint ID {id} out of range for u16")))?;
+        Ok((id, kind, self.access(name).unwrap_or(Access::ReadWrite)))
+    }
+
+    /// SDO endpoint ID of `name` for writing `value`, checking its kind.
+    pub(crate) fn resolve_write(&self, name: &str, value: &Value) -> crate::Result<u16> {
+        let (id, kind) = self.resolve(name, Access::Write)?;
+        if value.kind() != kind {
+            return Err(crate::Error::Config(format!(
+                "Configuration endpoint {name} is {kind:?}, not {:?}",
+                value.kind()
+            )));
+        }
+        Ok(id)
+    }
+
+    /// Key, SDO endpoint ID, value and access of every entry of `config`.
+    ///
+    /// Fails with [`crate::Error::Config`] if any entry cannot be written,
+    /// so nothing is written unless everything can be.
+    pub(crate) fn resolve_configuration(
+        &self,
+        config: &serde_json::Value,
+    ) -> crate::Result<Vec<(String, u16, Value, Access)>> {
+        let Some(items) = config.as_object() else {
+            return Err(crate::Error::Config("Expected object".into()));
+        };
+
+        let mut writes = Vec::with_capacity(items.len());
+        for (key, value) in items.iter() {
+            let (endpoint, kind) = self.resolve(key, Access::Write)?;
+
+            let Some(value) = Value::try_from_json(value, kind) else {
+                return Err(crate::Error::Config(format!(
+                    "Configuration value for {key} not able to be converted into an SDO value"
+                )));
+            };
+            let access = self.access(key).unwrap_or(Access::ReadWrite);
+            writes.push((key.to_owned(), endpoint, value, access));
+        }
+        Ok(writes)
+    }
+
+    /// Name, SDO endpoint ID and type of every read-write endpoint, the
+    /// endpoints making up a configuration, sorted by name.
+    pub(crate) fn configuration_endpoints(&self) -> Vec<(&str, u16, ValueKind)> {
+        let mut endpoints: Vec<_> = self
+            .endpoints
+            .iter()
+            .filter(|(name, _)| self.access(name) == Some(Access::ReadWrite))
+            .filter_map(|(name, &(id, kind))| Some((name.as_str(), u16::try_from(id).ok()?, kind)))
+            .collect();
+        endpoints.sort_unstable_by_key(|&(name, ..)| name);
+        endpoints
+    }
+
+    /// Endpoint `name` bound to `T`.
+    ///
+    /// Fails with [`crate::Error::Config`] if the endpoint does not exist or
+    /// its values are not of type `T`.
+    pub fn typed<T: SdoType>(&self, name: &str) -> crate::Result<Endpoint<T>> {
+        let (id, kind, access) = self.lookup(name)?;
+        if kind != T::KIND {
+            return Err(crate::Error::Config(format!(
+                "Configuration endpoint {name} is {kind:?}, not {:?}",
+                T::KIND
+            )));
+        }
+        Ok(Endpoint {
+            id,
+            access,
+            ty: PhantomData,
+        })
     }
 }
 
@@ -59,6 +1217,10 @@ mod tests {
     use serde_json::json;
 
     use super::*;
+    use crate::can::ODrive;
+    use crate::mock::{MockBus, MockODrive};
+    use crate::transport::TokioTimer;
+    use std::{sync::Arc, time::Duration};
 
     #[test]
     fn parse_input() {
@@ -72,5 +1234,676 @@ mod tests {
         let endpoints = FlatEndpoints::from_json(input).unwrap();
 
         assert_eq!(endpoints.get("vbus_voltage"), Some((1, ValueKind::Float)));
+        assert_eq!(endpoints.access("vbus_voltage"), Some(Access::Read));
+    }
+
+    #[test]
+    fn reports_invalid_entries() {
+        assert!(matches!(
+            FlatEndpoints::from_json(json!({"vbus_voltage": {"id": 1, "type": "float"}})),
+            Err(ParseError::MissingEndpoints)
+        ));
+
+        let input = json!({"endpoints": {
+        "vbus_voltage": {"id": 1, "type": "float", "access": "r"},
+        "ibus": {"id": 2, "type": "float32"},
+        "control_loop_hz": {"type": "uint32"},
+        "serial_number": {"id": 5},
+        "hw_name": {"id": 70000, "type": "string"},
+        "error": {"id": 6, "type": "uint32", "access": "x"},
+        "reboot": {"id": 7, "type": "function", "inputs": {}}
+        }});
+        let err = FlatEndpoints::from_json(input).unwrap_err();
+        assert_eq!(
+            err.skipped(),
+            [
+                Skipped {
+                    name: "control_loop_hz".into(),
+                    reason: SkipReason::MissingId
+                },
+                Skipped {
+                    name: "error".into(),
+                    reason: SkipReason::InvalidAccess("x".into())
+                },
+                Skipped {
+                    name: "hw_name".into(),
+                    reason: SkipReason::IdOutOfRange(70000)
+                },
+                Skipped {
+                    name: "ibus".into(),
+                    reason: SkipReason::UnknownType("float32".into())
+                },
+                Skipped {
+                    name: "reboot".into(),
+                    reason: SkipReason::InvalidFunction
+                },
+                Skipped {
+                    name: "serial_number".into(),
+                    reason: SkipReason::MissingType
+                },
+            ]
+        );
+        assert!(err.to_string().contains("6 invalid"));
+        assert!(matches!(crate::Error::from(err), crate::Error::Config(_)));
+
+        let err = FlatEndpoints::from_json(json!({"endpoints": {
+        "vbus_voltage": {"id": 1, "type": "float"},
+        "ibus": {"id": 2, "type": "float32"}
+        }}))
+        .unwrap_err();
+        let partial = err.into_partial().unwrap();
+        assert_eq!(partial.get("vbus_voltage"), Some((1, ValueKind::Float)));
+        assert!(partial.get("ibus").is_none());
+    }
+
+    #[test]
+    fn calls_functions() {
+        let input = json!({"endpoints": {
+        "save_configuration": {
+          "id": 250,
+          "type": "function",
+          "inputs": [],
+          "outputs": [{"name": "result", "id": 251, "type": "bool", "access": "r"}]
+        },
+        "test_function": {
+          "id": 693,
+          "type": "function",
+          "inputs": [{"name": "delta", "id": 694, "type": "int32", "access": "rw"}],
+          "outputs": [{"name": "result", "id": 695, "type": "int32", "access": "r"}]
+        }}});
+        let endpoints = FlatEndpoints::from_json(input).unwrap();
+        assert_eq!(endpoints.function("save_configuration").unwrap().id, 250);
+        assert!(endpoints.get("test_function").is_none());
+        assert!(endpoints.resolve_call("test_function", &[]).is_err());
+        assert!(
+            endpoints
+                .resolve_call("test_function", &[Value::Float(1.0)])
+                .is_err()
+        );
+
+        let node = Arc::new(MockODrive::new(1));
+        node.update(|state| {
+            state.endpoints.insert(695, 42i32.to_le_bytes());
+        });
+        let odrive = ODrive::new(MockBus::new().with_node(node.clone()), 1).unwrap();
+        let outputs = tokio_test::block_on(odrive.call_function(
+            &endpoints,
+            "test_function",
+            &[Value::I32(-3)],
+        ))
+        .unwrap();
+        assert!(matches!(outputs[..], [Value::I32(42)]));
+
+        let state = node.state();
+        assert_eq!(state.endpoints[&694], (-3i32).to_le_bytes());
+        assert!(state.endpoints.contains_key(&693));
+    }
+
+    #[test]
+    fn downloads() {
+        let cache = std::env::temp_dir().join(format!("odrive-download-{}", std::process::id()));
+        let node = Arc::new(MockODrive::new(1));
+        node.update(|state| {
+            state
+                .endpoints
+                .insert(JSON_CRC_ENDPOINT, 0x1234u32.to_le_bytes());
+        });
+        let odrive = ODrive::new(MockBus::new().with_node(node), 1).unwrap();
+        let download = |json: serde_json::Value| {
+            tokio_test::block_on(FlatEndpoints::download(&odrive, &cache, |version| {
+                assert_eq!(version.fw_version_minor, 6);
+                Ok(json.to_string().into_bytes())
+            }))
+        };
+
+        let endpoints = download(json!({"json_crc": 0x1234, "endpoints": {
+            "vbus_voltage": {"id": 1, "type": "float", "access": "r"}
+        }}))
+        .unwrap();
+        assert_eq!(endpoints.get("vbus_voltage"), Some((1, ValueKind::Float)));
+        std::fs::remove_dir_all(&cache).unwrap();
+
+        assert!(matches!(
+            download(json!({"json_crc": 0x4321, "endpoints": {}})),
+            Err(crate::Error::EndpointsMismatch { .. })
+        ));
+        std::fs::remove_dir_all(&cache).unwrap();
+    }
+
+    #[test]
+    fn fetches() {
+        let cache = std::env::temp_dir().join(format!("odrive-fetch-{}", std::process::id()));
+        let version = crate::mock::MockState::default().version;
+        let json = br#"{"endpoints": {"vbus_voltage": {"id": 1, "type": "float"}}}"#;
+
+        let endpoints = FlatEndpoints::fetch(&version, &cache, |_| Ok(json.to_vec())).unwrap();
+        assert_eq!(endpoints.get("vbus_voltage"), Some((1, ValueKind::Float)));
+        // cached, not downloaded again
+        let endpoints = FlatEndpoints::fetch(&version, &cache, |_| unreachable!()).unwrap();
+        assert_eq!(endpoints.get("vbus_voltage"), Some((1, ValueKind::Float)));
+        // development builds of the same version are cached apart
+        let unreleased = Version {
+            fw_version_unreleased: true,
+            ..version
+        };
+        let json = br#"{"endpoints": {"ibus": {"id": 2, "type": "float"}}}"#;
+        let endpoints = FlatEndpoints::fetch(&unreleased, &cache, |_| Ok(json.to_vec())).unwrap();
+        assert_eq!(endpoints.get("ibus"), Some((2, ValueKind::Float)));
+        std::fs::remove_dir_all(&cache).unwrap();
+
+        let invalid = FlatEndpoints::fetch(&version, &cache, |_| Ok(b"<html>".to_vec()));
+        assert!(matches!(invalid, Err(crate::Error::Config(_))));
+        assert!(!cache.exists());
+    }
+
+    #[test]
+    fn verifies_crc() {
+        let node = Arc::new(MockODrive::new(1));
+        node.update(|state| {
+            state
+                .endpoints
+                .insert(JSON_CRC_ENDPOINT, 0x1234u32.to_le_bytes());
+        });
+        let odrive = ODrive::new(MockBus::new().with_node(node), 1).unwrap();
+        let verify = |input| {
+            let endpoints = FlatEndpoints::from_json(input).unwrap();
+            tokio_test::block_on(endpoints.verify(&odrive))
+        };
+
+        assert!(verify(json!({"json_crc": 0x1234, "endpoints": {}})).is_ok());
+        assert!(matches!(
+            verify(json!({"json_crc": 0x4321, "endpoints": {}})),
+            Err(crate::Error::EndpointsMismatch {
+                expected: 0x4321,
+                actual: 0x1234
+            })
+        ));
+        assert!(matches!(
+            verify(json!({"endpoints": {}})),
+            Err(crate::Error::Config(_))
+        ));
+    }
+
+    #[test]
+    fn ignores_strings() {
+        let input = json!({"endpoints": {
+        "hw_name": {"id": 42, "type": "string", "access": "r"}
+        }});
+        let endpoints = FlatEndpoints::from_json(input).unwrap();
+        assert!(endpoints.get("hw_name").is_none());
+    }
+
+    #[test]
+    fn browses() {
+        let endpoint = json!({"id": 1, "type": "float"});
+        let input = json!({"endpoints": {
+        "vbus_voltage": endpoint,
+        "axis0.requested_state": endpoint,
+        "axis0.controller.config.vel_limit": endpoint,
+        "axis0.controller.config.pos_gain": endpoint,
+        "axis0.controller.input_pos": endpoint,
+        "axis1.controller.config.vel_limit": endpoint
+        }});
+        let endpoints = FlatEndpoints::from_json(input).unwrap();
+
+        assert_eq!(
+            endpoints.find("axis0.controller.config.*"),
+            [
+                "axis0.controller.config.pos_gain",
+                "axis0.controller.config.vel_limit"
+            ]
+        );
+        assert_eq!(endpoints.find("axis?.*.vel_limit").len(), 2);
+        assert_eq!(endpoints.find("*state"), ["axis0.requested_state"]);
+        assert!(endpoints.find("axis0").is_empty());
+
+        assert_eq!(
+            endpoints.children(""),
+            [
+                Entry::Namespace("axis0"),
+                Entry::Namespace("axis1"),
+                Entry::Endpoint("vbus_voltage")
+            ]
+        );
+        assert_eq!(
+            endpoints.children("axis0"),
+            [
+                Entry::Namespace("controller"),
+                Entry::Endpoint("requested_state")
+            ]
+        );
+        assert!(endpoints.children("axis").is_empty());
+
+        let tree = endpoints.tree();
+        assert_eq!(
+            tree.children.keys().collect::<Vec<_>>(),
+            ["axis0", "axis1", "vbus_voltage"]
+        );
+        let config = tree.get("axis0.controller.config").unwrap();
+        assert!(config.is_namespace());
+        assert_eq!(config.name(), "config");
+        let vel_limit = &config.children["vel_limit"];
+        assert_eq!(vel_limit.path, "axis0.controller.config.vel_limit");
+        assert_eq!(
+            vel_limit.endpoint,
+            Some((1, ValueKind::Float, Access::ReadWrite))
+        );
+        assert!(tree.get("axis0.encoder").is_none());
+        let leaves = tree
+            .walk()
+            .into_iter()
+            .filter(|node| node.endpoint.is_some());
+        assert_eq!(leaves.count(), endpoints.endpoints().len());
+    }
+
+    #[test]
+    fn diffs() {
+        let old = FlatEndpoints::from_json(json!({"endpoints": {
+        "vbus_voltage": {"id": 1, "type": "float"},
+        "axis0.config.can.node_id": {"id": 2, "type": "uint8"},
+        "axis0.encoder.config.cpr": {"id": 3, "type": "int32"},
+        "axis0.controller.config.vel_limit": {"id": 4, "type": "float"}
+        }}))
+        .unwrap();
+        let new = FlatEndpoints::from_json(json!({"endpoints": {
+        "vbus_voltage": {"id": 1, "type": "float"},
+        "axis0.config.can.node_id": {"id": 2, "type": "uint32"},
+        "axis0.controller.config.vel_limit": {"id": 7, "type": "float"},
+        "axis0.controller.config.vel_limit_tolerance": {"id": 8, "type": "float"},
+        "ibus": {"id": 9, "type": "float"}
+        }}))
+        .unwrap();
+
+        let diff = old.diff(&new);
+        assert_eq!(
+            diff.added,
+            ["axis0.controller.config.vel_limit_tolerance", "ibus"]
+        );
+        assert_eq!(diff.removed, ["axis0.encoder.config.cpr"]);
+        assert_eq!(
+            diff.retyped,
+            [Retyped {
+                name: "axis0.config.can.node_id".into(),
+                old: ValueKind::U8,
+                new: ValueKind::U32,
+            }]
+        );
+        assert!(old.diff(&old).is_empty());
+    }
+
+    #[test]
+    fn enforces_access() {
+        let input = json!({"endpoints": {
+        "vbus_voltage": {"id": 1, "type": "float", "access": "r"},
+        "ibus_report_filter_k": {"id": 3, "type": "float", "access": "rw"},
+        "legacy": {"id": 4, "type": "uint32"}
+        }});
+        let endpoints = FlatEndpoints::from_json(input).unwrap();
+
+        assert_eq!(
+            endpoints.resolve("vbus_voltage", Access::Read).unwrap(),
+            (1, ValueKind::Float)
+        );
+        let err = endpoints
+            .resolve("vbus_voltage", Access::Write)
+            .unwrap_err();
+        assert!(err.to_string().contains("vbus_voltage is read-only"));
+        assert!(
+            endpoints
+                .resolve("ibus_report_filter_k", Access::Write)
+                .is_ok()
+        );
+        assert!(endpoints.resolve("legacy", Access::ReadWrite).is_ok());
+        assert!(endpoints.resolve("missing", Access::Read).is_err());
+    }
+
+    #[test]
+    fn applies_configuration() {
+        let input = json!({"endpoints": {
+        "axis0.controller.config.vel_limit": {"id": 7, "type": "float", "access": "rw"},
+        // aliases vel_limit, like a setting the firmware derives from another
+        "axis0.controller.config.vel_limit_alias": {"id": 7, "type": "float", "access": "rw"},
+        "axis0.config.can.node_id": {"id": 8, "type": "uint8", "access": "rw"},
+        "axis0.requested_state": {"id": 9, "type": "uint32", "access": "w"},
+        "vbus_voltage": {"id": 1, "type": "float", "access": "r"}
+        }});
+        let endpoints = FlatEndpoints::from_json(input).unwrap();
+        let node = Arc::new(MockODrive::new(1));
+        let odrive = ODrive::new(MockBus::new().with_node(node.clone()), 1).unwrap();
+
+        let config = json!({
+            "axis0.controller.config.vel_limit": 10.0,
+            "axis0.config.can.node_id": 3,
+            "axis0.requested_state": 1
+        });
+        let report = tokio_test::block_on(odrive.apply_configuration(
+            &endpoints,
+            &config,
+            Verify::READ_BACK,
+        ))
+        .unwrap();
+        assert!(report.is_ok());
+        assert_eq!(report.applied.len(), 3);
+        assert_eq!(node.state().endpoints[&8], [3, 0, 0, 0]);
+
+        let config = json!({
+            "axis0.controller.config.vel_limit": 10.0,
+            "axis0.controller.config.vel_limit_alias": 12.0
+        });
+        let report = tokio_test::block_on(odrive.apply_configuration(
+            &endpoints,
+            &config,
+            Verify::READ_BACK,
+        ))
+        .unwrap();
+        assert_eq!(report.applied, ["axis0.controller.config.vel_limit_alias"]);
+        let [(key, mismatch)] = &report.mismatched[..] else {
+            panic!("expected one mismatch, got {:?}", report.mismatched);
+        };
+        assert_eq!(key, "axis0.controller.config.vel_limit");
+        assert!(matches!(mismatch.written, Value::Float(10.0)));
+        assert!(matches!(mismatch.read, Value::Float(12.0)));
+
+        let report =
+            tokio_test::block_on(odrive.apply_configuration(&endpoints, &config, Verify::None))
+                .unwrap();
+        assert!(report.is_ok());
+        let tolerant = Verify::ReadBack { tolerance: 0.5 };
+        let report =
+            tokio_test::block_on(odrive.apply_configuration(&endpoints, &config, tolerant))
+                .unwrap();
+        assert!(report.is_ok());
+
+        let config = json!({
+            "axis0.config.can.node_id": 3,
+            "axis0.controller.config.vel_limit": 20.0,
+            "axis0.requested_state": 1
+        });
+        let changes =
+            tokio_test::block_on(odrive.dry_run_configuration(&endpoints, &config)).unwrap();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].key, "axis0.controller.config.vel_limit");
+        assert!(matches!(changes[0].current, Some(Value::Float(12.0))));
+        assert!(matches!(changes[0].desired, Value::Float(20.0)));
+        assert_eq!(changes[1].key, "axis0.requested_state");
+        assert!(changes[1].current.is_none());
+        assert_eq!(node.state().endpoints[&7], 12.0f32.to_le_bytes());
+
+        let config = json!({
+            "axis0.config.can.node_id": 4,
+            "axis0.controller.config.vel_limit": 12.1,
+            "axis0.requested_state": 1
+        });
+        let differences =
+            tokio_test::block_on(odrive.diff_configuration(&endpoints, &config, 0.0)).unwrap();
+        let keys: Vec<_> = differences.iter().map(|diff| diff.key.as_str()).collect();
+        assert_eq!(
+            keys,
+            [
+                "axis0.config.can.node_id",
+                "axis0.controller.config.vel_limit"
+            ]
+        );
+        assert!(matches!(differences[0].expected, Value::U8(4)));
+        assert!(matches!(differences[0].actual, Value::U8(3)));
+        let differences =
+            tokio_test::block_on(odrive.diff_configuration(&endpoints, &config, 0.01)).unwrap();
+        assert_eq!(differences.len(), 1);
+
+        // nothing is written if any entry cannot be
+        let config = json!({"axis0.config.can.node_id": 4, "vbus_voltage": 12.0});
+        assert!(matches!(
+            tokio_test::block_on(odrive.apply_configuration(&endpoints, &config, Verify::None)),
+            Err(crate::Error::Config(_))
+        ));
+        assert_eq!(node.state().endpoints[&8], [3, 0, 0, 0]);
+    }
+
+    #[test]
+    fn backs_up_configuration() {
+        let input = json!({"endpoints": {
+        "axis0.controller.config.vel_limit": {"id": 7, "type": "float", "access": "rw"},
+        "axis0.config.can.node_id": {"id": 8, "type": "uint8", "access": "rw"},
+        "axis0.requested_state": {"id": 9, "type": "uint32", "access": "w"},
+        "vbus_voltage": {"id": 1, "type": "float", "access": "r"}
+        }});
+        let endpoints = FlatEndpoints::from_json(input).unwrap();
+        let node = Arc::new(MockODrive::new(1));
+        node.update(|state| {
+            state.endpoints.insert(7, 2.5f32.to_le_bytes());
+            state.endpoints.insert(8, 3u32.to_le_bytes());
+        });
+        let odrive = ODrive::new(MockBus::new().with_node(node.clone()), 1).unwrap();
+
+        let mut progress = Vec::new();
+        let backup = tokio_test::block_on(
+            odrive.backup_configuration(&endpoints, |done, total| progress.push((done, total))),
+        )
+        .unwrap();
+        assert_eq!(
+            backup,
+            json!({
+                "axis0.config.can.node_id": 3,
+                "axis0.controller.config.vel_limit": 2.5
+            })
+        );
+        assert_eq!(progress.last(), Some(&(2, 2)));
+
+        #[cfg(feature = "toml")]
+        {
+            let toml = config_to_toml(&backup).unwrap();
+            assert!(toml.contains("\"axis0.config.can.node_id\" = 3"));
+            assert_eq!(config_from_toml(&toml).unwrap(), backup);
+            assert!(config_from_toml("vel_limit = ").is_err());
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn restores_configuration() {
+        let input = json!({"endpoints": {
+        "axis0.controller.config.vel_limit": {"id": 7, "type": "float", "access": "rw"},
+        "axis0.config.can.node_id": {"id": 8, "type": "uint8", "access": "rw"},
+        "axis0.requested_state": {"id": 9, "type": "uint32", "access": "w"},
+        "vbus_voltage": {"id": 1, "type": "float", "access": "r"}
+        }});
+        let endpoints = FlatEndpoints::from_json(input).unwrap();
+        // heartbeats keep coming until the drive resets, reads fail until it
+        // is back up
+        let node = Arc::new(MockODrive::new(1));
+        node.update(|state| state.reboot_time = Duration::from_secs(4));
+        let bus = MockBus::<TokioTimer>::default()
+            .with_node(node.clone())
+            .with_heartbeats(Duration::from_millis(100));
+        let odrive = ODrive::new(bus, 1).unwrap();
+
+        let backup = json!({
+            "axis0.controller.config.vel_limit": 2.5,
+            "axis0.config.can.node_id": 3,
+            "axis0.requested_state": 1
+        });
+        let start = tokio::time::Instant::now();
+        let report = odrive
+            .restore_configuration(&endpoints, &backup)
+            .await
+            .unwrap();
+        assert!(report.is_ok(), "{report:?}");
+        assert!(start.elapsed() >= Duration::from_secs(4));
+        assert!(!node.is_rebooting());
+        assert_eq!(report.apply.applied.len(), 3);
+        assert_eq!(node.state().endpoints[&7], 2.5f32.to_le_bytes());
+
+        let backup = json!({"vbus_voltage": 12.0});
+        assert!(matches!(
+            odrive.restore_configuration(&endpoints, &backup).await,
+            Err(crate::Error::Config(_))
+        ));
+    }
+
+    #[test]
+    fn applies_profiles() {
+        let input = json!({"endpoints": {
+        "axis0.config.motor.current_soft_max": {"id": 7, "type": "float", "access": "rw"},
+        "axis0.controller.config.vel_limit": {"id": 8, "type": "float", "access": "rw"},
+        "vbus_voltage": {"id": 1, "type": "float", "access": "r"}
+        }});
+        let endpoints = FlatEndpoints::from_json(input).unwrap();
+        let profiles = Profiles::from_json(json!({
+            "low-power": {"axis0.config.motor.current_soft_max": 5.0},
+            "performance": {"axis0": {
+                "config.motor.current_soft_max": 40.0,
+                "controller": {"config": {"vel_limit": 20.0}}
+            }},
+            "broken": {"vbus_voltage": 12.0}
+        }))
+        .unwrap();
+        assert_eq!(
+            profiles.names().collect::<Vec<_>>(),
+            ["broken", "low-power", "performance"]
+        );
+        assert_eq!(
+            profiles.get("performance"),
+            Some(&json!({
+                "axis0.config.motor.current_soft_max": 40.0,
+                "axis0.controller.config.vel_limit": 20.0
+            }))
+        );
+        assert!(Profiles::from_json(json!({"low-power": 5.0})).is_err());
+
+        let node = Arc::new(MockODrive::new(1));
+        node.update(|state| {
+            state.endpoints.insert(7, 10.0f32.to_le_bytes());
+            state.endpoints.insert(8, 2.0f32.to_le_bytes());
+        });
+        let odrive = ODrive::new(MockBus::new().with_node(node.clone()), 1).unwrap();
+        tokio_test::block_on(async {
+            let report = odrive
+                .apply_profile(&endpoints, &profiles, "performance")
+                .await
+                .unwrap();
+            assert!(report.is_ok());
+            assert!(
+                odrive
+                    .apply_profile(&endpoints, &profiles, "broken")
+                    .await
+                    .is_err()
+            );
+            assert!(
+                odrive
+                    .apply_profile(&endpoints, &profiles, "turbo")
+                    .await
+                    .is_err()
+            );
+        });
+        let state = node.state();
+        assert_eq!(state.endpoints[&7], 40.0f32.to_le_bytes());
+        assert_eq!(state.endpoints[&8], 20.0f32.to_le_bytes());
+
+        #[cfg(feature = "toml")]
+        {
+            let profiles = Profiles::from_toml(
+                r#"
+                [low-power]
+                "axis0.config.motor.current_soft_max" = 5.0
+
+                [performance.axis0.config.motor]
+                current_soft_max = 40.0
+                "#,
+            )
+            .unwrap();
+            assert_eq!(
+                profiles.get("performance"),
+                Some(&json!({"axis0.config.motor.current_soft_max": 40.0}))
+            );
+            assert_eq!(
+                profiles.get("low-power"),
+                Some(&json!({"axis0.config.motor.current_soft_max": 5.0}))
+            );
+        }
+    }
+
+    #[test]
+    fn renders_templates() {
+        let template = json!({
+            "axis${axis}.config.can.node_id": "${node_id}",
+            "axis${axis}.config.motor.current_soft_max": "${current_limit}",
+            "axis${axis}.controller.config.enable_vel_limit": true,
+            "name": "axis ${axis} of ${machine}"
+        });
+        let vars = json!({"axis": 1, "node_id": 7, "current_limit": 12.5, "machine": "lathe"});
+        assert_eq!(
+            render_template(&template, &vars).unwrap(),
+            json!({
+                "axis1.config.can.node_id": 7,
+                "axis1.config.motor.current_soft_max": 12.5,
+                "axis1.controller.config.enable_vel_limit": true,
+                "name": "axis 1 of lathe"
+            })
+        );
+
+        let missing = render_template(&template, &json!({"axis": 1}));
+        assert!(missing.unwrap_err().to_string().contains("node_id"));
+        assert!(render_template(&json!({"axis${axis": 1}), &vars).is_err());
+        assert!(render_template(&json!([]), &vars).is_err());
+    }
+
+    #[test]
+    fn typed_endpoints() {
+        let input = json!({"endpoints": {
+        "vbus_voltage": {"id": 1, "type": "float", "access": "r"},
+        "control_loop_hz": {"id": 4, "type": "uint32", "access": "r"}
+        }});
+        let endpoints = FlatEndpoints::from_json(input).unwrap();
+
+        let vbus = endpoints.typed::<f32>("vbus_voltage").unwrap();
+        assert_eq!(vbus.id(), 1);
+        assert!(vbus.check(Access::Read).is_ok());
+        assert!(vbus.check(Access::Write).is_err());
+        assert!(endpoints.typed::<f32>("control_loop_hz").is_err());
+
+        let node = Arc::new(MockODrive::new(1));
+        node.update(|state| {
+            state.endpoints.insert(1, 24.5f32.to_le_bytes());
+        });
+        let odrive = ODrive::new(MockBus::new().with_node(node), 1).unwrap();
+        tokio_test::block_on(async {
+            assert_eq!(odrive.read_endpoint(&vbus).await.unwrap(), 24.5);
+            assert!(odrive.write_endpoint(&vbus, 12.0).await.is_err());
+        });
+    }
+
+    #[test]
+    fn named_endpoints() {
+        let input = json!({"endpoints": {
+        "vbus_voltage": {"id": 1, "type": "float", "access": "r"},
+        "axis0.config.motor.current_soft_max": {"id": 7, "type": "float", "access": "rw"}
+        }});
+        let endpoints = FlatEndpoints::from_json(input).unwrap();
+        let name = "axis0.config.motor.current_soft_max";
+        let node = Arc::new(MockODrive::new(1));
+        let odrive = ODrive::new(MockBus::new().with_node(node.clone()), 1).unwrap();
+
+        tokio_test::block_on(async {
+            odrive
+                .write_named(&endpoints, name, Value::Float(40.0))
+                .await
+                .unwrap();
+            assert!(matches!(
+                odrive.read_named(&endpoints, name).await.unwrap(),
+                Value::Float(40.0)
+            ));
+            assert!(
+                odrive
+                    .write_named(&endpoints, name, Value::U32(40))
+                    .await
+                    .is_err()
+            );
+            assert!(
+                odrive
+                    .write_named(&endpoints, "vbus_voltage", Value::Float(1.0))
+                    .await
+                    .is_err()
+            );
+        });
+        assert_eq!(node.state().endpoints[&7], 40.0f32.to_le_bytes());
     }
 }
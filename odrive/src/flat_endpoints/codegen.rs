@@ -0,0 +1,296 @@
+//! # Code generation
+//!
+//! [`generate`] turns a [`FlatEndpoints`] into Rust source with one type per
+//! namespace and one method per endpoint, so endpoint names and types are
+//! checked at compile time. It is meant to be called from a build script:
+//!
+//! ```no_run
+//! // build.rs, with odrive as a build dependency
+//! use odrive::flat_endpoints::{FlatEndpoints, codegen};
+//!
+//! let json = std::fs::read_to_string("flat_endpoints.json").unwrap();
+//! let endpoints = FlatEndpoints::from_json(serde_json::from_str(&json).unwrap()).unwrap();
+//! let out = std::path::Path::new(&std::env::var("OUT_DIR").unwrap()).join("endpoints.rs");
+//! std::fs::write(out, codegen::generate(&endpoints)).unwrap();
+//! ```
+//!
+//! The generated `Device` wraps an async driver:
+//!
+//! ```ignore
+//! mod endpoints {
+//!     include!(concat!(env!("OUT_DIR"), "/endpoints.rs"));
+//! }
+//!
+//! let dev = endpoints::Device(&odrive);
+//! dev.axis0().controller().config().vel_limit().set(10.0).await?;
+//! ```
+//!
+//! Namespace `axis0.controller` becomes the type `Axis0_Controller`.
+//! Endpoint and namespace names that are Rust keywords are escaped, `type`
+//! becomes `r#type` and `self` becomes `self_`. Names that would collide,
+//! such as an endpoint named like a sibling namespace, get `_` appended
+//! until they are unique, namespaces keep theirs.
+//!
+//! `tests/codegen` holds the source generated from an endpoints file with
+//! such names, the `codegen` test compiles and runs it.
+
+use super::{Access, Endpoint, FlatEndpoints};
+use crate::can::ODrive;
+use crate::protocol::{SdoType, ValueKind};
+use crate::transport::Transport;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write;
+
+/// Endpoint bound to a driver, returned by the generated methods.
+pub struct Bound<'a, T, V> {
+    odrive: &'a ODrive<T>,
+    endpoint: Endpoint<V>,
+}
+
+impl<'a, T: Transport, V: SdoType> Bound<'a, T, V> {
+    /// Bind `endpoint` to `odrive`, called by the generated methods.
+    pub fn new(odrive: &'a ODrive<T>, endpoint: Endpoint<V>) -> Self {
+        Self { odrive, endpoint }
+    }
+
+    /// The endpoint, for the driver methods taking an [`Endpoint`].
+    pub fn endpoint(&self) -> Endpoint<V> {
+        self.endpoint
+    }
+
+    /// Read the endpoint, see [`ODrive::read_endpoint`].
+    pub async fn get(&self) -> crate::Result<V> {
+        self.odrive.read_endpoint(&self.endpoint).await
+    }
+
+    /// Write the endpoint, see [`ODrive::write_endpoint`].
+    pub async fn set(&self, value: V) -> crate::Result<()> {
+        self.odrive.write_endpoint(&self.endpoint, value).await
+    }
+}
+
+/// Namespace of the generated source.
+#[derive(Default)]
+struct Namespace<'a> {
+    /// Paths of the child namespaces by name.
+    namespaces: BTreeMap<&'a str, &'a str>,
+    endpoints: BTreeMap<&'a str, (u16, ValueKind, Access)>,
+}
+
+/// Generate the source of the typed accessors of `endpoints`.
+///
+/// Endpoints with an ID that is not a valid SDO endpoint are skipped.
+pub fn generate(endpoints: &FlatEndpoints) -> String {
+    let mut namespaces = BTreeMap::<&str, Namespace>::new();
+    namespaces.insert("", Namespace::default());
+    for (name, &(id, kind)) in endpoints.endpoints() {
+        let Ok(id) = u16::try_from(id) else {
+            continue;
+        };
+        let access = endpoints.access(name).unwrap_or(Access::ReadWrite);
+        let (parent, leaf) = name.rsplit_once('.').unwrap_or(("", name));
+        namespaces
+            .entry(parent)
+            .or_default()
+            .endpoints
+            .insert(leaf, (id, kind, access));
+
+        // register the namespace in each of its ancestors
+        let mut path = parent;
+        while !path.is_empty() {
+            let (ancestor, child) = path.rsplit_once('.').unwrap_or(("", path));
+            namespaces
+                .entry(ancestor)
+                .or_default()
+                .namespaces
+                .insert(child, path);
+            path = ancestor;
+        }
+    }
+
+    // the root comes first and keeps its name
+    let mut taken = HashSet::new();
+    let types: BTreeMap<&str, String> = namespaces
+        .keys()
+        .map(|&path| {
+            let name = if path.is_empty() {
+                "Device".to_owned()
+            } else {
+                type_name(path)
+            };
+            (path, unique(name, &mut taken))
+        })
+        .collect();
+
+    let mut out = String::from(
+        "// Generated by odrive::flat_endpoints::codegen, do not edit.\n\n\
+         use ::odrive::can::ODrive;\n\
+         use ::odrive::flat_endpoints::{Access, Endpoint, codegen::Bound};\n\
+         use ::odrive::transport::Transport;\n",
+    );
+    for (path, namespace) in &namespaces {
+        let name = &types[path];
+        let visibility = if path.is_empty() { "pub " } else { "" };
+        let doc = if path.is_empty() {
+            "Root of the endpoints tree.".to_owned()
+        } else {
+            format!("`{path}`")
+        };
+        let _ = write!(
+            out,
+            "\n/// {doc}\n\
+             #[allow(non_camel_case_types)]\n\
+             pub struct {name}<'a, T>({visibility}&'a ODrive<T>);\n\n\
+             impl<'a, T: Transport> {name}<'a, T> {{\n"
+        );
+        let mut methods = HashSet::new();
+        for (child, child_path) in &namespace.namespaces {
+            let method = unique(ident(child), &mut methods);
+            let child_type = &types[child_path];
+            let _ = write!(
+                out,
+                "    pub fn {method}(&self) -> {child_type}<'a, T> {{\n        {child_type}(self.0)\n    }}\n",
+            );
+        }
+        for (leaf, &(id, kind, access)) in &namespace.endpoints {
+            let method = unique(ident(leaf), &mut methods);
+            let _ = write!(
+                out,
+                "    pub fn {method}(&self) -> Bound<'a, T, {ty}> {{\n        \
+                 Bound::new(self.0, Endpoint::new({id}, Access::{access:?}))\n    }}\n",
+                ty = rust_type(kind),
+            );
+        }
+        out.push_str("}\n");
+    }
+    out
+}
+
+/// Type of namespace `path`, `Axis0_Controller` for `axis0.controller`.
+fn type_name(path: &str) -> String {
+    let segments: Vec<String> = path
+        .split('.')
+        .map(|segment| {
+            segment
+                .split('_')
+                .map(|word| {
+                    let mut chars = word.chars();
+                    chars.next().map_or(String::new(), |first| {
+                        first.to_ascii_uppercase().to_string() + chars.as_str()
+                    })
+                })
+                .collect()
+        })
+        .collect();
+    let name = segments.join("_");
+    if name == "Self" {
+        "Self_".to_owned()
+    } else if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{name}")
+    } else {
+        name
+    }
+}
+
+/// `name`, with `_` appended until it is not `taken`.
+fn unique(mut name: String, taken: &mut HashSet<String>) -> String {
+    while taken.contains(&name) {
+        name.push('_');
+    }
+    taken.insert(name.clone());
+    name
+}
+
+/// Method name of `name`, escaped if it is a keyword.
+fn ident(name: &str) -> String {
+    const KEYWORDS: &[&str] = &[
+        "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do",
+        "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in",
+        "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref",
+        "return", "static", "struct", "trait", "true", "try", "type", "typeof", "unsafe",
+        "unsized", "use", "virtual", "where", "while", "yield",
+    ];
+    if matches!(name, "self" | "Self" | "super" | "crate" | "_") {
+        format!("{name}_")
+    } else if KEYWORDS.contains(&name) {
+        format!("r#{name}")
+    } else if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{name}")
+    } else {
+        name.to_owned()
+    }
+}
+
+fn rust_type(kind: ValueKind) -> &'static str {
+    match kind {
+        ValueKind::Bool => "bool",
+        ValueKind::U8 => "u8",
+        ValueKind::I8 => "i8",
+        ValueKind::U16 => "u16",
+        ValueKind::I16 => "i16",
+        ValueKind::U32 => "u32",
+        ValueKind::I32 => "i32",
+        ValueKind::Float => "f32",
+        ValueKind::U64 => "u64",
+        ValueKind::I64 => "i64",
+        ValueKind::Double => "f64",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn generates() {
+        let endpoints = FlatEndpoints::from_json(json!({"endpoints": {
+            "vbus_voltage": {"id": 1, "type": "float", "access": "r"},
+            "axis0.controller.config.vel_limit": {"id": 2, "type": "float", "access": "rw"},
+            "axis0.config.type": {"id": 3, "type": "uint8", "access": "rw"}
+        }}))
+        .unwrap();
+        let source = generate(&endpoints);
+
+        assert!(source.contains("pub struct Device<'a, T>(pub &'a ODrive<T>);"));
+        assert!(source.contains(
+            "    pub fn vbus_voltage(&self) -> Bound<'a, T, f32> {\n        \
+             Bound::new(self.0, Endpoint::new(1, Access::Read))\n    }"
+        ));
+        assert!(
+            source.contains(
+                "    pub fn axis0(&self) -> Axis0<'a, T> {\n        Axis0(self.0)\n    }"
+            )
+        );
+        assert!(source.contains(
+            "impl<'a, T: Transport> Axis0_Controller_Config<'a, T> {\n    pub fn vel_limit"
+        ));
+        assert!(source.contains("pub fn r#type(&self) -> Bound<'a, T, u8>"));
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(type_name("axis0.pos_vel_mapper"), "Axis0_PosVelMapper");
+        assert_eq!(ident("self"), "self_");
+        assert_eq!(ident("move"), "r#move");
+        assert_eq!(ident("vel_limit"), "vel_limit");
+        assert_eq!(ident("_"), "__");
+        assert_eq!(type_name("self"), "Self_");
+        assert_eq!(type_name("0"), "_0");
+
+        let mut taken = HashSet::new();
+        assert_eq!(unique("config".to_owned(), &mut taken), "config");
+        assert_eq!(unique("config".to_owned(), &mut taken), "config_");
+    }
+
+    #[test]
+    fn fixture_is_current() {
+        let json = include_str!("../../tests/codegen/flat_endpoints.json");
+        let endpoints = FlatEndpoints::from_json(serde_json::from_str(json).unwrap()).unwrap();
+        assert_eq!(
+            generate(&endpoints),
+            include_str!("../../tests/codegen/endpoints.rs"),
+            "regenerate tests/codegen/endpoints.rs"
+        );
+    }
+}
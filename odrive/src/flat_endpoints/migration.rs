@@ -0,0 +1,282 @@
+//! # Configuration migration
+//!
+//! Endpoints are renamed and moved between firmware releases, so a stored
+//! configuration no longer applies after an upgrade. A [`Migration`] lists
+//! the renames from one release to the next, [`Migrations::migrate`] chains
+//! them to carry a configuration across several releases:
+//!
+//! ```
+//! use odrive::flat_endpoints::migration::Migrations;
+//! use serde_json::json;
+//!
+//! let migrations = Migrations::from_json(json!([{
+//!     "from": "0.5.6",
+//!     "to": "0.6.0",
+//!     "renames": {
+//!         "axis0.motor.config": "axis0.config.motor",
+//!         "axis0.encoder.config.cpr": null
+//!     }
+//! }]))
+//! .unwrap();
+//!
+//! let config = json!({
+//!     "axis0.motor.config.current_lim": 20.0,
+//!     "axis0.encoder.config.cpr": 8192
+//! });
+//! assert_eq!(
+//!     migrations.migrate(&config, (0, 5, 6), (0, 6, 0)).unwrap(),
+//!     json!({"axis0.config.motor.current_lim": 20.0})
+//! );
+//! ```
+//!
+//! A rename of a namespace applies to every endpoint below it, the most
+//! specific rename wins. Endpoints renamed to `null` were removed and are
+//! dropped from the configuration.
+
+use crate::protocol::Version;
+use std::collections::BTreeMap;
+
+/// Firmware major, minor and revision.
+pub type Release = (u8, u8, u8);
+
+/// Firmware release `version` reports.
+pub fn release(version: &Version) -> Release {
+    version.firmware()
+}
+
+/// Renames from one firmware release to another.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Migration {
+    pub from: Release,
+    pub to: Release,
+    /// New name of each renamed endpoint or namespace, [`None`] if removed.
+    pub renames: BTreeMap<String, Option<String>>,
+}
+
+impl Migration {
+    pub fn new(from: Release, to: Release) -> Self {
+        Self {
+            from,
+            to,
+            renames: BTreeMap::new(),
+        }
+    }
+
+    /// Rename the endpoint or namespace `old` to `new`.
+    pub fn rename(mut self, old: &str, new: &str) -> Self {
+        self.renames.insert(old.to_owned(), Some(new.to_owned()));
+        self
+    }
+
+    /// Drop the endpoint or namespace `old`.
+    pub fn remove(mut self, old: &str) -> Self {
+        self.renames.insert(old.to_owned(), None);
+        self
+    }
+
+    /// Name of `key` after the migration, [`None`] if removed.
+    fn apply(&self, key: &str) -> Option<String> {
+        // the longest prefix is the most specific rename
+        let mut prefix = key;
+        loop {
+            if let Some(renamed) = self.renames.get(prefix) {
+                return renamed
+                    .as_ref()
+                    .map(|renamed| format!("{renamed}{}", &key[prefix.len()..]));
+            }
+            match prefix.rsplit_once('.') {
+                Some((parent, _)) => prefix = parent,
+                None => return Some(key.to_owned()),
+            }
+        }
+    }
+}
+
+/// Table of migrations between firmware releases.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Migrations {
+    steps: Vec<Migration>,
+}
+
+impl Migrations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `migration` to the table.
+    pub fn with(mut self, migration: Migration) -> Self {
+        self.steps.push(migration);
+        self
+    }
+
+    /// Table from a JSON array of migrations with `from` and `to` releases
+    /// such as `"0.6.10"` and a `renames` object.
+    pub fn from_json(input: serde_json::Value) -> crate::Result<Self> {
+        let invalid = |what: &str| crate::Error::Config(format!("Invalid migration: {what}"));
+        let Some(steps) = input.as_array() else {
+            return Err(invalid("expected array"));
+        };
+        let mut migrations = Self::new();
+        for step in steps {
+            let release = |key| {
+                step.get(key)
+                    .and_then(|release| release.as_str())
+                    .and_then(parse_release)
+                    .ok_or_else(|| invalid(key))
+            };
+            let mut migration = Migration::new(release("from")?, release("to")?);
+            let renames = step.get("renames").and_then(|renames| renames.as_object());
+            for (old, new) in renames.ok_or_else(|| invalid("renames"))? {
+                let new = match new {
+                    serde_json::Value::Null => None,
+                    serde_json::Value::String(new) => Some(new.to_owned()),
+                    _ => return Err(invalid(old)),
+                };
+                migration.renames.insert(old.to_owned(), new);
+            }
+            migrations = migrations.with(migration);
+        }
+        Ok(migrations)
+    }
+
+    /// `config` written for release `from` migrated to release `to`.
+    ///
+    /// Fails with [`crate::Error::Config`] if the table has no chain of
+    /// migrations from `from` to `to` or two entries are renamed to the
+    /// same endpoint.
+    pub fn migrate(
+        &self,
+        config: &serde_json::Value,
+        from: Release,
+        to: Release,
+    ) -> crate::Result<serde_json::Value> {
+        let Some(entries) = config.as_object() else {
+            return Err(crate::Error::Config("Expected object".into()));
+        };
+        let mut config = entries.clone();
+        let mut release = from;
+        while release != to {
+            let Some(step) = self
+                .steps
+                .iter()
+                .filter(|step| step.from == release && step.to > release && step.to <= to)
+                .max_by_key(|step| step.to)
+            else {
+                return Err(crate::Error::Config(format!(
+                    "No migration from {} to {}",
+                    format_release(release),
+                    format_release(to)
+                )));
+            };
+
+            let mut migrated = serde_json::Map::with_capacity(config.len());
+            for (key, value) in config {
+                let Some(renamed) = step.apply(&key) else {
+                    continue;
+                };
+                if migrated.insert(renamed.clone(), value).is_some() {
+                    return Err(crate::Error::Config(format!(
+                        "Migration to {} renames two entries to {renamed}",
+                        format_release(step.to)
+                    )));
+                }
+            }
+            config = migrated;
+            release = step.to;
+        }
+        Ok(config.into())
+    }
+}
+
+fn parse_release(release: &str) -> Option<Release> {
+    let mut parts = release.split('.').map(|part| part.parse().ok());
+    let release = (parts.next()??, parts.next()??, parts.next()??);
+    parts.next().is_none().then_some(release)
+}
+
+fn format_release((major, minor, revision): Release) -> String {
+    format!("{major}.{minor}.{revision}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn migrates() {
+        let migrations = Migrations::new()
+            .with(
+                Migration::new((0, 5, 6), (0, 6, 0))
+                    .rename("axis0.motor.config", "axis0.config.motor")
+                    .rename(
+                        "axis0.motor.config.current_lim",
+                        "axis0.config.motor.current_soft_max",
+                    )
+                    .remove("axis0.encoder"),
+            )
+            .with(
+                Migration::new((0, 6, 0), (0, 6, 10))
+                    .rename("can.config.baud_rate", "can.config.baudrate"),
+            );
+
+        let config = json!({
+            "axis0.motor.config.current_lim": 20.0,
+            "axis0.motor.config.pole_pairs": 7,
+            "axis0.encoder.config.cpr": 8192,
+            "can.config.baud_rate": 250000,
+            "axis0.controller.config.vel_limit": 10.0
+        });
+        assert_eq!(
+            migrations.migrate(&config, (0, 5, 6), (0, 6, 10)).unwrap(),
+            json!({
+                "axis0.config.motor.current_soft_max": 20.0,
+                "axis0.config.motor.pole_pairs": 7,
+                "can.config.baudrate": 250000,
+                "axis0.controller.config.vel_limit": 10.0
+            })
+        );
+        assert_eq!(
+            migrations.migrate(&config, (0, 6, 10), (0, 6, 10)).unwrap(),
+            config
+        );
+        assert!(migrations.migrate(&config, (0, 6, 10), (0, 5, 6)).is_err());
+        assert!(migrations.migrate(&config, (0, 5, 6), (0, 6, 11)).is_err());
+
+        let colliding = json!({"a.x": 1, "b.x": 2});
+        let migrations =
+            Migrations::new().with(Migration::new((0, 1, 0), (0, 2, 0)).rename("a", "b"));
+        assert!(
+            migrations
+                .migrate(&colliding, (0, 1, 0), (0, 2, 0))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn parses() {
+        let migrations = Migrations::from_json(json!([{
+            "from": "0.6.0",
+            "to": "0.6.10",
+            "renames": {"can.config.baud_rate": "can.config.baudrate", "axis0.encoder": null}
+        }]))
+        .unwrap();
+        assert_eq!(
+            migrations,
+            Migrations::new().with(
+                Migration::new((0, 6, 0), (0, 6, 10))
+                    .rename("can.config.baud_rate", "can.config.baudrate")
+                    .remove("axis0.encoder")
+            )
+        );
+        assert!(
+            Migrations::from_json(json!([{"from": "0.6", "to": "0.6.10", "renames": {}}])).is_err()
+        );
+        assert!(
+            Migrations::from_json(json!([{"from": "0.6.0", "to": "0.6.10", "renames": {"a": 1}}]))
+                .is_err()
+        );
+        assert_eq!(parse_release("0.6.10"), Some((0, 6, 10)));
+        assert_eq!(parse_release("0.6.10.1"), None);
+    }
+}
@@ -0,0 +1,311 @@
+//! # Fleet health
+//!
+//! [`Fleet`] tracks the health of many drives on one or more buses: whether
+//! they are online, their axis state, active errors, temperatures and
+//! firmware versions. It is fed by frame subscribers, one per bus, and calls
+//! back whenever the health of a drive changes.
+//!
+//! ```no_run
+//! # tokio_test::block_on(async {
+//! use odrive::{can::Bus, fleet::Fleet};
+//! use std::sync::Arc;
+//!
+//! let fleet = Arc::new(Fleet::new().on_change(|drive, health| {
+//!     println!("node {} on bus {}: {:?}", drive.node, drive.bus, health.errors);
+//! }));
+//!
+//! let socket = socketcan::tokio::CanSocket::open("can0").unwrap();
+//! let bus = Bus::new(socket).on_frame(fleet.subscriber(0));
+//! let odrive = bus.axis(1).unwrap();
+//! let version = odrive.get_version().await.unwrap();
+//! fleet.set_version(fleet.drive(0, 1), version);
+//!
+//! odrive.listen().await.unwrap();
+//! # });
+//! ```
+//!
+//! Drives are added when their first heartbeat is received or with
+//! [`Fleet::track`].
+
+use crate::protocol::{Frame, TelemetryMessage, Temperature, Version};
+use crate::{AxisErrors, AxisState};
+use cansimple::Id;
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+type ChangeCallback = Arc<dyn Fn(DriveId, &DriveHealth) + Send + Sync>;
+
+/// Default time without a heartbeat after which a drive is offline.
+pub const DEFAULT_OFFLINE_AFTER: Duration = Duration::from_millis(500);
+
+/// Drive in a [`Fleet`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DriveId {
+    /// Index of the bus passed to [`Fleet::subscriber`].
+    pub bus: usize,
+    pub node: u8,
+}
+
+/// Last known health of a drive.
+#[derive(Debug, Clone, Copy)]
+pub struct DriveHealth {
+    /// When the last heartbeat was received.
+    pub last_heartbeat: Option<Instant>,
+    pub axis_state: Option<AxisState>,
+    /// Active errors reported by the last heartbeat.
+    pub errors: AxisErrors,
+    pub temperature: Option<Temperature>,
+    pub version: Option<Version>,
+}
+
+impl Default for DriveHealth {
+    fn default() -> Self {
+        Self {
+            last_heartbeat: None,
+            axis_state: None,
+            errors: AxisErrors::empty(),
+            temperature: None,
+            version: None,
+        }
+    }
+}
+
+impl DriveHealth {
+    /// Whether a heartbeat was received within `window`.
+    pub fn is_online(&self, window: Duration) -> bool {
+        self.is_online_at(window, Instant::now())
+    }
+
+    /// Whether a heartbeat was received within `window` before `now`.
+    pub fn is_online_at(&self, window: Duration, now: Instant) -> bool {
+        self.last_heartbeat
+            .is_some_and(|received| now.saturating_duration_since(received) <= window)
+    }
+}
+
+/// Aggregated health of a [`Fleet`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FleetSummary {
+    pub drives: usize,
+    pub online: usize,
+    /// Drives with active errors.
+    pub faulted: usize,
+    pub max_fet_temperature: Option<f32>,
+    pub max_motor_temperature: Option<f32>,
+}
+
+/// Health of drives on one or more buses.
+pub struct Fleet {
+    drives: Mutex<BTreeMap<DriveId, DriveHealth>>,
+    offline_after: Duration,
+    on_change: Option<ChangeCallback>,
+    clock: fn() -> Instant,
+}
+
+impl Default for Fleet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Fleet {
+    pub fn new() -> Self {
+        Self {
+            drives: Mutex::new(BTreeMap::new()),
+            offline_after: DEFAULT_OFFLINE_AFTER,
+            on_change: None,
+            clock: Instant::now,
+        }
+    }
+
+    /// Timestamp heartbeats with `clock`, such as
+    /// [`Timer::now`](crate::transport::Timer::now).
+    ///
+    /// Defaults to the system clock.
+    pub fn with_clock(mut self, clock: fn() -> Instant) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Consider drives offline after `window` without a heartbeat.
+    ///
+    /// Defaults to [`DEFAULT_OFFLINE_AFTER`].
+    pub fn with_offline_after(mut self, window: Duration) -> Self {
+        self.offline_after = window;
+        self
+    }
+
+    /// Call `callback` when a drive comes online or its axis state, errors
+    /// or version change.
+    pub fn on_change(
+        mut self,
+        callback: impl Fn(DriveId, &DriveHealth) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_change = Some(Arc::new(callback));
+        self
+    }
+
+    /// Identifier of `node` on `bus`.
+    pub fn drive(&self, bus: usize, node: u8) -> DriveId {
+        DriveId { bus, node }
+    }
+
+    /// Track a drive before its first heartbeat, so it is reported offline.
+    pub fn track(&self, drive: DriveId) {
+        self.lock().entry(drive).or_default();
+    }
+
+    /// Frame subscriber for the bus with index `bus`.
+    ///
+    /// To be passed to [`Bus::on_frame`](crate::can::Bus::on_frame) or
+    /// [`ODrive::on_frame`](crate::can::ODrive::on_frame).
+    pub fn subscriber(self: &Arc<Self>, bus: usize) -> impl Fn(&Frame) + Send + Sync + 'static {
+        let fleet = Arc::downgrade(self);
+        move |frame| {
+            if let Some(fleet) = fleet.upgrade() {
+                fleet.record(bus, frame);
+            }
+        }
+    }
+
+    /// Update the health of the drive `frame` belongs to.
+    pub fn record(&self, bus: usize, frame: &Frame) {
+        let Ok(id) = Id::try_from(frame.id()) else {
+            return;
+        };
+        let drive = self.drive(bus, id.node());
+
+        let changed = match TelemetryMessage::decode(frame) {
+            Some(Ok(TelemetryMessage::Heartbeat(heartbeat))) => {
+                self.update(drive, |health, online| {
+                    let changed = !online
+                        || health.axis_state != Some(heartbeat.axis_state)
+                        || health.errors != heartbeat.axis_error;
+                    health.last_heartbeat = Some((self.clock)());
+                    health.axis_state = Some(heartbeat.axis_state);
+                    health.errors = heartbeat.axis_error;
+                    changed
+                })
+            }
+            Some(Ok(TelemetryMessage::Temperature(temperature))) => {
+                self.update(drive, |health, _| {
+                    health.temperature = Some(temperature);
+                    false
+                })
+            }
+            _ => None,
+        };
+        self.notify(drive, changed);
+    }
+
+    /// Store the firmware version of `drive`, see
+    /// [`ODrive::get_version`](crate::can::ODrive::get_version).
+    pub fn set_version(&self, drive: DriveId, version: Version) {
+        let changed = self.update(drive, |health, _| {
+            let changed = health.version != Some(version);
+            health.version = Some(version);
+            changed
+        });
+        self.notify(drive, changed);
+    }
+
+    /// Health of `drive`.
+    pub fn health(&self, drive: DriveId) -> Option<DriveHealth> {
+        self.lock().get(&drive).copied()
+    }
+
+    /// Whether `drive` sent a heartbeat recently.
+    pub fn is_online(&self, drive: DriveId) -> bool {
+        self.health(drive)
+            .is_some_and(|health| health.is_online_at(self.offline_after, (self.clock)()))
+    }
+
+    /// Health of every drive.
+    pub fn drives(&self) -> Vec<(DriveId, DriveHealth)> {
+        self.lock()
+            .iter()
+            .map(|(drive, health)| (*drive, *health))
+            .collect()
+    }
+
+    /// Health of the fleet as a whole.
+    pub fn summary(&self) -> FleetSummary {
+        let max = |a: Option<f32>, b: f32| Some(a.map_or(b, |a| a.max(b)));
+        let now = (self.clock)();
+        self.lock()
+            .values()
+            .fold(FleetSummary::default(), |mut summary, health| {
+                summary.drives += 1;
+                summary.online += health.is_online_at(self.offline_after, now) as usize;
+                summary.faulted += !health.errors.is_empty() as usize;
+                if let Some(temperature) = health.temperature {
+                    summary.max_fet_temperature = max(summary.max_fet_temperature, temperature.fet);
+                    summary.max_motor_temperature =
+                        max(summary.max_motor_temperature, temperature.motor);
+                }
+                summary
+            })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<DriveId, DriveHealth>> {
+        self.drives.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Apply `update` to the health of `drive`, returns the new health if
+    /// `update` reported a change.
+    fn update(
+        &self,
+        drive: DriveId,
+        update: impl FnOnce(&mut DriveHealth, bool) -> bool,
+    ) -> Option<DriveHealth> {
+        let mut drives = self.lock();
+        let health = drives.entry(drive).or_default();
+        let online = health.is_online_at(self.offline_after, (self.clock)());
+        update(health, online).then_some(*health)
+    }
+
+    fn notify(&self, drive: DriveId, changed: Option<DriveHealth>) {
+        if let (Some(callback), Some(health)) = (&self.on_change, changed) {
+            callback(drive, &health);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_health() {
+        let changes = Arc::new(Mutex::new(vec![]));
+        let log = changes.clone();
+        let fleet =
+            Arc::new(Fleet::new().on_change(move |drive, _| log.lock().unwrap().push(drive)));
+        let subscriber = fleet.subscriber(0);
+        let heartbeat = |node, error: u8| {
+            Frame::new(Id::new(node, 0x01).unwrap(), &[error, 0, 0, 0, 8, 0, 0, 0]).unwrap()
+        };
+
+        fleet.track(fleet.drive(1, 3));
+        subscriber(&heartbeat(1, 0));
+        subscriber(&heartbeat(1, 0));
+        subscriber(&heartbeat(2, 1));
+        assert_eq!(
+            *changes.lock().unwrap(),
+            [fleet.drive(0, 1), fleet.drive(0, 2)]
+        );
+
+        let summary = fleet.summary();
+        assert_eq!(summary.drives, 3);
+        assert_eq!(summary.online, 2);
+        assert_eq!(summary.faulted, 1);
+        assert!(!fleet.is_online(fleet.drive(1, 3)));
+        assert_eq!(
+            fleet.health(fleet.drive(0, 1)).unwrap().axis_state,
+            Some(AxisState::ClosedLoopControl)
+        );
+    }
+}
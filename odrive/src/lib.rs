@@ -1,16 +1,68 @@
 #![doc = include_str!("../README.md")]
 
+/// Emit a `tracing` event if the `tracing` feature is enabled.
+#[allow(unused_macros)]
+macro_rules! trace_event {
+    ($level:ident, $($arg:tt)+) => {
+        #[cfg(feature = "tracing")]
+        tracing::$level!($($arg)+);
+    };
+}
+
+#[cfg(feature = "actor")]
+pub mod actor;
+#[cfg(feature = "can")]
+pub mod alarm;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(feature = "can")]
+pub mod cache;
+#[cfg(feature = "can")]
 pub mod can;
+#[cfg(feature = "can")]
+pub mod conformance;
+#[cfg(feature = "can")]
+pub mod csv;
+pub mod diagnostics;
+mod error;
 #[cfg(feature = "flat-endpoints")]
 pub mod flat_endpoints;
+#[cfg(feature = "can")]
+pub mod fleet;
+#[cfg(feature = "can")]
+pub mod liveness;
+#[cfg(feature = "mcap")]
+pub mod mcap;
+#[cfg(feature = "can")]
+pub mod metrics;
+#[cfg(feature = "can")]
+pub mod middleware;
+#[cfg(feature = "can")]
+pub mod mock;
+#[cfg(feature = "can")]
+mod pending;
+pub mod protocol;
+#[cfg(feature = "can")]
+pub mod selftest;
+#[cfg(feature = "can")]
+pub mod stats;
+#[cfg(feature = "can")]
+pub mod streamer;
+#[cfg(feature = "can")]
+pub mod transport;
+
+pub use error::{Error, Result};
 
 use bitflags::bitflags;
+use std::fmt;
 
 bitflags! {
     /// Axis error.
     ///
     /// [Reference](https://docs.odriverobotics.com/v/latest/fibre_types/com_odriverobotics_ODrive.html#ODrive.Error)
     #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
     pub struct AxisErrors: u32 {
         const INITIALIZING = 0x1;
         const SYSTEM_LEVEL = 0x2;
@@ -38,10 +90,290 @@ bitflags! {
     }
 }
 
+/// Official name and description of each axis error flag.
+const AXIS_ERRORS: [(AxisErrors, &str, &str); 22] = [
+    (
+        AxisErrors::INITIALIZING,
+        "INITIALIZING",
+        "the system is initializing or reconfiguring",
+    ),
+    (
+        AxisErrors::SYSTEM_LEVEL,
+        "SYSTEM_LEVEL",
+        "unexpected system error such as memory corruption or a frozen thread",
+    ),
+    (
+        AxisErrors::TIMING_ERROR,
+        "TIMING_ERROR",
+        "an internal hard timing requirement was violated",
+    ),
+    (
+        AxisErrors::MISSING_ESTIMATE,
+        "MISSING_ESTIMATE",
+        "the position, velocity or phase estimate was invalid",
+    ),
+    (
+        AxisErrors::BAD_CONFNIG,
+        "BAD_CONFIG",
+        "the configuration is invalid or incomplete",
+    ),
+    (
+        AxisErrors::DRV_FAULT,
+        "DRV_FAULT",
+        "the gate driver chip reported an error",
+    ),
+    (
+        AxisErrors::MISSING_INPUT,
+        "MISSING_INPUT",
+        "no value was provided for the position, velocity or torque input",
+    ),
+    (
+        AxisErrors::DC_BUS_OVER_VOLTAGE,
+        "DC_BUS_OVER_VOLTAGE",
+        "the DC voltage exceeded the configured overvoltage trip level",
+    ),
+    (
+        AxisErrors::DC_BUS_UNDER_VOLTAGE,
+        "DC_BUS_UNDER_VOLTAGE",
+        "the DC voltage fell below the configured undervoltage trip level",
+    ),
+    (
+        AxisErrors::DC_BUS_OVER_CURRENT,
+        "DC_BUS_OVER_CURRENT",
+        "too much DC current was pulled",
+    ),
+    (
+        AxisErrors::DC_BUS_OVER_REGEN_CURRENT,
+        "DC_BUS_OVER_REGEN_CURRENT",
+        "too much DC current was regenerated",
+    ),
+    (
+        AxisErrors::CURRENT_LIMIT_VIOLATION,
+        "CURRENT_LIMIT_VIOLATION",
+        "the motor current exceeded the hard current limit",
+    ),
+    (
+        AxisErrors::MOTOR_OVER_TEMP,
+        "MOTOR_OVER_TEMP",
+        "the motor temperature exceeded the upper limit",
+    ),
+    (
+        AxisErrors::INVERTER_OVER_TEMP,
+        "INVERTER_OVER_TEMP",
+        "the inverter temperature exceeded the upper limit",
+    ),
+    (
+        AxisErrors::VELOCITY_LIMIT_VIOLATION,
+        "VELOCITY_LIMIT_VIOLATION",
+        "the velocity exceeded the configured limit",
+    ),
+    (
+        AxisErrors::POSITION_LIMIT_VIOLATION,
+        "POSITION_LIMIT_VIOLATION",
+        "the position exceeded the configured limits",
+    ),
+    (
+        AxisErrors::WATCHDOG_TIMER_EXPIRED,
+        "WATCHDOG_TIMER_EXPIRED",
+        "the axis watchdog timer expired",
+    ),
+    (
+        AxisErrors::ESTOP_REQUESTED,
+        "ESTOP_REQUESTED",
+        "an emergency stop was requested",
+    ),
+    (
+        AxisErrors::SPINOUT_DETECTED,
+        "SPINOUT_DETECTED",
+        "electrical and mechanical power did not match",
+    ),
+    (
+        AxisErrors::BRAKE_RESISTOR_DISARMED,
+        "BRAKE_RESISTOR_DISARMED",
+        "the brake resistor was disarmed by another error",
+    ),
+    (
+        AxisErrors::THERMISTOR_DISCONNECTED,
+        "THERMISTOR_DISCONNECTED",
+        "the motor thermistor is enabled but disconnected",
+    ),
+    (
+        AxisErrors::CALIBRATION_ERROR,
+        "CALIBRATION_ERROR",
+        "a calibration procedure failed, see the procedure result",
+    ),
+];
+
+impl AxisErrors {
+    /// Official name and a short description of every set flag, such as
+    /// `("DC_BUS_UNDER_VOLTAGE", "the DC voltage fell below ...")`.
+    ///
+    /// Undocumented flags are skipped, see the [`Display`](fmt::Display)
+    /// implementation.
+    pub fn descriptions(&self) -> impl Iterator<Item = (&'static str, &'static str)> + '_ {
+        AXIS_ERRORS
+            .iter()
+            .filter(|(flag, _, _)| self.contains(*flag))
+            .map(|(_, name, description)| (*name, *description))
+    }
+}
+
+/// Official names of the set flags separated by `|`, undocumented flags in
+/// hex, or `none`.
+impl fmt::Display for AxisErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return f.write_str("none");
+        }
+        let mut rest = *self;
+        let mut separator = "";
+        for (flag, name, _) in AXIS_ERRORS {
+            if self.contains(flag) {
+                write!(f, "{separator}{name}")?;
+                rest.remove(flag);
+                separator = " | ";
+            }
+        }
+        if !rest.is_empty() {
+            write!(f, "{separator}{:#x}", rest.bits())?;
+        }
+        Ok(())
+    }
+}
+
+/// Raw bits, the names are looked up on the host from the bitflags.
+#[cfg(feature = "defmt")]
+impl defmt::Format for AxisErrors {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "AxisErrors({=u32:#x})", self.bits());
+    }
+}
+
+bitflags! {
+    /// Motor error of 0.5.x firmware.
+    ///
+    /// [Reference](https://docs.odriverobotics.com/v/0.5.6/fibre_types/com_odriverobotics_ODrive.html#ODrive.Motor.Error)
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+    pub struct MotorErrors: u64 {
+        const PHASE_RESISTANCE_OUT_OF_RANGE = 0x1;
+        const PHASE_INDUCTANCE_OUT_OF_RANGE = 0x2;
+        const DRV_FAULT = 0x8;
+        const CONTROL_DEADLINE_MISSED = 0x10;
+        const MODULATION_MAGNITUDE = 0x80;
+        const CURRENT_SENSE_SATURATION = 0x400;
+        const CURRENT_LIMIT_VIOLATION = 0x1000;
+        const MODULATION_IS_NAN = 0x10000;
+        const MOTOR_THERMISTOR_OVER_TEMP = 0x20000;
+        const FET_THERMISTOR_OVER_TEMP = 0x40000;
+        const TIMER_UPDATE_MISSED = 0x80000;
+        const CURRENT_MEASUREMENT_UNAVAILABLE = 0x100000;
+        const CONTROLLER_FAILED = 0x200000;
+        const I_BUS_OUT_OF_RANGE = 0x400000;
+        const BRAKE_RESISTOR_DISARMED = 0x800000;
+        const SYSTEM_LEVEL = 0x1000000;
+        const BAD_TIMING = 0x2000000;
+        const UNKNOWN_PHASE_ESTIMATE = 0x4000000;
+        const UNKNOWN_PHASE_VEL = 0x8000000;
+        const UNKNOWN_TORQUE = 0x10000000;
+        const UNKNOWN_CURRENT_COMMAND = 0x20000000;
+        const UNKNOWN_CURRENT_MEASUREMENT = 0x40000000;
+        const UNKNOWN_VBUS_VOLTAGE = 0x80000000;
+        const UNKNOWN_VOLTAGE_COMMAND = 0x100000000;
+        const UNKNOWN_GAINS = 0x200000000;
+        const CONTROLLER_INITIALIZING = 0x400000000;
+        const UNBALANCED_PHASES = 0x800000000;
+        const _ = !0; // the source may set any flags
+    }
+
+    /// Encoder error of 0.5.x firmware.
+    ///
+    /// [Reference](https://docs.odriverobotics.com/v/0.5.6/fibre_types/com_odriverobotics_ODrive.html#ODrive.Encoder.Error)
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+    pub struct EncoderErrors: u32 {
+        const UNSTABLE_GAIN = 0x1;
+        const CPR_POLEPAIRS_MISMATCH = 0x2;
+        const NO_RESPONSE = 0x4;
+        const UNSUPPORTED_ENCODER_MODE = 0x8;
+        const ILLEGAL_HALL_STATE = 0x10;
+        const INDEX_NOT_FOUND_YET = 0x20;
+        const ABS_SPI_TIMEOUT = 0x40;
+        const ABS_SPI_COM_FAIL = 0x80;
+        const ABS_SPI_NOT_READY = 0x100;
+        const HALL_NOT_CALIBRATED_YET = 0x200;
+        const _ = !0; // the source may set any flags
+    }
+
+    /// Controller error of 0.5.x firmware.
+    ///
+    /// [Reference](https://docs.odriverobotics.com/v/0.5.6/fibre_types/com_odriverobotics_ODrive.html#ODrive.Controller.Error)
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+    pub struct ControllerErrors: u32 {
+        const OVERSPEED = 0x1;
+        const INVALID_INPUT_MODE = 0x2;
+        const UNSTABLE_GAIN = 0x4;
+        const INVALID_MIRROR_AXIS = 0x8;
+        const INVALID_LOAD_ENCODER = 0x10;
+        const INVALID_ESTIMATE = 0x20;
+        const INVALID_CIRCULAR_RANGE = 0x40;
+        const SPINOUT_DETECTED = 0x80;
+        const _ = !0; // the source may set any flags
+    }
+
+    /// Sensorless estimator error of 0.5.x firmware.
+    ///
+    /// [Reference](https://docs.odriverobotics.com/v/0.5.6/fibre_types/com_odriverobotics_ODrive.html#ODrive.SensorlessEstimator.Error)
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+    pub struct SensorlessErrors: u32 {
+        const UNSTABLE_GAIN = 0x1;
+        const UNKNOWN_CURRENT_MEASUREMENT = 0x2;
+        const _ = !0; // the source may set any flags
+    }
+}
+
+/// `Display` listing the names of set flags like [`AxisErrors`] and
+/// `defmt::Format` for the 0.5.x error words.
+macro_rules! legacy_errors {
+    ($($ty:ident),*) => {$(
+        impl fmt::Display for $ty {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                if self.is_empty() {
+                    return f.write_str("none");
+                }
+                bitflags::parser::to_writer(self, f)
+            }
+        }
+
+        #[cfg(feature = "defmt")]
+        impl defmt::Format for $ty {
+            fn format(&self, f: defmt::Formatter) {
+                defmt::write!(f, "{=str}({:#x})", stringify!($ty), self.bits());
+            }
+        }
+    )*};
+}
+
+legacy_errors!(
+    MotorErrors,
+    EncoderErrors,
+    ControllerErrors,
+    SensorlessErrors
+);
+
 /// Axis state.
 ///
 /// [Reference](https://docs.odriverobotics.com/v/latest/fibre_types/com_odriverobotics_ODrive.html#ODrive.Axis.AxisState)
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(u8)]
 pub enum AxisState {
     /// Will fall through to idle.
@@ -81,10 +413,42 @@ pub enum AxisState {
     HarmonicCalibrationCommutation = 16,
 }
 
+impl TryFrom<u8> for AxisState {
+    type Error = u8;
+
+    /// Returns the value back if it is not a known axis state.
+    fn try_from(value: u8) -> Result<Self, u8> {
+        use AxisState::*;
+
+        Ok(match value {
+            0 => Undefined,
+            1 => Idle,
+            2 => StartupSequence,
+            3 => FullCalibration,
+            4 => MotorCalibration,
+            6 => EncoderIndexSearch,
+            7 => EncoderOffsetCalibration,
+            8 => ClosedLoopControl,
+            9 => LockinSpin,
+            10 => EncoderDirFind,
+            11 => Homing,
+            12 => EncoderHallPolarityCalibration,
+            13 => EncoderHallPhaseCalibration,
+            14 => AnticoggingCalibration,
+            15 => HarmonicCalibration,
+            16 => HarmonicCalibrationCommutation,
+            _ => return Err(value),
+        })
+    }
+}
+
 /// Procedure result.
 ///
 /// [Reference](https://docs.odriverobotics.com/v/latest/fibre_types/com_odriverobotics_ODrive.html#ODrive.ProcedureResult)
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(u8)]
 pub enum ProcedureResult {
     /// The procedure finished without any faults.
@@ -126,10 +490,42 @@ pub enum ProcedureResult {
     NotConverging = 15,
 }
 
+impl TryFrom<u8> for ProcedureResult {
+    type Error = u8;
+
+    /// Returns the value back if it is not a known procedure result.
+    fn try_from(value: u8) -> Result<Self, u8> {
+        use ProcedureResult::*;
+
+        Ok(match value {
+            0 => Success,
+            1 => Busy,
+            2 => Cancelled,
+            3 => Disarmed,
+            4 => NoResponse,
+            5 => PolePairCprMismatch,
+            6 => PhaseResistanceOutOfRange,
+            7 => PhaseInductanceOutOfRange,
+            8 => UnbalancedPhases,
+            9 => InvalidMotorType,
+            10 => IllegalHallState,
+            11 => Timeout,
+            12 => HomingWithoutEndstop,
+            13 => InvalidState,
+            14 => NotCalibrated,
+            15 => NotConverging,
+            _ => return Err(value),
+        })
+    }
+}
+
 /// Control mode.
 ///
 /// [Reference](https://docs.odriverobotics.com/v/latest/fibre_types/com_odriverobotics_ODrive.html#ODrive.Controller.ControlMode)
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(u8)]
 pub enum ControlMode {
     /// This mode is not used internally.
@@ -143,10 +539,30 @@ pub enum ControlMode {
     PositionControl = 3,
 }
 
+impl TryFrom<u8> for ControlMode {
+    type Error = u8;
+
+    /// Returns the value back if it is not a known control mode.
+    fn try_from(value: u8) -> Result<Self, u8> {
+        use ControlMode::*;
+
+        Ok(match value {
+            0 => VoltageControl,
+            1 => TorqueControl,
+            2 => VelocityControl,
+            3 => PositionControl,
+            _ => return Err(value),
+        })
+    }
+}
+
 /// Input mode.
 ///
 /// [Reference](https://docs.odriverobotics.com/v/latest/fibre_types/com_odriverobotics_ODrive.html#ODrive.Controller.InputMode)
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 #[repr(u8)]
 pub enum InputMode {
     /// Disable inputs. Setpoints retainr their last value.
@@ -168,3 +584,211 @@ pub enum InputMode {
     /// Tuning mode.
     Tuning = 8,
 }
+
+impl TryFrom<u8> for InputMode {
+    type Error = u8;
+
+    /// Returns the value back if it is not a known input mode.
+    fn try_from(value: u8) -> Result<Self, u8> {
+        use InputMode::*;
+
+        Ok(match value {
+            0 => Inactive,
+            1 => Passthrough,
+            2 => VelocityRamp,
+            3 => PositionFilter,
+            4 => MixChannels,
+            5 => TrapezoidalTrajectory,
+            6 => TroqueRamp,
+            7 => Mirror,
+            8 => Tuning,
+            _ => return Err(value),
+        })
+    }
+}
+
+/// Conversions between the enums and raw integers as in telemetry or SDO
+/// values, next to their `TryFrom<u8>` implementations.
+macro_rules! raw_enum {
+    ($($ty:ident),* $(,)?) => {$(
+        impl TryFrom<u32> for $ty {
+            type Error = u32;
+
+            /// Returns the value back if it is out of range or unknown.
+            fn try_from(value: u32) -> Result<Self, u32> {
+                u8::try_from(value)
+                    .ok()
+                    .and_then(|raw| Self::try_from(raw).ok())
+                    .ok_or(value)
+            }
+        }
+
+        impl From<$ty> for u8 {
+            fn from(value: $ty) -> u8 {
+                value as u8
+            }
+        }
+
+        impl From<$ty> for u32 {
+            fn from(value: $ty) -> u32 {
+                u32::from(value as u8)
+            }
+        }
+    )*};
+}
+
+raw_enum!(AxisState, ProcedureResult, ControlMode, InputMode);
+
+/// `ALL` variants and their official names, such as `CLOSED_LOOP_CONTROL`.
+macro_rules! enum_names {
+    ($($ty:ident { $($variant:ident => $name:literal,)* })*) => {$(
+        impl $ty {
+            /// Every variant in order of its value.
+            pub const ALL: &[Self] = &[$(Self::$variant),*];
+
+            /// Official name as in the ODrive documentation.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(Self::$variant => $name,)*
+                }
+            }
+        }
+    )*};
+}
+
+enum_names! {
+    AxisState {
+        Undefined => "UNDEFINED",
+        Idle => "IDLE",
+        StartupSequence => "STARTUP_SEQUENCE",
+        FullCalibration => "FULL_CALIBRATION",
+        MotorCalibration => "MOTOR_CALIBRATION",
+        EncoderIndexSearch => "ENCODER_INDEX_SEARCH",
+        EncoderOffsetCalibration => "ENCODER_OFFSET_CALIBRATION",
+        ClosedLoopControl => "CLOSED_LOOP_CONTROL",
+        LockinSpin => "LOCKIN_SPIN",
+        EncoderDirFind => "ENCODER_DIR_FIND",
+        Homing => "HOMING",
+        EncoderHallPolarityCalibration => "ENCODER_HALL_POLARITY_CALIBRATION",
+        EncoderHallPhaseCalibration => "ENCODER_HALL_PHASE_CALIBRATION",
+        AnticoggingCalibration => "ANTICOGGING_CALIBRATION",
+        HarmonicCalibration => "HARMONIC_CALIBRATION",
+        HarmonicCalibrationCommutation => "HARMONIC_CALIBRATION_COMMUTATION",
+    }
+    ProcedureResult {
+        Success => "SUCCESS",
+        Busy => "BUSY",
+        Cancelled => "CANCELLED",
+        Disarmed => "DISARMED",
+        NoResponse => "NO_RESPONSE",
+        PolePairCprMismatch => "POLE_PAIR_CPR_MISMATCH",
+        PhaseResistanceOutOfRange => "PHASE_RESISTANCE_OUT_OF_RANGE",
+        PhaseInductanceOutOfRange => "PHASE_INDUCTANCE_OUT_OF_RANGE",
+        UnbalancedPhases => "UNBALANCED_PHASES",
+        InvalidMotorType => "INVALID_MOTOR_TYPE",
+        IllegalHallState => "ILLEGAL_HALL_STATE",
+        Timeout => "TIMEOUT",
+        HomingWithoutEndstop => "HOMING_WITHOUT_ENDSTOP",
+        InvalidState => "INVALID_STATE",
+        NotCalibrated => "NOT_CALIBRATED",
+        NotConverging => "NOT_CONVERGING",
+    }
+    ControlMode {
+        VoltageControl => "VOLTAGE_CONTROL",
+        TorqueControl => "TORQUE_CONTROL",
+        VelocityControl => "VELOCITY_CONTROL",
+        PositionControl => "POSITION_CONTROL",
+    }
+    InputMode {
+        Inactive => "INACTIVE",
+        Passthrough => "PASSTHROUGH",
+        VelocityRamp => "VELOCITY_RAMP",
+        PositionFilter => "POSITION_FILTER",
+        MixChannels => "MIX_CHANNELS",
+        TrapezoidalTrajectory => "TRAPEZOIDAL_TRAJECTORY",
+        TroqueRamp => "TORQUE_RAMP",
+        Mirror => "MIRROR",
+        Tuning => "TUNING",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitflags::Flags;
+
+    #[test]
+    fn axis_error_descriptions() {
+        let errors = AxisErrors::DC_BUS_UNDER_VOLTAGE | AxisErrors::BAD_CONFNIG;
+        assert_eq!(
+            errors.descriptions().collect::<Vec<_>>(),
+            [
+                ("BAD_CONFIG", "the configuration is invalid or incomplete"),
+                (
+                    "DC_BUS_UNDER_VOLTAGE",
+                    "the DC voltage fell below the configured undervoltage trip level"
+                ),
+            ]
+        );
+        assert_eq!(errors.to_string(), "BAD_CONFIG | DC_BUS_UNDER_VOLTAGE");
+        assert_eq!(
+            AxisErrors::from_bits_retain(0x80 | 0x1).to_string(),
+            "INITIALIZING | 0x80"
+        );
+        assert_eq!(AxisErrors::from_bits_retain(0x80).to_string(), "0x80");
+        assert_eq!(AxisErrors::empty().to_string(), "none");
+
+        // every defined flag is documented
+        for flag in AxisErrors::FLAGS.iter().filter(|flag| flag.is_named()) {
+            assert_eq!(flag.value().descriptions().count(), 1, "{}", flag.name());
+        }
+    }
+
+    #[test]
+    fn enum_names() {
+        assert_eq!(AxisState::ALL.len(), 16);
+        assert_eq!(AxisState::ALL[7], AxisState::ClosedLoopControl);
+        assert_eq!(AxisState::ClosedLoopControl.name(), "CLOSED_LOOP_CONTROL");
+        assert_eq!(InputMode::TroqueRamp.name(), "TORQUE_RAMP");
+        assert_eq!(
+            ProcedureResult::PolePairCprMismatch.name(),
+            "POLE_PAIR_CPR_MISMATCH"
+        );
+
+        // in order of value and complete
+        for (index, mode) in ControlMode::ALL.iter().enumerate() {
+            assert_eq!(usize::from(u8::from(*mode)), index);
+        }
+        let count = (0..=u8::MAX)
+            .filter(|raw| InputMode::try_from(*raw).is_ok())
+            .count();
+        assert_eq!(InputMode::ALL.len(), count);
+    }
+
+    #[test]
+    fn legacy_errors() {
+        let errors = MotorErrors::from_bits_retain(0x8 | 0x800000000 | 0x4);
+        assert_eq!(errors.to_string(), "DRV_FAULT | UNBALANCED_PHASES | 0x4");
+        assert_eq!(EncoderErrors::empty().to_string(), "none");
+        assert_eq!(
+            ControllerErrors::from_bits_retain(0x80),
+            ControllerErrors::SPINOUT_DETECTED
+        );
+        assert!(SensorlessErrors::from_bits_retain(3).contains(SensorlessErrors::UNSTABLE_GAIN));
+    }
+
+    #[test]
+    fn raw_enums() {
+        assert_eq!(AxisState::try_from(8u32), Ok(AxisState::ClosedLoopControl));
+        assert_eq!(AxisState::try_from(5u32), Err(5));
+        assert_eq!(ControlMode::try_from(0x103u32), Err(0x103));
+        assert_eq!(u32::from(InputMode::Tuning), 8);
+        assert_eq!(u8::from(ProcedureResult::NotConverging), 15);
+
+        for raw in 0..=u8::MAX {
+            if let Ok(state) = AxisState::try_from(raw) {
+                assert_eq!(u8::from(state), raw);
+            }
+        }
+    }
+}
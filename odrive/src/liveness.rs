@@ -0,0 +1,211 @@
+//! # Heartbeat liveness
+//!
+//! [`Liveness`] watches the heartbeats of the nodes on a bus. A node is
+//! reported [`LivenessEvent::Offline`] if no heartbeat was received within
+//! the window, and [`LivenessEvent::Online`] again with its next heartbeat.
+//!
+//! Heartbeats are fed by a frame subscriber while [`Liveness::run`] checks
+//! for missing heartbeats in a separate task:
+//!
+//! ```no_run
+//! # tokio_test::block_on(async {
+//! use odrive::{can::Bus, liveness::Liveness, transport::TokioTimer};
+//! use std::{sync::Arc, time::Duration};
+//!
+//! let liveness = Arc::new(
+//!     Liveness::new(Duration::from_millis(300)).on_event(|event| eprintln!("{event:?}")),
+//! );
+//! let socket = socketcan::tokio::CanSocket::open("can0").unwrap();
+//! let bus = Bus::new(socket).on_frame(liveness.subscriber());
+//!
+//! tokio::spawn({
+//!     let liveness = liveness.clone();
+//!     async move { liveness.run::<TokioTimer>().await }
+//! });
+//! bus.axis(1).unwrap().listen().await.unwrap();
+//! # });
+//! ```
+
+use crate::protocol::{Frame, command};
+use crate::transport::Timer;
+use cansimple::Id;
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+type EventCallback = Arc<dyn Fn(LivenessEvent) + Send + Sync>;
+
+/// A node started or stopped sending heartbeats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LivenessEvent {
+    Online(u8),
+    Offline(u8),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Node {
+    last_heartbeat: Instant,
+    online: bool,
+}
+
+/// Tracks which nodes on a bus are sending heartbeats.
+pub struct Liveness {
+    window: Duration,
+    nodes: Mutex<BTreeMap<u8, Node>>,
+    on_event: Option<EventCallback>,
+    clock: fn() -> Instant,
+}
+
+impl Liveness {
+    /// Nodes are offline after `window` without a heartbeat.
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            nodes: Mutex::new(BTreeMap::new()),
+            on_event: None,
+            clock: Instant::now,
+        }
+    }
+
+    /// Timestamp heartbeats with `clock`, such as [`Timer::now`] of the
+    /// timer passed to [`Liveness::run`].
+    ///
+    /// Defaults to the system clock.
+    pub fn with_clock(mut self, clock: fn() -> Instant) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Call `callback` whenever a node goes online or offline.
+    pub fn on_event(mut self, callback: impl Fn(LivenessEvent) + Send + Sync + 'static) -> Self {
+        self.on_event = Some(Arc::new(callback));
+        self
+    }
+
+    /// Frame subscriber feeding the heartbeats.
+    ///
+    /// To be passed to [`Bus::on_frame`](crate::can::Bus::on_frame) or
+    /// [`ODrive::on_frame`](crate::can::ODrive::on_frame).
+    pub fn subscriber(self: &Arc<Self>) -> impl Fn(&Frame) + Send + Sync + 'static {
+        let liveness = Arc::downgrade(self);
+        move |frame| {
+            if let Some(liveness) = liveness.upgrade() {
+                liveness.record(frame);
+            }
+        }
+    }
+
+    /// Record a received frame, heartbeats mark their node as online.
+    pub fn record(&self, frame: &Frame) {
+        let Ok(id) = Id::try_from(frame.id()) else {
+            return;
+        };
+        if id.command() != command::HEARTBEAT || frame.is_remote_frame() {
+            return;
+        }
+
+        let previous = self.lock().insert(
+            id.node(),
+            Node {
+                last_heartbeat: (self.clock)(),
+                online: true,
+            },
+        );
+        if !previous.is_some_and(|node| node.online) {
+            self.notify(LivenessEvent::Online(id.node()));
+        }
+    }
+
+    /// Report nodes whose last heartbeat is older than the window.
+    pub fn check(&self) {
+        let now = (self.clock)();
+        let offline: Vec<_> = self
+            .lock()
+            .iter_mut()
+            .filter(|(_, node)| {
+                node.online && now.saturating_duration_since(node.last_heartbeat) > self.window
+            })
+            .map(|(id, node)| {
+                node.online = false;
+                *id
+            })
+            .collect();
+        for node in offline {
+            self.notify(LivenessEvent::Offline(node));
+        }
+    }
+
+    /// Call [`Liveness::check`] periodically, never returns.
+    pub async fn run<M: Timer>(&self) {
+        loop {
+            M::sleep(self.window / 4).await;
+            self.check();
+        }
+    }
+
+    /// Whether `node` is sending heartbeats.
+    pub fn is_online(&self, node: u8) -> bool {
+        self.lock().get(&node).is_some_and(|node| node.online)
+    }
+
+    /// Nodes seen so far, with whether they are online.
+    pub fn nodes(&self) -> Vec<(u8, bool)> {
+        self.lock()
+            .iter()
+            .map(|(id, node)| (*id, node.online))
+            .collect()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<u8, Node>> {
+        self.nodes.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn notify(&self, event: LivenessEvent) {
+        trace_event!(info, ?event, "liveness changed");
+        if let Some(callback) = &self.on_event {
+            callback(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(start_paused = true)]
+    async fn online_offline() {
+        use crate::transport::TokioTimer;
+
+        let events = Arc::new(Mutex::new(vec![]));
+        let log = events.clone();
+        let liveness = Arc::new(
+            Liveness::new(Duration::from_millis(20))
+                .with_clock(TokioTimer::now)
+                .on_event(move |event| log.lock().unwrap().push(event)),
+        );
+        let subscriber = liveness.subscriber();
+        let heartbeat = Frame::new(Id::new(4, command::HEARTBEAT).unwrap(), &[0; 8]).unwrap();
+
+        subscriber(&heartbeat);
+        subscriber(&heartbeat);
+        liveness.check();
+        assert!(liveness.is_online(4));
+
+        tokio::time::advance(Duration::from_millis(30)).await;
+        liveness.check();
+        assert!(!liveness.is_online(4));
+        subscriber(&heartbeat);
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                LivenessEvent::Online(4),
+                LivenessEvent::Offline(4),
+                LivenessEvent::Online(4)
+            ]
+        );
+    }
+}
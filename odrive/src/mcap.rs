@@ -0,0 +1,310 @@
+//! # MCAP recording
+//!
+//! [`McapRecorder`] writes decoded telemetry into an
+//! [MCAP](https://mcap.dev) file which can be opened in Foxglove,
+//! PlotJuggler and other robotics tools. Every message type of every node
+//! is a channel with a topic such as `/odrive/1/encoder_estimates`, the
+//! messages are JSON objects of their fields, see
+//! [`TelemetryMessage::fields`].
+//!
+//! ```no_run
+//! # tokio_test::block_on(async {
+//! use odrive::{can::ODrive, mcap::McapRecorder};
+//! use std::{fs::File, io::BufWriter, sync::Arc};
+//!
+//! let file = BufWriter::new(File::create("session.mcap").unwrap());
+//! let recorder = Arc::new(McapRecorder::new(file).unwrap());
+//! let socket = socketcan::tokio::CanSocket::open("can0").unwrap();
+//! let odrive = ODrive::new(socket, 1)
+//!     .unwrap()
+//!     .on_frame(recorder.subscriber());
+//!
+//! let _ = tokio::time::timeout(std::time::Duration::from_secs(10), odrive.listen()).await;
+//! recorder.finish().unwrap();
+//! # });
+//! ```
+//!
+//! The file is written without chunks or an index, readers scan it from the
+//! start. It is only valid once [`McapRecorder::finish`] was called.
+
+use crate::protocol::{Frame, TelemetryMessage};
+use cansimple::Id;
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    sync::{Arc, Mutex, PoisonError},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const MAGIC: &[u8] = b"\x89MCAP0\r\n";
+
+mod op {
+    pub const HEADER: u8 = 0x01;
+    pub const FOOTER: u8 = 0x02;
+    pub const SCHEMA: u8 = 0x03;
+    pub const CHANNEL: u8 = 0x04;
+    pub const MESSAGE: u8 = 0x05;
+    pub const DATA_END: u8 = 0x0f;
+}
+
+/// Record content in the MCAP serialization.
+#[derive(Default)]
+struct Record(Vec<u8>);
+
+impl Record {
+    fn u16(mut self, value: u16) -> Self {
+        self.0.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    fn u32(mut self, value: u32) -> Self {
+        self.0.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    fn u64(mut self, value: u64) -> Self {
+        self.0.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    /// Length prefixed bytes.
+    fn bytes(self, value: &[u8]) -> Self {
+        let mut record = self.u32(value.len() as u32);
+        record.0.extend_from_slice(value);
+        record
+    }
+
+    fn string(self, value: &str) -> Self {
+        self.bytes(value.as_bytes())
+    }
+
+    /// Bytes without length prefix, only valid as the last field.
+    fn rest(mut self, value: &[u8]) -> Self {
+        self.0.extend_from_slice(value);
+        self
+    }
+}
+
+struct Writer<W> {
+    out: W,
+    schemas: HashMap<&'static str, u16>,
+    channels: HashMap<(u8, &'static str), u16>,
+    sequence: u32,
+    finished: bool,
+}
+
+impl<W: Write> Writer<W> {
+    fn write(&mut self, op: u8, record: Record) -> io::Result<()> {
+        self.out.write_all(&[op])?;
+        self.out.write_all(&(record.0.len() as u64).to_le_bytes())?;
+        self.out.write_all(&record.0)
+    }
+
+    /// Schema of the messages called `name`, written on first use.
+    fn schema(&mut self, message: &TelemetryMessage) -> io::Result<u16> {
+        let name = message.name();
+        if let Some(id) = self.schemas.get(name) {
+            return Ok(*id);
+        }
+
+        let properties: serde_json::Map<_, _> = message
+            .fields()
+            .into_iter()
+            .map(|(field, _)| (field.into(), serde_json::json!({ "type": "number" })))
+            .collect();
+        let schema = serde_json::json!({ "type": "object", "properties": properties });
+
+        let id = self.schemas.len() as u16 + 1;
+        self.write(
+            op::SCHEMA,
+            Record::default()
+                .u16(id)
+                .string(&format!("odrive.{name}"))
+                .string("jsonschema")
+                .bytes(schema.to_string().as_bytes()),
+        )?;
+        self.schemas.insert(name, id);
+        Ok(id)
+    }
+
+    /// Channel of the messages of `node`, written on first use.
+    fn channel(&mut self, node: u8, message: &TelemetryMessage) -> io::Result<u16> {
+        let key = (node, message.name());
+        if let Some(id) = self.channels.get(&key) {
+            return Ok(*id);
+        }
+
+        let schema = self.schema(message)?;
+        let id = self.channels.len() as u16;
+        self.write(
+            op::CHANNEL,
+            Record::default()
+                .u16(id)
+                .u16(schema)
+                .string(&format!("/odrive/{node}/{}", key.1))
+                .string("json")
+                // no metadata
+                .u32(0),
+        )?;
+        self.channels.insert(key, id);
+        Ok(id)
+    }
+}
+
+/// Records telemetry into an MCAP file.
+pub struct McapRecorder<W> {
+    writer: Mutex<Writer<W>>,
+}
+
+impl<W: Write + Send> McapRecorder<W> {
+    /// Start an MCAP file in `out`.
+    pub fn new(mut out: W) -> io::Result<Self> {
+        out.write_all(MAGIC)?;
+        let mut writer = Writer {
+            out,
+            schemas: HashMap::new(),
+            channels: HashMap::new(),
+            sequence: 0,
+            finished: false,
+        };
+        writer.write(
+            op::HEADER,
+            Record::default()
+                .string("")
+                .string(concat!("odrive-rust ", env!("CARGO_PKG_VERSION"))),
+        )?;
+        Ok(Self {
+            writer: Mutex::new(writer),
+        })
+    }
+
+    /// Record `message` of `node` received at `time`.
+    ///
+    /// Fails if the recording has been finished.
+    pub fn record(&self, node: u8, message: &TelemetryMessage, time: SystemTime) -> io::Result<()> {
+        let mut writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        if writer.finished {
+            return Err(io::Error::other("recording finished"));
+        }
+
+        let channel = writer.channel(node, message)?;
+        let data: serde_json::Map<_, _> = message
+            .fields()
+            .into_iter()
+            .map(|(field, value)| (field.into(), value.into()))
+            .collect();
+        let time = time
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos() as u64;
+        let sequence = writer.sequence;
+        writer.sequence = sequence.wrapping_add(1);
+        writer.write(
+            op::MESSAGE,
+            Record::default()
+                .u16(channel)
+                .u32(sequence)
+                .u64(time)
+                .u64(time)
+                .rest(serde_json::Value::Object(data).to_string().as_bytes()),
+        )
+    }
+
+    /// Record `frame` received now if it is a telemetry message.
+    pub fn record_frame(&self, frame: &Frame) -> io::Result<()> {
+        let embedded_can::Id::Standard(id) = frame.id() else {
+            return Ok(());
+        };
+        match TelemetryMessage::decode(frame) {
+            Some(Ok(message)) => self.record(Id::from(id).node(), &message, SystemTime::now()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Frame subscriber recording the received telemetry.
+    ///
+    /// To be passed to [`Bus::on_frame`](crate::can::Bus::on_frame) or
+    /// [`ODrive::on_frame`](crate::can::ODrive::on_frame). Write errors are
+    /// ignored.
+    pub fn subscriber(self: &Arc<Self>) -> impl Fn(&Frame) + Send + Sync + 'static
+    where
+        W: 'static,
+    {
+        let recorder = Arc::downgrade(self);
+        move |frame| {
+            if let Some(recorder) = recorder.upgrade() {
+                let _ = recorder.record_frame(frame);
+            }
+        }
+    }
+
+    /// Complete the file, later messages are not recorded.
+    pub fn finish(&self) -> io::Result<()> {
+        let mut writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        if writer.finished {
+            return Ok(());
+        }
+        writer.finished = true;
+
+        // no CRCs and no summary section
+        writer.write(op::DATA_END, Record::default().u32(0))?;
+        writer.write(op::FOOTER, Record::default().u64(0).u64(0).u32(0))?;
+        writer.out.write_all(MAGIC)?;
+        writer.out.flush()
+    }
+
+    /// The underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+            .out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::Temperature;
+
+    #[test]
+    fn records() {
+        let recorder = McapRecorder::new(Vec::new()).unwrap();
+        let temperature = TelemetryMessage::Temperature(Temperature {
+            fet: 40.0,
+            motor: 30.0,
+        });
+        recorder.record(1, &temperature, UNIX_EPOCH).unwrap();
+        recorder.record(1, &temperature, UNIX_EPOCH).unwrap();
+        recorder.record(2, &temperature, UNIX_EPOCH).unwrap();
+        recorder.finish().unwrap();
+        assert!(recorder.record(1, &temperature, UNIX_EPOCH).is_err());
+
+        let file = recorder.into_inner();
+        assert!(file.starts_with(MAGIC) && file.ends_with(MAGIC));
+        let mut records = &file[MAGIC.len()..file.len() - MAGIC.len()];
+        let mut ops = vec![];
+        while !records.is_empty() {
+            let len = u64::from_le_bytes(records[1..9].try_into().unwrap()) as usize;
+            ops.push(records[0]);
+            if records[0] == op::MESSAGE {
+                assert_eq!(&records[9 + 22..9 + len], br#"{"fet":40.0,"motor":30.0}"#);
+            }
+            records = &records[9 + len..];
+        }
+        assert_eq!(
+            ops,
+            [
+                op::HEADER,
+                op::SCHEMA,
+                op::CHANNEL,
+                op::MESSAGE,
+                op::MESSAGE,
+                op::CHANNEL,
+                op::MESSAGE,
+                op::DATA_END,
+                op::FOOTER,
+            ]
+        );
+    }
+}
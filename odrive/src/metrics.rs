@@ -0,0 +1,325 @@
+//! # Prometheus metrics
+//!
+//! [`Metrics`] collects the bus voltage, temperatures, errors and axis state
+//! of every node from the received telemetry, together with the
+//! [`Stats`] of the drivers, and renders them in the Prometheus text
+//! exposition format. Every metric is labelled with the node ID.
+//!
+//! ```no_run
+//! # tokio_test::block_on(async {
+//! use odrive::{can::Bus, metrics::Metrics};
+//! use std::sync::Arc;
+//!
+//! let metrics = Arc::new(Metrics::new());
+//! let socket = socketcan::tokio::CanSocket::open("can0").unwrap();
+//! let bus = Bus::new(socket).on_frame(metrics.subscriber());
+//! let odrive = bus.axis(1).unwrap();
+//! metrics.add_stats(1, {
+//!     let odrive = odrive.clone();
+//!     move || odrive.stats()
+//! });
+//!
+//! std::thread::spawn({
+//!     let metrics = metrics.clone();
+//!     move || metrics.serve("0.0.0.0:9100")
+//! });
+//! odrive.listen().await.unwrap();
+//! # });
+//! ```
+
+use crate::protocol::{Frame, TelemetryMessage};
+use crate::stats::Stats;
+use cansimple::Id;
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+/// Time a scrape has to send its request and to receive the metrics.
+pub const SCRAPE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Longest request head read before answering.
+const MAX_REQUEST_HEAD: u64 = 8 * 1024;
+
+type StatsSource = Box<dyn Fn() -> Stats + Send + Sync>;
+type Counter = fn(&Stats) -> u64;
+
+/// Gauges as name, help and field of a telemetry message.
+const GAUGES: [(&str, &str, &str, &str); 6] = [
+    (
+        "odrive_bus_voltage_volts",
+        "Bus voltage.",
+        "bus_voltage_current",
+        "voltage",
+    ),
+    (
+        "odrive_bus_current_amps",
+        "Bus current.",
+        "bus_voltage_current",
+        "current",
+    ),
+    (
+        "odrive_fet_temperature_celsius",
+        "FET temperature.",
+        "temperature",
+        "fet",
+    ),
+    (
+        "odrive_motor_temperature_celsius",
+        "Motor temperature.",
+        "temperature",
+        "motor",
+    ),
+    (
+        "odrive_axis_error",
+        "Active axis error flags.",
+        "heartbeat",
+        "axis_error",
+    ),
+    (
+        "odrive_axis_state",
+        "Axis state.",
+        "heartbeat",
+        "axis_state",
+    ),
+];
+
+/// Counters as name, help and value of the [`Stats`].
+const COUNTERS: [(&str, &str, Counter); 5] = [
+    ("odrive_frames_sent_total", "Frames sent.", |stats| {
+        stats.frames_sent
+    }),
+    (
+        "odrive_frames_received_total",
+        "Frames received.",
+        |stats| stats.frames_received,
+    ),
+    (
+        "odrive_timeouts_total",
+        "Requests that timed out.",
+        |stats| stats.timeouts,
+    ),
+    (
+        "odrive_retries_total",
+        "Requests sent again after a timeout.",
+        |stats| stats.retries,
+    ),
+    (
+        "odrive_decode_failures_total",
+        "Responses that failed to decode.",
+        |stats| stats.decode_failures,
+    ),
+];
+
+/// Metrics of the nodes on a bus.
+#[derive(Default)]
+pub struct Metrics {
+    gauges: Mutex<BTreeMap<(&'static str, u8), f64>>,
+    stats: Mutex<Vec<(u8, StatsSource)>>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Frame subscriber feeding the telemetry gauges.
+    ///
+    /// To be passed to [`Bus::on_frame`](crate::can::Bus::on_frame) or
+    /// [`ODrive::on_frame`](crate::can::ODrive::on_frame).
+    pub fn subscriber(self: &Arc<Self>) -> impl Fn(&Frame) + Send + Sync + 'static {
+        let metrics = Arc::downgrade(self);
+        move |frame| {
+            if let Some(metrics) = metrics.upgrade() {
+                metrics.record(frame);
+            }
+        }
+    }
+
+    /// Update the gauges of the node `frame` belongs to.
+    pub fn record(&self, frame: &Frame) {
+        let embedded_can::Id::Standard(id) = frame.id() else {
+            return;
+        };
+        let Some(Ok(message)) = TelemetryMessage::decode(frame) else {
+            return;
+        };
+
+        let node = Id::from(id).node();
+        let mut gauges = self.lock_gauges();
+        for (field, value) in message.fields() {
+            if let Some((name, ..)) = GAUGES.iter().find(|(.., message_name, gauge)| {
+                *message_name == message.name() && *gauge == field
+            }) {
+                gauges.insert((name, node), value);
+            }
+        }
+    }
+
+    /// Export the counters and request latencies returned by `stats`, such
+    /// as [`ODrive::stats`](crate::can::ODrive::stats), for `node`.
+    pub fn add_stats(&self, node: u8, stats: impl Fn() -> Stats + Send + Sync + 'static) {
+        self.stats
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push((node, Box::new(stats)));
+    }
+
+    /// Metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let gauges = self.lock_gauges().clone();
+        for (name, help, ..) in GAUGES {
+            family(&mut out, name, help, "gauge");
+            for ((_, node), value) in gauges.range((name, 0)..=(name, u8::MAX)) {
+                let _ = writeln!(out, "{name}{{node=\"{node}\"}} {value}");
+            }
+        }
+
+        let stats: Vec<_> = self
+            .stats
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|(node, stats)| (*node, stats()))
+            .collect();
+        for (name, help, value) in COUNTERS {
+            family(&mut out, name, help, "counter");
+            for (node, stats) in &stats {
+                let _ = writeln!(out, "{name}{{node=\"{node}\"}} {}", value(stats));
+            }
+        }
+
+        let name = "odrive_request_latency_seconds";
+        family(&mut out, name, "Round trip time of requests.", "summary");
+        for (node, stats) in &stats {
+            for (command, latency) in &stats.latency {
+                let labels = format!("node=\"{node}\",command=\"{command:#04x}\"");
+                for (quantile, percent) in [("0.5", 50.0), ("0.9", 90.0), ("0.99", 99.0)] {
+                    let value = latency.percentile(percent).as_secs_f64();
+                    let _ = writeln!(out, "{name}{{{labels},quantile=\"{quantile}\"}} {value}");
+                }
+                let sum = latency.total().as_secs_f64();
+                let _ = writeln!(out, "{name}_sum{{{labels}}} {sum}");
+                let _ = writeln!(out, "{name}_count{{{labels}}} {}", latency.count);
+            }
+        }
+        out
+    }
+
+    /// Serve [`Metrics::render`] over HTTP on `addr`, never returns unless
+    /// binding fails.
+    ///
+    /// Connections are handled one at a time, every request is answered
+    /// with the metrics whatever its path. A client that stalls holds up
+    /// the others for at most [`SCRAPE_TIMEOUT`].
+    pub fn serve(&self, addr: impl ToSocketAddrs) -> io::Result<()> {
+        self.serve_on(TcpListener::bind(addr)?)
+    }
+
+    /// Like [`Self::serve`] on a bound `listener`, for example one bound to
+    /// port 0.
+    pub fn serve_on(&self, listener: TcpListener) -> io::Result<()> {
+        for stream in listener.incoming().flatten() {
+            let _ = self.answer(stream);
+        }
+        Ok(())
+    }
+
+    fn answer(&self, mut stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(SCRAPE_TIMEOUT))?;
+        stream.set_write_timeout(Some(SCRAPE_TIMEOUT))?;
+
+        // skip the request up to the empty line ending its head
+        let mut reader = BufReader::new((&stream).take(MAX_REQUEST_HEAD));
+        let mut line = String::new();
+        while reader.read_line(&mut line).is_ok_and(|read| read > 2) {
+            line.clear();
+        }
+
+        let body = self.render();
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\n\
+             Content-Type: text/plain; version=0.0.4\r\n\
+             Content-Length: {}\r\n\
+             Connection: close\r\n\r\n{body}",
+            body.len()
+        )
+    }
+
+    fn lock_gauges(&self) -> MutexGuard<'_, BTreeMap<(&'static str, u8), f64>> {
+        self.gauges.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+fn family(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders() {
+        let metrics = Metrics::new();
+        let mut data = [0; 8];
+        data[..4].copy_from_slice(&24.5f32.to_le_bytes());
+        metrics.record(&Frame::new(Id::new(3, 0x17).unwrap(), &data).unwrap());
+        metrics.add_stats(3, || {
+            let mut stats = Stats {
+                retries: 2,
+                ..Stats::default()
+            };
+            stats.record_latency(0x14, Duration::from_millis(2));
+            stats
+        });
+
+        let text = metrics.render();
+        assert!(text.contains("# TYPE odrive_bus_voltage_volts gauge\n"));
+        assert!(text.contains("odrive_bus_voltage_volts{node=\"3\"} 24.5\n"));
+        assert!(text.contains("odrive_bus_current_amps{node=\"3\"} 0\n"));
+        assert!(!text.contains("odrive_fet_temperature_celsius{"));
+        assert!(text.contains("odrive_retries_total{node=\"3\"} 2\n"));
+        assert!(
+            text.contains("odrive_request_latency_seconds_count{node=\"3\",command=\"0x14\"} 1\n")
+        );
+        assert!(text.contains(
+            "odrive_request_latency_seconds{node=\"3\",command=\"0x14\",quantile=\"0.99\"} 0.002\n"
+        ));
+    }
+
+    #[test]
+    fn serves() {
+        let metrics = Arc::new(Metrics::new());
+        let mut data = [0; 8];
+        data[..4].copy_from_slice(&24.5f32.to_le_bytes());
+        metrics.record(&Frame::new(Id::new(3, 0x17).unwrap(), &data).unwrap());
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn({
+            let metrics = metrics.clone();
+            move || metrics.serve_on(listener)
+        });
+
+        for _ in 0..2 {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream
+                .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+                .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            let (head, body) = response.split_once("\r\n\r\n").unwrap();
+            assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+            assert!(head.contains(&format!("Content-Length: {}", body.len())));
+            assert!(body.contains("odrive_bus_voltage_volts{node=\"3\"} 24.5\n"));
+        }
+    }
+}
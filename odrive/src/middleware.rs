@@ -0,0 +1,136 @@
+//! # Frame middleware
+//!
+//! [`Middleware`] sees every frame a driver writes to or reads from its
+//! transport, before anything else does. It can log frames, modify them or
+//! drop them, for example to inject faults in tests or to limit the rate of
+//! setpoints, without wrapping the transport.
+//!
+//! ```no_run
+//! use odrive::can::ODrive;
+//! use odrive::middleware::Middleware;
+//! use odrive::protocol::Frame;
+//!
+//! struct Log;
+//!
+//! impl Middleware for Log {
+//!     fn outgoing(&self, frame: Frame) -> Option<Frame> {
+//!         println!("tx {:?}", frame.id());
+//!         Some(frame)
+//!     }
+//! }
+//!
+//! let socket = socketcan::tokio::CanSocket::open("can0").unwrap();
+//! let odrive = ODrive::new(socket, 1).unwrap().with_middleware(Log);
+//! ```
+//!
+//! Middleware is shared by every driver using the same transport, so it
+//! sees the frames of all drivers of a [`Bus`](crate::can::Bus). It runs in
+//! the order it was added, outgoing and incoming alike.
+//!
+//! - [`candump::CandumpLog`] logs all traffic in the `candump -L` format.
+//! - [`blackbox::Blackbox`] keeps the last frames for post-mortem analysis.
+//! - [`session::SessionRecorder`] records whole sessions for replay.
+
+pub mod blackbox;
+pub mod candump;
+pub mod session;
+
+use crate::protocol::Frame;
+use std::sync::{Arc, PoisonError, RwLock};
+
+/// Observes, modifies or drops frames, see the [module](self)
+/// documentation.
+pub trait Middleware: Send + Sync {
+    /// Called for every frame before it is written.
+    ///
+    /// Dropped frames are not written, requests sent in them time out.
+    fn outgoing(&self, frame: Frame) -> Option<Frame> {
+        Some(frame)
+    }
+
+    /// Called for every frame after it was read.
+    ///
+    /// Dropped frames are treated as if they were never received.
+    fn incoming(&self, frame: Frame) -> Option<Frame> {
+        Some(frame)
+    }
+}
+
+/// Shared middleware, so it can still be inspected after it was added.
+impl<M: Middleware + ?Sized> Middleware for Arc<M> {
+    fn outgoing(&self, frame: Frame) -> Option<Frame> {
+        (**self).outgoing(frame)
+    }
+
+    fn incoming(&self, frame: Frame) -> Option<Frame> {
+        (**self).incoming(frame)
+    }
+}
+
+/// Middleware of a transport, in the order it was added.
+#[derive(Default)]
+pub(crate) struct Chain {
+    list: RwLock<Vec<Arc<dyn Middleware>>>,
+}
+
+impl Chain {
+    pub(crate) fn add(&self, middleware: Arc<dyn Middleware>) {
+        self.list
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(middleware);
+    }
+
+    pub(crate) fn outgoing(&self, frame: Frame) -> Option<Frame> {
+        self.apply(frame, |middleware, frame| middleware.outgoing(frame))
+    }
+
+    pub(crate) fn incoming(&self, frame: Frame) -> Option<Frame> {
+        self.apply(frame, |middleware, frame| middleware.incoming(frame))
+    }
+
+    fn apply(
+        &self,
+        frame: Frame,
+        hook: impl Fn(&dyn Middleware, Frame) -> Option<Frame>,
+    ) -> Option<Frame> {
+        // middleware may add middleware, so do not hold the lock while calling it
+        let list = self
+            .list
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        list.iter()
+            .try_fold(frame, |frame, middleware| hook(middleware.as_ref(), frame))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cansimple::Id;
+
+    struct DropNode(u8);
+
+    impl Middleware for DropNode {
+        fn incoming(&self, frame: Frame) -> Option<Frame> {
+            let Ok(id) = Id::try_from(frame.id()) else {
+                return Some(frame);
+            };
+            (id.node() != self.0).then_some(frame)
+        }
+    }
+
+    #[test]
+    fn chain() {
+        let chain = Chain::default();
+        chain.add(Arc::new(DropNode(2)));
+        chain.add(Arc::new(DropNode(3)));
+        let frame = |node| Frame::new(Id::new(node, 0x01).unwrap(), &[0; 8]).unwrap();
+
+        assert!(chain.incoming(frame(1)).is_some());
+        assert!(chain.incoming(frame(2)).is_none());
+        assert!(chain.incoming(frame(3)).is_none());
+        assert!(chain.outgoing(frame(2)).is_some());
+    }
+}
@@ -0,0 +1,158 @@
+//! # Blackbox
+//!
+//! [`Blackbox`] keeps the last frames written and read in a ring buffer, for
+//! post-mortem analysis of intermittent bus problems. Add it in an
+//! [`Arc`](std::sync::Arc) to dump the buffer after a request failed:
+//!
+//! ```no_run
+//! # tokio_test::block_on(async {
+//! use odrive::{can::ODrive, middleware::blackbox::Blackbox};
+//! use std::sync::Arc;
+//!
+//! let blackbox = Arc::new(Blackbox::new(64));
+//! let socket = socketcan::tokio::CanSocket::open("can0").unwrap();
+//! let odrive = ODrive::new(socket, 1)
+//!     .unwrap()
+//!     .with_middleware(blackbox.clone());
+//!
+//! if let Err(err) = odrive.get_iq().await {
+//!     eprintln!("{err}, last frames:\n{}", blackbox.dump_string());
+//! }
+//! # });
+//! ```
+
+use super::{Middleware, candump::format_frame};
+use crate::protocol::Frame;
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{Mutex, PoisonError},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Whether a frame was written or read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Tx,
+    Rx,
+}
+
+/// Frame in a [`Blackbox`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Entry {
+    pub direction: Direction,
+    pub frame: Frame,
+    pub time: SystemTime,
+}
+
+impl fmt::Display for Entry {
+    /// Formats like `candump -L` with the direction instead of the
+    /// interface.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let time = self.time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let direction = match self.direction {
+            Direction::Tx => "tx",
+            Direction::Rx => "rx",
+        };
+        write!(
+            f,
+            "({}.{:06}) {direction} {}",
+            time.as_secs(),
+            time.subsec_micros(),
+            format_frame(&self.frame)
+        )
+    }
+}
+
+/// Middleware keeping the last frames in a ring buffer.
+pub struct Blackbox {
+    entries: Mutex<VecDeque<Entry>>,
+    capacity: usize,
+}
+
+impl Blackbox {
+    /// Keep the last `capacity` frames.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity,
+        }
+    }
+
+    fn push(&self, direction: Direction, frame: Frame) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        if entries.len() == self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(Entry {
+            direction,
+            frame,
+            time: SystemTime::now(),
+        });
+    }
+
+    /// Buffered frames, oldest first.
+    pub fn dump(&self) -> Vec<Entry> {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .copied()
+            .collect()
+    }
+
+    /// Buffered frames, one line each, see [`Entry`].
+    pub fn dump_string(&self) -> String {
+        self.dump()
+            .iter()
+            .map(|entry| format!("{entry}\n"))
+            .collect()
+    }
+
+    /// Drop all buffered frames.
+    pub fn clear(&self) {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+}
+
+impl Middleware for Blackbox {
+    fn outgoing(&self, frame: Frame) -> Option<Frame> {
+        self.push(Direction::Tx, frame);
+        Some(frame)
+    }
+
+    fn incoming(&self, frame: Frame) -> Option<Frame> {
+        self.push(Direction::Rx, frame);
+        Some(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cansimple::Id;
+
+    #[test]
+    fn keeps_last() {
+        let blackbox = Blackbox::new(2);
+        let frame = |command| Frame::new(Id::new(1, command).unwrap(), &[]).unwrap();
+        blackbox.outgoing(frame(0x14));
+        blackbox.incoming(frame(0x15));
+        blackbox.incoming(frame(0x17));
+
+        let entries = blackbox.dump();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].direction, Direction::Rx);
+        assert_eq!(entries[0].frame, frame(0x15));
+        assert!(blackbox.dump_string().ends_with(" rx 037#\n"));
+
+        blackbox.clear();
+        assert!(blackbox.dump().is_empty());
+    }
+}
@@ -0,0 +1,197 @@
+//! # candump logging
+//!
+//! [`CandumpLog`] writes every frame in the `candump -L` log file format, so
+//! sessions can be replayed with `canplayer` and inspected with the other
+//! SocketCAN tools:
+//!
+//! ```text
+//! (1718000000.123456) can0 001#0000000008000000
+//! (1718000000.124001) can0 014#R
+//! ```
+//!
+//! ```no_run
+//! use odrive::{can::ODrive, middleware::candump::CandumpLog};
+//! use std::{fs::File, io::BufWriter};
+//!
+//! let log = BufWriter::new(File::create("session.log").unwrap());
+//! let socket = socketcan::tokio::CanSocket::open("can0").unwrap();
+//! let odrive = ODrive::new(socket, 1)
+//!     .unwrap()
+//!     .with_middleware(CandumpLog::new(log, "can0"));
+//! ```
+//!
+//! Buffered writers are flushed when the last driver sharing the transport
+//! is dropped.
+
+use super::Middleware;
+use crate::protocol::Frame;
+use std::{
+    fmt::Write as _,
+    io::Write,
+    sync::{Mutex, PoisonError},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Frame in the `candump` notation, such as `123#DEADBEEF`.
+pub fn format_frame(frame: &Frame) -> String {
+    let mut line = match frame.id() {
+        embedded_can::Id::Standard(id) => format!("{:03X}#", id.as_raw()),
+        embedded_can::Id::Extended(id) => format!("{:08X}#", id.as_raw()),
+    };
+    if frame.is_remote_frame() {
+        line.push('R');
+        if frame.dlc() > 0 {
+            let _ = write!(line, "{}", frame.dlc());
+        }
+        return line;
+    }
+    if frame.is_fd() {
+        let _ = write!(line, "#{:X}", frame.is_brs() as u8);
+    }
+    for byte in frame.data() {
+        let _ = write!(line, "{byte:02X}");
+    }
+    line
+}
+
+/// Parse a frame in the `candump` notation, see [`format_frame`].
+pub fn parse_frame(text: &str) -> Option<Frame> {
+    let (id, data) = text.split_once('#')?;
+    let raw = u32::from_str_radix(id, 16).ok()?;
+    let id: embedded_can::Id = match id.len() {
+        3 => embedded_can::StandardId::new(raw.try_into().ok()?)?.into(),
+        8 => embedded_can::ExtendedId::new(raw)?.into(),
+        _ => return None,
+    };
+
+    if let Some(dlc) = data.strip_prefix('R') {
+        let dlc = if dlc.is_empty() { 0 } else { dlc.parse().ok()? };
+        return Frame::new_remote(id, dlc);
+    }
+    let (fd, data) = match data.strip_prefix('#') {
+        Some(data) => {
+            let flags = u8::from_str_radix(data.get(..1)?, 16).ok()?;
+            (Some(flags & 1 != 0), &data[1..])
+        }
+        None => (None, data),
+    };
+    if data.len() % 2 != 0 {
+        return None;
+    }
+    let bytes = (0..data.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(data.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<_>>>()?;
+    match fd {
+        Some(brs) => Frame::new_fd(id, &bytes, brs),
+        None => Frame::new(id, &bytes),
+    }
+}
+
+/// Middleware writing all traffic in the `candump -L` format.
+pub struct CandumpLog<W> {
+    writer: Mutex<W>,
+    interface: String,
+}
+
+impl<W: Write + Send> CandumpLog<W> {
+    /// Log to `writer`, naming the bus `interface`.
+    pub fn new(writer: W, interface: impl Into<String>) -> Self {
+        Self {
+            writer: Mutex::new(writer),
+            interface: interface.into(),
+        }
+    }
+
+    /// Write a line for `frame` with the current time.
+    pub fn log(&self, frame: &Frame) {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let line = format!(
+            "({}.{:06}) {} {}\n",
+            time.as_secs(),
+            time.subsec_micros(),
+            self.interface,
+            format_frame(frame)
+        );
+        // a failing log must not affect the traffic
+        let _ = self
+            .writer
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .write_all(line.as_bytes());
+    }
+
+    /// The underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<W: Write + Send> Middleware for CandumpLog<W> {
+    fn outgoing(&self, frame: Frame) -> Option<Frame> {
+        self.log(&frame);
+        Some(frame)
+    }
+
+    fn incoming(&self, frame: Frame) -> Option<Frame> {
+        self.log(&frame);
+        Some(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cansimple::Id;
+    use embedded_can::ExtendedId;
+
+    #[test]
+    fn formats() {
+        let id = Id::new(1, 0x01).unwrap();
+        let data = Frame::new(id, &[0, 0, 0, 0, 8, 0, 0, 0xab]).unwrap();
+        assert_eq!(format_frame(&data), "021#00000000080000AB");
+        assert_eq!(format_frame(&Frame::new_remote(id, 0).unwrap()), "021#R");
+        assert_eq!(format_frame(&Frame::new_remote(id, 8).unwrap()), "021#R8");
+        assert_eq!(
+            format_frame(&Frame::new_fd(id, &[1, 2], true).unwrap()),
+            "021##10102"
+        );
+        let extended = Frame::new(ExtendedId::new(0x1234).unwrap(), &[]).unwrap();
+        assert_eq!(format_frame(&extended), "00001234#");
+    }
+
+    #[test]
+    fn parses() {
+        let id = Id::new(1, 0x01).unwrap();
+        for frame in [
+            Frame::new(id, &[0, 0, 0, 0, 8, 0, 0, 0xab]).unwrap(),
+            Frame::new_remote(id, 0).unwrap(),
+            Frame::new_remote(id, 8).unwrap(),
+            Frame::new_fd(id, &[1; 12], true).unwrap(),
+            Frame::new(ExtendedId::new(0x1234).unwrap(), &[]).unwrap(),
+        ] {
+            assert_eq!(parse_frame(&format_frame(&frame)), Some(frame));
+        }
+        assert_eq!(parse_frame("021#0"), None);
+        assert_eq!(parse_frame("21#00"), None);
+        assert_eq!(parse_frame("021#R9"), None);
+    }
+
+    #[test]
+    fn logs_lines() {
+        let log = CandumpLog::new(Vec::new(), "vcan0");
+        let frame = Frame::new(Id::new(3, 0x14).unwrap(), &[0xff]).unwrap();
+        log.outgoing(frame);
+        log.incoming(frame);
+
+        let text = String::from_utf8(log.into_inner()).unwrap();
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with('('));
+        assert!(lines[0].ends_with(") vcan0 074#FF"));
+    }
+}
@@ -0,0 +1,192 @@
+//! # Session recording
+//!
+//! [`SessionRecorder`] records every frame written and read together with
+//! its time. The resulting [`Session`] can be saved, attached to a bug
+//! report and replayed against the driver with
+//! [`Replay`](crate::transport::replay::Replay) to reproduce the problem
+//! without the hardware:
+//!
+//! ```no_run
+//! # tokio_test::block_on(async {
+//! use odrive::{can::ODrive, middleware::session::SessionRecorder};
+//! use std::sync::Arc;
+//!
+//! let recorder = Arc::new(SessionRecorder::new());
+//! let socket = socketcan::tokio::CanSocket::open("can0").unwrap();
+//! let odrive = ODrive::new(socket, 1)
+//!     .unwrap()
+//!     .with_middleware(recorder.clone());
+//!
+//! let result = odrive.get_iq().await;
+//! std::fs::write("session.log", recorder.session().to_string()).unwrap();
+//! # });
+//! ```
+//!
+//! Sessions are stored one frame per line as formatted by
+//! [`super::blackbox::Entry`].
+
+use super::{
+    Middleware,
+    blackbox::{Direction, Entry},
+    candump::parse_frame,
+};
+use crate::protocol::Frame;
+use std::{
+    fmt,
+    str::FromStr,
+    sync::{Mutex, PoisonError},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Frames written and read, oldest first.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Session {
+    pub entries: Vec<Entry>,
+}
+
+impl From<Vec<Entry>> for Session {
+    /// Session of the frames in a [`Blackbox`](super::blackbox::Blackbox)
+    /// dump.
+    fn from(entries: Vec<Entry>) -> Self {
+        Self { entries }
+    }
+}
+
+impl fmt::Display for Session {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.entries
+            .iter()
+            .try_for_each(|entry| writeln!(f, "{entry}"))
+    }
+}
+
+/// Line of a session that could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("invalid session line {line}")]
+pub struct ParseSessionError {
+    /// Line number, starting at 1.
+    pub line: usize,
+}
+
+impl FromStr for Session {
+    type Err = ParseSessionError;
+
+    /// Parse a session, empty lines are skipped.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let entries = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| parse_entry(line).ok_or(ParseSessionError { line: index + 1 }))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { entries })
+    }
+}
+
+fn parse_entry(line: &str) -> Option<Entry> {
+    let mut parts = line.split_whitespace();
+    let time = parts.next()?.strip_prefix('(')?.strip_suffix(')')?;
+    let (secs, micros) = time.split_once('.')?;
+    if micros.len() != 6 {
+        return None;
+    }
+    let time = UNIX_EPOCH
+        .checked_add(Duration::from_secs(secs.parse().ok()?))?
+        .checked_add(Duration::from_micros(micros.parse().ok()?))?;
+    let direction = match parts.next()? {
+        "tx" => Direction::Tx,
+        "rx" => Direction::Rx,
+        _ => return None,
+    };
+    let frame = parse_frame(parts.next()?)?;
+    if parts.next().is_some() {
+        return None;
+    }
+    Some(Entry {
+        direction,
+        frame,
+        time,
+    })
+}
+
+/// Middleware recording a [`Session`].
+#[derive(Default)]
+pub struct SessionRecorder {
+    entries: Mutex<Vec<Entry>>,
+}
+
+impl SessionRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn push(&self, direction: Direction, frame: Frame) {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(Entry {
+                direction,
+                frame,
+                time: SystemTime::now(),
+            });
+    }
+
+    /// Frames recorded so far.
+    pub fn session(&self) -> Session {
+        Session {
+            entries: self
+                .entries
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone(),
+        }
+    }
+}
+
+impl Middleware for SessionRecorder {
+    fn outgoing(&self, frame: Frame) -> Option<Frame> {
+        self.push(Direction::Tx, frame);
+        Some(frame)
+    }
+
+    fn incoming(&self, frame: Frame) -> Option<Frame> {
+        self.push(Direction::Rx, frame);
+        Some(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cansimple::Id;
+
+    #[test]
+    fn round_trip() {
+        let recorder = SessionRecorder::new();
+        let id = Id::new(1, 0x14).unwrap();
+        recorder.outgoing(Frame::new_remote(id, 8).unwrap());
+        recorder.incoming(Frame::new(id, &[1, 2, 3, 4, 5, 6, 7, 8]).unwrap());
+
+        let mut session = recorder.session();
+        // only microseconds are stored
+        for entry in &mut session.entries {
+            let time = entry.time.duration_since(UNIX_EPOCH).unwrap();
+            entry.time = UNIX_EPOCH + Duration::from_micros(time.as_micros() as u64);
+        }
+        let text = session.to_string();
+        assert_eq!(text.parse::<Session>(), Ok(session));
+        assert_eq!(
+            "\n(1.000000) tx 021#R\n(1.5) up 021#R"
+                .parse::<Session>()
+                .unwrap_err()
+                .line,
+            3
+        );
+        // out of range times are rejected instead of overflowing
+        assert!(
+            "(18446744073709551615.000000) tx 021#R"
+                .parse::<Session>()
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,587 @@
+//! # Mock ODrive
+//!
+//! [`MockODrive`] is an in-process fake ODrive node. It answers version,
+//! error, telemetry and SDO requests and follows axis state changes, so
+//! motion logic built on the driver can be unit tested without hardware.
+//! [`MockBus`] connects any number of mock nodes to the driver as a
+//! [`Transport`]:
+//!
+//! ```
+//! # tokio_test::block_on(async {
+//! use odrive::{AxisState, can::ODrive, mock::{MockBus, MockODrive}};
+//! use std::sync::Arc;
+//!
+//! let node = Arc::new(MockODrive::new(1));
+//! let bus = MockBus::new().with_node(node.clone());
+//! let odrive = ODrive::new(bus, 1).unwrap();
+//!
+//! odrive.set_axis_state(AxisState::ClosedLoopControl).await.unwrap();
+//! assert_eq!(node.state().axis_state, AxisState::ClosedLoopControl);
+//! # });
+//! ```
+//!
+//! Without a [`Motor`] the mock does not move, setpoints are only stored.
+//! [`MockODrive::update`] changes its state from the test, for example to
+//! raise an error. [`scenario::Responder`] answers from a script instead,
+//! also over virtual CAN interfaces.
+
+pub mod motor;
+pub mod scenario;
+
+use crate::protocol::{
+    self, BusVoltageCurrent, EncoderEstimate, Error, Frame, Heartbeat, Power, Temperature, Torque,
+    Version, command,
+};
+use crate::streamer::Setpoint;
+use crate::transport::{Timer, Transport, replay::NoTimeout, timeout};
+use crate::{AxisErrors, AxisState, ProcedureResult};
+use cansimple::Id;
+use motor::Motor;
+use std::{
+    collections::{BTreeMap, VecDeque},
+    future::poll_fn,
+    io,
+    marker::PhantomData,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    task::{Poll, Waker},
+    time::{Duration, Instant},
+};
+
+/// State of a [`MockODrive`].
+#[derive(Debug, Clone)]
+pub struct MockState {
+    pub version: Version,
+    pub axis_state: AxisState,
+    pub procedure_result: ProcedureResult,
+    pub trajectory_done: bool,
+    pub active_errors: AxisErrors,
+    pub disarm_reason: AxisErrors,
+    pub encoder: EncoderEstimate,
+    /// Current setpoint and measurement in A.
+    pub iq: (f32, f32),
+    pub temperature: Temperature,
+    pub bus: BusVoltageCurrent,
+    pub torque: Torque,
+    pub power: Power,
+    /// Last setpoint received.
+    pub setpoint: Option<Setpoint>,
+    /// Raw values of the endpoints written or set by the test.
+    pub endpoints: BTreeMap<u16, [u8; 4]>,
+    /// Simulated motor moving the encoder, see [`MockODrive::step`].
+    pub motor: Option<Motor>,
+    /// Time a reboot takes. The node ignores frames and sends no heartbeats
+    /// meanwhile, zero to be back up at once.
+    pub reboot_time: Duration,
+    /// Remaining time of the reboot in progress, see [`MockODrive::step`].
+    pub rebooting: Option<Duration>,
+}
+
+impl Default for MockState {
+    /// Idle on a 24 V bus without errors.
+    fn default() -> Self {
+        Self {
+            version: Version {
+                protocol_version: 2,
+                hw_version_major: 4,
+                hw_version_minor: 4,
+                hw_version_variant: 0,
+                fw_version_major: 0,
+                fw_version_minor: 6,
+                fw_version_revision: 10,
+                fw_version_unreleased: false,
+            },
+            axis_state: AxisState::Idle,
+            procedure_result: ProcedureResult::Success,
+            trajectory_done: true,
+            active_errors: AxisErrors::empty(),
+            disarm_reason: AxisErrors::empty(),
+            encoder: EncoderEstimate {
+                position: 0.0,
+                velocity: 0.0,
+            },
+            iq: (0.0, 0.0),
+            temperature: Temperature {
+                fet: 25.0,
+                motor: 25.0,
+            },
+            bus: BusVoltageCurrent {
+                voltage: 24.0,
+                current: 0.0,
+            },
+            torque: Torque {
+                target: 0.0,
+                estimate: 0.0,
+            },
+            power: Power {
+                electrical: 0.0,
+                mechanical: 0.0,
+            },
+            setpoint: None,
+            endpoints: BTreeMap::new(),
+            motor: None,
+            reboot_time: Duration::ZERO,
+            rebooting: None,
+        }
+    }
+}
+
+/// Fake ODrive node.
+pub struct MockODrive {
+    node: u8,
+    state: Mutex<MockState>,
+}
+
+impl MockODrive {
+    /// Node `node` in the default [`MockState`].
+    pub fn new(node: u8) -> Self {
+        Self::with_state(node, MockState::default())
+    }
+
+    pub fn with_state(node: u8, state: MockState) -> Self {
+        Self {
+            node,
+            state: Mutex::new(state),
+        }
+    }
+
+    /// Simulate `motor`, see [`MockODrive::step`].
+    pub fn with_motor(self, motor: Motor) -> Self {
+        self.update(|state| state.motor = Some(motor));
+        self
+    }
+
+    pub fn node(&self) -> u8 {
+        self.node
+    }
+
+    /// Snapshot of the current state.
+    pub fn state(&self) -> MockState {
+        self.lock().clone()
+    }
+
+    /// Change the state, e.g. to raise an error.
+    pub fn update(&self, update: impl FnOnce(&mut MockState)) {
+        update(&mut self.lock());
+    }
+
+    /// Advance the simulated motor and a reboot in progress by `dt`.
+    pub fn step(&self, dt: Duration) {
+        let mut state = self.lock();
+        if let Some(motor) = state.motor {
+            motor.step(&mut state, dt);
+        }
+        if let Some(remaining) = state.rebooting {
+            state.rebooting = Some(remaining.saturating_sub(dt)).filter(|r| !r.is_zero());
+        }
+    }
+
+    /// Whether a reboot is in progress, see [`MockState::reboot_time`].
+    pub fn is_rebooting(&self) -> bool {
+        self.lock().rebooting.is_some()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, MockState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Heartbeat message of the current state.
+    ///
+    /// Fails with [`crate::Error::InvalidNode`] if the node is out of range.
+    pub fn heartbeat(&self) -> crate::Result<Frame> {
+        self.heartbeat_of(&self.lock())
+    }
+
+    fn heartbeat_of(&self, state: &MockState) -> crate::Result<Frame> {
+        let heartbeat = Heartbeat {
+            axis_error: state.active_errors,
+            axis_state: state.axis_state,
+            procedure_result: state.procedure_result,
+            trajectory_done: state.trajectory_done,
+        };
+        self.frame(command::HEARTBEAT, &protocol::encode_heartbeat(&heartbeat))
+    }
+
+    /// Handle a frame sent to the bus, returns the responses.
+    ///
+    /// Frames for other nodes and unsupported or malformed commands are
+    /// ignored.
+    pub fn handle(&self, frame: &Frame) -> Vec<Frame> {
+        let Ok(id) = Id::try_from(frame.id()) else {
+            return Vec::new();
+        };
+        if id.node() != self.node || self.is_rebooting() {
+            return Vec::new();
+        }
+        if frame.is_remote_frame() {
+            return self.respond(id.command()).into_iter().collect();
+        }
+
+        let data = frame.data();
+        let mut state = self.lock();
+        match id.command() {
+            command::ESTOP => {
+                state.axis_state = AxisState::Idle;
+                state.active_errors |= AxisErrors::ESTOP_REQUESTED;
+                state.disarm_reason |= AxisErrors::ESTOP_REQUESTED;
+            }
+            command::RX_SDO => match protocol::decode_sdo_request(data) {
+                Ok((endpoint, None)) => {
+                    if let Some(value) = state.endpoints.get(&endpoint) {
+                        let data = protocol::encode_sdo_value(endpoint, *value);
+                        return self.frame(command::TX_SDO, &data).into_iter().collect();
+                    }
+                }
+                Ok((endpoint, Some(value))) => {
+                    state.endpoints.insert(endpoint, value);
+                }
+                Err(_) => {}
+            },
+            command::SET_AXIS_STATE => match protocol::decode_set_axis_state(data) {
+                Ok(axis_state) if state.active_errors.is_empty() => {
+                    state.axis_state = axis_state;
+                    state.procedure_result = ProcedureResult::Success;
+                }
+                Ok(_) => state.procedure_result = ProcedureResult::Disarmed,
+                Err(_) => state.procedure_result = ProcedureResult::InvalidState,
+            },
+            command::SET_INPUT_POS => {
+                if let Ok((position, velocity, torque)) = protocol::decode_set_input_position(data)
+                {
+                    state.setpoint = Some(Setpoint::Position {
+                        position,
+                        velocity,
+                        torque,
+                    });
+                }
+            }
+            command::SET_INPUT_VEL => {
+                if let Ok((velocity, torque)) = protocol::decode_set_input_velocity(data) {
+                    state.setpoint = Some(Setpoint::Velocity { velocity, torque });
+                }
+            }
+            command::SET_INPUT_TORQUE => {
+                if let Ok(torque) = protocol::decode_set_input_torque(data) {
+                    state.setpoint = Some(Setpoint::Torque { torque });
+                }
+            }
+            command::SET_ABSOLUTE_POSITION => {
+                if let Ok(position) = protocol::decode_set_absolute_position(data) {
+                    state.encoder.position = position;
+                }
+            }
+            command::SET_LIMITS => {
+                if let (Some(motor), Ok((velocity, current))) =
+                    (&mut state.motor, protocol::decode_set_limits(data))
+                {
+                    motor.velocity_limit = velocity;
+                    motor.current_limit = current;
+                }
+            }
+            command::SET_POS_GAIN => {
+                if let (Some(motor), Ok(gain)) =
+                    (&mut state.motor, protocol::decode_set_position_gain(data))
+                {
+                    motor.position_gain = gain;
+                }
+            }
+            command::SET_VEL_GAINS => {
+                if let (Some(motor), Ok((gain, _))) =
+                    (&mut state.motor, protocol::decode_set_velocity_gains(data))
+                {
+                    motor.velocity_gain = gain;
+                }
+            }
+            command::CLEAR_ERRORS => {
+                state.active_errors = AxisErrors::empty();
+                state.disarm_reason = AxisErrors::empty();
+                state.procedure_result = ProcedureResult::Success;
+            }
+            command::REBOOT => {
+                // the cyclic heartbeat sent just before resetting, or the
+                // first one once back up
+                let heartbeat = self.heartbeat_of(&state);
+                state.axis_state = AxisState::Idle;
+                state.setpoint = None;
+                if !state.reboot_time.is_zero() {
+                    state.rebooting = Some(state.reboot_time);
+                    return heartbeat.into_iter().collect();
+                }
+                drop(state);
+                return self.heartbeat().into_iter().collect();
+            }
+            _ => {}
+        }
+        Vec::new()
+    }
+
+    /// Response to a remote frame requesting `command`.
+    fn respond(&self, command: u8) -> Option<Frame> {
+        if command == command::HEARTBEAT {
+            return self.heartbeat().ok();
+        }
+        let state = self.lock().clone();
+        let data = match command {
+            command::GET_VERSION => protocol::encode_version(&state.version),
+            command::GET_ERROR => protocol::encode_error(&Error {
+                active_errors: state.active_errors,
+                disarm_reason: state.disarm_reason,
+            }),
+            command::GET_ENCODER_ESTIMATES => protocol::encode_encoder_estimates(&state.encoder),
+            command::GET_IQ => protocol::encode_iq(state.iq),
+            command::GET_TEMPERATURE => protocol::encode_temperature(&state.temperature),
+            command::GET_BUS_VOLTAGE_CURRENT => protocol::encode_bus_voltage_current(&state.bus),
+            command::GET_TORQUES => protocol::encode_torques(&state.torque),
+            command::GET_POWERS => protocol::encode_powers(&state.power),
+            _ => return None,
+        };
+        self.frame(command, &data).ok()
+    }
+
+    fn frame(&self, command: u8, data: &[u8]) -> crate::Result<Frame> {
+        let id = Id::new(self.node, command).ok_or(crate::Error::InvalidNode(self.node))?;
+        Frame::new(id, data).ok_or(crate::Error::InvalidLength {
+            expected: 8,
+            actual: data.len(),
+        })
+    }
+}
+
+#[derive(Default)]
+struct Inbox {
+    frames: VecDeque<Frame>,
+    wakers: Vec<Waker>,
+}
+
+/// Transport connecting [`MockODrive`] nodes to the driver.
+///
+/// Responses are available as soon as the request was written. Requests to
+/// nodes not on the bus are never answered, use a real timer such as
+/// [`TokioTimer`](crate::transport::TokioTimer) for `M` where those should
+/// time out.
+pub struct MockBus<M = NoTimeout> {
+    nodes: Vec<Arc<MockODrive>>,
+    inbox: Mutex<Inbox>,
+    heartbeat_interval: Option<Duration>,
+    next_heartbeat: Mutex<Option<Instant>>,
+    timer: PhantomData<fn() -> M>,
+}
+
+impl<M> Default for MockBus<M> {
+    fn default() -> Self {
+        Self {
+            nodes: Vec::new(),
+            inbox: Mutex::new(Inbox::default()),
+            heartbeat_interval: None,
+            next_heartbeat: Mutex::new(None),
+            timer: PhantomData,
+        }
+    }
+}
+
+impl MockBus {
+    /// Bus without nodes whose requests never time out, see
+    /// [`MockBus::default`] for other timers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<M> MockBus<M> {
+    /// Connect `node` to the bus.
+    pub fn with_node(mut self, node: Arc<MockODrive>) -> Self {
+        self.nodes.push(node);
+        self
+    }
+
+    /// Send cyclic heartbeats every `interval` of the timer while the driver
+    /// reads, stepping the nodes by `interval` each time. Needs a real timer
+    /// such as [`TokioTimer`](crate::transport::TokioTimer) for `M`.
+    pub fn with_heartbeats(mut self, interval: Duration) -> Self {
+        self.heartbeat_interval = Some(interval);
+        self
+    }
+
+    /// Queue `frame` to be read by the driver, as if sent by a node.
+    pub fn push(&self, frame: Frame) {
+        let mut inbox = self.lock();
+        inbox.frames.push_back(frame);
+        inbox.wakers.drain(..).for_each(Waker::wake);
+    }
+
+    /// Advance all nodes by `dt`, nodes done rebooting announce themselves
+    /// with a heartbeat.
+    pub fn step(&self, dt: Duration) {
+        for node in &self.nodes {
+            let rebooting = node.is_rebooting();
+            node.step(dt);
+            if rebooting
+                && !node.is_rebooting()
+                && let Ok(heartbeat) = node.heartbeat()
+            {
+                self.push(heartbeat);
+            }
+        }
+    }
+
+    /// Queue a heartbeat of every node that is not rebooting.
+    pub fn heartbeats(&self) {
+        for node in self.nodes.iter().filter(|node| !node.is_rebooting()) {
+            if let Ok(heartbeat) = node.heartbeat() {
+                self.push(heartbeat);
+            }
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inbox> {
+        self.inbox.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self, frame: &Frame) {
+        for response in self.nodes.iter().flat_map(|node| node.handle(frame)) {
+            self.push(response);
+        }
+    }
+
+    fn read(&self, waker: Option<&Waker>) -> Poll<Frame> {
+        let mut inbox = self.lock();
+        match inbox.frames.pop_front() {
+            Some(frame) => Poll::Ready(frame),
+            None => {
+                if let Some(waker) = waker {
+                    inbox.wakers.push(waker.clone());
+                }
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<M: Timer> MockBus<M> {
+    /// Send the cyclic heartbeats that are due, returns the time until the
+    /// next ones.
+    fn tick(&self, interval: Duration) -> Duration {
+        let now = M::now();
+        let mut next = self
+            .next_heartbeat
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let next = next.get_or_insert(now + interval);
+        while *next <= now {
+            self.step(interval);
+            self.heartbeats();
+            *next += interval;
+        }
+        *next - now
+    }
+}
+
+impl<M: Timer> Transport for MockBus<M> {
+    type Timer = M;
+
+    async fn write_frame(&self, frame: &Frame) -> io::Result<()> {
+        self.write(frame);
+        Ok(())
+    }
+
+    async fn read_frame(&self) -> io::Result<Frame> {
+        let Some(interval) = self.heartbeat_interval else {
+            return Ok(poll_fn(|cx| self.read(Some(cx.waker()))).await);
+        };
+        loop {
+            let wait = self.tick(interval);
+            let read = poll_fn(|cx| self.read(Some(cx.waker())));
+            if let Some(frame) = timeout::<M, _>(wait, read).await {
+                return Ok(frame);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl<M> crate::blocking::Transport for MockBus<M> {
+    fn write_frame(&self, frame: &Frame) -> io::Result<()> {
+        self.write(frame);
+        Ok(())
+    }
+
+    /// Fails with [`io::ErrorKind::WouldBlock`] if no frame is queued.
+    fn read_frame(&self) -> io::Result<Frame> {
+        match self.read(None) {
+            Poll::Ready(frame) => Ok(frame),
+            Poll::Pending => Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                "no frame queued on the mock bus",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        can::ODrive,
+        protocol::{Value, ValueKind},
+    };
+
+    #[test]
+    fn responds() {
+        let node = Arc::new(MockODrive::new(3));
+        let bus = MockBus::new().with_node(node.clone());
+        bus.heartbeats();
+        let odrive = ODrive::new(bus, 3).unwrap();
+
+        tokio_test::block_on(async {
+            let version = odrive.get_version().await.unwrap();
+            assert_eq!(version.fw_version_minor, 6);
+            assert!(odrive.require_firmware(0, 6, 10).await.is_ok());
+            assert!(matches!(
+                odrive.require_firmware(0, 6, 11).await,
+                Err(crate::Error::FirmwareTooOld { .. })
+            ));
+
+            odrive.sdo_write(0x99, Value::Float(1.5)).await.unwrap();
+            assert!(matches!(
+                odrive.sdo_read(0x99, ValueKind::Float).await.unwrap(),
+                Value::Float(1.5)
+            ));
+            odrive.sdo_write(0x9a, 7u16).await.unwrap();
+            assert_eq!(odrive.sdo_read_as::<u16>(0x9a).await.unwrap(), 7);
+            assert!(matches!(
+                odrive.sdo_write(0x9b, -1.5e300f64).await,
+                Err(crate::Error::ValueTooWide(ValueKind::Double))
+            ));
+            assert!(!node.state().endpoints.contains_key(&0x9b));
+
+            odrive
+                .set_axis_state(AxisState::ClosedLoopControl)
+                .await
+                .unwrap();
+            odrive.set_input_velocity(2.0, 0.5).await.unwrap();
+            odrive.estop().await.unwrap();
+            let error = odrive.get_error().await.unwrap();
+            assert_eq!(error.disarm_reason, AxisErrors::ESTOP_REQUESTED);
+        });
+
+        let state = node.state();
+        assert_eq!(state.axis_state, AxisState::Idle);
+        assert!(matches!(
+            state.setpoint,
+            Some(Setpoint::Velocity { velocity: 2.0, .. })
+        ));
+        assert_eq!(
+            odrive.cache().latest_heartbeat().unwrap().value.axis_state,
+            AxisState::Idle
+        );
+
+        // out of range nodes cannot send
+        let invalid = MockODrive::new(64);
+        assert!(matches!(
+            invalid.heartbeat(),
+            Err(crate::Error::InvalidNode(64))
+        ));
+        let bus = MockBus::new().with_node(Arc::new(invalid));
+        bus.heartbeats();
+        assert!(bus.read(None).is_pending());
+    }
+}
@@ -0,0 +1,178 @@
+//! # Simulated motor
+//!
+//! [`Motor`] models the controller, motor and load of a [`MockODrive`] as a
+//! single inertia with viscous friction, so the encoder estimates follow the
+//! setpoints plausibly. Time only advances when the mock is stepped, which
+//! keeps closed loop tests deterministic:
+//!
+//! ```
+//! # tokio_test::block_on(async {
+//! use odrive::{AxisState, can::ODrive, mock::{MockBus, MockODrive, motor::Motor}};
+//! use std::{sync::Arc, time::Duration};
+//!
+//! let node = Arc::new(MockODrive::new(1).with_motor(Motor::default()));
+//! let odrive = ODrive::new(MockBus::new().with_node(node.clone()), 1).unwrap();
+//!
+//! odrive.set_axis_state(AxisState::ClosedLoopControl).await.unwrap();
+//! odrive.set_input_position(2.0, 0, 0).await.unwrap();
+//! for _ in 0..2000 {
+//!     node.step(Duration::from_millis(1));
+//! }
+//! let estimate = odrive.get_encoder_estimates().await.unwrap();
+//! assert!((estimate.position - 2.0).abs() < 0.01);
+//! # });
+//! ```
+//!
+//! [`MockODrive`]: super::MockODrive
+
+use super::MockState;
+use crate::AxisState;
+use crate::streamer::Setpoint;
+use std::{f32::consts::TAU, time::Duration};
+
+/// Position error in rev below which a position setpoint is reached.
+const POSITION_TOLERANCE: f32 = 0.01;
+
+/// Parameters of a simulated motor and its controller.
+///
+/// The controller is a cascaded proportional position and velocity loop
+/// like the ODrive's, without the velocity integrator.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Motor {
+    /// Inertia of rotor and load in Nm/(rev/s²).
+    pub inertia: f32,
+    /// Viscous friction in Nm/(rev/s).
+    pub damping: f32,
+    /// Nm/A.
+    pub torque_constant: f32,
+    /// Ohm.
+    pub phase_resistance: f32,
+    /// Position gain in (rev/s)/rev.
+    pub position_gain: f32,
+    /// Velocity gain in Nm/(rev/s).
+    pub velocity_gain: f32,
+    /// rev/s.
+    pub velocity_limit: f32,
+    /// A.
+    pub current_limit: f32,
+}
+
+impl Default for Motor {
+    /// Small hobby motor with the ODrive's default gains.
+    fn default() -> Self {
+        Self {
+            inertia: 0.001,
+            damping: 0.0005,
+            torque_constant: 0.083,
+            phase_resistance: 0.05,
+            position_gain: 20.0,
+            velocity_gain: 0.16,
+            velocity_limit: 10.0,
+            current_limit: 10.0,
+        }
+    }
+}
+
+impl Motor {
+    /// Advance `state` by `dt`.
+    ///
+    /// The motor is only driven in closed loop control, in any other state
+    /// it coasts to a stop.
+    pub fn step(&self, state: &mut MockState, dt: Duration) {
+        let dt = dt.as_secs_f32();
+        let position = state.encoder.position;
+        let velocity = state.encoder.velocity;
+
+        let mut target = None;
+        let torque = match state.setpoint {
+            Some(setpoint) if state.axis_state == AxisState::ClosedLoopControl => match setpoint {
+                Setpoint::Position {
+                    position: setpoint,
+                    velocity: feed_forward,
+                    torque: torque_feed_forward,
+                } => {
+                    target = Some(setpoint);
+                    let command =
+                        self.position_gain * (setpoint - position) + feed_forward as f32 * 0.001;
+                    self.velocity_loop(command, velocity) + torque_feed_forward as f32 * 0.001
+                }
+                Setpoint::Velocity {
+                    velocity: setpoint,
+                    torque: feed_forward,
+                } => self.velocity_loop(setpoint, velocity) + feed_forward,
+                Setpoint::Torque { torque } => torque,
+            },
+            _ => 0.0,
+        };
+        let limit = self.current_limit * self.torque_constant;
+        let torque = torque.clamp(-limit, limit);
+
+        // semi-implicit Euler stays stable for the default gains at 1 kHz
+        let velocity = velocity + (torque - self.damping * velocity) / self.inertia * dt;
+        state.encoder.velocity = velocity;
+        state.encoder.position = position + velocity * dt;
+
+        let current = torque / self.torque_constant;
+        state.iq = (current, current);
+        state.torque.target = torque;
+        state.torque.estimate = torque;
+        state.power.mechanical = torque * velocity * TAU;
+        state.power.electrical = state.power.mechanical + current * current * self.phase_resistance;
+        if state.bus.voltage > 0.0 {
+            state.bus.current = state.power.electrical / state.bus.voltage;
+        }
+        state.trajectory_done = target
+            .is_none_or(|target| (target - state.encoder.position).abs() < POSITION_TOLERANCE);
+    }
+
+    /// Torque commanded by the velocity loop.
+    fn velocity_loop(&self, setpoint: f32, velocity: f32) -> f32 {
+        let setpoint = setpoint.clamp(-self.velocity_limit, self.velocity_limit);
+        self.velocity_gain * (setpoint - velocity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(motor: &Motor, state: &mut MockState, millis: u32) {
+        for _ in 0..millis {
+            motor.step(state, Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn follows_setpoints() {
+        let motor = Motor::default();
+        let mut state = MockState {
+            axis_state: AxisState::ClosedLoopControl,
+            setpoint: Some(Setpoint::Velocity {
+                velocity: 50.0,
+                torque: 0.0,
+            }),
+            ..MockState::default()
+        };
+        run(&motor, &mut state, 1000);
+        // limited, and the friction keeps it slightly below the limit
+        assert!((9.9..10.0).contains(&state.encoder.velocity));
+        assert!(state.iq.1 > 0.0 && state.bus.current > 0.0);
+
+        state.setpoint = Some(Setpoint::Position {
+            position: -3.0,
+            velocity: 0,
+            torque: 0,
+        });
+        run(&motor, &mut state, 100);
+        assert!(!state.trajectory_done);
+        run(&motor, &mut state, 3000);
+        assert!((state.encoder.position + 3.0).abs() < POSITION_TOLERANCE);
+        assert!(state.trajectory_done);
+
+        state.axis_state = AxisState::Idle;
+        state.encoder.velocity = 1.0;
+        run(&motor, &mut state, 10);
+        assert!(state.encoder.velocity < 1.0);
+        assert_eq!(state.torque.estimate, 0.0);
+    }
+}
@@ -0,0 +1,316 @@
+//! # Scripted responder
+//!
+//! [`Responder`] answers the driver according to a [`Scenario`], a text
+//! file of rules matching requests in the `candump` notation:
+//!
+//! ```text
+//! # version request, answered after 20 ms
+//! 020#R delay 20ms => 020#0204040000060A00
+//! # the first two bus voltage requests are lost, exercising retries
+//! 037#R drop 2 => 037#0000C0410000A040
+//! # any SDO request is never answered and times out
+//! 024#* =>
+//! ```
+//!
+//! Each rule is a request, optional `drop <n>` and `delay <duration>`
+//! options, `=>` and the responses. Requests match the whole frame, or only
+//! the ID with `#*`. The first matching rule applies, frames no rule matches
+//! are passed to the [`MockODrive`] nodes added to the responder.
+//!
+//! Run on a virtual CAN interface, the responder tests the driver and the
+//! applications built on it without hardware:
+//!
+//! ```no_run
+//! # tokio_test::block_on(async {
+//! use odrive::mock::scenario::{Responder, Scenario};
+//!
+//! let scenario: Scenario = std::fs::read_to_string("scenario.txt")
+//!     .unwrap()
+//!     .parse()
+//!     .unwrap();
+//! let socket = socketcan::tokio::CanSocket::open("vcan0").unwrap();
+//! Responder::new(scenario).serve(&socket).await.unwrap();
+//! # });
+//! ```
+//!
+//! The `responder` example does the same from the command line.
+
+use super::MockODrive;
+use crate::middleware::candump::parse_frame;
+use crate::protocol::Frame;
+use crate::transport::{Timer, Transport};
+use std::{
+    str::FromStr,
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+
+/// Frames a [`Rule`] applies to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pattern {
+    /// Any frame with the ID, `123#*`.
+    Id(embedded_can::Id),
+    /// Exactly the frame.
+    Frame(Frame),
+}
+
+impl Pattern {
+    pub fn matches(&self, frame: &Frame) -> bool {
+        match self {
+            Pattern::Id(id) => frame.id() == *id,
+            Pattern::Frame(pattern) => pattern == frame,
+        }
+    }
+}
+
+/// Line of a [`Scenario`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub request: Pattern,
+    /// Number of matching requests ignored before answering.
+    pub drop: u32,
+    /// Time between the request and the responses.
+    pub delay: Duration,
+    /// Frames sent in response, none to never answer.
+    pub responses: Vec<Frame>,
+}
+
+/// Rules a [`Responder`] follows.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Scenario {
+    pub rules: Vec<Rule>,
+}
+
+/// Line of a scenario that could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("invalid scenario line {line}")]
+pub struct ParseScenarioError {
+    /// Line number, starting at 1.
+    pub line: usize,
+}
+
+impl FromStr for Scenario {
+    type Err = ParseScenarioError;
+
+    /// Parse a scenario, empty lines and lines starting with `#` are
+    /// skipped.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let rules = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| {
+                let line = line.trim();
+                !line.is_empty() && !line.starts_with('#')
+            })
+            .map(|(index, line)| parse_rule(line).ok_or(ParseScenarioError { line: index + 1 }))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { rules })
+    }
+}
+
+fn parse_rule(line: &str) -> Option<Rule> {
+    let (request, responses) = line.split_once("=>")?;
+    let mut parts = request.split_whitespace();
+    let request = match parts.next()? {
+        pattern if pattern.ends_with("#*") => {
+            let id = parse_frame(&pattern.replace("#*", "#"))?.id();
+            Pattern::Id(id)
+        }
+        pattern => Pattern::Frame(parse_frame(pattern)?),
+    };
+
+    let mut rule = Rule {
+        request,
+        drop: 0,
+        delay: Duration::ZERO,
+        responses: Vec::new(),
+    };
+    while let Some(option) = parts.next() {
+        match option {
+            "drop" => rule.drop = parts.next()?.parse().ok()?,
+            "delay" => rule.delay = parse_duration(parts.next()?)?,
+            _ => return None,
+        }
+    }
+    rule.responses = responses
+        .split_whitespace()
+        .map(parse_frame)
+        .collect::<Option<_>>()?;
+    Some(rule)
+}
+
+/// Duration such as `250us`, `20ms` or `1s`.
+fn parse_duration(text: &str) -> Option<Duration> {
+    let split = text.find(|c: char| !c.is_ascii_digit())?;
+    let value = text[..split].parse().ok()?;
+    match &text[split..] {
+        "us" => Some(Duration::from_micros(value)),
+        "ms" => Some(Duration::from_millis(value)),
+        "s" => Some(Duration::from_secs(value)),
+        _ => None,
+    }
+}
+
+/// Answers requests according to a [`Scenario`].
+pub struct Responder {
+    /// Rules with the number of requests they matched.
+    rules: Mutex<Vec<(Rule, u32)>>,
+    nodes: Vec<Arc<MockODrive>>,
+}
+
+impl Responder {
+    pub fn new(scenario: Scenario) -> Self {
+        Self {
+            rules: Mutex::new(scenario.rules.into_iter().map(|rule| (rule, 0)).collect()),
+            nodes: Vec::new(),
+        }
+    }
+
+    /// Answer frames no rule matches with `node`.
+    pub fn with_node(mut self, node: Arc<MockODrive>) -> Self {
+        self.nodes.push(node);
+        self
+    }
+
+    /// Delay and responses to `frame`.
+    pub fn respond(&self, frame: &Frame) -> (Duration, Vec<Frame>) {
+        let mut rules = self.rules.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some((rule, matched)) = rules
+            .iter_mut()
+            .find(|(rule, _)| rule.request.matches(frame))
+        {
+            *matched += 1;
+            if *matched <= rule.drop {
+                return (Duration::ZERO, Vec::new());
+            }
+            return (rule.delay, rule.responses.clone());
+        }
+        drop(rules);
+
+        let responses = self.nodes.iter().flat_map(|node| node.handle(frame));
+        (Duration::ZERO, responses.collect())
+    }
+
+    /// Answer the frames read from `transport` until reading fails.
+    ///
+    /// Requests are handled in order, a delayed response also delays the
+    /// responses to the requests after it.
+    pub async fn serve<T: Transport>(&self, transport: &T) -> std::io::Result<()> {
+        loop {
+            let frame = transport.read_frame().await?;
+            let (delay, responses) = self.respond(&frame);
+            if responses.is_empty() {
+                continue;
+            }
+            if !delay.is_zero() {
+                T::Timer::sleep(delay).await;
+            }
+            for response in &responses {
+                transport.write_frame(response).await?;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::can::ODrive;
+    use cansimple::Id;
+    use std::io;
+    use tokio::sync::mpsc;
+
+    struct Sleep;
+
+    impl Timer for Sleep {
+        async fn sleep(duration: Duration) {
+            tokio::time::sleep(duration).await
+        }
+    }
+
+    /// End of an in-memory bus, reading the frames the other end writes.
+    struct End {
+        tx: mpsc::UnboundedSender<Frame>,
+        rx: tokio::sync::Mutex<mpsc::UnboundedReceiver<Frame>>,
+    }
+
+    fn bus() -> (End, End) {
+        let (a_tx, a_rx) = mpsc::unbounded_channel();
+        let (b_tx, b_rx) = mpsc::unbounded_channel();
+        let end = |tx, rx| End {
+            tx,
+            rx: tokio::sync::Mutex::new(rx),
+        };
+        (end(a_tx, b_rx), end(b_tx, a_rx))
+    }
+
+    impl Transport for End {
+        type Timer = Sleep;
+
+        async fn write_frame(&self, frame: &Frame) -> io::Result<()> {
+            self.tx
+                .send(*frame)
+                .map_err(|_| io::ErrorKind::BrokenPipe.into())
+        }
+
+        async fn read_frame(&self) -> io::Result<Frame> {
+            self.rx
+                .lock()
+                .await
+                .recv()
+                .await
+                .ok_or_else(|| io::ErrorKind::UnexpectedEof.into())
+        }
+    }
+
+    #[test]
+    fn follows_rules() {
+        let scenario: Scenario = "\
+            # comment\n\
+            037#R drop 1 delay 20ms => 037#0000C0410000A040 037#0000C0410000A040\n\
+            \n\
+            024#* =>\n"
+            .parse()
+            .unwrap();
+        assert_eq!(scenario.rules.len(), 2);
+        let responder = Responder::new(scenario).with_node(Arc::new(MockODrive::new(1)));
+
+        let request = |command| Frame::new_remote(Id::new(1, command).unwrap(), 0).unwrap();
+        assert_eq!(responder.respond(&request(0x17)), (Duration::ZERO, vec![]));
+        let (delay, responses) = responder.respond(&request(0x17));
+        assert_eq!(delay, Duration::from_millis(20));
+        assert_eq!(responses.len(), 2);
+
+        let sdo = Frame::new(Id::new(1, 0x04).unwrap(), &[0; 8]).unwrap();
+        assert!(responder.respond(&sdo).1.is_empty());
+        // answered by the mock
+        assert_eq!(responder.respond(&request(0x14)).1.len(), 1);
+
+        assert_eq!(
+            "037#R delay 20 => 037#"
+                .parse::<Scenario>()
+                .unwrap_err()
+                .line,
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn serves_driver() {
+        let scenario: Scenario = "037#R drop 1 => 037#0000C0410000A040".parse().unwrap();
+        let responder = Responder::new(scenario);
+        let (driver, node) = bus();
+        let odrive = ODrive::new(driver, 1)
+            .unwrap()
+            .with_timeout(Some(Duration::from_millis(50)))
+            .with_retries(1);
+
+        let reading = tokio::select! {
+            result = responder.serve(&node) => panic!("responder stopped: {result:?}"),
+            reading = odrive.get_bus_voltage_current() => reading.unwrap(),
+        };
+        assert_eq!(reading.voltage, 24.0);
+        assert_eq!(reading.current, 5.0);
+        assert_eq!(odrive.stats().retries, 1);
+    }
+}
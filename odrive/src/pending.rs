@@ -0,0 +1,387 @@
+//! # Request correlation
+//!
+//! Several requests may be waiting for responses on the same transport at
+//! once. Only one of them reads from the transport at a time, the reader
+//! routes every received frame to the request waiting for it. Frames no
+//! request is waiting for are returned to the reader so they can be passed
+//! to the subscribers.
+//!
+//! Requests for the same response are resolved in the order they were
+//! registered.
+//!
+//! [`Subscribers`] are shared by every driver using the same transport, so a
+//! frame is delivered to the subscribers of its node whichever driver read
+//! it.
+
+use crate::protocol::{Frame, Request, Validation};
+use cansimple::Id;
+use std::{
+    sync::{
+        Arc, Condvar, Mutex, MutexGuard, PoisonError, RwLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    task::{Context, Poll, Waker},
+};
+
+pub(crate) type FrameCallback = Arc<dyn Fn(&Frame) + Send + Sync>;
+
+/// Subscriber removed when dropped, see [`Subscribers::subscribe`].
+pub(crate) struct Subscription {
+    subscribers: Arc<Subscribers>,
+    id: u64,
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.subscribers.remove(self.id);
+    }
+}
+
+/// Callbacks for frames that are not the response to a request.
+#[derive(Default)]
+pub(crate) struct Subscribers {
+    next: AtomicU64,
+    list: RwLock<Vec<(u64, Option<u8>, FrameCallback)>>,
+}
+
+impl Subscribers {
+    /// Call `callback` for frames of `node`, or every frame if [`None`].
+    ///
+    /// Returns an identifier to [`Subscribers::remove`] the callback.
+    pub(crate) fn add(&self, node: Option<u8>, callback: FrameCallback) -> u64 {
+        let id = self.next.fetch_add(1, Ordering::Relaxed);
+        self.list
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push((id, node, callback));
+        id
+    }
+
+    pub(crate) fn remove(&self, id: u64) {
+        self.list
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|(subscriber, ..)| *subscriber != id);
+    }
+
+    /// Call `callback` like [`Subscribers::add`] until the returned
+    /// [`Subscription`] is dropped.
+    pub(crate) fn subscribe(
+        self: &Arc<Self>,
+        node: Option<u8>,
+        callback: FrameCallback,
+    ) -> Subscription {
+        Subscription {
+            subscribers: self.clone(),
+            id: self.add(node, callback),
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.list
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    /// Pass `frame` to the subscribers of its node.
+    pub(crate) fn dispatch(&self, frame: &Frame) {
+        let node = Id::try_from(frame.id()).ok().map(|id| id.node());
+        // callbacks may subscribe, so do not hold the lock while calling them
+        let callbacks: Vec<_> = self
+            .list
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .filter(|(_, subscribed, _)| subscribed.is_none() || *subscribed == node)
+            .map(|(.., callback)| callback.clone())
+            .collect();
+        for callback in callbacks {
+            callback(frame);
+        }
+    }
+}
+
+/// Identifies the response a request is waiting for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Key {
+    id: Id,
+    endpoint: Option<u16>,
+    validation: Validation,
+}
+
+impl Key {
+    pub(crate) fn of<M>(request: &Request<M>) -> Self {
+        Self {
+            id: request.response_id(),
+            endpoint: request.endpoint(),
+            validation: request.validation(),
+        }
+    }
+
+    fn matches(&self, frame: &Frame) -> bool {
+        if frame.id() != self.id.into() || frame.is_remote_frame() {
+            return false;
+        }
+
+        match self.endpoint {
+            Some(endpoint) => self.validation.sdo_endpoint(frame.data()) == Some(endpoint),
+            None => true,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    token: u64,
+    /// Cleared once the response was delivered.
+    key: Option<Key>,
+    frame: Option<Frame>,
+    waker: Option<Waker>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    next: u64,
+    reading: bool,
+    entries: Vec<Entry>,
+}
+
+impl State {
+    fn entry(&mut self, token: u64) -> Option<&mut Entry> {
+        self.entries.iter_mut().find(|entry| entry.token == token)
+    }
+
+    fn wake_all(&mut self) {
+        for entry in &mut self.entries {
+            if let Some(waker) = entry.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+
+    /// Frame routed to `token`, otherwise the reader role if it is free.
+    fn turn<'a>(&mut self, pending: &'a Pending, token: u64) -> Option<Turn<'a>> {
+        if let Some(frame) = self.entry(token).and_then(|entry| entry.frame.take()) {
+            return Some(Turn::Frame(frame));
+        }
+        if !self.reading {
+            self.reading = true;
+            return Some(Turn::Read(Reader(pending)));
+        }
+        None
+    }
+}
+
+/// Requests waiting for responses on one transport.
+#[derive(Debug, Default)]
+pub(crate) struct Pending {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+/// What a waiting request should do next.
+pub(crate) enum Turn<'a> {
+    /// A frame for the request was received.
+    Frame(Frame),
+    /// The request has to read the next frame from the transport.
+    Read(Reader<'a>),
+}
+
+impl Pending {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Start waiting for the response matching `key`.
+    ///
+    /// Without a key no frame is routed to the entry, which is still allowed
+    /// to take turns reading.
+    pub(crate) fn register(&self, key: Option<Key>) -> Registration<'_> {
+        let mut state = self.lock();
+        let token = state.next;
+        state.next += 1;
+        state.entries.push(Entry {
+            token,
+            key,
+            frame: None,
+            waker: None,
+        });
+        Registration {
+            pending: self,
+            token,
+        }
+    }
+
+    /// Deliver `frame` to the oldest request waiting for it.
+    ///
+    /// Every request receives one response only, later frames are delivered
+    /// to the next request waiting for the same response. Returns the frame
+    /// if no request is waiting for it.
+    pub(crate) fn route(&self, frame: Frame) -> Option<Frame> {
+        let mut state = self.lock();
+        let Some(entry) = state
+            .entries
+            .iter_mut()
+            .find(|entry| entry.key.is_some_and(|key| key.matches(&frame)))
+        else {
+            return Some(frame);
+        };
+
+        entry.key = None;
+        entry.frame = Some(frame);
+        if let Some(waker) = entry.waker.take() {
+            waker.wake();
+        }
+        drop(state);
+        self.changed.notify_all();
+        None
+    }
+}
+
+/// A request waiting for its response, unregistered when dropped.
+pub(crate) struct Registration<'a> {
+    pending: &'a Pending,
+    token: u64,
+}
+
+impl<'a> Registration<'a> {
+    /// Wait for a routed frame or the reader role.
+    pub(crate) fn poll_turn(&self, cx: &mut Context<'_>) -> Poll<Turn<'a>> {
+        let mut state = self.pending.lock();
+        if let Some(turn) = state.turn(self.pending, self.token) {
+            return Poll::Ready(turn);
+        }
+        if let Some(entry) = state.entry(self.token) {
+            entry.waker = Some(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+
+    /// Block until a frame is routed or the reader role is free.
+    ///
+    /// Returns [`None`] if `deadline` passed first.
+    #[cfg(feature = "blocking")]
+    pub(crate) fn wait_turn(&self, deadline: Option<std::time::Instant>) -> Option<Turn<'a>> {
+        let mut state = self.pending.lock();
+        loop {
+            if let Some(turn) = state.turn(self.pending, self.token) {
+                return Some(turn);
+            }
+            state = match deadline {
+                Some(deadline) => {
+                    let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+                    if remaining.is_zero() {
+                        return None;
+                    }
+                    self.pending
+                        .changed
+                        .wait_timeout(state, remaining)
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0
+                }
+                None => self
+                    .pending
+                    .changed
+                    .wait(state)
+                    .unwrap_or_else(PoisonError::into_inner),
+            };
+        }
+    }
+}
+
+impl Drop for Registration<'_> {
+    fn drop(&mut self) {
+        self.pending
+            .lock()
+            .entries
+            .retain(|entry| entry.token != self.token);
+    }
+}
+
+/// The reader role, released when dropped.
+pub(crate) struct Reader<'a>(&'a Pending);
+
+impl Drop for Reader<'_> {
+    fn drop(&mut self) {
+        let mut state = self.0.lock();
+        state.reading = false;
+        state.wake_all();
+        drop(state);
+        self.0.changed.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol;
+    use std::task::Waker;
+
+    fn poll<'a>(registration: &Registration<'a>) -> Poll<Turn<'a>> {
+        registration.poll_turn(&mut Context::from_waker(Waker::noop()))
+    }
+
+    #[test]
+    fn routes_responses() {
+        let pending = Pending::default();
+        let iq = pending.register(Some(Key::of(&protocol::get_iq(1).unwrap())));
+        let sdo = pending.register(Some(Key::of(&protocol::sdo_read(1, 3).unwrap())));
+
+        let Poll::Ready(Turn::Read(reader)) = poll(&iq) else {
+            panic!("first request should read");
+        };
+        assert!(poll(&sdo).is_pending());
+
+        let id = |command| Id::new(1, command).unwrap();
+        let sdo_response = Frame::new(id(0x05), &[0, 3, 0, 0, 1, 0, 0, 0]).unwrap();
+        let other_endpoint = Frame::new(id(0x05), &[0, 4, 0, 0, 1, 0, 0, 0]).unwrap();
+        let short = Frame::new(id(0x05), &[0, 3, 0, 0, 1]).unwrap();
+        // a frame the request would reject does not take its place
+        assert_eq!(pending.route(short), Some(short));
+        assert_eq!(pending.route(sdo_response), None);
+        assert_eq!(pending.route(other_endpoint), Some(other_endpoint));
+        drop(reader);
+
+        assert!(matches!(poll(&sdo), Poll::Ready(Turn::Frame(frame)) if frame == sdo_response));
+        assert!(matches!(poll(&sdo), Poll::Ready(Turn::Read(_))));
+    }
+
+    #[test]
+    fn subscribers_by_node() {
+        let subscribers = Subscribers::default();
+        let counts = Arc::new(Mutex::new([0; 3]));
+        for (i, node) in [Some(1), Some(2), None].into_iter().enumerate() {
+            let counts = counts.clone();
+            subscribers.add(node, Arc::new(move |_| counts.lock().unwrap()[i] += 1));
+        }
+        let removed = subscribers.add(Some(1), Arc::new(|_| panic!("removed")));
+        subscribers.remove(removed);
+        let subscribers = Arc::new(subscribers);
+        drop(subscribers.subscribe(None, Arc::new(|_| panic!("unsubscribed"))));
+
+        subscribers.dispatch(&Frame::new(Id::new(1, 0x01).unwrap(), &[]).unwrap());
+        let extended = embedded_can::ExtendedId::new(0x1234).unwrap();
+        subscribers.dispatch(&Frame::new(extended, &[]).unwrap());
+        assert_eq!(*counts.lock().unwrap(), [1, 0, 2]);
+    }
+
+    #[test]
+    fn same_response_in_order() {
+        let pending = Pending::default();
+        let first = pending.register(Some(Key::of(&protocol::get_iq(1).unwrap())));
+        let second = pending.register(Some(Key::of(&protocol::get_iq(1).unwrap())));
+
+        let response = Frame::new(Id::new(1, 0x14).unwrap(), &[0; 8]).unwrap();
+        pending.route(response);
+        pending.route(response);
+        assert!(matches!(poll(&first), Poll::Ready(Turn::Frame(_))));
+        assert!(matches!(poll(&second), Poll::Ready(Turn::Frame(_))));
+
+        pending.route(response);
+        drop(first);
+        // the response routed to a cancelled request is not redelivered
+        assert!(matches!(poll(&second), Poll::Ready(Turn::Read(_))));
+    }
+}
@@ -0,0 +1,2740 @@
+//! # Sans-IO protocol core
+//!
+//! Transport-free implementation of the CANSimple protocol used by ODrives.
+//! Every command is available as a function that returns the [`Frame`] to
+//! send, queries return a [`Request`] which also knows how to recognise and
+//! decode the matching response. Both fail with
+//! [`Error::InvalidNode`](crate::Error::InvalidNode) if the node is out of
+//! range.
+//!
+//! The drivers in [`can`](crate::can) and `blocking` are thin wrappers around
+//! this module, but it can equally be used to drive other transports or to
+//! decode recorded bus traffic.
+//!
+//! The wire format is symmetric: every `decode_*` function for messages sent
+//! by the ODrive has an `encode_*` counterpart returning the data bytes, and
+//! every command with arguments has a `decode_*` counterpart returning them,
+//! such as [`decode_set_input_velocity`] for [`set_input_velocity`].
+//!
+//! ```
+//! use odrive::protocol;
+//!
+//! let request = protocol::get_bus_voltage_current(1).unwrap();
+//! assert!(request.frame().is_remote_frame());
+//!
+//! // Feed received frames to the request until it resolves.
+//! let mut data = [0; 8];
+//! data[..4].copy_from_slice(&24.0_f32.to_le_bytes());
+//! let response = protocol::Frame::new(request.response_id(), &data).unwrap();
+//! let bus = request.response(&response).unwrap().unwrap();
+//! assert_eq!(bus.voltage, 24.0);
+//! ```
+
+use crate::Result;
+#[cfg(feature = "flat-endpoints")]
+use crate::flat_endpoints::FlatEndpoints;
+use crate::{AxisErrors, AxisState, ControlMode, InputMode, ProcedureResult};
+pub use cansimple::Command;
+use cansimple::{EncodeError, Id, Message};
+use std::fmt;
+use std::str::FromStr;
+#[cfg(feature = "flat-endpoints")]
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Maximum data length of a CAN FD frame.
+pub const FD_MAX_LEN: usize = 64;
+
+/// Valid CAN FD data lengths.
+const FD_LENGTHS: [usize; 16] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 12, 16, 20, 24, 32, 48, 64];
+
+/// Classic CAN or CAN FD frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame {
+    id: embedded_can::Id,
+    remote: bool,
+    fd: bool,
+    brs: bool,
+    len: u8,
+    data: [u8; FD_MAX_LEN],
+}
+
+impl Frame {
+    /// Creates a new classic data frame.
+    ///
+    /// Will return [`None`] if `data` is longer than 8 bytes.
+    pub fn new(id: impl Into<embedded_can::Id>, data: &[u8]) -> Option<Self> {
+        if data.len() > 8 {
+            return None;
+        }
+
+        let mut buf = [0; FD_MAX_LEN];
+        buf[..data.len()].copy_from_slice(data);
+
+        Some(Self {
+            id: id.into(),
+            remote: false,
+            fd: false,
+            brs: false,
+            len: data.len() as u8,
+            data: buf,
+        })
+    }
+
+    /// Creates a new CAN FD data frame.
+    ///
+    /// `data` is zero padded to the next valid CAN FD length. Will return
+    /// [`None`] if `data` is longer than 64 bytes.
+    pub fn new_fd(id: impl Into<embedded_can::Id>, data: &[u8], brs: bool) -> Option<Self> {
+        let len = *FD_LENGTHS.iter().find(|len| **len >= data.len())?;
+
+        let mut buf = [0; FD_MAX_LEN];
+        buf[..data.len()].copy_from_slice(data);
+
+        Some(Self {
+            id: id.into(),
+            remote: false,
+            fd: true,
+            brs,
+            len: len as u8,
+            data: buf,
+        })
+    }
+
+    /// Creates a new remote frame.
+    ///
+    /// Will return [`None`] if `dlc` is > 8.
+    pub fn new_remote(id: impl Into<embedded_can::Id>, dlc: usize) -> Option<Self> {
+        if dlc > 8 {
+            return None;
+        }
+
+        Some(Self {
+            id: id.into(),
+            remote: true,
+            fd: false,
+            brs: false,
+            len: dlc as u8,
+            data: [0; FD_MAX_LEN],
+        })
+    }
+
+    /// Frame identifier.
+    pub fn id(&self) -> embedded_can::Id {
+        self.id
+    }
+
+    /// Returns true if this is a remote frame.
+    pub fn is_remote_frame(&self) -> bool {
+        self.remote
+    }
+
+    /// Returns true if this is a CAN FD frame.
+    pub fn is_fd(&self) -> bool {
+        self.fd
+    }
+
+    /// Returns true if the data phase of this CAN FD frame uses bit rate
+    /// switching.
+    pub fn is_brs(&self) -> bool {
+        self.brs
+    }
+
+    /// Data length in bytes.
+    ///
+    /// For remote frames this is the requested length.
+    pub fn dlc(&self) -> usize {
+        self.len as usize
+    }
+
+    /// Frame data.
+    ///
+    /// Empty for remote frames.
+    pub fn data(&self) -> &[u8] {
+        if self.remote {
+            &[]
+        } else {
+            &self.data[..self.len as usize]
+        }
+    }
+
+    /// Mutable frame data, empty for remote frames.
+    #[cfg(feature = "can")]
+    pub(crate) fn data_mut(&mut self) -> &mut [u8] {
+        if self.remote {
+            &mut []
+        } else {
+            &mut self.data[..self.len as usize]
+        }
+    }
+
+    /// Convert this frame to the given format.
+    ///
+    /// Remote frames do not exist in CAN FD and are returned unchanged.
+    pub fn with_format(self, format: FrameFormat) -> Self {
+        if self.remote {
+            return self;
+        }
+
+        match format {
+            FrameFormat::Classic if self.fd && self.len <= 8 => Self {
+                fd: false,
+                brs: false,
+                ..self
+            },
+            FrameFormat::Fd { bit_rate_switch } => Self {
+                fd: true,
+                brs: bit_rate_switch,
+                ..self
+            },
+            _ => self,
+        }
+    }
+}
+
+/// Format used for transmitted frames.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FrameFormat {
+    /// Classic CAN 2.0 frames.
+    #[default]
+    Classic,
+    /// CAN FD frames, optionally with bit rate switching.
+    Fd { bit_rate_switch: bool },
+}
+
+/// Acceptance filter for received frames.
+///
+/// A frame is accepted when its identifier has the same kind as `id` and
+/// `frame_id & mask == id & mask`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Filter {
+    pub id: embedded_can::Id,
+    pub mask: u32,
+}
+
+impl Filter {
+    /// Mask selecting the node bits of a CANSimple identifier.
+    const NODE_MASK: u32 = 0x7e0;
+
+    /// Accept every CANSimple message from or to `node`, including SDO
+    /// responses.
+    pub fn node(node: u8) -> Result<Self> {
+        Ok(Self {
+            id: id(node, 0)?.into(),
+            mask: Self::NODE_MASK,
+        })
+    }
+
+    /// Returns true if `frame` passes this filter.
+    pub fn matches(&self, frame: &Frame) -> bool {
+        use embedded_can::Id::{Extended, Standard};
+
+        match (self.id, frame.id()) {
+            (Standard(id), Standard(other)) => {
+                (id.as_raw() as u32 ^ other.as_raw() as u32) & self.mask == 0
+            }
+            (Extended(id), Extended(other)) => (id.as_raw() ^ other.as_raw()) & self.mask == 0,
+            _ => false,
+        }
+    }
+}
+
+impl From<cansimple::Filter> for Filter {
+    fn from(filter: cansimple::Filter) -> Self {
+        Self {
+            id: filter.id.into(),
+            mask: filter.mask as u32,
+        }
+    }
+}
+
+impl embedded_can::Frame for Frame {
+    fn new(id: impl Into<embedded_can::Id>, data: &[u8]) -> Option<Self> {
+        Frame::new(id, data)
+    }
+
+    fn new_remote(id: impl Into<embedded_can::Id>, dlc: usize) -> Option<Self> {
+        Frame::new_remote(id, dlc)
+    }
+
+    fn is_extended(&self) -> bool {
+        matches!(self.id, embedded_can::Id::Extended(_))
+    }
+
+    fn is_remote_frame(&self) -> bool {
+        self.remote
+    }
+
+    fn id(&self) -> embedded_can::Id {
+        self.id
+    }
+
+    fn dlc(&self) -> usize {
+        self.len as usize
+    }
+
+    fn data(&self) -> &[u8] {
+        Frame::data(self)
+    }
+}
+
+/// CANSimple command identifiers as numbers for matching, see [`Command`].
+pub mod command {
+    use super::Command;
+
+    pub const GET_VERSION: u8 = Command::GetVersion as u8;
+    pub const HEARTBEAT: u8 = Command::Heartbeat as u8;
+    pub const ESTOP: u8 = Command::Estop as u8;
+    pub const GET_ERROR: u8 = Command::GetError as u8;
+    pub const RX_SDO: u8 = Command::RxSdo as u8;
+    pub const TX_SDO: u8 = Command::TxSdo as u8;
+    pub const ADDRESS: u8 = Command::Address as u8;
+    pub const SET_AXIS_STATE: u8 = Command::SetAxisState as u8;
+    pub const GET_ENCODER_ESTIMATES: u8 = Command::GetEncoderEstimates as u8;
+    pub const SET_CONTROLLER_MODE: u8 = Command::SetControllerMode as u8;
+    pub const SET_INPUT_POS: u8 = Command::SetInputPos as u8;
+    pub const SET_INPUT_VEL: u8 = Command::SetInputVel as u8;
+    pub const SET_INPUT_TORQUE: u8 = Command::SetInputTorque as u8;
+    pub const SET_LIMITS: u8 = Command::SetLimits as u8;
+    pub const SET_TRAJ_VEL_LIMIT: u8 = Command::SetTrajVelLimit as u8;
+    pub const SET_TRAJ_ACCEL_LIMITS: u8 = Command::SetTrajAccelLimits as u8;
+    pub const SET_TRAJ_INERTIA: u8 = Command::SetTrajInertia as u8;
+    pub const GET_IQ: u8 = Command::GetIq as u8;
+    pub const GET_TEMPERATURE: u8 = Command::GetTemperature as u8;
+    pub const REBOOT: u8 = Command::Reboot as u8;
+    pub const GET_BUS_VOLTAGE_CURRENT: u8 = Command::GetBusVoltageCurrent as u8;
+    pub const CLEAR_ERRORS: u8 = Command::ClearErrors as u8;
+    pub const SET_ABSOLUTE_POSITION: u8 = Command::SetAbsolutePosition as u8;
+    pub const SET_POS_GAIN: u8 = Command::SetPosGain as u8;
+    pub const SET_VEL_GAINS: u8 = Command::SetVelGains as u8;
+    pub const GET_TORQUES: u8 = Command::GetTorques as u8;
+    pub const GET_POWERS: u8 = Command::GetPowers as u8;
+    pub const ENTER_DFU_MODE: u8 = Command::EnterDfuMode as u8;
+}
+
+/// Oldest firmware supporting `command`, [`None`] for unknown commands.
+///
+/// Firmware before 0.6.0 used another protocol, so every command needs at
+/// least 0.6.0.
+pub fn supported_since(command: u8) -> Option<(u8, u8, u8)> {
+    Some(match command {
+        command::GET_VERSION => (0, 6, 1),
+        command::SET_ABSOLUTE_POSITION => (0, 6, 3),
+        command::ENTER_DFU_MODE => (0, 6, 8),
+        command::GET_POWERS => (0, 6, 9),
+        command::HEARTBEAT..=command::GET_TORQUES => (0, 6, 0),
+        _ => return None,
+    })
+}
+
+/// Fails with [`crate::Error::UnsupportedByFirmware`] if `frame` is a
+/// command that `firmware` does not support, see [`supported_since`].
+pub fn check_supported(frame: &Frame, firmware: (u8, u8, u8)) -> Result<()> {
+    let Ok(id) = Id::try_from(frame.id()) else {
+        return Ok(());
+    };
+    let command = id.command();
+    match supported_since(command) {
+        Some(required) if firmware < required => Err(crate::Error::UnsupportedByFirmware {
+            command,
+            required,
+            actual: firmware,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Transmit priority class of a frame, highest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// Emergency stop, clearing errors and axis state changes.
+    Safety,
+    /// Input position, velocity and torque setpoints.
+    Setpoint,
+    /// Requests for telemetry and parameter reads.
+    Query,
+    /// Configuration writes and everything else.
+    Config,
+}
+
+impl Priority {
+    /// Number of priority classes.
+    pub const COUNT: usize = 4;
+
+    /// Priority class of a CANSimple frame.
+    pub fn of(frame: &Frame) -> Self {
+        let embedded_can::Id::Standard(id) = frame.id() else {
+            return Priority::Config;
+        };
+        if frame.is_remote_frame() {
+            return Priority::Query;
+        }
+
+        match Id::from(id).command() {
+            command::ESTOP | command::CLEAR_ERRORS | command::SET_AXIS_STATE => Priority::Safety,
+            command::SET_INPUT_POS | command::SET_INPUT_VEL | command::SET_INPUT_TORQUE => {
+                Priority::Setpoint
+            }
+            // opcode 0 is an SDO read
+            command::RX_SDO if frame.data().first() == Some(&0) => Priority::Query,
+            _ => Priority::Config,
+        }
+    }
+}
+
+/// Data length of every CANSimple response.
+const RESPONSE_LEN: usize = 8;
+
+/// Length check applied to responses.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Validation {
+    /// Responses must have exactly the expected length.
+    #[default]
+    Strict,
+    /// Shorter responses are zero filled and padding beyond the expected
+    /// length is ignored, for gateways that do not preserve the DLC.
+    Lenient,
+}
+
+impl Validation {
+    /// Endpoint of the SDO response `data`, if its length is valid.
+    pub(crate) fn sdo_endpoint(self, data: &[u8]) -> Option<u16> {
+        if self == Self::Strict && data.len() != RESPONSE_LEN {
+            return None;
+        }
+        let byte = |at: usize| data.get(at).copied().unwrap_or(0);
+        Some(u16::from_le_bytes([byte(1), byte(2)]))
+    }
+}
+
+/// How the drivers query messages that are requested with remote frames.
+///
+/// Some gateways cannot transmit remote frames, the other modes do without.
+#[derive(Debug, Clone, Default)]
+pub enum ReadMode {
+    /// Send a remote frame.
+    #[default]
+    Remote,
+    /// Use the cyclic broadcast of the message.
+    ///
+    /// The last broadcast is used if it is younger than `max_age`, otherwise
+    /// the next one is awaited. Requests for messages that are not broadcast
+    /// time out.
+    Telemetry { max_age: Duration },
+    /// Read the values of the message with SDO requests.
+    ///
+    /// Fails with [`Error::Config`](crate::Error::Config) for messages
+    /// without matching endpoints, such as the version.
+    #[cfg(feature = "flat-endpoints")]
+    Sdo(Arc<FlatEndpoints>),
+}
+
+/// Endpoints holding the two values of a message requested with a remote
+/// frame, see [`ReadMode::Sdo`].
+#[cfg(feature = "flat-endpoints")]
+pub(crate) fn sdo_endpoints(command: u8) -> Option<[&'static str; 2]> {
+    Some(match command {
+        command::GET_ERROR => ["axis0.active_errors", "axis0.disarm_reason"],
+        command::GET_ENCODER_ESTIMATES => ["axis0.pos_estimate", "axis0.vel_estimate"],
+        command::GET_IQ => ["axis0.motor.foc.Iq_setpoint", "axis0.motor.foc.Iq_measured"],
+        command::GET_TEMPERATURE => [
+            "axis0.motor.fet_thermistor.temperature",
+            "axis0.motor.motor_thermistor.temperature",
+        ],
+        command::GET_BUS_VOLTAGE_CURRENT => ["vbus_voltage", "ibus"],
+        command::GET_TORQUES => [
+            "axis0.controller.effective_torque_setpoint",
+            "axis0.motor.torque_estimate",
+        ],
+        command::GET_POWERS => [
+            "axis0.motor.electrical_power",
+            "axis0.motor.mechanical_power",
+        ],
+        _ => return None,
+    })
+}
+
+/// A query awaiting a response from the ODrive.
+///
+/// [`Request::frame`] is the frame to send, every frame received afterwards
+/// should be passed to [`Request::response`] until it returns [`Some`].
+#[derive(Debug, Clone, Copy)]
+pub struct Request<M> {
+    frame: Frame,
+    response: Id,
+    endpoint: Option<u16>,
+    validation: Validation,
+    decode: fn(&[u8]) -> Result<M>,
+}
+
+impl<M> Request<M> {
+    /// Request for a message that is requested with an RTR frame.
+    fn rtr(node: u8, command: u8, decode: fn(&[u8]) -> Result<M>) -> Result<Self> {
+        let id = id(node, command)?;
+        Ok(Self {
+            frame: Frame {
+                id: id.into(),
+                remote: true,
+                fd: false,
+                brs: false,
+                len: 0,
+                data: [0; FD_MAX_LEN],
+            },
+            response: id,
+            endpoint: None,
+            validation: Validation::Strict,
+            decode,
+        })
+    }
+
+    /// Frame to send to start the request.
+    pub fn frame(&self) -> &Frame {
+        &self.frame
+    }
+
+    /// Identifier of the expected response.
+    pub fn response_id(&self) -> Id {
+        self.response
+    }
+
+    /// Endpoint of an SDO read.
+    #[cfg(feature = "can")]
+    pub(crate) fn endpoint(&self) -> Option<u16> {
+        self.endpoint
+    }
+
+    /// Length check applied to the response.
+    #[cfg(feature = "can")]
+    pub(crate) fn validation(&self) -> Validation {
+        self.validation
+    }
+
+    /// Check the length of the response according to `validation`.
+    ///
+    /// Defaults to [`Validation::Strict`].
+    pub fn with_validation(mut self, validation: Validation) -> Self {
+        self.validation = validation;
+        self
+    }
+
+    /// Handle a received frame.
+    ///
+    /// Returns [`None`] if the frame is not the response to this request,
+    /// otherwise the decoded response.
+    pub fn response(&self, frame: &Frame) -> Option<Result<M>> {
+        if frame.id() != self.response.into() || frame.is_remote_frame() {
+            return None;
+        }
+
+        let mut padded = [0; RESPONSE_LEN];
+        let data = match self.validation {
+            Validation::Strict => frame.data(),
+            Validation::Lenient => {
+                let data = &frame.data()[..frame.data().len().min(RESPONSE_LEN)];
+                padded[..data.len()].copy_from_slice(data);
+                &padded
+            }
+        };
+
+        if let Some(endpoint) = self.endpoint
+            && self.validation.sdo_endpoint(data) != Some(endpoint)
+        {
+            return None;
+        }
+
+        Some((self.decode)(data))
+    }
+
+    /// Decode response data assembled from SDO reads.
+    #[cfg(feature = "flat-endpoints")]
+    pub(crate) fn decode_data(&self, data: &[u8; RESPONSE_LEN]) -> Result<M> {
+        (self.decode)(data)
+    }
+}
+
+/// CANSimple identifier of `command` for `node`.
+fn id(node: u8, command: u8) -> Result<Id> {
+    Id::new(node, command).ok_or(crate::Error::InvalidNode(node))
+}
+
+/// Frame of `message` for `node`.
+fn message_frame(node: u8, message: Message) -> Result<Frame> {
+    let mut data = [0; 8];
+    let (id, len) = message.encode(node, &mut data).map_err(|err| match err {
+        EncodeError::InvalidNode(node) => crate::Error::InvalidNode(node),
+        EncodeError::BufferTooShort { expected, actual } => {
+            crate::Error::InvalidLength { expected, actual }
+        }
+    })?;
+    let data = data.get(..len).unwrap_or_default();
+    Frame::new(id, data).ok_or(crate::Error::InvalidLength {
+        expected: 8,
+        actual: data.len(),
+    })
+}
+
+/// Data of a message of eight bytes, such as the telemetry messages.
+fn message_data(message: Message) -> [u8; 8] {
+    let mut data = [0; 8];
+    // node 0 and an eight byte buffer fit every message
+    let _ = message.encode(0, &mut data);
+    data
+}
+
+fn check_length(data: &[u8], length: usize) -> Result<()> {
+    if data.len() != length {
+        return Err(crate::Error::InvalidLength {
+            expected: length,
+            actual: data.len(),
+        });
+    }
+
+    Ok(())
+}
+
+fn f32_pair(data: &[u8]) -> Result<(f32, f32)> {
+    check_length(data, 8)?;
+
+    Ok((
+        f32::from_le_bytes([data[0], data[1], data[2], data[3]]),
+        f32::from_le_bytes([data[4], data[5], data[6], data[7]]),
+    ))
+}
+
+fn decode_4_bytes(data: &[u8]) -> Result<[u8; 4]> {
+    check_length(data, 4)?;
+
+    Ok([data[0], data[1], data[2], data[3]])
+}
+
+/// Get version information.
+pub fn get_version(node: u8) -> Result<Request<Version>> {
+    Request::rtr(node, command::GET_VERSION, decode_version)
+}
+
+/// Decode a version message.
+pub fn decode_version(data: &[u8]) -> Result<Version> {
+    check_length(data, 8)?;
+
+    Ok(Version {
+        protocol_version: data[0],
+        hw_version_major: data[1],
+        hw_version_minor: data[2],
+        hw_version_variant: data[3],
+        fw_version_major: data[4],
+        fw_version_minor: data[5],
+        fw_version_revision: data[6],
+        fw_version_unreleased: data[7] == 1,
+    })
+}
+
+/// Encode a version message.
+pub fn encode_version(version: &Version) -> [u8; 8] {
+    message_data(Message::GetVersion {
+        protocol_version: version.protocol_version,
+        hw_version_major: version.hw_version_major,
+        hw_version_minor: version.hw_version_minor,
+        hw_version_variant: version.hw_version_variant,
+        fw_version_major: version.fw_version_major,
+        fw_version_minor: version.fw_version_minor,
+        fw_version_revision: version.fw_version_revision,
+        fw_version_unreleased: version.fw_version_unreleased,
+    })
+}
+
+/// Cause the axis to disarm.
+pub fn estop(node: u8) -> Result<Frame> {
+    message_frame(node, Message::Estop)
+}
+
+/// Decode a heartbeat message.
+pub fn decode_heartbeat(data: &[u8]) -> Result<Heartbeat> {
+    check_length(data, 8)?;
+
+    let axis_state = AxisState::try_from(data[4])
+        .map_err(|state| crate::Error::Protocol(format!("unknown axis state {state}")))?;
+    let procedure_result = ProcedureResult::try_from(data[5])
+        .map_err(|result| crate::Error::Protocol(format!("unknown procedure result {result}")))?;
+
+    Ok(Heartbeat {
+        axis_error: AxisErrors::from_bits_retain(u32::from_le_bytes([
+            data[0], data[1], data[2], data[3],
+        ])),
+        axis_state,
+        procedure_result,
+        trajectory_done: data[6] != 0,
+    })
+}
+
+/// Encode a heartbeat message.
+pub fn encode_heartbeat(heartbeat: &Heartbeat) -> [u8; 8] {
+    message_data(Message::Heartbeat {
+        axis_error: heartbeat.axis_error.bits(),
+        axis_state: heartbeat.axis_state as u8,
+        procedure_result: heartbeat.procedure_result as u8,
+        trajectory_done: heartbeat.trajectory_done,
+    })
+}
+
+/// Get errors.
+pub fn get_error(node: u8) -> Result<Request<Error>> {
+    Request::rtr(node, command::GET_ERROR, decode_error)
+}
+
+/// Decode an error message.
+pub fn decode_error(data: &[u8]) -> Result<Error> {
+    check_length(data, 8)?;
+
+    Ok(Error {
+        active_errors: AxisErrors::from_bits_retain(u32::from_le_bytes([
+            data[0], data[1], data[2], data[3],
+        ])),
+        disarm_reason: AxisErrors::from_bits_retain(u32::from_le_bytes([
+            data[4], data[5], data[6], data[7],
+        ])),
+    })
+}
+
+/// Encode an error message.
+pub fn encode_error(error: &Error) -> [u8; 8] {
+    message_data(Message::GetError {
+        active_errors: error.active_errors.bits(),
+        disarm_reason: error.disarm_reason.bits(),
+    })
+}
+
+/// Write an arbitrary parameter.
+///
+/// Fails with [`Error::ValueTooWide`](crate::Error::ValueTooWide) for
+/// 64-bit values, see [`check_sdo_kind`].
+pub fn sdo_write(node: u8, endpoint: u16, value: Value) -> Result<Frame> {
+    check_sdo_kind(value.kind())?;
+
+    message_frame(
+        node,
+        Message::RxSdo {
+            opcode: 1, // write
+            endpoint,
+            value: value.to_le_bytes(),
+        },
+    )
+}
+
+/// Read an arbitrary parameter.
+///
+/// Resolves to the raw value bytes, see [`Value::from_le_bytes`].
+pub fn sdo_read(node: u8, endpoint: u16) -> Result<Request<[u8; 4]>> {
+    let read = Message::RxSdo {
+        opcode: 0, // read
+        endpoint,
+        value: [0; 4],
+    };
+
+    Ok(Request {
+        frame: message_frame(node, read)?,
+        response: id(node, command::TX_SDO)?,
+        endpoint: Some(endpoint),
+        validation: Validation::Strict,
+        decode: decode_sdo_value,
+    })
+}
+
+/// Check that values of `kind` fit the 4 value bytes of an SDO transfer.
+///
+/// Fails with [`Error::ValueTooWide`](crate::Error::ValueTooWide) for
+/// 64-bit kinds, the protocol has no way to move them.
+pub fn check_sdo_kind(kind: ValueKind) -> Result<()> {
+    match kind {
+        ValueKind::U64 | ValueKind::I64 | ValueKind::Double => {
+            Err(crate::Error::ValueTooWide(kind))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Decode an SDO request.
+///
+/// Returns the endpoint and the value bytes of a write, or [`None`] for a
+/// read.
+pub fn decode_sdo_request(data: &[u8]) -> Result<(u16, Option<[u8; 4]>)> {
+    check_length(data, 8)?;
+
+    let endpoint = u16::from_le_bytes([data[1], data[2]]);
+    match data[0] {
+        0 => Ok((endpoint, None)),
+        1 => Ok((endpoint, Some([data[4], data[5], data[6], data[7]]))),
+        opcode => Err(crate::Error::Protocol(format!(
+            "unknown SDO opcode {opcode}"
+        ))),
+    }
+}
+
+/// Decode the value bytes of an SDO response.
+pub fn decode_sdo_value(data: &[u8]) -> Result<[u8; 4]> {
+    check_length(data, 8)?;
+
+    Ok([data[4], data[5], data[6], data[7]])
+}
+
+/// Encode an SDO response with the value bytes of `endpoint`.
+pub fn encode_sdo_value(endpoint: u16, value: [u8; 4]) -> [u8; 8] {
+    message_data(Message::TxSdo { endpoint, value })
+}
+
+/// Change the axis state.
+pub fn set_axis_state(node: u8, state: AxisState) -> Result<Frame> {
+    message_frame(
+        node,
+        Message::SetAxisState {
+            axis_requested_state: state as u32,
+        },
+    )
+}
+
+/// Decode an axis state change.
+pub fn decode_set_axis_state(data: &[u8]) -> Result<AxisState> {
+    let state = u32::from_le_bytes(decode_4_bytes(data)?);
+    u8::try_from(state)
+        .ok()
+        .and_then(|state| AxisState::try_from(state).ok())
+        .ok_or_else(|| crate::Error::Protocol(format!("unknown axis state {state}")))
+}
+
+/// Get the encoder estimates.
+pub fn get_encoder_estimates(node: u8) -> Result<Request<EncoderEstimate>> {
+    Request::rtr(
+        node,
+        command::GET_ENCODER_ESTIMATES,
+        decode_encoder_estimates,
+    )
+}
+
+/// Decode an encoder estimates message.
+pub fn decode_encoder_estimates(data: &[u8]) -> Result<EncoderEstimate> {
+    let (position, velocity) = f32_pair(data)?;
+    Ok(EncoderEstimate { position, velocity })
+}
+
+/// Encode an encoder estimates message.
+pub fn encode_encoder_estimates(estimate: &EncoderEstimate) -> [u8; 8] {
+    message_data(Message::GetEncoderEstimates {
+        position: estimate.position,
+        velocity: estimate.velocity,
+    })
+}
+
+/// Set the control loop mode.
+pub fn set_controller_mode(
+    node: u8,
+    control_mode: ControlMode,
+    input_mode: InputMode,
+) -> Result<Frame> {
+    message_frame(
+        node,
+        Message::SetControllerMode {
+            control_mode: control_mode as u32,
+            input_mode: input_mode as u32,
+        },
+    )
+}
+
+/// Decode a control loop mode change.
+pub fn decode_set_controller_mode(data: &[u8]) -> Result<(ControlMode, InputMode)> {
+    check_length(data, 8)?;
+
+    let control = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+    let input = u32::from_le_bytes([data[4], data[5], data[6], data[7]]);
+    let control_mode = u8::try_from(control)
+        .ok()
+        .and_then(|mode| ControlMode::try_from(mode).ok())
+        .ok_or_else(|| crate::Error::Protocol(format!("unknown control mode {control}")))?;
+    let input_mode = u8::try_from(input)
+        .ok()
+        .and_then(|mode| InputMode::try_from(mode).ok())
+        .ok_or_else(|| crate::Error::Protocol(format!("unknown input mode {input}")))?;
+    Ok((control_mode, input_mode))
+}
+
+/// Set input position.
+///
+/// - `position` rev.
+/// - `velocity` 0.001 rev/s (default).
+/// - `torque` 0.001 Nm (default).
+pub fn set_input_position(node: u8, position: f32, velocity: i16, torque: i16) -> Result<Frame> {
+    message_frame(
+        node,
+        Message::SetInputPos {
+            position,
+            velocity_ff: velocity,
+            torque_ff: torque,
+        },
+    )
+}
+
+/// Decode an input position.
+///
+/// Returns (position, velocity, torque) in the units of
+/// [`set_input_position`].
+pub fn decode_set_input_position(data: &[u8]) -> Result<(f32, i16, i16)> {
+    check_length(data, 8)?;
+
+    Ok((
+        f32::from_le_bytes([data[0], data[1], data[2], data[3]]),
+        i16::from_le_bytes([data[4], data[5]]),
+        i16::from_le_bytes([data[6], data[7]]),
+    ))
+}
+
+/// Set input velocity.
+///
+/// - `velocity` rev/s.
+/// - `torque` Nm.
+pub fn set_input_velocity(node: u8, velocity: f32, torque: f32) -> Result<Frame> {
+    message_frame(
+        node,
+        Message::SetInputVel {
+            velocity,
+            torque_ff: torque,
+        },
+    )
+}
+
+/// Decode an input velocity.
+///
+/// Returns (velocity, torque).
+pub fn decode_set_input_velocity(data: &[u8]) -> Result<(f32, f32)> {
+    f32_pair(data)
+}
+
+/// Set input torque.
+///
+/// - `torque` Nm.
+pub fn set_input_torque(node: u8, torque: f32) -> Result<Frame> {
+    message_frame(node, Message::SetInputTorque { torque })
+}
+
+/// Decode an input torque.
+pub fn decode_set_input_torque(data: &[u8]) -> Result<f32> {
+    decode_4_bytes(data).map(f32::from_le_bytes)
+}
+
+/// Set limits.
+///
+/// - `velocity` limit rev/s.
+/// - `current` limit amps.
+pub fn set_limits(node: u8, velocity: f32, current: f32) -> Result<Frame> {
+    message_frame(node, Message::SetLimits { velocity, current })
+}
+
+/// Decode limits.
+///
+/// Returns (velocity, current).
+pub fn decode_set_limits(data: &[u8]) -> Result<(f32, f32)> {
+    f32_pair(data)
+}
+
+/// Set trajectory velocity limit.
+///
+/// `velocity` limit rev/s.
+pub fn set_trajectory_velocity_limit(node: u8, velocity: f32) -> Result<Frame> {
+    message_frame(node, Message::SetTrajVelLimit { velocity })
+}
+
+/// Decode a trajectory velocity limit.
+pub fn decode_set_trajectory_velocity_limit(data: &[u8]) -> Result<f32> {
+    decode_4_bytes(data).map(f32::from_le_bytes)
+}
+
+/// Set trajectory acceleration limits.
+///
+/// `acceleration` limit rev/s^2.
+/// `deceleration` limit rev/s^2.
+pub fn set_trajectory_acceleration_limit(
+    node: u8,
+    acceleration: f32,
+    deceleration: f32,
+) -> Result<Frame> {
+    message_frame(
+        node,
+        Message::SetTrajAccelLimits {
+            acceleration,
+            deceleration,
+        },
+    )
+}
+
+/// Decode trajectory acceleration limits.
+///
+/// Returns (acceleration, deceleration).
+pub fn decode_set_trajectory_acceleration_limit(data: &[u8]) -> Result<(f32, f32)> {
+    f32_pair(data)
+}
+
+/// Set trajectory inertia.
+///
+/// `inertia` Nm/(rev/s^2).
+pub fn set_trajectory_inertia(node: u8, inertia: f32) -> Result<Frame> {
+    message_frame(node, Message::SetTrajInertia { inertia })
+}
+
+/// Decode a trajectory inertia.
+pub fn decode_set_trajectory_inertia(data: &[u8]) -> Result<f32> {
+    decode_4_bytes(data).map(f32::from_le_bytes)
+}
+
+/// Get motor current.
+///
+/// Response: (setpoint, measured)
+pub fn get_iq(node: u8) -> Result<Request<(f32, f32)>> {
+    Request::rtr(node, command::GET_IQ, decode_iq)
+}
+
+/// Decode a motor current message.
+///
+/// Returns (setpoint, measured).
+pub fn decode_iq(data: &[u8]) -> Result<(f32, f32)> {
+    f32_pair(data)
+}
+
+/// Encode a motor current message from (setpoint, measured).
+pub fn encode_iq(iq: (f32, f32)) -> [u8; 8] {
+    message_data(Message::GetIq {
+        setpoint: iq.0,
+        measured: iq.1,
+    })
+}
+
+/// Get temperature.
+pub fn get_temperature(node: u8) -> Result<Request<Temperature>> {
+    Request::rtr(node, command::GET_TEMPERATURE, decode_temperature)
+}
+
+/// Decode a temperature message.
+pub fn decode_temperature(data: &[u8]) -> Result<Temperature> {
+    let (fet, motor) = f32_pair(data)?;
+    Ok(Temperature { fet, motor })
+}
+
+/// Encode a temperature message.
+pub fn encode_temperature(temperature: &Temperature) -> [u8; 8] {
+    message_data(Message::GetTemperature {
+        fet: temperature.fet,
+        motor: temperature.motor,
+    })
+}
+
+/// Reboot the device.
+pub fn reboot(node: u8) -> Result<Frame> {
+    message_frame(node, Message::Reboot { action: 0 })
+}
+
+/// Get bus voltage and current.
+pub fn get_bus_voltage_current(node: u8) -> Result<Request<BusVoltageCurrent>> {
+    Request::rtr(
+        node,
+        command::GET_BUS_VOLTAGE_CURRENT,
+        decode_bus_voltage_current,
+    )
+}
+
+/// Decode a bus voltage and current message.
+pub fn decode_bus_voltage_current(data: &[u8]) -> Result<BusVoltageCurrent> {
+    let (voltage, current) = f32_pair(data)?;
+    Ok(BusVoltageCurrent { voltage, current })
+}
+
+/// Encode a bus voltage and current message.
+pub fn encode_bus_voltage_current(bus: &BusVoltageCurrent) -> [u8; 8] {
+    message_data(Message::GetBusVoltageCurrent {
+        voltage: bus.voltage,
+        current: bus.current,
+    })
+}
+
+/// Save configuration.
+pub fn save_configuration(node: u8) -> Result<Frame> {
+    message_frame(node, Message::Reboot { action: 1 })
+}
+
+/// Erase configuration.
+pub fn erase_configuration(node: u8) -> Result<Frame> {
+    message_frame(node, Message::Reboot { action: 2 })
+}
+
+/// Enter DFU mode 2.
+pub fn enter_dfu_mode2(node: u8) -> Result<Frame> {
+    message_frame(node, Message::Reboot { action: 3 })
+}
+
+/// Clear disarm reason and procedure result.
+pub fn clear_errors(node: u8, identify: bool) -> Result<Frame> {
+    message_frame(node, Message::ClearErrors { identify })
+}
+
+/// Decode a clear errors command.
+///
+/// Returns whether the ODrive should identify itself.
+pub fn decode_clear_errors(data: &[u8]) -> Result<bool> {
+    check_length(data, 1)?;
+
+    Ok(data[0] != 0)
+}
+
+/// Set the absolute position estimate.
+///
+/// - `position` rev.
+pub fn set_absolute_position(node: u8, position: f32) -> Result<Frame> {
+    message_frame(node, Message::SetAbsolutePosition { position })
+}
+
+/// Decode an absolute position.
+pub fn decode_set_absolute_position(data: &[u8]) -> Result<f32> {
+    decode_4_bytes(data).map(f32::from_le_bytes)
+}
+
+/// Set position gain.
+///
+/// - `gain` (rev/s)/rev.
+pub fn set_position_gain(node: u8, gain: f32) -> Result<Frame> {
+    message_frame(node, Message::SetPosGain { gain })
+}
+
+/// Decode a position gain.
+pub fn decode_set_position_gain(data: &[u8]) -> Result<f32> {
+    decode_4_bytes(data).map(f32::from_le_bytes)
+}
+
+/// Set velocity gains.
+///
+/// - `gain` Nm/(rev/s).
+/// - `integrator_gain` Nm/rev.
+pub fn set_velocity_gains(node: u8, gain: f32, integrator_gain: f32) -> Result<Frame> {
+    message_frame(
+        node,
+        Message::SetVelGains {
+            gain,
+            integrator_gain,
+        },
+    )
+}
+
+/// Decode velocity gains.
+///
+/// Returns (gain, integrator_gain).
+pub fn decode_set_velocity_gains(data: &[u8]) -> Result<(f32, f32)> {
+    f32_pair(data)
+}
+
+/// Get torque values.
+pub fn get_torques(node: u8) -> Result<Request<Torque>> {
+    Request::rtr(node, command::GET_TORQUES, decode_torques)
+}
+
+/// Decode a torques message.
+pub fn decode_torques(data: &[u8]) -> Result<Torque> {
+    let (target, estimate) = f32_pair(data)?;
+    Ok(Torque { target, estimate })
+}
+
+/// Encode a torques message.
+pub fn encode_torques(torque: &Torque) -> [u8; 8] {
+    message_data(Message::GetTorques {
+        target: torque.target,
+        estimate: torque.estimate,
+    })
+}
+
+/// Get power values.
+pub fn get_powers(node: u8) -> Result<Request<Power>> {
+    Request::rtr(node, command::GET_POWERS, decode_powers)
+}
+
+/// Decode a powers message.
+pub fn decode_powers(data: &[u8]) -> Result<Power> {
+    let (electrical, mechanical) = f32_pair(data)?;
+    Ok(Power {
+        electrical,
+        mechanical,
+    })
+}
+
+/// Encode a powers message.
+pub fn encode_powers(power: &Power) -> [u8; 8] {
+    message_data(Message::GetPowers {
+        electrical: power.electrical,
+        mechanical: power.mechanical,
+    })
+}
+
+/// Heartbeat message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Heartbeat {
+    /// Active errors
+    pub axis_error: AxisErrors,
+    /// Current axis state
+    pub axis_state: AxisState,
+    /// Result of the last procedure
+    pub procedure_result: ProcedureResult,
+    /// Trajectory finished
+    pub trajectory_done: bool,
+}
+
+/// Message broadcast cyclically by an ODrive.
+///
+/// The broadcast rates are configured with the `*_msg_rate_ms` endpoints.
+///
+/// Serializes with the [`name`](Self::name) in a `message` field.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "message", rename_all = "snake_case")
+)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TelemetryMessage {
+    Heartbeat(Heartbeat),
+    EncoderEstimates(EncoderEstimate),
+    Iq { setpoint: f32, measured: f32 },
+    Temperature(Temperature),
+    BusVoltageCurrent(BusVoltageCurrent),
+    Torques(Torque),
+    Powers(Power),
+}
+
+impl TelemetryMessage {
+    /// Decode a received frame.
+    ///
+    /// Returns [`None`] if the frame is not a telemetry message.
+    pub fn decode(frame: &Frame) -> Option<Result<Self>> {
+        let embedded_can::Id::Standard(id) = frame.id() else {
+            return None;
+        };
+        if frame.is_remote_frame() {
+            return None;
+        }
+
+        let data = frame.data();
+        Some(match Id::from(id).command() {
+            command::HEARTBEAT => decode_heartbeat(data).map(Self::Heartbeat),
+            command::GET_ENCODER_ESTIMATES => {
+                decode_encoder_estimates(data).map(Self::EncoderEstimates)
+            }
+            command::GET_IQ => {
+                decode_iq(data).map(|(setpoint, measured)| Self::Iq { setpoint, measured })
+            }
+            command::GET_TEMPERATURE => decode_temperature(data).map(Self::Temperature),
+            command::GET_BUS_VOLTAGE_CURRENT => {
+                decode_bus_voltage_current(data).map(Self::BusVoltageCurrent)
+            }
+            command::GET_TORQUES => decode_torques(data).map(Self::Torques),
+            command::GET_POWERS => decode_powers(data).map(Self::Powers),
+            _ => return None,
+        })
+    }
+
+    /// Name of the message, such as `encoder_estimates`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Heartbeat(_) => "heartbeat",
+            Self::EncoderEstimates(_) => "encoder_estimates",
+            Self::Iq { .. } => "iq",
+            Self::Temperature(_) => "temperature",
+            Self::BusVoltageCurrent(_) => "bus_voltage_current",
+            Self::Torques(_) => "torques",
+            Self::Powers(_) => "powers",
+        }
+    }
+
+    /// Values of the message by field name.
+    ///
+    /// Heartbeat errors, states and results are given by their numeric
+    /// values.
+    pub fn fields(&self) -> Vec<(&'static str, f64)> {
+        match *self {
+            Self::Heartbeat(heartbeat) => vec![
+                ("axis_error", heartbeat.axis_error.bits() as f64),
+                ("axis_state", heartbeat.axis_state as u8 as f64),
+                ("procedure_result", heartbeat.procedure_result as u8 as f64),
+                ("trajectory_done", heartbeat.trajectory_done as u8 as f64),
+            ],
+            Self::EncoderEstimates(estimate) => vec![
+                ("position", estimate.position as f64),
+                ("velocity", estimate.velocity as f64),
+            ],
+            Self::Iq { setpoint, measured } => {
+                vec![("setpoint", setpoint as f64), ("measured", measured as f64)]
+            }
+            Self::Temperature(temperature) => vec![
+                ("fet", temperature.fet as f64),
+                ("motor", temperature.motor as f64),
+            ],
+            Self::BusVoltageCurrent(bus) => vec![
+                ("voltage", bus.voltage as f64),
+                ("current", bus.current as f64),
+            ],
+            Self::Torques(torque) => vec![
+                ("target", torque.target as f64),
+                ("estimate", torque.estimate as f64),
+            ],
+            Self::Powers(power) => vec![
+                ("electrical", power.electrical as f64),
+                ("mechanical", power.mechanical as f64),
+            ],
+        }
+    }
+}
+
+/// Version information.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Version {
+    pub protocol_version: u8,
+    pub hw_version_major: u8,
+    pub hw_version_minor: u8,
+    pub hw_version_variant: u8,
+    pub fw_version_major: u8,
+    pub fw_version_minor: u8,
+    pub fw_version_revision: u8,
+    pub fw_version_unreleased: bool,
+}
+
+impl Version {
+    /// Firmware major, minor and revision, compared in that order.
+    pub fn firmware(&self) -> (u8, u8, u8) {
+        (
+            self.fw_version_major,
+            self.fw_version_minor,
+            self.fw_version_revision,
+        )
+    }
+
+    /// Whether the firmware is `major.minor.revision` or newer.
+    ///
+    /// Unreleased builds count as the release they report.
+    pub fn fw_at_least(&self, major: u8, minor: u8, revision: u8) -> bool {
+        self.firmware() >= (major, minor, revision)
+    }
+
+    /// Fails with [`crate::Error::FirmwareTooOld`] if the firmware is older
+    /// than `major.minor.revision`.
+    pub fn require_firmware(&self, major: u8, minor: u8, revision: u8) -> Result<()> {
+        if self.fw_at_least(major, minor, revision) {
+            Ok(())
+        } else {
+            Err(crate::Error::FirmwareTooOld {
+                required: (major, minor, revision),
+                actual: self.firmware(),
+            })
+        }
+    }
+}
+
+/// Firmware and hardware version such as `fw 0.6.8 / hw 4.4-58V`, with a
+/// `-dev` suffix for unreleased firmware.
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (major, minor, revision) = self.firmware();
+        write!(f, "fw {major}.{minor}.{revision}")?;
+        if self.fw_version_unreleased {
+            f.write_str("-dev")?;
+        }
+        write!(
+            f,
+            " / hw {}.{}-{}V",
+            self.hw_version_major, self.hw_version_minor, self.hw_version_variant
+        )
+    }
+}
+
+/// Error message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Error {
+    /// Active errors
+    pub active_errors: AxisErrors,
+    /// Reason for disarm
+    pub disarm_reason: AxisErrors,
+}
+
+impl Error {
+    /// Fails with [`crate::Error::Device`] if any errors are active.
+    pub fn check(&self) -> Result<()> {
+        if self.active_errors.is_empty() {
+            Ok(())
+        } else {
+            Err(crate::Error::Device(self.active_errors))
+        }
+    }
+}
+
+/// Encoder estimates.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct EncoderEstimate {
+    /// Position estimate in revolutions
+    pub position: f32,
+    /// Velocity estimate in rev/s
+    pub velocity: f32,
+}
+
+/// Temperature message.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Temperature {
+    /// FET temperature
+    pub fet: f32,
+    /// Motor temperature
+    pub motor: f32,
+}
+
+/// Bus voltage and current.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BusVoltageCurrent {
+    /// Bus voltage in volts
+    pub voltage: f32,
+    /// Bus current in amps
+    pub current: f32,
+}
+
+/// Torque values
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Torque {
+    /// Torque target in Nm
+    pub target: f32,
+    /// Torque estimate in Nm
+    pub estimate: f32,
+}
+
+/// Power values.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Power {
+    /// Electrical power in watts
+    pub electrical: f32,
+    /// Mechanical power in watts
+    pub mechanical: f32,
+}
+
+/// Arbitrary parameter value.
+///
+/// Serializes with its type name used in the flat endpoints, such as
+/// `{"type": "float", "value": 2.5}`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "type", content = "value")
+)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Value {
+    #[cfg_attr(feature = "serde", serde(rename = "bool"))]
+    Bool(bool),
+    #[cfg_attr(feature = "serde", serde(rename = "uint8"))]
+    U8(u8),
+    #[cfg_attr(feature = "serde", serde(rename = "int8"))]
+    I8(i8),
+    #[cfg_attr(feature = "serde", serde(rename = "uint16"))]
+    U16(u16),
+    #[cfg_attr(feature = "serde", serde(rename = "int16"))]
+    I16(i16),
+    #[cfg_attr(feature = "serde", serde(rename = "uint32"))]
+    U32(u32),
+    #[cfg_attr(feature = "serde", serde(rename = "int32"))]
+    I32(i32),
+    #[cfg_attr(feature = "serde", serde(rename = "float"))]
+    Float(f32),
+    #[cfg_attr(feature = "serde", serde(rename = "uint64"))]
+    U64(u64),
+    #[cfg_attr(feature = "serde", serde(rename = "int64"))]
+    I64(i64),
+    #[cfg_attr(feature = "serde", serde(rename = "float64"))]
+    Double(f64),
+}
+
+impl Value {
+    /// Convert to a const length slice.
+    ///
+    /// Any unused bytes will be zero. 64-bit values do not fit and are cut
+    /// to their low four bytes, SDO transfers reject them, see
+    /// [`check_sdo_kind`].
+    pub fn to_le_bytes(&self) -> [u8; 4] {
+        let low = |data: [u8; 8]| [data[0], data[1], data[2], data[3]];
+        match *self {
+            Self::Bool(b) => [b as u8, 0, 0, 0],
+            Self::U8(u) => [u, 0, 0, 0],
+            Self::I8(i) => [i.to_le_bytes()[0], 0, 0, 0],
+            Self::U16(u) => [u.to_le_bytes()[0], u.to_le_bytes()[1], 0, 0],
+            Self::I16(i) => [i.to_le_bytes()[0], i.to_le_bytes()[1], 0, 0],
+            Self::U32(u) => u.to_le_bytes(),
+            Self::I32(i) => i.to_le_bytes(),
+            Self::Float(f) => f.to_le_bytes(),
+            Self::U64(u) => low(u.to_le_bytes()),
+            Self::I64(i) => low(i.to_le_bytes()),
+            Self::Double(f) => low(f.to_le_bytes()),
+        }
+    }
+
+    /// Whether `other` equals this value, floats within `tolerance` relative
+    /// to the larger magnitude.
+    ///
+    /// The ODrive stores most values as `f32`, so floats rarely read back bit
+    /// for bit. [`Value::Float`] and [`Value::Double`] compare with each
+    /// other and NaN equals NaN. Other kinds only equal the same kind and
+    /// value.
+    pub fn approx_eq(&self, other: &Value, tolerance: f64) -> bool {
+        let float = |value: &Value| match *value {
+            Self::Float(f) => Some(f64::from(f)),
+            Self::Double(f) => Some(f),
+            _ => None,
+        };
+        match (float(self), float(other)) {
+            (Some(this), Some(other)) => {
+                this == other
+                    || (this.is_nan() && other.is_nan())
+                    || (this.is_finite()
+                        && other.is_finite()
+                        && (this - other).abs() <= tolerance * this.abs().max(other.abs()))
+            }
+            _ => self == other,
+        }
+    }
+
+    pub fn kind(&self) -> ValueKind {
+        match self {
+            Self::Bool(_) => ValueKind::Bool,
+            Self::U8(_) => ValueKind::U8,
+            Self::I8(_) => ValueKind::I8,
+            Self::U16(_) => ValueKind::U16,
+            Self::I16(_) => ValueKind::I16,
+            Self::U32(_) => ValueKind::U32,
+            Self::I32(_) => ValueKind::I32,
+            Self::Float(_) => ValueKind::Float,
+            Self::U64(_) => ValueKind::U64,
+            Self::I64(_) => ValueKind::I64,
+            Self::Double(_) => ValueKind::Double,
+        }
+    }
+
+    /// Interpret raw value bytes as the given kind.
+    ///
+    /// 64-bit kinds take the bytes as their low four bytes.
+    pub fn from_le_bytes(kind: ValueKind, data: [u8; 4]) -> Self {
+        let [a, b, c, d] = data;
+        let wide = [a, b, c, d, 0, 0, 0, 0];
+        match kind {
+            ValueKind::Bool => Value::Bool(data[0] == 1),
+            ValueKind::U8 => Value::U8(data[0]),
+            ValueKind::I8 => Value::I8(i8::from_le_bytes([data[0]])),
+            ValueKind::U16 => Value::U16(u16::from_le_bytes([data[0], data[1]])),
+            ValueKind::I16 => Value::I16(i16::from_le_bytes([data[0], data[1]])),
+            ValueKind::U32 => Value::U32(u32::from_le_bytes(data)),
+            ValueKind::I32 => Value::I32(i32::from_le_bytes(data)),
+            ValueKind::Float => Value::Float(f32::from_le_bytes(data)),
+            ValueKind::U64 => Value::U64(u64::from_le_bytes(wide)),
+            ValueKind::I64 => Value::I64(i64::from_le_bytes(wide)),
+            ValueKind::Double => Value::Double(f64::from_le_bytes(wide)),
+        }
+    }
+
+    /// Value as `f64` if exactly representable, which excludes bools and
+    /// 64-bit integers above 2^53.
+    pub fn as_f64(&self) -> Option<f64> {
+        f64::try_from(*self).ok()
+    }
+
+    /// Value as `i64` if integral and in range, which excludes bools.
+    pub fn as_i64(&self) -> Option<i64> {
+        i64::try_from(*self).ok()
+    }
+
+    /// Value as `u64` if integral and in range, which excludes bools.
+    pub fn as_u64(&self) -> Option<u64> {
+        u64::try_from(*self).ok()
+    }
+
+    /// Returns [`None`] unless the value is a [`Value::Bool`].
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            Self::Bool(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// Integers widened to `i128`, floats to `f64`.
+    fn number(&self) -> Option<Number> {
+        Some(match *self {
+            Self::Bool(_) => return None,
+            Self::U8(u) => Number::Int(u.into()),
+            Self::I8(i) => Number::Int(i.into()),
+            Self::U16(u) => Number::Int(u.into()),
+            Self::I16(i) => Number::Int(i.into()),
+            Self::U32(u) => Number::Int(u.into()),
+            Self::I32(i) => Number::Int(i.into()),
+            Self::Float(f) => Number::Float(f.into()),
+            Self::U64(u) => Number::Int(u.into()),
+            Self::I64(i) => Number::Int(i.into()),
+            Self::Double(f) => Number::Float(f),
+        })
+    }
+
+    /// Parse `text` as a value of `kind`, the inverse of the [`Display`]
+    /// implementation.
+    ///
+    /// [`Display`]: fmt::Display
+    pub fn parse(kind: ValueKind, text: &str) -> Result<Self, ParseValueError> {
+        fn number<T: FromStr>(text: &str) -> Option<T> {
+            text.parse().ok()
+        }
+
+        let text = text.trim();
+        let value = match kind {
+            ValueKind::Bool => number(text).map(Self::Bool),
+            ValueKind::U8 => number(text).map(Self::U8),
+            ValueKind::I8 => number(text).map(Self::I8),
+            ValueKind::U16 => number(text).map(Self::U16),
+            ValueKind::I16 => number(text).map(Self::I16),
+            ValueKind::U32 => number(text).map(Self::U32),
+            ValueKind::I32 => number(text).map(Self::I32),
+            ValueKind::Float => number(text).map(Self::Float),
+            ValueKind::U64 => number(text).map(Self::U64),
+            ValueKind::I64 => number(text).map(Self::I64),
+            ValueKind::Double => number(text).map(Self::Double),
+        };
+        value.ok_or_else(|| ParseValueError {
+            text: text.to_owned(),
+        })
+    }
+
+    #[cfg(feature = "flat-endpoints")]
+    pub fn try_from_json(value: &serde_json::Value, kind: ValueKind) -> Option<Self> {
+        match kind {
+            ValueKind::Bool => value.as_bool().map(Self::Bool),
+            ValueKind::U8 => value.as_u64().map(|b| Self::U8(b as u8)),
+            ValueKind::I8 => value.as_i64().map(|b| Self::I8(b as i8)),
+            ValueKind::U16 => value.as_u64().map(|b| Self::U16(b as u16)),
+            ValueKind::I16 => value.as_i64().map(|b| Self::I16(b as i16)),
+            ValueKind::U32 => value.as_u64().map(|b| Self::U32(b as u32)),
+            ValueKind::I32 => value.as_i64().map(|b| Self::I32(b as i32)),
+            ValueKind::Float => value
+                .as_number()
+                .and_then(|n| n.as_f64())
+                .map(|f| Self::Float(f as f32)),
+            ValueKind::U64 => value.as_u64().map(Self::U64),
+            ValueKind::I64 => value.as_i64().map(Self::I64),
+            ValueKind::Double => value.as_number().and_then(|n| n.as_f64()).map(Self::Double),
+        }
+    }
+
+    /// JSON number or bool, the inverse of [`Self::try_from_json`].
+    ///
+    /// Floats are converted through their shortest representation, so
+    /// `0.1f32` becomes `0.1` rather than `0.10000000149011612`. Non-finite
+    /// floats become `null`.
+    #[cfg(feature = "flat-endpoints")]
+    pub fn to_json(&self) -> serde_json::Value {
+        match *self {
+            Self::Bool(b) => b.into(),
+            Self::U8(u) => u.into(),
+            Self::I8(i) => i.into(),
+            Self::U16(u) => u.into(),
+            Self::I16(i) => i.into(),
+            Self::U32(u) => u.into(),
+            Self::I32(i) => i.into(),
+            Self::Float(f) => f.to_string().parse::<f64>().unwrap_or(f64::NAN).into(),
+            Self::U64(u) => u.into(),
+            Self::I64(i) => i.into(),
+            Self::Double(f) => f.into(),
+        }
+    }
+}
+
+/// Floats always print a decimal point or exponent, so [`FromStr`] reads
+/// them back as floats.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bool(b) => b.fmt(f),
+            Self::U8(u) => u.fmt(f),
+            Self::I8(i) => i.fmt(f),
+            Self::U16(u) => u.fmt(f),
+            Self::I16(i) => i.fmt(f),
+            Self::U32(u) => u.fmt(f),
+            Self::I32(i) => i.fmt(f),
+            Self::Float(x) => write!(f, "{x:?}"),
+            Self::U64(u) => u.fmt(f),
+            Self::I64(i) => i.fmt(f),
+            Self::Double(x) => write!(f, "{x:?}"),
+        }
+    }
+}
+
+/// Parse a value of unknown kind.
+///
+/// Accepts `true`, `false` and numbers with an optional Rust type suffix
+/// such as `7u8` or `2.5f64`. Numbers without suffix are [`Value::I32`],
+/// or [`Value::I64`] and [`Value::U64`] if out of range, or
+/// [`Value::Float`] with a decimal point or exponent. Use [`Value::parse`]
+/// if the kind is known, such as from the flat endpoints.
+impl FromStr for Value {
+    type Err = ParseValueError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        const SUFFIXES: [(&str, ValueKind); 10] = [
+            ("u8", ValueKind::U8),
+            ("i8", ValueKind::I8),
+            ("u16", ValueKind::U16),
+            ("i16", ValueKind::I16),
+            ("u32", ValueKind::U32),
+            ("i32", ValueKind::I32),
+            ("f32", ValueKind::Float),
+            ("u64", ValueKind::U64),
+            ("i64", ValueKind::I64),
+            ("f64", ValueKind::Double),
+        ];
+
+        let text = text.trim();
+        let error = || ParseValueError {
+            text: text.to_owned(),
+        };
+        if let Some((number, kind)) = SUFFIXES
+            .iter()
+            .find_map(|(suffix, kind)| Some((text.strip_suffix(suffix)?, *kind)))
+        {
+            return Self::parse(kind, number.strip_suffix('_').unwrap_or(number))
+                .map_err(|_| error());
+        }
+
+        if let Ok(b) = text.parse() {
+            Ok(Self::Bool(b))
+        } else if let Ok(i) = text.parse::<i64>() {
+            Ok(i32::try_from(i).map_or(Self::I64(i), Self::I32))
+        } else if let Ok(u) = text.parse() {
+            Ok(Self::U64(u))
+        } else {
+            text.parse().map(Self::Float).map_err(|_| error())
+        }
+    }
+}
+
+/// Text that is not a value, see [`Value::parse`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("invalid value {text:?}")]
+pub struct ParseValueError {
+    pub text: String,
+}
+
+enum Number {
+    Int(i128),
+    Float(f64),
+}
+
+/// Value that does not fit the type converted to without loss, see
+/// [`Value::as_f64`].
+#[derive(Debug, Clone, Copy, PartialEq, thiserror::Error)]
+#[error("{value:?} does not fit {target}")]
+pub struct TryFromValueError {
+    pub value: Value,
+    /// Name of the type converted to.
+    pub target: &'static str,
+}
+
+impl TryFromValueError {
+    fn new<T>(value: Value) -> Self {
+        Self {
+            value,
+            target: std::any::type_name::<T>(),
+        }
+    }
+}
+
+/// Integers convert if in range, floats only if integral.
+macro_rules! try_from_value_int {
+    ($($ty:ty),*) => {$(
+        impl TryFrom<Value> for $ty {
+            type Error = TryFromValueError;
+
+            fn try_from(value: Value) -> Result<Self, Self::Error> {
+                let int = match value.number() {
+                    Some(Number::Int(i)) => Some(i),
+                    // saturates out of range, which no target accepts
+                    Some(Number::Float(f)) if f.fract() == 0.0 => Some(f as i128),
+                    _ => None,
+                };
+                int.and_then(|i| <$ty>::try_from(i).ok())
+                    .ok_or_else(|| TryFromValueError::new::<$ty>(value))
+            }
+        }
+    )*};
+}
+
+try_from_value_int!(u8, i8, u16, i16, u32, i32, u64, i64);
+
+/// Integers convert if exactly representable, doubles if they round-trip.
+macro_rules! try_from_value_float {
+    ($($ty:ty),*) => {$(
+        impl TryFrom<Value> for $ty {
+            type Error = TryFromValueError;
+
+            fn try_from(value: Value) -> Result<Self, Self::Error> {
+                let float = match value.number() {
+                    Some(Number::Int(i)) => Some(i as $ty).filter(|f| *f as i128 == i),
+                    Some(Number::Float(f)) => {
+                        Some(f as $ty).filter(|g| f64::from(*g) == f || f.is_nan())
+                    }
+                    None => None,
+                };
+                float.ok_or_else(|| TryFromValueError::new::<$ty>(value))
+            }
+        }
+    )*};
+}
+
+try_from_value_float!(f32, f64);
+
+impl TryFrom<Value> for bool {
+    type Error = TryFromValueError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        value
+            .as_bool()
+            .ok_or_else(|| TryFromValueError::new::<bool>(value))
+    }
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// Primitive type of an arbitrary parameter, see
+/// [`ODrive::sdo_read_as`](crate::can::ODrive::sdo_read_as).
+///
+/// Implemented for `bool`, `u8`, `i8`, `u16`, `i16`, `u32`, `i32`, `f32`,
+/// `u64`, `i64` and `f64`.
+pub trait SdoType: sealed::Sealed + Into<Value> + Sized {
+    const KIND: ValueKind;
+
+    /// Returns [`None`] if `value` is of another kind.
+    fn from_value(value: Value) -> Option<Self>;
+}
+
+macro_rules! sdo_type {
+    ($($ty:ty => $variant:ident),* $(,)?) => {$(
+        impl sealed::Sealed for $ty {}
+
+        impl SdoType for $ty {
+            const KIND: ValueKind = ValueKind::$variant;
+
+            fn from_value(value: Value) -> Option<Self> {
+                match value {
+                    Value::$variant(value) => Some(value),
+                    _ => None,
+                }
+            }
+        }
+
+        impl From<$ty> for Value {
+            fn from(value: $ty) -> Self {
+                Value::$variant(value)
+            }
+        }
+    )*};
+}
+
+sdo_type!(
+    bool => Bool,
+    u8 => U8,
+    i8 => I8,
+    u16 => U16,
+    i16 => I16,
+    u32 => U32,
+    i32 => I32,
+    f32 => Float,
+    u64 => U64,
+    i64 => I64,
+    f64 => Double,
+);
+
+/// Arbitrary parameter value kind.
+///
+/// Serializes as its type name used in the flat endpoints, see the
+/// [`Display`](fmt::Display) implementation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ValueKind {
+    #[cfg_attr(feature = "serde", serde(rename = "bool"))]
+    Bool,
+    #[cfg_attr(feature = "serde", serde(rename = "uint8"))]
+    U8,
+    #[cfg_attr(feature = "serde", serde(rename = "int8"))]
+    I8,
+    #[cfg_attr(feature = "serde", serde(rename = "uint16"))]
+    U16,
+    #[cfg_attr(feature = "serde", serde(rename = "int16"))]
+    I16,
+    #[cfg_attr(feature = "serde", serde(rename = "uint32"))]
+    U32,
+    #[cfg_attr(feature = "serde", serde(rename = "int32"))]
+    I32,
+    #[cfg_attr(feature = "serde", serde(rename = "float"))]
+    Float,
+    #[cfg_attr(feature = "serde", serde(rename = "uint64"))]
+    U64,
+    #[cfg_attr(feature = "serde", serde(rename = "int64"))]
+    I64,
+    #[cfg_attr(feature = "serde", serde(rename = "float64"))]
+    Double,
+}
+
+/// Type name used in the flat endpoints, such as `uint32` or `float`.
+impl fmt::Display for ValueKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Bool => "bool",
+            Self::U8 => "uint8",
+            Self::I8 => "int8",
+            Self::U16 => "uint16",
+            Self::I16 => "int16",
+            Self::U32 => "uint32",
+            Self::I32 => "int32",
+            Self::Float => "float",
+            Self::U64 => "uint64",
+            Self::I64 => "int64",
+            Self::Double => "float64",
+        })
+    }
+}
+
+/// Parse a type name used in the flat endpoints.
+///
+/// References to other endpoints, `endpoint_ref`, are [`ValueKind::U32`]
+/// holding the endpoint id in the low and the JSON CRC in the high half.
+impl FromStr for ValueKind {
+    type Err = ParseKindError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Ok(match text {
+            "bool" => Self::Bool,
+            "uint8" => Self::U8,
+            "int8" => Self::I8,
+            "uint16" => Self::U16,
+            "int16" => Self::I16,
+            "uint32" | "endpoint_ref" => Self::U32,
+            "int32" => Self::I32,
+            "float" => Self::Float,
+            "uint64" => Self::U64,
+            "int64" => Self::I64,
+            "float64" => Self::Double,
+            "function" | "string" => return Err(ParseKindError::NoValue(text.to_owned())),
+            _ => return Err(ParseKindError::Unknown(text.to_owned())),
+        })
+    }
+}
+
+#[cfg(feature = "flat-endpoints")]
+impl TryFrom<&serde_json::Value> for ValueKind {
+    type Error = ParseKindError;
+
+    fn try_from(value: &serde_json::Value) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            Some(text) => text.parse(),
+            None => Err(ParseKindError::NotAString(value.to_string())),
+        }
+    }
+}
+
+/// Type name that is not a [`ValueKind`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ParseKindError {
+    /// Type of endpoints without a fixed size value, `function` or `string`.
+    #[error("{0} endpoints have no value kind")]
+    NoValue(String),
+    #[error("unknown value type {0:?}")]
+    Unknown(String),
+    /// JSON type that is not a string, as JSON.
+    #[error("value type {0} is not a string")]
+    NotAString(String),
+}
+
+/// Error messages of 0.5.x firmware.
+///
+/// 0.5.x reports the error words of the axis components in dedicated
+/// messages. 0.6 reuses their command IDs for other messages, only send these
+/// requests to drives running 0.5.x.
+///
+/// [Reference](https://docs.odriverobotics.com/v/0.5.6/can-protocol.html)
+pub mod legacy {
+    use super::{Request, check_length};
+    use crate::{ControllerErrors, EncoderErrors, MotorErrors, Result, SensorlessErrors};
+
+    pub const GET_MOTOR_ERROR: u8 = 0x003;
+    pub const GET_ENCODER_ERROR: u8 = 0x004;
+    pub const GET_SENSORLESS_ERROR: u8 = 0x005;
+    pub const GET_CONTROLLER_ERROR: u8 = 0x01d;
+
+    /// Get the motor error.
+    pub fn get_motor_error(node: u8) -> Result<Request<MotorErrors>> {
+        Request::rtr(node, GET_MOTOR_ERROR, decode_motor_error)
+    }
+
+    /// Decode a motor error message.
+    pub fn decode_motor_error(data: &[u8]) -> Result<MotorErrors> {
+        check_length(data, 8)?;
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(data);
+        Ok(MotorErrors::from_bits_retain(u64::from_le_bytes(bytes)))
+    }
+
+    /// Get the encoder error.
+    pub fn get_encoder_error(node: u8) -> Result<Request<EncoderErrors>> {
+        Request::rtr(node, GET_ENCODER_ERROR, decode_encoder_error)
+    }
+
+    /// Decode an encoder error message.
+    pub fn decode_encoder_error(data: &[u8]) -> Result<EncoderErrors> {
+        word(data).map(EncoderErrors::from_bits_retain)
+    }
+
+    /// Get the sensorless estimator error.
+    pub fn get_sensorless_error(node: u8) -> Result<Request<SensorlessErrors>> {
+        Request::rtr(node, GET_SENSORLESS_ERROR, decode_sensorless_error)
+    }
+
+    /// Decode a sensorless estimator error message.
+    pub fn decode_sensorless_error(data: &[u8]) -> Result<SensorlessErrors> {
+        word(data).map(SensorlessErrors::from_bits_retain)
+    }
+
+    /// Get the controller error.
+    pub fn get_controller_error(node: u8) -> Result<Request<ControllerErrors>> {
+        Request::rtr(node, GET_CONTROLLER_ERROR, decode_controller_error)
+    }
+
+    /// Decode a controller error message.
+    pub fn decode_controller_error(data: &[u8]) -> Result<ControllerErrors> {
+        word(data).map(ControllerErrors::from_bits_retain)
+    }
+
+    /// Error word in the first four bytes of a message.
+    fn word(data: &[u8]) -> Result<u32> {
+        check_length(data, 8)?;
+        Ok(u32::from_le_bytes([data[0], data[1], data[2], data[3]]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn legacy_errors() {
+        let request = legacy::get_motor_error(3).unwrap();
+        assert!(request.frame().is_remote_frame());
+        assert_eq!(request.frame().id(), id(3, 0x003).unwrap().into());
+
+        let mut data = [0; 8];
+        data[0] = 0x08;
+        data[4] = 0x08;
+        assert_eq!(
+            legacy::decode_motor_error(&data).unwrap(),
+            crate::MotorErrors::DRV_FAULT | crate::MotorErrors::UNBALANCED_PHASES
+        );
+        assert_eq!(
+            legacy::decode_controller_error(&[0x80, 0, 0, 0, 0, 0, 0, 0]).unwrap(),
+            crate::ControllerErrors::SPINOUT_DETECTED
+        );
+        assert!(legacy::decode_encoder_error(&[0x80, 0, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn value_to_bytes() {
+        let value = Value::Float(1.234);
+        assert_eq!(value.to_le_bytes(), [0xb6, 0xf3, 0x9d, 0x3f]);
+    }
+
+    #[test]
+    fn wide_values_are_rejected() {
+        assert!(matches!(
+            sdo_write(1, 0x9b, Value::U64(0x0123_4567_89ab_cdef)),
+            Err(crate::Error::ValueTooWide(ValueKind::U64))
+        ));
+        let frame = sdo_write(1, 0x9b, Value::U32(7)).unwrap();
+        assert_eq!(frame.data(), &[1, 0x9b, 0, 0, 7, 0, 0, 0]);
+    }
+
+    #[test]
+    fn value_strings() {
+        for value in [
+            Value::Bool(true),
+            Value::U8(255),
+            Value::I16(-3),
+            Value::Float(1.0),
+            Value::Float(0.1),
+            Value::U64(u64::MAX),
+            Value::Double(-2.5e300),
+        ] {
+            let text = value.to_string();
+            assert_eq!(Value::parse(value.kind(), &text).unwrap(), value);
+            assert_eq!(value.kind().to_string().parse(), Ok(value.kind()));
+        }
+        assert_eq!(Value::Float(1.0).to_string(), "1.0");
+        assert_eq!(Value::parse(ValueKind::U8, " 7 "), Ok(Value::U8(7)));
+        assert!(Value::parse(ValueKind::U8, "256").is_err());
+        assert!(Value::parse(ValueKind::Bool, "1").is_err());
+
+        assert_eq!("false".parse(), Ok(Value::Bool(false)));
+        assert_eq!("-7".parse(), Ok(Value::I32(-7)));
+        assert_eq!("4294967296".parse(), Ok(Value::I64(1 << 32)));
+        assert_eq!(u64::MAX.to_string().parse(), Ok(Value::U64(u64::MAX)));
+        assert_eq!("2.5".parse(), Ok(Value::Float(2.5)));
+        assert_eq!("1e3".parse(), Ok(Value::Float(1000.0)));
+        assert_eq!("7u8".parse(), Ok(Value::U8(7)));
+        assert_eq!("2.5_f64".parse(), Ok(Value::Double(2.5)));
+        assert!("256u8".parse::<Value>().is_err());
+        assert!("seven".parse::<Value>().is_err());
+        assert_eq!(
+            "uint128".parse::<ValueKind>(),
+            Err(ParseKindError::Unknown("uint128".into()))
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn value_serde() {
+        use serde_json::json;
+
+        let value = Value::Float(2.5);
+        assert_eq!(
+            serde_json::to_value(value).unwrap(),
+            json!({"type": "float", "value": 2.5})
+        );
+        let value = json!({"type": "uint64", "value": u64::MAX});
+        assert_eq!(
+            serde_json::from_value::<Value>(value).unwrap(),
+            Value::U64(u64::MAX)
+        );
+        assert!(serde_json::from_value::<Value>(json!({"type": "uint8", "value": 256})).is_err());
+
+        assert_eq!(
+            serde_json::to_value(ValueKind::Double).unwrap(),
+            json!("float64")
+        );
+        assert_eq!(
+            serde_json::from_value::<ValueKind>(json!("int16")).unwrap(),
+            ValueKind::I16
+        );
+    }
+
+    #[test]
+    fn value_conversions() {
+        assert_eq!(u8::try_from(Value::U32(255)), Ok(255));
+        assert!(u8::try_from(Value::U32(256)).is_err());
+        assert!(u32::try_from(Value::I8(-1)).is_err());
+        assert_eq!(i64::try_from(Value::U64(1 << 40)), Ok(1 << 40));
+        assert!(i64::try_from(Value::U64(u64::MAX)).is_err());
+        assert_eq!(i32::try_from(Value::Float(-3.0)), Ok(-3));
+        assert!(i32::try_from(Value::Float(2.5)).is_err());
+        assert!(u64::try_from(Value::Double(1e30)).is_err());
+        assert!(u8::try_from(Value::Float(f32::NAN)).is_err());
+
+        assert_eq!(f32::try_from(Value::U32(1 << 24)), Ok(16777216.0));
+        assert!(f32::try_from(Value::U32((1 << 24) + 1)).is_err());
+        assert_eq!(f64::try_from(Value::Float(0.1)), Ok(0.1f32.into()));
+        assert!(f32::try_from(Value::Double(0.1)).is_err());
+        assert_eq!(f32::try_from(Value::Double(0.5)), Ok(0.5));
+        assert!(f32::try_from(Value::Double(f64::NAN)).unwrap().is_nan());
+
+        assert_eq!(bool::try_from(Value::Bool(true)), Ok(true));
+        assert!(bool::try_from(Value::U8(1)).is_err());
+        assert!(u8::try_from(Value::Bool(true)).is_err());
+
+        assert_eq!(Value::U8(7).as_f64(), Some(7.0));
+        assert_eq!(Value::U64(u64::MAX).as_f64(), None);
+        assert_eq!(Value::U64(u64::MAX).as_u64(), Some(u64::MAX));
+        assert_eq!(Value::I16(-2).as_u64(), None);
+        assert_eq!(Value::Double(-2.0).as_i64(), Some(-2));
+        assert_eq!(Value::Bool(false).as_bool(), Some(false));
+
+        let error = u8::try_from(Value::I32(-1)).unwrap_err();
+        assert_eq!(error.to_string(), "I32(-1) does not fit u8");
+    }
+
+    #[cfg(feature = "flat-endpoints")]
+    #[test]
+    fn value_kind_from_json() {
+        use serde_json::json;
+
+        let kind = |value| ValueKind::try_from(&value);
+        assert_eq!(kind(json!("uint64")), Ok(ValueKind::U64));
+        assert_eq!(kind(json!("int64")), Ok(ValueKind::I64));
+        assert_eq!(kind(json!("float64")), Ok(ValueKind::Double));
+        assert_eq!(kind(json!("endpoint_ref")), Ok(ValueKind::U32));
+        assert_eq!(
+            kind(json!("function")),
+            Err(ParseKindError::NoValue("function".into()))
+        );
+        assert_eq!(kind(json!(4)), Err(ParseKindError::NotAString("4".into())));
+        assert_eq!(
+            kind(json!("float32")).unwrap_err().to_string(),
+            "unknown value type \"float32\""
+        );
+    }
+
+    #[test]
+    fn value_approx_eq() {
+        let tolerance = 1e-6;
+        assert!(Value::Float(0.1).approx_eq(&Value::Double(0.1), tolerance));
+        assert!(!Value::Float(0.1).approx_eq(&Value::Double(0.1), 0.0));
+        assert!(Value::Double(1e6).approx_eq(&Value::Double(1e6 + 0.5), tolerance));
+        assert!(!Value::Double(1.0).approx_eq(&Value::Double(1.1), tolerance));
+        assert!(Value::Float(0.0).approx_eq(&Value::Float(-0.0), 0.0));
+        assert!(Value::Float(f32::NAN).approx_eq(&Value::Float(f32::NAN), 0.0));
+        assert!(Value::Float(f32::INFINITY).approx_eq(&Value::Double(f64::INFINITY), 0.0));
+        assert!(!Value::Float(f32::INFINITY).approx_eq(&Value::Float(f32::MAX), tolerance));
+
+        assert!(Value::U32(7).approx_eq(&Value::U32(7), 1.0));
+        assert!(!Value::U32(7).approx_eq(&Value::U32(8), 1.0));
+        assert!(!Value::U32(7).approx_eq(&Value::I32(7), 1.0));
+        assert!(!Value::U32(1).approx_eq(&Value::Float(1.0), 1.0));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn telemetry_serde() {
+        use serde_json::json;
+
+        let heartbeat = TelemetryMessage::Heartbeat(Heartbeat {
+            axis_error: AxisErrors::DC_BUS_UNDER_VOLTAGE,
+            axis_state: AxisState::Idle,
+            procedure_result: ProcedureResult::Success,
+            trajectory_done: true,
+        });
+        let value = serde_json::to_value(heartbeat).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "message": "heartbeat",
+                "axis_error": "DC_BUS_UNDER_VOLTAGE",
+                "axis_state": "Idle",
+                "procedure_result": "Success",
+                "trajectory_done": true
+            })
+        );
+        assert!(matches!(
+            serde_json::from_value(value).unwrap(),
+            TelemetryMessage::Heartbeat(decoded) if decoded.axis_state == AxisState::Idle
+        ));
+
+        let iq = TelemetryMessage::Iq {
+            setpoint: 1.5,
+            measured: 1.25,
+        };
+        assert_eq!(
+            serde_json::to_value(iq).unwrap(),
+            json!({"message": "iq", "setpoint": 1.5, "measured": 1.25})
+        );
+    }
+
+    #[test]
+    fn version() {
+        let version = Version {
+            protocol_version: 2,
+            hw_version_major: 4,
+            hw_version_minor: 4,
+            hw_version_variant: 58,
+            fw_version_major: 0,
+            fw_version_minor: 6,
+            fw_version_revision: 8,
+            fw_version_unreleased: false,
+        };
+        assert_eq!(version.to_string(), "fw 0.6.8 / hw 4.4-58V");
+        assert!(version.fw_at_least(0, 6, 8));
+        assert!(version.fw_at_least(0, 5, 12));
+        assert!(!version.fw_at_least(0, 6, 9));
+        assert!(!version.fw_at_least(1, 0, 0));
+        assert!(version.require_firmware(0, 6, 0).is_ok());
+        assert!(matches!(
+            version.require_firmware(0, 6, 10),
+            Err(crate::Error::FirmwareTooOld {
+                required: (0, 6, 10),
+                actual: (0, 6, 8)
+            })
+        ));
+
+        let unreleased = Version {
+            fw_version_unreleased: true,
+            ..version
+        };
+        assert_eq!(unreleased.to_string(), "fw 0.6.8-dev / hw 4.4-58V");
+    }
+
+    #[test]
+    fn firmware_support() {
+        assert_eq!(supported_since(command::GET_POWERS), Some((0, 6, 9)));
+        assert_eq!(supported_since(command::GET_TORQUES), Some((0, 6, 0)));
+        assert_eq!(supported_since(0x3f), None);
+
+        let powers = get_powers(1).unwrap();
+        assert!(check_supported(powers.frame(), (0, 6, 9)).is_ok());
+        assert!(matches!(
+            check_supported(powers.frame(), (0, 6, 8)),
+            Err(crate::Error::UnsupportedByFirmware {
+                command: command::GET_POWERS,
+                required: (0, 6, 9),
+                actual: (0, 6, 8)
+            })
+        ));
+        assert!(check_supported(&estop(1).unwrap(), (0, 6, 0)).is_ok());
+        assert!(check_supported(&estop(1).unwrap(), (0, 5, 6)).is_err());
+    }
+
+    #[test]
+    fn value_from_bytes() {
+        let value = Value::from_le_bytes(ValueKind::Float, [0xb6, 0xf3, 0x9d, 0x3f]);
+        assert!(matches!(value, Value::Float(f) if f == 1.234));
+    }
+
+    #[test]
+    fn frame_data_length() {
+        let id = embedded_can::StandardId::new(0x029).unwrap();
+        assert!(Frame::new(id, &[0; 9]).is_none());
+
+        let frame = Frame::new(id, &[1, 2, 3]).unwrap();
+        assert_eq!(frame.data(), &[1, 2, 3]);
+        assert_eq!(frame.dlc(), 3);
+    }
+
+    #[test]
+    fn remote_frame_has_no_data() {
+        let id = embedded_can::StandardId::new(0x029).unwrap();
+        let frame = Frame::new_remote(id, 8).unwrap();
+        assert!(frame.is_remote_frame());
+        assert_eq!(frame.dlc(), 8);
+        assert!(frame.data().is_empty());
+    }
+
+    #[test]
+    fn node_filter() {
+        let filter = Filter::node(3).unwrap();
+        let frame = |node, command| Frame::new(Id::new(node, command).unwrap(), &[]).unwrap();
+
+        assert!(filter.matches(&frame(3, command::HEARTBEAT)));
+        assert!(filter.matches(&frame(3, command::TX_SDO)));
+        assert!(!filter.matches(&frame(4, command::HEARTBEAT)));
+
+        let extended = embedded_can::ExtendedId::new(3 << 5).unwrap();
+        assert!(!filter.matches(&Frame::new(extended, &[]).unwrap()));
+    }
+
+    #[test]
+    fn cansimple_filter() {
+        let filter = Filter::from(cansimple::Filter::sdo_responses());
+        let frame = |node, command| Frame::new(Id::new(node, command).unwrap(), &[]).unwrap();
+
+        assert!(filter.matches(&frame(7, command::TX_SDO)));
+        assert!(!filter.matches(&frame(7, command::RX_SDO)));
+        assert_eq!(
+            Filter::from(cansimple::Filter::node(3).unwrap()),
+            Filter::node(3).unwrap()
+        );
+    }
+
+    #[test]
+    fn lenient_validation() {
+        let frame = Frame::new(Id::new(1, 0x15).unwrap(), &[0; 4]).unwrap();
+        let request = get_temperature(1).unwrap();
+        assert!(request.response(&frame).unwrap().is_err());
+
+        let request = request.with_validation(Validation::Lenient);
+        assert!(request.response(&frame).unwrap().is_ok());
+
+        let padded = Frame::new_fd(Id::new(1, 0x15).unwrap(), &[0; 12], false).unwrap();
+        assert!(request.response(&padded).unwrap().is_ok());
+    }
+
+    #[test]
+    fn telemetry_decode() {
+        let frame = Frame::new(Id::new(1, 0x01).unwrap(), &[4, 0, 0, 0, 8, 0, 1, 0]).unwrap();
+        let Some(Ok(TelemetryMessage::Heartbeat(heartbeat))) = TelemetryMessage::decode(&frame)
+        else {
+            panic!("not a heartbeat");
+        };
+        assert_eq!(heartbeat.axis_error, AxisErrors::TIMING_ERROR);
+        assert_eq!(heartbeat.axis_state, AxisState::ClosedLoopControl);
+        assert!(heartbeat.trajectory_done);
+
+        let frame = Frame::new(Id::new(1, 0x14).unwrap(), &encode_iq((1.0, 2.0))).unwrap();
+        assert!(matches!(
+            TelemetryMessage::decode(&frame),
+            Some(Ok(TelemetryMessage::Iq { measured: 2.0, .. }))
+        ));
+
+        let frame = *sdo_read(1, 3).unwrap().frame();
+        assert!(TelemetryMessage::decode(&frame).is_none());
+    }
+
+    #[test]
+    fn frame_priority() {
+        assert_eq!(Priority::of(&estop(1).unwrap()), Priority::Safety);
+        assert_eq!(
+            Priority::of(&clear_errors(1, false).unwrap()),
+            Priority::Safety
+        );
+        assert_eq!(
+            Priority::of(&set_input_torque(1, 0.5).unwrap()),
+            Priority::Setpoint
+        );
+        assert_eq!(Priority::of(get_iq(1).unwrap().frame()), Priority::Query);
+        assert_eq!(
+            Priority::of(sdo_read(1, 2).unwrap().frame()),
+            Priority::Query
+        );
+        assert_eq!(
+            Priority::of(&sdo_write(1, 2, Value::Bool(true)).unwrap()),
+            Priority::Config
+        );
+        assert!(Priority::Safety < Priority::Config);
+    }
+
+    #[test]
+    fn fd_frame_padding() {
+        let id = embedded_can::StandardId::new(0x029).unwrap();
+        let frame = Frame::new_fd(id, &[1; 9], true).unwrap();
+        assert!(frame.is_fd());
+        assert!(frame.is_brs());
+        assert_eq!(frame.data().len(), 12);
+        assert_eq!(&frame.data()[9..], &[0, 0, 0]);
+
+        assert!(Frame::new_fd(id, &[0; 65], false).is_none());
+    }
+
+    #[test]
+    fn frame_format_conversion() {
+        let id = embedded_can::StandardId::new(0x029).unwrap();
+        let fd = FrameFormat::Fd {
+            bit_rate_switch: false,
+        };
+
+        let frame = Frame::new(id, &[1, 2]).unwrap().with_format(fd);
+        assert!(frame.is_fd());
+        assert_eq!(frame.data(), &[1, 2]);
+        assert!(!frame.with_format(FrameFormat::Classic).is_fd());
+
+        let remote = Frame::new_remote(id, 8).unwrap().with_format(fd);
+        assert!(!remote.is_fd());
+    }
+
+    #[test]
+    fn request_matches_response() {
+        let request = get_encoder_estimates(1).unwrap();
+        assert_eq!(request.frame().id(), Id::from_raw(0x029).into());
+
+        // a different node replying is ignored
+        let other = Frame::new(Id::new(2, 0x09).unwrap(), &[0; 8]).unwrap();
+        assert!(request.response(&other).is_none());
+
+        let data = encode_encoder_estimates(&EncoderEstimate {
+            position: 1.0,
+            velocity: 2.0,
+        });
+        let frame = Frame::new(Id::new(1, 0x09).unwrap(), &data).unwrap();
+        let estimate = request.response(&frame).unwrap().unwrap();
+        assert_eq!(estimate.position, 1.0);
+        assert_eq!(estimate.velocity, 2.0);
+    }
+
+    #[test]
+    fn request_rejects_short_response() {
+        let request = get_temperature(1).unwrap();
+        let frame = Frame::new(Id::new(1, 0x15).unwrap(), &[0; 4]).unwrap();
+        assert!(request.response(&frame).unwrap().is_err());
+    }
+
+    #[test]
+    fn sdo_read_matches_endpoint() {
+        let request = sdo_read(1, 3).unwrap();
+        assert_eq!(request.frame().data(), &[0, 3, 0, 0, 0, 0, 0, 0]);
+
+        let id = Id::new(1, 0x05).unwrap();
+        let wrong = Frame::new(id, &[0, 4, 0, 0, 1, 0, 0, 0]).unwrap();
+        assert!(request.response(&wrong).is_none());
+
+        let right = Frame::new(id, &[0, 3, 0, 0, 1, 0, 0, 0]).unwrap();
+        assert_eq!(request.response(&right).unwrap().unwrap(), [1, 0, 0, 0]);
+    }
+
+    #[test]
+    fn sdo_write_frame() {
+        let frame = sdo_write(1, 3, Value::U16(0x1234)).unwrap();
+        assert_eq!(frame.id(), Id::new(1, 0x04).unwrap().into());
+        assert_eq!(frame.data(), &[1, 3, 0, 0, 0x34, 0x12, 0, 0]);
+    }
+
+    /// Command taking two floats and its decoder.
+    type Pair = (
+        fn(u8, f32, f32) -> Result<Frame>,
+        fn(&[u8]) -> Result<(f32, f32)>,
+    );
+    /// Command taking a float and its decoder.
+    type Single = (fn(u8, f32) -> Result<Frame>, fn(&[u8]) -> Result<f32>);
+
+    /// Values of type `T` that `from` accepts.
+    fn known<T: Clone + fmt::Debug + 'static>(
+        from: impl Fn(u8) -> Option<T>,
+    ) -> impl Strategy<Value = T> {
+        prop::sample::select((0..=u8::MAX).filter_map(from).collect::<Vec<_>>())
+    }
+
+    /// Pairs of any floats, NaNs included.
+    fn f32s() -> impl Strategy<Value = (f32, f32)> {
+        any::<(u32, u32)>().prop_map(|(a, b)| (f32::from_bits(a), f32::from_bits(b)))
+    }
+
+    fn bits((a, b): (f32, f32)) -> (u32, u32) {
+        (a.to_bits(), b.to_bits())
+    }
+
+    proptest! {
+        #[test]
+        fn responses_round_trip(
+            (version, unreleased) in any::<([u8; 7], bool)>(),
+            axis_error in any::<u32>(),
+            axis_state in known(|value| AxisState::try_from(value).ok()),
+            procedure_result in known(|value| ProcedureResult::try_from(value).ok()),
+            trajectory_done in any::<bool>(),
+            (active_errors, disarm_reason) in any::<(u32, u32)>(),
+            (endpoint, value) in any::<(u16, [u8; 4])>(),
+            floats in prop::array::uniform6(f32s()),
+        ) {
+            let version = Version {
+                protocol_version: version[0],
+                hw_version_major: version[1],
+                hw_version_minor: version[2],
+                hw_version_variant: version[3],
+                fw_version_major: version[4],
+                fw_version_minor: version[5],
+                fw_version_revision: version[6],
+                fw_version_unreleased: unreleased,
+            };
+            prop_assert_eq!(decode_version(&encode_version(&version)).unwrap(), version);
+
+            let heartbeat = Heartbeat {
+                axis_error: AxisErrors::from_bits_retain(axis_error),
+                axis_state,
+                procedure_result,
+                trajectory_done,
+            };
+            prop_assert_eq!(
+                decode_heartbeat(&encode_heartbeat(&heartbeat)).unwrap(),
+                heartbeat
+            );
+
+            let error = Error {
+                active_errors: AxisErrors::from_bits_retain(active_errors),
+                disarm_reason: AxisErrors::from_bits_retain(disarm_reason),
+            };
+            prop_assert_eq!(decode_error(&encode_error(&error)).unwrap(), error);
+
+            let data = encode_sdo_value(endpoint, value);
+            prop_assert_eq!(decode_sdo_value(&data).unwrap(), value);
+
+            let [estimates, iq, temperature, bus, torques, powers] = floats;
+            let (position, velocity) = estimates;
+            let data = encode_encoder_estimates(&EncoderEstimate { position, velocity });
+            let estimate = decode_encoder_estimates(&data).unwrap();
+            prop_assert_eq!(
+                bits((estimate.position, estimate.velocity)),
+                bits(estimates)
+            );
+
+            prop_assert_eq!(bits(decode_iq(&encode_iq(iq)).unwrap()), bits(iq));
+
+            let (fet, motor) = temperature;
+            let decoded = decode_temperature(&encode_temperature(&Temperature { fet, motor }));
+            let decoded = decoded.unwrap();
+            prop_assert_eq!(bits((decoded.fet, decoded.motor)), bits(temperature));
+
+            let (voltage, current) = bus;
+            let data = encode_bus_voltage_current(&BusVoltageCurrent { voltage, current });
+            let decoded = decode_bus_voltage_current(&data).unwrap();
+            prop_assert_eq!(bits((decoded.voltage, decoded.current)), bits(bus));
+
+            let (target, estimate) = torques;
+            let decoded = decode_torques(&encode_torques(&Torque { target, estimate })).unwrap();
+            prop_assert_eq!(bits((decoded.target, decoded.estimate)), bits(torques));
+
+            let (electrical, mechanical) = powers;
+            let data = encode_powers(&Power {
+                electrical,
+                mechanical,
+            });
+            let decoded = decode_powers(&data).unwrap();
+            prop_assert_eq!(
+                bits((decoded.electrical, decoded.mechanical)),
+                bits(powers)
+            );
+        }
+
+        #[test]
+        fn commands_round_trip(
+            node in 0..0x40u8,
+            (a, b) in f32s(),
+            (endpoint, value) in any::<(u16, u32)>(),
+            state in known(|value| AxisState::try_from(value).ok()),
+            control_mode in known(|value| ControlMode::try_from(value).ok()),
+            input_mode in known(|value| InputMode::try_from(value).ok()),
+            (velocity, torque) in any::<(i16, i16)>(),
+            identify in any::<bool>(),
+        ) {
+            let frame = sdo_write(node, endpoint, Value::U32(value)).unwrap();
+            prop_assert_eq!(
+                decode_sdo_request(frame.data()).unwrap(),
+                (endpoint, Some(value.to_le_bytes()))
+            );
+            let request = sdo_read(node, endpoint).unwrap();
+            prop_assert_eq!(
+                decode_sdo_request(request.frame().data()).unwrap(),
+                (endpoint, None)
+            );
+
+            let frame = set_axis_state(node, state).unwrap();
+            prop_assert_eq!(decode_set_axis_state(frame.data()).unwrap(), state);
+
+            let frame = set_controller_mode(node, control_mode, input_mode).unwrap();
+            prop_assert_eq!(
+                decode_set_controller_mode(frame.data()).unwrap(),
+                (control_mode, input_mode)
+            );
+
+            let frame = set_input_position(node, a, velocity, torque).unwrap();
+            let (position, decoded_velocity, decoded_torque) =
+                decode_set_input_position(frame.data()).unwrap();
+            prop_assert_eq!(
+                (position.to_bits(), decoded_velocity, decoded_torque),
+                (a.to_bits(), velocity, torque)
+            );
+
+            let pairs: [Pair; 4] = [
+                (set_input_velocity, decode_set_input_velocity),
+                (set_limits, decode_set_limits),
+                (
+                    set_trajectory_acceleration_limit,
+                    decode_set_trajectory_acceleration_limit,
+                ),
+                (set_velocity_gains, decode_set_velocity_gains),
+            ];
+            for (encode, decode) in pairs {
+                let frame = encode(node, a, b).unwrap();
+                prop_assert_eq!(bits(decode(frame.data()).unwrap()), bits((a, b)));
+            }
+
+            let singles: [Single; 5] = [
+                (set_input_torque, decode_set_input_torque),
+                (
+                    set_trajectory_velocity_limit,
+                    decode_set_trajectory_velocity_limit,
+                ),
+                (set_trajectory_inertia, decode_set_trajectory_inertia),
+                (set_absolute_position, decode_set_absolute_position),
+                (set_position_gain, decode_set_position_gain),
+            ];
+            for (encode, decode) in singles {
+                let frame = encode(node, a).unwrap();
+                prop_assert_eq!(decode(frame.data()).unwrap().to_bits(), a.to_bits());
+            }
+
+            let frame = clear_errors(node, identify).unwrap();
+            prop_assert_eq!(decode_clear_errors(frame.data()).unwrap(), identify);
+        }
+
+        #[test]
+        fn decoders_never_panic(data in prop::collection::vec(any::<u8>(), 0..=12)) {
+            fuzz_decoders(&data);
+        }
+    }
+
+    /// Run every decoder on `data`, see the `decode` fuzz target.
+    fn fuzz_decoders(data: &[u8]) {
+        let _ = decode_version(data);
+        let _ = decode_heartbeat(data);
+        let _ = decode_error(data);
+        let _ = decode_sdo_value(data);
+        let _ = decode_sdo_request(data);
+        let _ = decode_encoder_estimates(data);
+        let _ = decode_iq(data);
+        let _ = decode_temperature(data);
+        let _ = decode_bus_voltage_current(data);
+        let _ = decode_torques(data);
+        let _ = decode_powers(data);
+        let _ = decode_set_axis_state(data);
+        let _ = decode_set_controller_mode(data);
+        let _ = decode_set_input_position(data);
+        let _ = decode_set_input_velocity(data);
+        let _ = decode_set_input_torque(data);
+        let _ = decode_set_limits(data);
+        let _ = decode_set_trajectory_velocity_limit(data);
+        let _ = decode_set_trajectory_acceleration_limit(data);
+        let _ = decode_set_trajectory_inertia(data);
+        let _ = decode_clear_errors(data);
+        let _ = decode_set_absolute_position(data);
+        let _ = decode_set_position_gain(data);
+        let _ = decode_set_velocity_gains(data);
+        if let Some((&command, data)) = data.split_first() {
+            let id = Id::new(1, command & 0x1f).unwrap();
+            if let Some(frame) = Frame::new(id, data) {
+                let _ = TelemetryMessage::decode(&frame);
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "flat-endpoints")]
+    fn value_from_json() {
+        Value::try_from_json(&serde_json::json!(true), ValueKind::Bool).unwrap();
+        Value::try_from_json(&serde_json::json!(13), ValueKind::U8).unwrap();
+        Value::try_from_json(&serde_json::json!(-13), ValueKind::I8).unwrap();
+        Value::try_from_json(&serde_json::json!(13), ValueKind::U16).unwrap();
+        Value::try_from_json(&serde_json::json!(-13), ValueKind::I16).unwrap();
+        Value::try_from_json(&serde_json::json!(13), ValueKind::U32).unwrap();
+        Value::try_from_json(&serde_json::json!(-13), ValueKind::I32).unwrap();
+        Value::try_from_json(&serde_json::json!(0.0), ValueKind::Float).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "flat-endpoints")]
+    fn value_to_json() {
+        assert_eq!(Value::Bool(true).to_json(), serde_json::json!(true));
+        assert_eq!(Value::I16(-13).to_json(), serde_json::json!(-13));
+        assert_eq!(Value::U64(u64::MAX).to_json(), serde_json::json!(u64::MAX));
+        assert_eq!(Value::Float(0.1).to_json(), serde_json::json!(0.1));
+        assert!(Value::Float(f32::NAN).to_json().is_null());
+        let value = Value::Float(1.5).to_json();
+        assert!(matches!(
+            Value::try_from_json(&value, ValueKind::Float),
+            Some(Value::Float(1.5))
+        ));
+    }
+}
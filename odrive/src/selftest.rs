@@ -0,0 +1,261 @@
+//! # Bus self-test
+//!
+//! [`self_test`] listens to the bus, asks an ODrive for its version and
+//! turns what it saw into a [`Diagnosis`]. It answers the usual first
+//! question when an ODrive does not respond: is the interface up, is there
+//! any traffic at the configured bitrate, does anyone acknowledge our frames
+//! and is the node ID right.
+//!
+//! ```no_run
+//! # tokio_test::block_on(async {
+//! use odrive::selftest::self_test_socketcan;
+//! use std::time::Duration;
+//!
+//! let diagnosis = self_test_socketcan("can0", 1, Duration::from_millis(500))
+//!     .await
+//!     .unwrap();
+//! for problem in diagnosis.problems() {
+//!     eprintln!("{problem}");
+//! }
+//! # });
+//! ```
+
+use crate::protocol::{self, Frame, Version};
+use crate::transport::{BusError, BusState, ErrorCounts, Transport, timeout};
+use cansimple::Id;
+use std::{collections::BTreeSet, fmt, io, time::Duration};
+
+/// Observations of a [`self_test`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Diagnosis {
+    /// Whether the network interface is up, [`None`] if not checked.
+    pub interface_up: Option<bool>,
+    /// Frames received from any node.
+    pub frames_received: u64,
+    /// Nodes frames were received from.
+    pub nodes: BTreeSet<u8>,
+    /// Bus errors reported by the controller.
+    pub bus_errors: ErrorCounts,
+    /// Last bus state change reported by the controller.
+    pub bus_state: Option<BusState>,
+    /// Version of the tested node, if it answered.
+    pub version: Option<Version>,
+    /// Error that ended the test early.
+    pub transport_error: Option<String>,
+}
+
+/// Problem found by a [`self_test`], its [`Display`](fmt::Display) explains
+/// the likely causes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    InterfaceDown,
+    Transport(String),
+    BusOff,
+    /// Frames were not acknowledged and nothing was received.
+    NoAck,
+    /// Bit, stuff or form errors without any valid frame.
+    BitrateMismatch,
+    NoTraffic,
+    /// Other nodes were heard but the tested node did not answer.
+    NodeNotFound {
+        seen: Vec<u8>,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::InterfaceDown => f.write_str(
+                "interface is down, bring it up with `ip link set <interface> up type can bitrate <bitrate>`",
+            ),
+            Problem::Transport(err) => write!(f, "transport failed: {err}"),
+            Problem::BusOff => f.write_str(
+                "controller went bus off, check the wiring, termination and bitrate",
+            ),
+            Problem::NoAck => f.write_str(
+                "frames are not acknowledged, no other node is on the bus: check that the ODrive is powered, the wiring and the termination",
+            ),
+            Problem::BitrateMismatch => f.write_str(
+                "only malformed frames were seen, the bitrate probably differs from the ODrive's",
+            ),
+            Problem::NoTraffic => f.write_str(
+                "nothing was received, check that the ODrive is powered and connected",
+            ),
+            Problem::NodeNotFound { seen } => {
+                write!(f, "node did not answer, check the node ID, nodes seen: {seen:?}")
+            }
+        }
+    }
+}
+
+impl Diagnosis {
+    /// Problems found, most fundamental first. Empty if the node answered.
+    pub fn problems(&self) -> Vec<Problem> {
+        let mut problems = Vec::new();
+        if self.interface_up == Some(false) {
+            problems.push(Problem::InterfaceDown);
+        }
+        if let Some(err) = &self.transport_error {
+            problems.push(Problem::Transport(err.clone()));
+        }
+        if self.bus_state == Some(BusState::BusOff) {
+            problems.push(Problem::BusOff);
+        }
+        if self.version.is_some() {
+            return problems;
+        }
+        let errors = &self.bus_errors;
+        if self.frames_received == 0 {
+            if errors.ack > 0 {
+                problems.push(Problem::NoAck);
+            } else if errors.bit + errors.stuff + errors.form > 0 {
+                problems.push(Problem::BitrateMismatch);
+            } else if problems.is_empty() {
+                problems.push(Problem::NoTraffic);
+            }
+        } else {
+            problems.push(Problem::NodeNotFound {
+                seen: self.nodes.iter().copied().collect(),
+            });
+        }
+        problems
+    }
+
+    /// Whether the node answered without problems.
+    pub fn is_ok(&self) -> bool {
+        self.problems().is_empty()
+    }
+
+    /// Count a frame or a read error, returns false if the test should end.
+    fn observe(&mut self, result: io::Result<Frame>) -> bool {
+        match result {
+            Ok(frame) => {
+                self.frames_received += 1;
+                if let embedded_can::Id::Standard(id) = frame.id() {
+                    self.nodes.insert(Id::from(id).node());
+                }
+                true
+            }
+            Err(err) => {
+                if let Some(error) = BusError::from_error(&err) {
+                    self.bus_errors.record(error);
+                    true
+                } else if let Some(state) = BusState::from_error(&err) {
+                    self.bus_state = Some(state);
+                    state != BusState::BusOff
+                } else {
+                    self.transport_error = Some(err.to_string());
+                    false
+                }
+            }
+        }
+    }
+}
+
+/// Listen to the bus for `listen`, then request the version of `node` and
+/// wait for it at most `listen` again.
+///
+/// Does not check the interface, see [`interface_up`].
+pub async fn self_test<T: Transport>(transport: &T, node: u8, listen: Duration) -> Diagnosis {
+    let mut diagnosis = Diagnosis::default();
+    let request = match protocol::get_version(node) {
+        Ok(request) => request,
+        Err(err) => {
+            diagnosis.transport_error = Some(err.to_string());
+            return diagnosis;
+        }
+    };
+
+    let listening = async {
+        loop {
+            if !diagnosis.observe(transport.read_frame().await) {
+                return false;
+            }
+        }
+    };
+    if timeout::<T::Timer, _>(listen, listening).await == Some(false) {
+        return diagnosis;
+    }
+
+    if let Err(err) = transport.write_frame(request.frame()).await {
+        diagnosis.transport_error = Some(err.to_string());
+        return diagnosis;
+    }
+    let waiting = async {
+        loop {
+            let result = transport.read_frame().await;
+            if let Ok(frame) = &result
+                && let Some(Ok(version)) = request.response(frame)
+            {
+                diagnosis.version = Some(version);
+            }
+            if !diagnosis.observe(result) || diagnosis.version.is_some() {
+                return;
+            }
+        }
+    };
+    timeout::<T::Timer, _>(listen, waiting).await;
+    diagnosis
+}
+
+/// Whether the network interface `name` is administratively up.
+#[cfg(target_os = "linux")]
+pub fn interface_up(name: &str) -> io::Result<bool> {
+    const IFF_UP: u32 = 0x1;
+
+    let flags = std::fs::read_to_string(format!("/sys/class/net/{name}/flags"))?;
+    let flags = u32::from_str_radix(flags.trim().trim_start_matches("0x"), 16)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    Ok(flags & IFF_UP != 0)
+}
+
+/// [`self_test`] on the SocketCAN interface `name`, checking that it is up
+/// first.
+#[cfg(all(target_os = "linux", feature = "tokio"))]
+pub async fn self_test_socketcan(name: &str, node: u8, listen: Duration) -> io::Result<Diagnosis> {
+    if !interface_up(name)? {
+        return Ok(Diagnosis {
+            interface_up: Some(false),
+            ..Diagnosis::default()
+        });
+    }
+    let socket = socketcan::tokio::CanSocket::open(name)?;
+    // also subscribes to the error frames
+    crate::transport::set_socketcan_filters(&socket, &[])?;
+    let mut diagnosis = self_test(&socket, node, listen).await;
+    diagnosis.interface_up = Some(true);
+    Ok(diagnosis)
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use super::*;
+    use crate::mock::{MockBus, MockODrive};
+    use crate::transport::TokioTimer;
+    use std::sync::Arc;
+
+    #[tokio::test(start_paused = true)]
+    async fn diagnoses() {
+        let bus = MockBus::<TokioTimer>::default().with_node(Arc::new(MockODrive::new(1)));
+        bus.heartbeats();
+        let diagnosis = self_test(&bus, 1, Duration::from_millis(100)).await;
+        assert!(diagnosis.is_ok(), "{:?}", diagnosis.problems());
+        assert_eq!(diagnosis.nodes, BTreeSet::from([1]));
+
+        bus.heartbeats();
+        let diagnosis = self_test(&bus, 2, Duration::from_millis(100)).await;
+        assert_eq!(
+            diagnosis.problems(),
+            [Problem::NodeNotFound { seen: vec![1] }]
+        );
+
+        let diagnosis = Diagnosis {
+            bus_errors: ErrorCounts {
+                ack: 3,
+                ..ErrorCounts::default()
+            },
+            ..Diagnosis::default()
+        };
+        assert_eq!(diagnosis.problems(), [Problem::NoAck]);
+    }
+}
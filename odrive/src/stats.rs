@@ -0,0 +1,207 @@
+//! # Link statistics
+//!
+//! Every driver counts the frames it sends and receives, timed out and
+//! retried requests, responses that failed to decode and the round trip
+//! time of requests by command, see
+//! [`ODrive::stats`](crate::can::ODrive::stats). Clones of a driver share
+//! their statistics.
+//!
+//! Round trip times are kept in a histogram, so percentiles show how USB
+//! adapters, kernel queues and bus load spread the timing of a control
+//! loop:
+//!
+//! ```no_run
+//! # tokio_test::block_on(async {
+//! use odrive::{can::ODrive, protocol::command};
+//!
+//! let socket = socketcan::tokio::CanSocket::open("can0").unwrap();
+//! let odrive = ODrive::new(socket, 1).unwrap();
+//! for _ in 0..1000 {
+//!     odrive.get_encoder_estimates().await.unwrap();
+//! }
+//! let latency = odrive.stats().latency[&command::GET_ENCODER_ESTIMATES];
+//! println!("{:?}", latency.percentiles());
+//! # });
+//! ```
+
+use std::{collections::BTreeMap, time::Duration};
+
+/// Histogram buckets per power of two, percentiles are within 1/8.
+const SUB_BUCKETS: u64 = 8;
+
+/// Buckets up to about 8 s, longer round trips count in the last one.
+const BUCKETS: usize = 168;
+
+/// Bucket of `micros`, times below [`SUB_BUCKETS`] get exact buckets.
+fn bucket(micros: u64) -> usize {
+    if micros < SUB_BUCKETS {
+        return micros as usize;
+    }
+    let shift = (u64::BITS - 1 - micros.leading_zeros()) as u64 - SUB_BUCKETS.ilog2() as u64;
+    ((shift * SUB_BUCKETS + (micros >> shift)) as usize).min(BUCKETS - 1)
+}
+
+/// Largest time in µs counted in `bucket`.
+fn bucket_max(bucket: usize) -> u64 {
+    let bucket = bucket as u64;
+    let shift = (bucket / SUB_BUCKETS).saturating_sub(1);
+    let base = if bucket < SUB_BUCKETS {
+        bucket
+    } else {
+        bucket % SUB_BUCKETS + SUB_BUCKETS
+    };
+    ((base + 1) << shift) - 1
+}
+
+/// Round trip time percentiles of one command, see
+/// [`Latency::percentiles`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Percentiles {
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub p999: Duration,
+    pub max: Duration,
+}
+
+/// Round trip times of one command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Latency {
+    /// Number of responses.
+    pub count: u64,
+    pub last: Duration,
+    pub max: Duration,
+    total: Duration,
+    /// Counts by [`bucket`].
+    histogram: [u64; BUCKETS],
+}
+
+impl Default for Latency {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            last: Duration::ZERO,
+            max: Duration::ZERO,
+            total: Duration::ZERO,
+            histogram: [0; BUCKETS],
+        }
+    }
+}
+
+impl Latency {
+    fn record(&mut self, latency: Duration) {
+        self.count += 1;
+        self.last = latency;
+        self.max = self.max.max(latency);
+        self.total += latency;
+        let micros = u64::try_from(latency.as_micros()).unwrap_or(u64::MAX);
+        self.histogram[bucket(micros)] += 1;
+    }
+
+    /// Round trip time `percent` of the responses were at most as slow as.
+    ///
+    /// Within 1/8 above the exact value and never above the maximum. Zero
+    /// without responses.
+    pub fn percentile(&self, percent: f64) -> Duration {
+        if self.count == 0 {
+            return Duration::ZERO;
+        }
+        let rank = ((percent.clamp(0.0, 100.0) / 100.0 * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bucket, count) in self.histogram.iter().enumerate() {
+            seen += count;
+            if seen >= rank && bucket < BUCKETS - 1 {
+                return Duration::from_micros(bucket_max(bucket)).min(self.max);
+            }
+        }
+        self.max
+    }
+
+    /// Common percentiles.
+    pub fn percentiles(&self) -> Percentiles {
+        Percentiles {
+            p50: self.percentile(50.0),
+            p90: self.percentile(90.0),
+            p99: self.percentile(99.0),
+            p999: self.percentile(99.9),
+            max: self.max,
+        }
+    }
+
+    /// Sum of all round trip times.
+    pub fn total(&self) -> Duration {
+        self.total
+    }
+
+    /// Mean round trip time.
+    pub fn mean(&self) -> Duration {
+        Duration::from_nanos((self.total.as_nanos() / self.count.max(1) as u128) as u64)
+    }
+}
+
+/// Connection and protocol counters of a driver.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub frames_sent: u64,
+    pub frames_received: u64,
+    /// Requests that were not answered in time, including retried ones.
+    pub timeouts: u64,
+    /// Requests sent again after a timeout.
+    pub retries: u64,
+    /// Responses that could not be decoded.
+    pub decode_failures: u64,
+    /// Round trip times by command.
+    pub latency: BTreeMap<u8, Latency>,
+}
+
+impl Stats {
+    pub(crate) fn record_latency(&mut self, command: u8, latency: Duration) {
+        self.latency.entry(command).or_default().record(latency);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latency() {
+        let mut stats = Stats::default();
+        stats.record_latency(0x14, Duration::from_millis(1));
+        stats.record_latency(0x14, Duration::from_millis(3));
+
+        let latency = stats.latency[&0x14];
+        assert_eq!(latency.count, 2);
+        assert_eq!(latency.mean(), Duration::from_millis(2));
+        assert_eq!(latency.max, Duration::from_millis(3));
+        assert_eq!(Latency::default().mean(), Duration::ZERO);
+        assert_eq!(Latency::default().percentile(50.0), Duration::ZERO);
+    }
+
+    #[test]
+    fn percentiles() {
+        for micros in 0..100_000 {
+            let bucket = bucket(micros);
+            assert!(micros <= bucket_max(bucket));
+            assert!(bucket == 0 || micros > bucket_max(bucket - 1));
+        }
+
+        let mut latency = Latency::default();
+        for micros in 1..=1000 {
+            latency.record(Duration::from_micros(micros * 10));
+        }
+        let percentiles = latency.percentiles();
+        let close = |actual: Duration, micros: u64| {
+            let exact = Duration::from_micros(micros);
+            actual >= exact && actual <= exact + exact / 8
+        };
+        assert!(close(percentiles.p50, 5000), "{percentiles:?}");
+        assert!(close(percentiles.p90, 9000), "{percentiles:?}");
+        assert!(close(percentiles.p99, 9900), "{percentiles:?}");
+        assert_eq!(percentiles.p999, Duration::from_millis(10));
+        assert_eq!(latency.percentile(100.0), latency.max);
+
+        latency.record(Duration::from_secs(60));
+        assert_eq!(latency.percentile(100.0), Duration::from_secs(60));
+    }
+}
@@ -0,0 +1,232 @@
+//! # Fixed-rate setpoint streaming
+//!
+//! [`SetpointStreamer`] transmits the most recently commanded setpoint at a
+//! fixed rate, independent of how often the application updates it. The
+//! application calls [`SetpointStreamer::set`] whenever it has a new value
+//! while [`SetpointStreamer::run`] runs as a separate task.
+//!
+//! ```no_run
+//! # tokio_test::block_on(async {
+//! use odrive::{can::ODrive, streamer::{Setpoint, SetpointStreamer}};
+//! use std::{sync::Arc, time::Duration};
+//!
+//! let socket = socketcan::tokio::CanSocket::open("can0").unwrap();
+//! let odrive = ODrive::new(socket, 1).unwrap();
+//! let streamer = Arc::new(SetpointStreamer::new(Duration::from_micros(2000)));
+//!
+//! tokio::spawn({
+//!     let (odrive, streamer) = (odrive.clone(), streamer.clone());
+//!     async move { streamer.run(&odrive).await }
+//! });
+//!
+//! streamer.set(Setpoint::Velocity { velocity: 1.0, torque: 0.0 });
+//! # });
+//! ```
+
+use crate::can::ODrive;
+use crate::protocol::{self, Frame};
+use crate::transport::{Timer, Transport};
+use std::{
+    sync::{
+        Mutex, PoisonError,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+/// Setpoint transmitted by a [`SetpointStreamer`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Setpoint {
+    /// See [`ODrive::set_input_position`].
+    Position {
+        position: f32,
+        velocity: i16,
+        torque: i16,
+    },
+    /// See [`ODrive::set_input_velocity`].
+    Velocity { velocity: f32, torque: f32 },
+    /// See [`ODrive::set_input_torque`].
+    Torque { torque: f32 },
+}
+
+impl Setpoint {
+    fn frame(&self, node: u8) -> crate::Result<Frame> {
+        match *self {
+            Setpoint::Position {
+                position,
+                velocity,
+                torque,
+            } => protocol::set_input_position(node, position, velocity, torque),
+            Setpoint::Velocity { velocity, torque } => {
+                protocol::set_input_velocity(node, velocity, torque)
+            }
+            Setpoint::Torque { torque } => protocol::set_input_torque(node, torque),
+        }
+    }
+}
+
+/// Transmit timing statistics of a [`SetpointStreamer`].
+///
+/// Jitter is the delay between a scheduled transmission and the moment it
+/// was started.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct JitterStats {
+    /// Number of transmissions.
+    pub transmitted: u64,
+    /// Number of periods skipped because the streamer fell behind.
+    pub missed: u64,
+    /// Mean jitter.
+    pub mean: Duration,
+    /// Largest jitter.
+    pub max: Duration,
+    total: Duration,
+}
+
+impl JitterStats {
+    fn record(&mut self, jitter: Duration) {
+        self.transmitted += 1;
+        self.total += jitter;
+        self.max = self.max.max(jitter);
+        self.mean = Duration::from_nanos((self.total.as_nanos() / self.transmitted as u128) as u64);
+    }
+}
+
+/// Transmits the latest setpoint at a fixed rate.
+pub struct SetpointStreamer {
+    period: Duration,
+    setpoint: Mutex<Option<Setpoint>>,
+    stats: Mutex<JitterStats>,
+    stopped: AtomicBool,
+}
+
+impl SetpointStreamer {
+    /// Create a streamer transmitting once every `period`.
+    pub fn new(period: Duration) -> Self {
+        Self {
+            period,
+            setpoint: Mutex::new(None),
+            stats: Mutex::new(JitterStats::default()),
+            stopped: AtomicBool::new(false),
+        }
+    }
+
+    /// Create a streamer transmitting `rate` times per second.
+    ///
+    /// A rate of zero is treated as one.
+    pub fn with_rate(rate: u32) -> Self {
+        Self::new(Duration::from_secs(1) / rate.max(1))
+    }
+
+    /// Setpoint sent from the next period on.
+    ///
+    /// Nothing is sent until the first setpoint is set.
+    pub fn set(&self, setpoint: Setpoint) {
+        *self.setpoint.lock().unwrap_or_else(PoisonError::into_inner) = Some(setpoint);
+    }
+
+    /// Stop sending setpoints, [`SetpointStreamer::run`] returns after the
+    /// current period.
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+
+    /// Timing statistics so far.
+    pub fn stats(&self) -> JitterStats {
+        *self.stats.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Transmit setpoints to `odrive` until stopped or a write fails.
+    pub async fn run<T: Transport>(&self, odrive: &ODrive<T>) -> crate::Result<()> {
+        let mut deadline = T::Timer::now();
+
+        while !self.stopped.load(Ordering::Relaxed) {
+            deadline += self.period;
+            let now = T::Timer::now();
+            if let Some(remaining) = deadline.checked_duration_since(now) {
+                T::Timer::sleep(remaining).await;
+            }
+
+            let started = T::Timer::now();
+            let jitter = started.saturating_duration_since(deadline);
+
+            // skip periods that have already passed rather than bursting
+            let missed = (jitter.as_nanos() / self.period.as_nanos().max(1)) as u32;
+            deadline += self.period * missed;
+            if missed > 0 {
+                trace_event!(
+                    debug,
+                    node = odrive.axis(),
+                    missed,
+                    "setpoint periods missed"
+                );
+            }
+
+            {
+                let mut stats = self.stats.lock().unwrap_or_else(PoisonError::into_inner);
+                stats.missed += missed as u64;
+                stats.record(jitter - self.period * missed);
+            }
+
+            let setpoint = *self.setpoint.lock().unwrap_or_else(PoisonError::into_inner);
+            if let Some(setpoint) = setpoint {
+                odrive.send(setpoint.frame(odrive.axis())?).await?;
+            }
+        }
+
+        trace_event!(debug, node = odrive.axis(), "setpoint streamer stopped");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    struct NoDelay;
+
+    impl Timer for NoDelay {
+        async fn sleep(_: Duration) {}
+    }
+
+    /// Transport failing after three writes.
+    struct Counting(Mutex<Vec<Frame>>);
+
+    impl Transport for Counting {
+        type Timer = NoDelay;
+
+        async fn write_frame(&self, frame: &Frame) -> io::Result<()> {
+            let mut frames = self.0.lock().unwrap();
+            if frames.len() == 3 {
+                return Err(io::ErrorKind::BrokenPipe.into());
+            }
+            frames.push(*frame);
+            Ok(())
+        }
+
+        async fn read_frame(&self) -> io::Result<Frame> {
+            Err(io::ErrorKind::Unsupported.into())
+        }
+    }
+
+    #[test]
+    fn streams_latest_setpoint() {
+        let odrive = ODrive::new(Counting(Mutex::new(vec![])), 1).unwrap();
+        let streamer = SetpointStreamer::with_rate(10_000);
+        streamer.set(Setpoint::Torque { torque: 0.5 });
+
+        let result = tokio_test::block_on(streamer.run(&odrive));
+        assert!(result.is_err());
+        assert!(streamer.stats().transmitted >= 3);
+    }
+
+    #[test]
+    fn jitter_stats() {
+        let mut stats = JitterStats::default();
+        stats.record(Duration::from_micros(10));
+        stats.record(Duration::from_micros(30));
+        assert_eq!(stats.transmitted, 2);
+        assert_eq!(stats.mean, Duration::from_micros(20));
+        assert_eq!(stats.max, Duration::from_micros(30));
+    }
+}
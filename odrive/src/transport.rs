@@ -0,0 +1,673 @@
+//! # CAN transports
+//!
+//! The [`ODrive`](crate::can::ODrive) driver is not tied to a specific async
+//! runtime. Instead it talks to the bus through the [`Transport`] trait which
+//! is implemented for the `socketcan` sockets of the supported runtimes:
+//!
+//! - `tokio` implements [`Transport`] for [`socketcan::tokio::CanSocket`] and
+//!   [`socketcan::tokio::CanFdSocket`].
+//! - `async-io` implements [`Transport`] for [`socketcan::async_io::CanSocket`]
+//!   and [`socketcan::async_io::CanFdSocket`] which can be used with
+//!   `async-std`, `smol` or any other executor.
+//!
+//! CAN FD frames can only be written to transports that support them, others
+//! return an [`io::ErrorKind::Unsupported`] error.
+//!
+//! Additional transports are available as features:
+//!
+//! - `socketcand` provides [`socketcand::SocketCand`] for accessing a remote
+//!   bus through a socketcand daemon.
+//! - `cannelloni` provides [`cannelloni::Cannelloni`] for accessing a remote
+//!   bus tunnelled over UDP.
+//! - `gs-usb` provides [`gs_usb::GsUsb`] for gs_usb/candleLight USB adapters
+//!   on any platform supported by libusb.
+//! - `pcan` provides [`pcan::Pcan`] for PEAK-System adapters using the
+//!   PCAN-Basic library.
+//!
+//! [`reconnect::Reconnect`] wraps any transport and reopens it when it fails.
+//! [`queue::TxQueue`] wraps any transport and coalesces queued setpoints.
+//! [`load::LoadMonitor`] wraps any transport and estimates the bus load.
+//! [`fault::FaultInjector`] wraps any transport and injects bus faults.
+//! [`replay::Replay`] plays back a recorded session for regression tests.
+//!
+//! The `socketcan` transports are only available on Linux.
+//!
+//! Custom executors or CAN adapters can be supported by implementing
+//! [`Transport`] and [`Timer`] directly.
+
+#[cfg(feature = "cannelloni")]
+pub mod cannelloni;
+pub mod fault;
+#[cfg(feature = "gs-usb")]
+pub mod gs_usb;
+pub mod load;
+#[cfg(feature = "pcan")]
+pub mod pcan;
+pub mod queue;
+pub mod reconnect;
+pub mod replay;
+#[cfg(feature = "socketcand")]
+pub mod socketcand;
+
+pub use crate::protocol::{Filter, Frame};
+use std::{
+    fmt,
+    future::{Future, poll_fn},
+    io,
+    pin::pin,
+    task::Poll,
+    time::{Duration, Instant},
+};
+
+/// Asynchronous CAN bus access.
+pub trait Transport {
+    /// Timer matching the runtime the transport is driven by.
+    type Timer: Timer;
+
+    /// Write a frame to the bus.
+    ///
+    /// Transports that do not support CAN FD return an
+    /// [`io::ErrorKind::Unsupported`] error for CAN FD frames.
+    fn write_frame(&self, frame: &Frame) -> impl Future<Output = io::Result<()>> + Send;
+
+    /// Read the next frame from the bus.
+    ///
+    /// Frames that cannot be represented by [`Frame`], such as error frames,
+    /// are skipped. Transports that receive error frames report controller
+    /// state changes and bus errors as an error carrying a [`BusState`] or
+    /// [`BusError`], see [`BusState::from_error`] and [`BusError::from_error`].
+    fn read_frame(&self) -> impl Future<Output = io::Result<Frame>> + Send;
+
+    /// Only receive frames matching at least one of `filters`.
+    ///
+    /// An empty slice accepts every frame. Transports without hardware
+    /// filtering ignore the filters.
+    fn set_filters(&self, filters: &[Filter]) -> io::Result<()> {
+        let _ = filters;
+        Ok(())
+    }
+}
+
+/// CAN controller error state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BusState {
+    /// Normal operation.
+    ErrorActive,
+    /// Error counters reached the warning level.
+    ErrorWarning,
+    /// Error counters reached the error passive level, the controller no
+    /// longer sends active error flags.
+    ErrorPassive,
+    /// The controller has disconnected from the bus and must be restarted.
+    BusOff,
+}
+
+impl BusState {
+    /// Error reporting a change to this state.
+    pub fn into_error(self) -> io::Error {
+        let kind = match self {
+            BusState::BusOff => io::ErrorKind::NotConnected,
+            _ => io::ErrorKind::Other,
+        };
+        io::Error::new(kind, BusStateError(self))
+    }
+
+    /// Bus state reported by an error returned from a transport.
+    pub fn from_error(err: &io::Error) -> Option<Self> {
+        err.get_ref()?
+            .downcast_ref::<BusStateError>()
+            .map(|error| error.0)
+    }
+}
+
+impl fmt::Display for BusState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BusState::ErrorActive => "error active",
+            BusState::ErrorWarning => "error warning",
+            BusState::ErrorPassive => "error passive",
+            BusState::BusOff => "bus off",
+        })
+    }
+}
+
+#[derive(Debug)]
+struct BusStateError(BusState);
+
+impl fmt::Display for BusStateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CAN bus state changed: {}", self.0)
+    }
+}
+
+impl std::error::Error for BusStateError {}
+
+/// Error reported by the CAN controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BusError {
+    /// A transmitted bit was not read back from the bus.
+    Bit,
+    /// Bit stuffing rule violated.
+    Stuff,
+    /// Fixed-form bit field contained an illegal value.
+    Form,
+    /// Any other protocol violation.
+    Protocol,
+    /// No acknowledgement received for a transmitted frame, usually a
+    /// missing node, termination or bitrate mismatch.
+    Ack,
+    /// A receive or transmit buffer overflowed.
+    Overrun,
+    /// Arbitration was lost.
+    ArbitrationLost,
+    /// Transceiver reported a wiring fault.
+    Transceiver,
+    /// Transmission timed out in the driver.
+    TransmitTimeout,
+    /// Unclassified error.
+    Other,
+}
+
+impl BusError {
+    /// Error reporting this bus error.
+    pub fn into_error(self) -> io::Error {
+        io::Error::other(self)
+    }
+
+    /// Bus error reported by an error returned from a transport.
+    pub fn from_error(err: &io::Error) -> Option<Self> {
+        err.get_ref()?.downcast_ref::<BusError>().copied()
+    }
+}
+
+impl fmt::Display for BusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BusError::Bit => "CAN bit error",
+            BusError::Stuff => "CAN stuff error",
+            BusError::Form => "CAN form error",
+            BusError::Protocol => "CAN protocol violation",
+            BusError::Ack => "CAN acknowledgement error",
+            BusError::Overrun => "CAN controller overrun",
+            BusError::ArbitrationLost => "CAN arbitration lost",
+            BusError::Transceiver => "CAN transceiver error",
+            BusError::TransmitTimeout => "CAN transmit timeout",
+            BusError::Other => "CAN bus error",
+        })
+    }
+}
+
+impl std::error::Error for BusError {}
+
+/// Number of [`BusError`]s seen, by kind.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ErrorCounts {
+    pub bit: u64,
+    pub stuff: u64,
+    pub form: u64,
+    pub protocol: u64,
+    pub ack: u64,
+    pub overrun: u64,
+    pub arbitration_lost: u64,
+    pub transceiver: u64,
+    pub transmit_timeout: u64,
+    pub other: u64,
+}
+
+impl ErrorCounts {
+    /// Count `error`.
+    pub fn record(&mut self, error: BusError) {
+        let count = match error {
+            BusError::Bit => &mut self.bit,
+            BusError::Stuff => &mut self.stuff,
+            BusError::Form => &mut self.form,
+            BusError::Protocol => &mut self.protocol,
+            BusError::Ack => &mut self.ack,
+            BusError::Overrun => &mut self.overrun,
+            BusError::ArbitrationLost => &mut self.arbitration_lost,
+            BusError::Transceiver => &mut self.transceiver,
+            BusError::TransmitTimeout => &mut self.transmit_timeout,
+            BusError::Other => &mut self.other,
+        };
+        *count += 1;
+    }
+
+    /// Total number of errors.
+    pub fn total(&self) -> u64 {
+        self.bit
+            + self.stuff
+            + self.form
+            + self.protocol
+            + self.ack
+            + self.overrun
+            + self.arbitration_lost
+            + self.transceiver
+            + self.transmit_timeout
+            + self.other
+    }
+}
+
+/// Runtime timer.
+///
+/// All timing of the async drivers goes through the timer of the transport:
+/// timeouts, retries, periods and the timestamps compared against them. A
+/// timer with a virtual clock makes that logic testable without waiting.
+pub trait Timer {
+    /// Wait until `duration` has elapsed.
+    fn sleep(duration: Duration) -> impl Future<Output = ()> + Send;
+
+    /// Current time of the clock `sleep` waits on.
+    fn now() -> Instant {
+        Instant::now()
+    }
+}
+
+/// Run `future` for at most `duration`.
+///
+/// Returns [`None`] if the timer of `M` elapsed first.
+pub async fn timeout<M: Timer, F: Future>(duration: Duration, future: F) -> Option<F::Output> {
+    let mut future = pin!(future);
+    let mut sleep = pin!(M::sleep(duration));
+
+    poll_fn(|cx| {
+        if let Poll::Ready(output) = future.as_mut().poll(cx) {
+            return Poll::Ready(Some(output));
+        }
+        sleep.as_mut().poll(cx).map(|()| None)
+    })
+    .await
+}
+
+/// Error returned when writing a CAN FD frame to a classic CAN transport.
+#[cfg(any(
+    all(
+        target_os = "linux",
+        any(feature = "tokio", feature = "async-io", feature = "blocking")
+    ),
+    feature = "socketcand",
+    feature = "gs-usb",
+    feature = "pcan"
+))]
+pub(crate) fn fd_unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "CAN FD frames are not supported by this transport",
+    )
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(feature = "tokio", feature = "async-io", feature = "blocking")
+))]
+impl TryFrom<&Frame> for socketcan::CanFrame {
+    type Error = io::Error;
+
+    fn try_from(frame: &Frame) -> io::Result<Self> {
+        use embedded_can::Frame as _;
+
+        if frame.is_fd() {
+            return Err(fd_unsupported());
+        }
+
+        let invalid = || io::Error::new(io::ErrorKind::InvalidInput, "invalid CAN frame");
+        if frame.is_remote_frame() {
+            socketcan::CanFrame::new_remote(frame.id(), frame.dlc()).ok_or_else(invalid)
+        } else {
+            socketcan::CanFrame::new(frame.id(), frame.data()).ok_or_else(invalid)
+        }
+    }
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(feature = "tokio", feature = "async-io", feature = "blocking")
+))]
+impl TryFrom<&Frame> for socketcan::CanAnyFrame {
+    type Error = io::Error;
+
+    fn try_from(frame: &Frame) -> io::Result<Self> {
+        use embedded_can::Frame as _;
+
+        if frame.is_fd() {
+            let mut fd = socketcan::CanFdFrame::new(frame.id(), frame.data()).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "invalid CAN FD frame")
+            })?;
+            fd.set_brs(frame.is_brs());
+            Ok(socketcan::CanAnyFrame::Fd(fd))
+        } else {
+            Ok(match socketcan::CanFrame::try_from(frame)? {
+                socketcan::CanFrame::Data(data) => socketcan::CanAnyFrame::Normal(data),
+                socketcan::CanFrame::Remote(remote) => socketcan::CanAnyFrame::Remote(remote),
+                socketcan::CanFrame::Error(error) => socketcan::CanAnyFrame::Error(error),
+            })
+        }
+    }
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(feature = "tokio", feature = "async-io", feature = "blocking")
+))]
+impl TryFrom<socketcan::CanFrame> for Frame {
+    type Error = socketcan::CanFrame;
+
+    fn try_from(frame: socketcan::CanFrame) -> Result<Self, Self::Error> {
+        use embedded_can::Frame as _;
+
+        match frame {
+            socketcan::CanFrame::Data(_) => Frame::new(frame.id(), frame.data()).ok_or(frame),
+            socketcan::CanFrame::Remote(_) => {
+                Frame::new_remote(frame.id(), frame.dlc()).ok_or(frame)
+            }
+            socketcan::CanFrame::Error(_) => Err(frame),
+        }
+    }
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(feature = "tokio", feature = "async-io", feature = "blocking")
+))]
+impl TryFrom<socketcan::CanAnyFrame> for Frame {
+    type Error = socketcan::CanAnyFrame;
+
+    fn try_from(frame: socketcan::CanAnyFrame) -> Result<Self, Self::Error> {
+        use embedded_can::Frame as _;
+
+        match frame {
+            socketcan::CanAnyFrame::Normal(data) => Frame::new(data.id(), data.data()).ok_or(frame),
+            socketcan::CanAnyFrame::Remote(remote) => {
+                Frame::new_remote(remote.id(), remote.dlc()).ok_or(frame)
+            }
+            socketcan::CanAnyFrame::Fd(fd) => {
+                Frame::new_fd(fd.id(), fd.data(), fd.is_brs()).ok_or(frame)
+            }
+            socketcan::CanAnyFrame::Error(_) => Err(frame),
+        }
+    }
+}
+
+/// Bus state change or bus error reported by a SocketCAN error frame.
+#[cfg(all(
+    target_os = "linux",
+    any(feature = "tokio", feature = "async-io", feature = "blocking")
+))]
+pub(crate) fn socketcan_error(frame: socketcan::CanErrorFrame) -> io::Error {
+    use socketcan::{
+        CanError,
+        errors::{ControllerProblem, ViolationType},
+    };
+
+    match frame.into_error() {
+        CanError::BusOff => BusState::BusOff.into_error(),
+        CanError::Restarted => BusState::ErrorActive.into_error(),
+        CanError::ControllerProblem(problem) => {
+            match problem {
+                ControllerProblem::ReceiveErrorWarning
+                | ControllerProblem::TransmitErrorWarning => BusState::ErrorWarning.into_error(),
+                ControllerProblem::ReceiveErrorPassive
+                | ControllerProblem::TransmitErrorPassive => BusState::ErrorPassive.into_error(),
+                ControllerProblem::Active => BusState::ErrorActive.into_error(),
+                ControllerProblem::ReceiveBufferOverflow
+                | ControllerProblem::TransmitBufferOverflow => BusError::Overrun.into_error(),
+                ControllerProblem::Unspecified => BusError::Other.into_error(),
+            }
+        }
+        CanError::ProtocolViolation { vtype, .. } => match vtype {
+            ViolationType::SingleBitError
+            | ViolationType::UnableToSendDominantBit
+            | ViolationType::UnableToSendRecessiveBit => BusError::Bit.into_error(),
+            ViolationType::BitStuffingError => BusError::Stuff.into_error(),
+            ViolationType::FrameFormatError => BusError::Form.into_error(),
+            _ => BusError::Protocol.into_error(),
+        },
+        CanError::NoAck => BusError::Ack.into_error(),
+        CanError::LostArbitration(_) => BusError::ArbitrationLost.into_error(),
+        CanError::TransceiverError => BusError::Transceiver.into_error(),
+        CanError::TransmitTimeout => BusError::TransmitTimeout.into_error(),
+        _ => BusError::Other.into_error(),
+    }
+}
+
+/// Restart a SocketCAN interface after it went bus off.
+///
+/// Requires the `CAP_NET_ADMIN` capability. Can be passed to
+/// [`ODrive::with_bus_recovery`](crate::can::ODrive::with_bus_recovery):
+///
+/// ```no_run
+/// # tokio_test::block_on(async {
+/// use odrive::{can::ODrive, transport::restart_interface};
+///
+/// let socket = socketcan::tokio::CanSocket::open("can0").unwrap();
+/// let odrive = ODrive::new(socket, 1)
+///     .unwrap()
+///     .with_bus_recovery(|| restart_interface("can0"));
+/// # });
+/// ```
+#[cfg(all(
+    target_os = "linux",
+    any(feature = "tokio", feature = "async-io", feature = "blocking")
+))]
+pub fn restart_interface(name: &str) -> io::Result<()> {
+    let interface = socketcan::CanInterface::open(name).map_err(io::Error::from)?;
+    interface.restart().map_err(io::Error::other)
+}
+
+/// Install `filters` on a SocketCAN socket.
+///
+/// Also subscribes to all error frames.
+#[cfg(all(
+    target_os = "linux",
+    any(feature = "tokio", feature = "async-io", feature = "blocking")
+))]
+pub(crate) fn set_socketcan_filters(
+    socket: &impl socketcan::SocketOptions,
+    filters: &[Filter],
+) -> io::Result<()> {
+    socket.set_error_filter_accept_all()?;
+
+    if filters.is_empty() {
+        return socket.set_filter_accept_all();
+    }
+
+    let filters: Vec<_> = filters
+        .iter()
+        .copied()
+        .map(socketcan::CanFilter::from)
+        .collect();
+    socket.set_filters(&filters)
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(feature = "tokio", feature = "async-io", feature = "blocking")
+))]
+impl From<Filter> for socketcan::CanFilter {
+    fn from(filter: Filter) -> Self {
+        use embedded_can::Id;
+
+        const EFF_FLAG: u32 = 0x8000_0000;
+
+        // including the extended flag in the mask keeps standard and
+        // extended identifiers apart
+        let (id, mask) = match filter.id {
+            Id::Standard(id) => (id.as_raw() as u32, filter.mask),
+            Id::Extended(id) => (id.as_raw() | EFF_FLAG, filter.mask),
+        };
+        socketcan::CanFilter::new(id, mask | EFF_FLAG)
+    }
+}
+
+/// Timer for the tokio runtime.
+#[cfg(feature = "tokio")]
+#[derive(Debug, Clone, Copy)]
+pub struct TokioTimer;
+
+#[cfg(feature = "tokio")]
+impl Timer for TokioTimer {
+    fn sleep(duration: Duration) -> impl Future<Output = ()> + Send {
+        tokio::time::sleep(duration)
+    }
+
+    /// Follows tokio's virtual time while it is paused, as in
+    /// `#[tokio::test(start_paused = true)]`.
+    fn now() -> Instant {
+        tokio::time::Instant::now().into_std()
+    }
+}
+
+#[cfg(all(feature = "tokio", target_os = "linux"))]
+impl Transport for socketcan::tokio::CanSocket {
+    type Timer = TokioTimer;
+
+    async fn write_frame(&self, frame: &Frame) -> io::Result<()> {
+        socketcan::tokio::CanSocket::write_frame(self, frame.try_into()?).await
+    }
+
+    async fn read_frame(&self) -> io::Result<Frame> {
+        loop {
+            match socketcan::tokio::CanSocket::read_frame(self).await? {
+                socketcan::CanFrame::Error(error) => {
+                    return Err(socketcan_error(error));
+                }
+                frame => {
+                    if let Ok(frame) = Frame::try_from(frame) {
+                        return Ok(frame);
+                    }
+                }
+            }
+        }
+    }
+
+    fn set_filters(&self, filters: &[Filter]) -> io::Result<()> {
+        set_socketcan_filters(self, filters)
+    }
+}
+
+#[cfg(all(feature = "tokio", target_os = "linux"))]
+impl Transport for socketcan::tokio::CanFdSocket {
+    type Timer = TokioTimer;
+
+    async fn write_frame(&self, frame: &Frame) -> io::Result<()> {
+        let frame = socketcan::CanAnyFrame::try_from(frame)?;
+        socketcan::tokio::CanFdSocket::write_frame(self, &frame).await
+    }
+
+    async fn read_frame(&self) -> io::Result<Frame> {
+        loop {
+            match socketcan::tokio::CanFdSocket::read_frame(self).await? {
+                socketcan::CanAnyFrame::Error(error) => {
+                    return Err(socketcan_error(error));
+                }
+                frame => {
+                    if let Ok(frame) = Frame::try_from(frame) {
+                        return Ok(frame);
+                    }
+                }
+            }
+        }
+    }
+
+    fn set_filters(&self, filters: &[Filter]) -> io::Result<()> {
+        set_socketcan_filters(self, filters)
+    }
+}
+
+/// Timer for `async-io` based runtimes such as `async-std` and `smol`.
+#[cfg(feature = "async-io")]
+#[derive(Debug, Clone, Copy)]
+pub struct AsyncIoTimer;
+
+#[cfg(feature = "async-io")]
+impl Timer for AsyncIoTimer {
+    async fn sleep(duration: Duration) {
+        async_io::Timer::after(duration).await;
+    }
+}
+
+#[cfg(all(feature = "async-io", target_os = "linux"))]
+impl Transport for socketcan::async_io::CanSocket {
+    type Timer = AsyncIoTimer;
+
+    async fn write_frame(&self, frame: &Frame) -> io::Result<()> {
+        let frame = socketcan::CanFrame::try_from(frame)?;
+        socketcan::async_io::CanSocket::write_frame(self, &frame).await
+    }
+
+    async fn read_frame(&self) -> io::Result<Frame> {
+        loop {
+            match socketcan::async_io::CanSocket::read_frame(self).await? {
+                socketcan::CanFrame::Error(error) => {
+                    return Err(socketcan_error(error));
+                }
+                frame => {
+                    if let Ok(frame) = Frame::try_from(frame) {
+                        return Ok(frame);
+                    }
+                }
+            }
+        }
+    }
+
+    fn set_filters(&self, filters: &[Filter]) -> io::Result<()> {
+        set_socketcan_filters(self, filters)
+    }
+}
+
+#[cfg(all(feature = "async-io", target_os = "linux"))]
+impl Transport for socketcan::async_io::CanFdSocket {
+    type Timer = AsyncIoTimer;
+
+    async fn write_frame(&self, frame: &Frame) -> io::Result<()> {
+        let frame = socketcan::CanAnyFrame::try_from(frame)?;
+        socketcan::async_io::CanFdSocket::write_frame(self, &frame).await
+    }
+
+    async fn read_frame(&self) -> io::Result<Frame> {
+        loop {
+            match socketcan::async_io::CanFdSocket::read_frame(self).await? {
+                socketcan::CanAnyFrame::Error(error) => {
+                    return Err(socketcan_error(error));
+                }
+                frame => {
+                    if let Ok(frame) = Frame::try_from(frame) {
+                        return Ok(frame);
+                    }
+                }
+            }
+        }
+    }
+
+    fn set_filters(&self, filters: &[Filter]) -> io::Result<()> {
+        set_socketcan_filters(self, filters)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bus_state_error() {
+        let err = BusState::BusOff.into_error();
+        assert_eq!(err.kind(), io::ErrorKind::NotConnected);
+        assert_eq!(BusState::from_error(&err), Some(BusState::BusOff));
+
+        let err = io::Error::other("unrelated");
+        assert_eq!(BusState::from_error(&err), None);
+        assert_eq!(BusError::from_error(&err), None);
+    }
+
+    #[test]
+    fn error_counts() {
+        let mut counts = ErrorCounts::default();
+        counts.record(BusError::Ack);
+        counts.record(BusError::Ack);
+        counts.record(BusError::Bit);
+        assert_eq!(counts.ack, 2);
+        assert_eq!(counts.total(), 3);
+
+        let err = BusError::Ack.into_error();
+        assert_eq!(BusError::from_error(&err), Some(BusError::Ack));
+    }
+}
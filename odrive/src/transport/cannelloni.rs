@@ -0,0 +1,228 @@
+//! # cannelloni transport
+//!
+//! Access a remote CAN bus tunnelled over UDP by a
+//! [`cannelloni`](https://github.com/mguentner/cannelloni) instance.
+//!
+//! Each outgoing frame is sent in its own datagram to keep latency low.
+//! Received datagrams may contain any number of frames.
+//!
+//! ```no_run
+//! # tokio_test::block_on(async {
+//! use odrive::{can::ODrive, transport::cannelloni::Cannelloni};
+//!
+//! let transport = Cannelloni::connect("0.0.0.0:20000", "192.168.1.10:20000").await.unwrap();
+//! let odrive = ODrive::new(transport, 1).unwrap();
+//! # });
+//! ```
+
+use super::{Frame, TokioTimer, Transport};
+use embedded_can::{ExtendedId, Id, StandardId};
+use std::{
+    collections::VecDeque,
+    io,
+    sync::{
+        Mutex, PoisonError,
+        atomic::{AtomicU8, Ordering},
+    },
+};
+use tokio::net::{ToSocketAddrs, UdpSocket};
+
+/// Protocol version.
+const VERSION: u8 = 2;
+/// Data packet op code.
+const OP_DATA: u8 = 0;
+/// Header length.
+const HEADER_LEN: usize = 5;
+
+/// Extended frame flag in the identifier.
+const EFF_FLAG: u32 = 0x8000_0000;
+/// Remote frame flag in the identifier.
+const RTR_FLAG: u32 = 0x4000_0000;
+/// Error frame flag in the identifier.
+const ERR_FLAG: u32 = 0x2000_0000;
+/// CAN FD flag in the length field.
+const FD_FLAG: u8 = 0x80;
+/// Bit rate switch flag in the CAN FD flags byte.
+const BRS_FLAG: u8 = 0x01;
+
+/// Transport using a cannelloni UDP tunnel.
+pub struct Cannelloni {
+    socket: UdpSocket,
+    sequence: AtomicU8,
+    received: Mutex<VecDeque<Frame>>,
+}
+
+impl Cannelloni {
+    /// Bind to `local` and exchange frames with the cannelloni peer at
+    /// `remote`.
+    pub async fn connect(
+        local: impl ToSocketAddrs,
+        remote: impl ToSocketAddrs,
+    ) -> io::Result<Self> {
+        let socket = UdpSocket::bind(local).await?;
+        socket.connect(remote).await?;
+
+        Ok(Self {
+            socket,
+            sequence: AtomicU8::new(0),
+            received: Mutex::new(VecDeque::new()),
+        })
+    }
+}
+
+impl Transport for Cannelloni {
+    type Timer = TokioTimer;
+
+    async fn write_frame(&self, frame: &Frame) -> io::Result<()> {
+        let sequence = self.sequence.fetch_add(1, Ordering::Relaxed);
+        self.socket.send(&encode(sequence, &[*frame])).await?;
+        Ok(())
+    }
+
+    async fn read_frame(&self) -> io::Result<Frame> {
+        let mut buf = [0; 1500];
+
+        loop {
+            if let Some(frame) = self
+                .received
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .pop_front()
+            {
+                return Ok(frame);
+            }
+
+            let len = self.socket.recv(&mut buf).await?;
+            let frames = decode(&buf[..len])?;
+            self.received
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .extend(frames);
+        }
+    }
+}
+
+/// Encode frames into a data packet.
+fn encode(sequence: u8, frames: &[Frame]) -> Vec<u8> {
+    let mut packet = vec![VERSION, OP_DATA, sequence];
+    packet.extend((frames.len() as u16).to_be_bytes());
+
+    for frame in frames {
+        let mut id = match frame.id() {
+            Id::Standard(id) => id.as_raw() as u32,
+            Id::Extended(id) => id.as_raw() | EFF_FLAG,
+        };
+        if frame.is_remote_frame() {
+            id |= RTR_FLAG;
+        }
+
+        packet.extend(id.to_be_bytes());
+        if frame.is_fd() {
+            packet.push(frame.dlc() as u8 | FD_FLAG);
+            packet.push(if frame.is_brs() { BRS_FLAG } else { 0 });
+        } else {
+            packet.push(frame.dlc() as u8);
+        }
+        packet.extend(frame.data());
+    }
+
+    packet
+}
+
+/// Decode a data packet.
+///
+/// Error frames are skipped.
+fn decode(packet: &[u8]) -> io::Result<Vec<Frame>> {
+    let invalid = |reason: &str| io::Error::new(io::ErrorKind::InvalidData, reason.to_owned());
+
+    if packet.len() < HEADER_LEN {
+        return Err(invalid("cannelloni packet too short"));
+    }
+    if packet[0] != VERSION {
+        return Err(invalid("Unsupported cannelloni version"));
+    }
+    if packet[1] != OP_DATA {
+        return Ok(vec![]);
+    }
+
+    let count = u16::from_be_bytes([packet[3], packet[4]]);
+    let mut rest = &packet[HEADER_LEN..];
+    let mut frames = vec![];
+
+    for _ in 0..count {
+        let Some((header, tail)) = rest.split_first_chunk::<5>() else {
+            return Err(invalid("cannelloni packet truncated"));
+        };
+        let raw = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+        let mut len = header[4];
+
+        let fd = len & FD_FLAG != 0;
+        let mut brs = false;
+        let mut tail = tail;
+        if fd {
+            len &= !FD_FLAG;
+            let Some((flags, rest)) = tail.split_first() else {
+                return Err(invalid("cannelloni packet truncated"));
+            };
+            brs = flags & BRS_FLAG != 0;
+            tail = rest;
+        }
+
+        let remote = raw & RTR_FLAG != 0;
+        let data_len = if remote { 0 } else { len as usize };
+        let Some((data, tail)) = tail.split_at_checked(data_len) else {
+            return Err(invalid("cannelloni packet truncated"));
+        };
+        rest = tail;
+
+        if raw & ERR_FLAG != 0 {
+            continue;
+        }
+
+        let id = if raw & EFF_FLAG != 0 {
+            ExtendedId::new(raw & !(EFF_FLAG | RTR_FLAG)).map(Id::Extended)
+        } else {
+            StandardId::new((raw & 0x7ff) as u16).map(Id::Standard)
+        };
+        let Some(id) = id else {
+            continue;
+        };
+
+        let frame = if fd {
+            Frame::new_fd(id, data, brs)
+        } else if remote {
+            Frame::new_remote(id, len as usize)
+        } else {
+            Frame::new(id, data)
+        };
+        frames.extend(frame);
+    }
+
+    Ok(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let frames = [
+            Frame::new(StandardId::new(0x029).unwrap(), &[1, 2, 3]).unwrap(),
+            Frame::new_remote(StandardId::new(0x029).unwrap(), 8).unwrap(),
+            Frame::new(ExtendedId::new(0x1234567).unwrap(), &[4]).unwrap(),
+            Frame::new_fd(StandardId::new(0x029).unwrap(), &[5; 12], true).unwrap(),
+        ];
+
+        let packet = encode(7, &frames);
+        assert_eq!(&packet[..HEADER_LEN], &[2, 0, 7, 0, 4]);
+        assert_eq!(decode(&packet).unwrap(), frames);
+    }
+
+    #[test]
+    fn truncated_packet() {
+        let frames = [Frame::new(StandardId::new(0x029).unwrap(), &[1, 2, 3]).unwrap()];
+        let packet = encode(0, &frames);
+        assert!(decode(&packet[..packet.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,350 @@
+//! # Fault injection
+//!
+//! [`FaultInjector`] wraps another transport and drops, delays, duplicates
+//! or corrupts the frames passing through it, to validate retries, timeouts
+//! and safety logic against a misbehaving bus:
+//!
+//! ```no_run
+//! # tokio_test::block_on(async {
+//! use odrive::{can::ODrive, transport::fault::{FaultInjector, Faults}};
+//! use std::time::Duration;
+//!
+//! let socket = socketcan::tokio::CanSocket::open("vcan0").unwrap();
+//! let faults = Faults {
+//!     drop: 0.1,
+//!     delay: 0.2,
+//!     delay_by: Duration::from_millis(5),
+//!     ..Faults::default()
+//! };
+//! let transport = FaultInjector::new(socket, 42).with_rx(faults);
+//! let odrive = ODrive::new(transport, 1).unwrap().with_retries(3);
+//! # });
+//! ```
+//!
+//! Faults are drawn from a pseudo random generator seeded on creation, so a
+//! run can be repeated with the same seed as long as the traffic is the
+//! same.
+
+use super::{Filter, Frame, Timer, Transport};
+use std::{
+    io,
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+/// Probabilities of faults, from 0 to 1 per frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Faults {
+    /// Frame is lost.
+    pub drop: f64,
+    /// Frame is received twice.
+    pub duplicate: f64,
+    /// A bit of the frame data is flipped.
+    pub corrupt: f64,
+    /// Frame is held back by `delay_by`.
+    pub delay: f64,
+    pub delay_by: Duration,
+}
+
+/// Number of faults injected.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Injected {
+    pub dropped: u64,
+    pub duplicated: u64,
+    pub corrupted: u64,
+    pub delayed: u64,
+}
+
+/// Faults drawn for a frame.
+#[derive(Default)]
+struct Fault {
+    drop: bool,
+    duplicate: bool,
+    delay: Option<Duration>,
+}
+
+struct State {
+    rng: u64,
+    injected: Injected,
+    /// Duplicate of the last frame read, returned by the next read.
+    duplicate: Option<Frame>,
+}
+
+impl State {
+    /// xorshift64*, plenty for picking faults.
+    fn next(&mut self) -> u64 {
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        self.rng.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        // 53 random bits make a uniform f64 in [0, 1)
+        let sample = (self.next() >> 11) as f64 / (1u64 << 53) as f64;
+        probability > 0.0 && sample < probability
+    }
+}
+
+/// Transport injecting faults into the frames written and read.
+pub struct FaultInjector<T> {
+    transport: T,
+    tx: Faults,
+    rx: Faults,
+    state: Mutex<State>,
+}
+
+impl<T> FaultInjector<T> {
+    /// Wrap `transport` without faults, `seed` seeds the fault generator.
+    pub fn new(transport: T, seed: u64) -> Self {
+        Self {
+            transport,
+            tx: Faults::default(),
+            rx: Faults::default(),
+            state: Mutex::new(State {
+                // the generator is stuck at zero
+                rng: seed.max(1),
+                injected: Injected::default(),
+                duplicate: None,
+            }),
+        }
+    }
+
+    /// Inject `faults` into written frames.
+    pub fn with_tx(mut self, faults: Faults) -> Self {
+        self.tx = faults;
+        self
+    }
+
+    /// Inject `faults` into read frames.
+    pub fn with_rx(mut self, faults: Faults) -> Self {
+        self.rx = faults;
+        self
+    }
+
+    /// Wrapped transport.
+    pub fn get_ref(&self) -> &T {
+        &self.transport
+    }
+
+    /// Faults injected so far.
+    pub fn injected(&self) -> Injected {
+        self.lock().injected
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Draw the faults for `frame`, corrupting it in place.
+    fn inject(&self, faults: &Faults, frame: &mut Frame) -> Fault {
+        let mut state = self.lock();
+        let mut fault = Fault::default();
+        if state.chance(faults.drop) {
+            state.injected.dropped += 1;
+            fault.drop = true;
+            return fault;
+        }
+        if !frame.data().is_empty() && state.chance(faults.corrupt) {
+            let bit = state.next() as usize % (frame.data().len() * 8);
+            frame.data_mut()[bit / 8] ^= 1 << (bit % 8);
+            state.injected.corrupted += 1;
+        }
+        if state.chance(faults.duplicate) {
+            state.injected.duplicated += 1;
+            fault.duplicate = true;
+        }
+        if state.chance(faults.delay) {
+            state.injected.delayed += 1;
+            fault.delay = Some(faults.delay_by);
+        }
+        fault
+    }
+
+    fn take_duplicate(&self) -> Option<Frame> {
+        self.lock().duplicate.take()
+    }
+
+    /// Inject faults into a read frame, returns it with its delay unless it
+    /// was dropped.
+    fn received(&self, mut frame: Frame) -> Option<(Frame, Option<Duration>)> {
+        let fault = self.inject(&self.rx, &mut frame);
+        if fault.drop {
+            return None;
+        }
+        if fault.duplicate {
+            self.lock().duplicate = Some(frame);
+        }
+        Some((frame, fault.delay))
+    }
+}
+
+impl<T: Transport + Sync> Transport for FaultInjector<T> {
+    type Timer = T::Timer;
+
+    async fn write_frame(&self, frame: &Frame) -> io::Result<()> {
+        let mut frame = *frame;
+        let fault = self.inject(&self.tx, &mut frame);
+        if let Some(delay) = fault.delay {
+            T::Timer::sleep(delay).await;
+        }
+        if fault.drop {
+            return Ok(());
+        }
+        self.transport.write_frame(&frame).await?;
+        if fault.duplicate {
+            self.transport.write_frame(&frame).await?;
+        }
+        Ok(())
+    }
+
+    async fn read_frame(&self) -> io::Result<Frame> {
+        if let Some(frame) = self.take_duplicate() {
+            return Ok(frame);
+        }
+        loop {
+            let frame = self.transport.read_frame().await?;
+            if let Some((frame, delay)) = self.received(frame) {
+                if let Some(delay) = delay {
+                    T::Timer::sleep(delay).await;
+                }
+                return Ok(frame);
+            }
+        }
+    }
+
+    fn set_filters(&self, filters: &[Filter]) -> io::Result<()> {
+        self.transport.set_filters(filters)
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl<T: crate::blocking::Transport> crate::blocking::Transport for FaultInjector<T> {
+    fn write_frame(&self, frame: &Frame) -> io::Result<()> {
+        let mut frame = *frame;
+        let fault = self.inject(&self.tx, &mut frame);
+        if let Some(delay) = fault.delay {
+            std::thread::sleep(delay);
+        }
+        if fault.drop {
+            return Ok(());
+        }
+        self.transport.write_frame(&frame)?;
+        if fault.duplicate {
+            self.transport.write_frame(&frame)?;
+        }
+        Ok(())
+    }
+
+    fn read_frame(&self) -> io::Result<Frame> {
+        if let Some(frame) = self.take_duplicate() {
+            return Ok(frame);
+        }
+        loop {
+            let frame = self.transport.read_frame()?;
+            if let Some((frame, delay)) = self.received(frame) {
+                if let Some(delay) = delay {
+                    std::thread::sleep(delay);
+                }
+                return Ok(frame);
+            }
+        }
+    }
+
+    /// Dropped frames count towards the timeout. Delays do not, a delayed
+    /// frame may be returned after the timeout expired.
+    fn read_frame_timeout(&self, timeout: Duration) -> io::Result<Option<Frame>> {
+        if let Some(frame) = self.take_duplicate() {
+            return Ok(Some(frame));
+        }
+        let deadline = std::time::Instant::now().checked_add(timeout);
+        loop {
+            let remaining = deadline.map_or(timeout, |deadline| {
+                deadline.saturating_duration_since(std::time::Instant::now())
+            });
+            let Some(frame) = self.transport.read_frame_timeout(remaining)? else {
+                return Ok(None);
+            };
+            if let Some((frame, delay)) = self.received(frame) {
+                if let Some(delay) = delay {
+                    std::thread::sleep(delay);
+                }
+                return Ok(Some(frame));
+            }
+        }
+    }
+
+    fn set_filters(&self, filters: &[Filter]) -> io::Result<()> {
+        self.transport.set_filters(filters)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_can::StandardId;
+    use std::collections::VecDeque;
+
+    struct Immediate;
+
+    impl Timer for Immediate {
+        async fn sleep(_: Duration) {}
+    }
+
+    /// Reads back the frames written.
+    #[derive(Default)]
+    struct Loopback(Mutex<VecDeque<Frame>>);
+
+    impl Transport for Loopback {
+        type Timer = Immediate;
+
+        async fn write_frame(&self, frame: &Frame) -> io::Result<()> {
+            self.0.lock().unwrap().push_back(*frame);
+            Ok(())
+        }
+
+        async fn read_frame(&self) -> io::Result<Frame> {
+            self.0
+                .lock()
+                .unwrap()
+                .pop_front()
+                .ok_or_else(|| io::ErrorKind::UnexpectedEof.into())
+        }
+    }
+
+    #[test]
+    fn injects_faults() {
+        let frame = Frame::new(StandardId::new(0x029).unwrap(), &[0; 8]).unwrap();
+        let injector = FaultInjector::new(Loopback::default(), 7)
+            .with_tx(Faults {
+                duplicate: 1.0,
+                corrupt: 1.0,
+                ..Faults::default()
+            })
+            .with_rx(Faults {
+                drop: 0.5,
+                delay: 1.0,
+                ..Faults::default()
+            });
+
+        tokio_test::block_on(async {
+            for _ in 0..50 {
+                injector.write_frame(&frame).await.unwrap();
+            }
+            let mut read = 0;
+            while let Ok(read_frame) = injector.read_frame().await {
+                // exactly one bit flipped
+                let flipped: u32 = read_frame.data().iter().map(|b| b.count_ones()).sum();
+                assert_eq!(flipped, 1);
+                read += 1;
+            }
+
+            let injected = injector.injected();
+            assert_eq!(injected.duplicated, 50);
+            assert_eq!(injected.corrupted, 50);
+            assert_eq!(read + injected.dropped, 100);
+            assert_eq!(injected.delayed, read);
+            assert!((30..70).contains(&read));
+        });
+    }
+}
@@ -0,0 +1,381 @@
+//! # gs_usb transport
+//!
+//! Access a CAN bus through a USB adapter running gs_usb compatible firmware
+//! such as [candleLight](https://github.com/candle-usb/candleLight_fw). The
+//! device is accessed directly with libusb so it works on Windows, macOS and
+//! Linux without a kernel driver.
+//!
+//! [`GsUsb`] implements the async [`Transport`](super::Transport) for tokio,
+//! which the feature enables, and the blocking
+//! [`Transport`](crate::blocking::Transport) when the `blocking` feature is
+//! enabled.
+//!
+//! ```no_run
+//! # tokio_test::block_on(async {
+//! use odrive::{can::ODrive, transport::gs_usb::GsUsb};
+//!
+//! let transport = GsUsb::open(250_000).unwrap();
+//! let odrive = ODrive::new(transport, 1).unwrap();
+//! # });
+//! ```
+
+use super::Frame;
+use embedded_can::{ExtendedId, Id, StandardId};
+use futures::{StreamExt, channel::mpsc, lock::Mutex};
+use rusb::{DeviceHandle, GlobalContext};
+use std::{
+    io,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+/// Known gs_usb compatible (vendor id, product id) pairs.
+const DEVICES: [(u16, u16); 4] = [
+    (0x1d50, 0x606f), // candleLight / CANtact
+    (0x1209, 0x2323), // CES CANext FD
+    (0x1cd2, 0x606f), // ABE CANdebugger FD
+    (0x16d0, 0x10b8), // Canable 2
+];
+
+const REQUEST_HOST_FORMAT: u8 = 0;
+const REQUEST_BITTIMING: u8 = 1;
+const REQUEST_MODE: u8 = 2;
+const REQUEST_BT_CONST: u8 = 4;
+
+const MODE_RESET: u32 = 0;
+const MODE_START: u32 = 1;
+
+const ENDPOINT_IN: u8 = 0x81;
+const ENDPOINT_OUT: u8 = 0x02;
+
+/// Echo id used by the device for received frames.
+const ECHO_ID_RX: u32 = 0xffff_ffff;
+/// Length of a classic host frame without timestamp.
+const HOST_FRAME_LEN: usize = 20;
+
+const EFF_FLAG: u32 = 0x8000_0000;
+const RTR_FLAG: u32 = 0x4000_0000;
+const ERR_FLAG: u32 = 0x2000_0000;
+
+const TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Transport using a gs_usb USB adapter.
+pub struct GsUsb {
+    device: Arc<Device>,
+    frames: Mutex<mpsc::UnboundedReceiver<io::Result<Frame>>>,
+}
+
+struct Device {
+    handle: DeviceHandle<GlobalContext>,
+    channel: u8,
+    running: AtomicBool,
+}
+
+impl GsUsb {
+    /// Open the first gs_usb adapter found and start channel 0 at `bitrate`.
+    pub fn open(bitrate: u32) -> io::Result<Self> {
+        let devices = rusb::devices().map_err(usb_error)?;
+        let device = devices
+            .iter()
+            .find(|device| {
+                device
+                    .device_descriptor()
+                    .is_ok_and(|desc| DEVICES.contains(&(desc.vendor_id(), desc.product_id())))
+            })
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No gs_usb device found"))?;
+
+        Self::open_device(&device, 0, bitrate)
+    }
+
+    /// Open a specific USB device and start `channel` at `bitrate`.
+    pub fn open_device(
+        device: &rusb::Device<GlobalContext>,
+        channel: u8,
+        bitrate: u32,
+    ) -> io::Result<Self> {
+        let handle = device.open().map_err(usb_error)?;
+        // not supported on all platforms
+        let _ = handle.set_auto_detach_kernel_driver(true);
+        handle.claim_interface(0).map_err(usb_error)?;
+
+        let device = Device {
+            handle,
+            channel,
+            running: AtomicBool::new(true),
+        };
+
+        device.control_out(REQUEST_HOST_FORMAT, &0x0000_beef_u32.to_le_bytes())?;
+        device.control_out(REQUEST_MODE, &mode(MODE_RESET))?;
+
+        let mut constants = [0; 40];
+        device.control_in(REQUEST_BT_CONST, &mut constants)?;
+        let timing = bit_timing(&constants, bitrate).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Bitrate {bitrate} not supported by device"),
+            )
+        })?;
+        device.control_out(REQUEST_BITTIMING, &timing)?;
+        device.control_out(REQUEST_MODE, &mode(MODE_START))?;
+
+        let device = Arc::new(device);
+        let (sender, receiver) = mpsc::unbounded();
+
+        let reader = device.clone();
+        thread::spawn(move || reader.read_loop(sender));
+
+        Ok(Self {
+            device,
+            frames: Mutex::new(receiver),
+        })
+    }
+
+    fn write(&self, frame: &Frame) -> io::Result<()> {
+        if frame.is_fd() {
+            return Err(super::fd_unsupported());
+        }
+        let buf = encode(frame, self.device.channel);
+        self.device
+            .handle
+            .write_bulk(ENDPOINT_OUT, &buf, TIMEOUT)
+            .map_err(usb_error)?;
+        Ok(())
+    }
+
+    async fn read(&self) -> io::Result<Frame> {
+        let mut frames = self.frames.lock().await;
+        match frames.next().await {
+            Some(frame) => frame,
+            None => Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "gs_usb device closed",
+            )),
+        }
+    }
+}
+
+impl Drop for GsUsb {
+    fn drop(&mut self) {
+        self.device.running.store(false, Ordering::Relaxed);
+        let _ = self.device.control_out(REQUEST_MODE, &mode(MODE_RESET));
+    }
+}
+
+impl Device {
+    fn control_out(&self, request: u8, data: &[u8]) -> io::Result<()> {
+        let request_type = rusb::request_type(
+            rusb::Direction::Out,
+            rusb::RequestType::Vendor,
+            rusb::Recipient::Interface,
+        );
+        self.handle
+            .write_control(request_type, request, self.channel as u16, 0, data, TIMEOUT)
+            .map_err(usb_error)?;
+        Ok(())
+    }
+
+    fn control_in(&self, request: u8, data: &mut [u8]) -> io::Result<()> {
+        let request_type = rusb::request_type(
+            rusb::Direction::In,
+            rusb::RequestType::Vendor,
+            rusb::Recipient::Interface,
+        );
+        self.handle
+            .read_control(request_type, request, self.channel as u16, 0, data, TIMEOUT)
+            .map_err(usb_error)?;
+        Ok(())
+    }
+
+    /// Forward received frames until the transport is dropped or the device
+    /// fails.
+    fn read_loop(&self, sender: mpsc::UnboundedSender<io::Result<Frame>>) {
+        let mut buf = [0; 64];
+
+        while self.running.load(Ordering::Relaxed) {
+            match self.handle.read_bulk(ENDPOINT_IN, &mut buf, TIMEOUT) {
+                Ok(len) => {
+                    if let Some(frame) = decode(&buf[..len], self.channel)
+                        && sender.unbounded_send(Ok(frame)).is_err()
+                    {
+                        return;
+                    }
+                }
+                Err(rusb::Error::Timeout) => {}
+                Err(err) => {
+                    let _ = sender.unbounded_send(Err(usb_error(err)));
+                    return;
+                }
+            }
+        }
+    }
+}
+
+impl super::Transport for GsUsb {
+    type Timer = super::TokioTimer;
+
+    async fn write_frame(&self, frame: &Frame) -> io::Result<()> {
+        self.write(frame)
+    }
+
+    async fn read_frame(&self) -> io::Result<Frame> {
+        self.read().await
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl crate::blocking::Transport for GsUsb {
+    fn write_frame(&self, frame: &Frame) -> io::Result<()> {
+        self.write(frame)
+    }
+
+    fn read_frame(&self) -> io::Result<Frame> {
+        futures::executor::block_on(self.read())
+    }
+}
+
+fn usb_error(err: rusb::Error) -> io::Error {
+    let kind = match err {
+        rusb::Error::NoDevice | rusb::Error::NotFound => io::ErrorKind::NotConnected,
+        rusb::Error::Access => io::ErrorKind::PermissionDenied,
+        rusb::Error::Timeout => io::ErrorKind::TimedOut,
+        _ => io::ErrorKind::Other,
+    };
+    io::Error::new(kind, err)
+}
+
+fn mode(mode: u32) -> [u8; 8] {
+    let mut data = [0; 8];
+    data[0..4].copy_from_slice(&mode.to_le_bytes());
+    data
+}
+
+/// Calculate the bit timing for `bitrate` from the device bit timing
+/// constants, aiming for a sample point of 87.5%.
+fn bit_timing(constants: &[u8; 40], bitrate: u32) -> Option<[u8; 20]> {
+    let field = |i: usize| {
+        u32::from_le_bytes([
+            constants[i * 4],
+            constants[i * 4 + 1],
+            constants[i * 4 + 2],
+            constants[i * 4 + 3],
+        ])
+    };
+    let fclk = field(1);
+    let (tseg1_min, tseg1_max) = (field(2), field(3));
+    let (tseg2_min, tseg2_max) = (field(4), field(5));
+    let (brp_min, brp_max, brp_inc) = (field(7), field(8), field(9).max(1));
+
+    let mut brp = brp_min.max(1);
+    while brp <= brp_max {
+        let divisor = brp * bitrate;
+        if divisor != 0 && fclk % divisor == 0 {
+            let quanta = fclk / divisor;
+            let tseg2 = (quanta / 8).max(1);
+            let tseg1 = quanta.saturating_sub(1 + tseg2);
+
+            if (tseg1_min..=tseg1_max).contains(&tseg1)
+                && (tseg2_min..=tseg2_max).contains(&tseg2)
+                && tseg1 >= 2
+            {
+                let mut timing = [0; 20];
+                timing[0..4].copy_from_slice(&1_u32.to_le_bytes()); // prop_seg
+                timing[4..8].copy_from_slice(&(tseg1 - 1).to_le_bytes()); // phase_seg1
+                timing[8..12].copy_from_slice(&tseg2.to_le_bytes()); // phase_seg2
+                timing[12..16].copy_from_slice(&1_u32.to_le_bytes()); // sjw
+                timing[16..20].copy_from_slice(&brp.to_le_bytes());
+                return Some(timing);
+            }
+        }
+        brp += brp_inc;
+    }
+
+    None
+}
+
+/// Encode a frame as a gs_usb host frame.
+fn encode(frame: &Frame, channel: u8) -> [u8; HOST_FRAME_LEN] {
+    let mut id = match frame.id() {
+        Id::Standard(id) => id.as_raw() as u32,
+        Id::Extended(id) => id.as_raw() | EFF_FLAG,
+    };
+    if frame.is_remote_frame() {
+        id |= RTR_FLAG;
+    }
+
+    let mut buf = [0; HOST_FRAME_LEN];
+    buf[0..4].copy_from_slice(&0_u32.to_le_bytes()); // echo id
+    buf[4..8].copy_from_slice(&id.to_le_bytes());
+    buf[8] = frame.dlc() as u8;
+    buf[9] = channel;
+    buf[12..12 + frame.data().len()].copy_from_slice(frame.data());
+    buf
+}
+
+/// Decode a gs_usb host frame.
+///
+/// Returns [`None`] for transmit echoes, error frames and frames from other
+/// channels.
+fn decode(buf: &[u8], channel: u8) -> Option<Frame> {
+    if buf.len() < HOST_FRAME_LEN {
+        return None;
+    }
+
+    let echo_id = u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]);
+    let raw = u32::from_le_bytes([buf[4], buf[5], buf[6], buf[7]]);
+    let dlc = (buf[8] as usize).min(8);
+
+    if echo_id != ECHO_ID_RX || buf[9] != channel || raw & ERR_FLAG != 0 {
+        return None;
+    }
+
+    let id = if raw & EFF_FLAG != 0 {
+        Id::Extended(ExtendedId::new(raw & 0x1fff_ffff)?)
+    } else {
+        Id::Standard(StandardId::new((raw & 0x7ff) as u16)?)
+    };
+
+    if raw & RTR_FLAG != 0 {
+        Frame::new_remote(id, dlc)
+    } else {
+        Frame::new(id, &buf[12..12 + dlc])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn host_frame_round_trip() {
+        let frame = Frame::new(StandardId::new(0x029).unwrap(), &[1, 2, 3]).unwrap();
+        let mut buf = encode(&frame, 0);
+
+        // transmit echoes are ignored
+        assert!(decode(&buf, 0).is_none());
+
+        buf[0..4].copy_from_slice(&ECHO_ID_RX.to_le_bytes());
+        assert_eq!(decode(&buf, 0), Some(frame));
+        assert!(decode(&buf, 1).is_none());
+    }
+
+    #[test]
+    fn bit_timing_for_candlelight() {
+        // STM32F072 based candleLight constants
+        let mut constants = [0; 40];
+        let fields = [0, 48_000_000, 1, 16, 1, 8, 4, 1, 1024, 1];
+        for (i, field) in fields.iter().enumerate() {
+            constants[i * 4..i * 4 + 4].copy_from_slice(&u32::to_le_bytes(*field));
+        }
+
+        let timing = bit_timing(&constants, 500_000).unwrap();
+        let field = |i: usize| u32::from_le_bytes(timing[i * 4..i * 4 + 4].try_into().unwrap());
+        let quanta = field(0) + field(1) + field(2) + 1;
+        assert_eq!(48_000_000 / (field(4) * quanta), 500_000);
+
+        assert!(bit_timing(&constants, 333_333).is_none());
+    }
+}
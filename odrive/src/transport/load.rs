@@ -0,0 +1,252 @@
+//! # Bus load estimation
+//!
+//! [`LoadMonitor`] wraps another transport and counts the frames and bytes
+//! it carries. [`LoadMonitor::load`] reports the rates over the last
+//! measurement window together with an estimate of the bus utilisation for
+//! the configured bitrate, which helps to tune cyclic message rates before
+//! the bus saturates.
+//!
+//! ```no_run
+//! # tokio_test::block_on(async {
+//! use odrive::{can::ODrive, transport::load::LoadMonitor};
+//!
+//! let socket = socketcan::tokio::CanSocket::open("can0").unwrap();
+//! let odrive = ODrive::new(LoadMonitor::new(socket, 1_000_000), 1).unwrap();
+//! # });
+//! ```
+//!
+//! Only frames passing through the monitor are counted, traffic between
+//! other nodes or dropped by filters is not seen.
+
+use super::{Filter, Frame, Transport};
+use std::{
+    io,
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+/// Number of bits a frame occupies on the bus.
+///
+/// Includes worst case bit stuffing and the interframe space. CAN FD frames
+/// are counted at the nominal bitrate, so the estimate is pessimistic when
+/// bit rate switching is used.
+pub fn frame_bits(frame: &Frame) -> u32 {
+    let data = if frame.is_remote_frame() {
+        0
+    } else {
+        8 * frame.data().len() as u32
+    };
+    // bits up to the CRC are subject to stuffing, 13 fixed form bits follow
+    let stuffed = match frame.id() {
+        embedded_can::Id::Standard(_) => 34 + data,
+        embedded_can::Id::Extended(_) => 54 + data,
+    };
+    stuffed + (stuffed - 1) / 4 + 13
+}
+
+/// Traffic counters.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Counts {
+    pub tx_frames: u64,
+    pub rx_frames: u64,
+    pub tx_bytes: u64,
+    pub rx_bytes: u64,
+    /// Estimated bits on the bus, see [`frame_bits`].
+    pub bits: u64,
+}
+
+/// Traffic rates over a measurement window.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BusLoad {
+    pub tx_frames_per_sec: f64,
+    pub rx_frames_per_sec: f64,
+    pub tx_bytes_per_sec: f64,
+    pub rx_bytes_per_sec: f64,
+    /// Estimated fraction of the bus capacity in use, from 0 to 1.
+    pub utilisation: f64,
+}
+
+#[derive(Debug)]
+struct Window {
+    start: Instant,
+    current: Counts,
+    last: Option<(Counts, Duration)>,
+    total: Counts,
+}
+
+impl Window {
+    /// Start a new window if the current one is complete.
+    fn roll(&mut self, now: Instant, window: Duration) {
+        let elapsed = now.duration_since(self.start);
+        if elapsed >= window {
+            self.last = Some((self.current, elapsed));
+            self.current = Counts::default();
+            self.start = now;
+        }
+    }
+}
+
+/// Transport counting traffic to estimate the bus load.
+pub struct LoadMonitor<T> {
+    transport: T,
+    bitrate: u32,
+    window: Duration,
+    stats: Mutex<Window>,
+}
+
+impl<T> LoadMonitor<T> {
+    /// Monitor `transport` on a bus running at `bitrate` bit/s.
+    pub fn new(transport: T, bitrate: u32) -> Self {
+        Self {
+            transport,
+            bitrate,
+            window: Duration::from_secs(1),
+            stats: Mutex::new(Window {
+                start: Instant::now(),
+                current: Counts::default(),
+                last: None,
+                total: Counts::default(),
+            }),
+        }
+    }
+
+    /// Measure rates over `window`, defaults to one second.
+    pub fn with_window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Wrapped transport.
+    pub fn get_ref(&self) -> &T {
+        &self.transport
+    }
+
+    /// Traffic counted since the monitor was created.
+    pub fn counts(&self) -> Counts {
+        self.lock().total
+    }
+
+    /// Rates over the last complete measurement window.
+    ///
+    /// Until the first window completes the rates since creation are
+    /// reported.
+    pub fn load(&self) -> BusLoad {
+        let now = Instant::now();
+        let mut stats = self.lock();
+        stats.roll(now, self.window);
+
+        let (counts, elapsed) = stats
+            .last
+            .unwrap_or((stats.current, now.duration_since(stats.start)));
+        let secs = elapsed.as_secs_f64();
+        if secs == 0.0 {
+            return BusLoad::default();
+        }
+
+        BusLoad {
+            tx_frames_per_sec: counts.tx_frames as f64 / secs,
+            rx_frames_per_sec: counts.rx_frames as f64 / secs,
+            tx_bytes_per_sec: counts.tx_bytes as f64 / secs,
+            rx_bytes_per_sec: counts.rx_bytes as f64 / secs,
+            utilisation: counts.bits as f64 / (self.bitrate as f64 * secs),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Window> {
+        self.stats.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn record(&self, frame: &Frame, tx: bool) {
+        let mut guard = self.lock();
+        let stats = &mut *guard;
+        stats.roll(Instant::now(), self.window);
+
+        let bytes = frame.data().len() as u64;
+        let bits = frame_bits(frame) as u64;
+        for counts in [&mut stats.current, &mut stats.total] {
+            if tx {
+                counts.tx_frames += 1;
+                counts.tx_bytes += bytes;
+            } else {
+                counts.rx_frames += 1;
+                counts.rx_bytes += bytes;
+            }
+            counts.bits += bits;
+        }
+    }
+}
+
+impl<T: Transport + Sync> Transport for LoadMonitor<T> {
+    type Timer = T::Timer;
+
+    async fn write_frame(&self, frame: &Frame) -> io::Result<()> {
+        self.transport.write_frame(frame).await?;
+        self.record(frame, true);
+        Ok(())
+    }
+
+    async fn read_frame(&self) -> io::Result<Frame> {
+        let frame = self.transport.read_frame().await?;
+        self.record(&frame, false);
+        Ok(frame)
+    }
+
+    fn set_filters(&self, filters: &[Filter]) -> io::Result<()> {
+        self.transport.set_filters(filters)
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl<T: crate::blocking::Transport> crate::blocking::Transport for LoadMonitor<T> {
+    fn write_frame(&self, frame: &Frame) -> io::Result<()> {
+        self.transport.write_frame(frame)?;
+        self.record(frame, true);
+        Ok(())
+    }
+
+    fn read_frame(&self) -> io::Result<Frame> {
+        let frame = self.transport.read_frame()?;
+        self.record(&frame, false);
+        Ok(frame)
+    }
+
+    fn read_frame_timeout(&self, timeout: Duration) -> io::Result<Option<Frame>> {
+        let frame = self.transport.read_frame_timeout(timeout)?;
+        if let Some(frame) = &frame {
+            self.record(frame, false);
+        }
+        Ok(frame)
+    }
+
+    fn set_filters(&self, filters: &[Filter]) -> io::Result<()> {
+        self.transport.set_filters(filters)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_can::StandardId;
+
+    #[test]
+    fn classic_frame_bits() {
+        let id = StandardId::new(0x029).unwrap();
+        // 111 bits plus up to 24 stuff bits for a full standard frame
+        assert_eq!(frame_bits(&Frame::new(id, &[0; 8]).unwrap()), 135);
+        assert_eq!(frame_bits(&Frame::new_remote(id, 8).unwrap()), 55);
+    }
+
+    #[test]
+    fn counts_traffic() {
+        let monitor = LoadMonitor::new((), 1_000_000).with_window(Duration::from_secs(3600));
+        let frame = Frame::new(StandardId::new(0x029).unwrap(), &[0; 8]).unwrap();
+        monitor.record(&frame, true);
+        monitor.record(&frame, false);
+
+        let counts = monitor.counts();
+        assert_eq!(counts.tx_frames, 1);
+        assert_eq!(counts.rx_bytes, 8);
+        assert_eq!(counts.bits, 270);
+        assert!(monitor.load().utilisation > 0.0);
+    }
+}
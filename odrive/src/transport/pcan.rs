@@ -0,0 +1,344 @@
+//! # PCAN-Basic transport
+//!
+//! Access a CAN bus through a PEAK-System PCAN adapter using the PCAN-Basic
+//! library. The library is loaded at runtime so it only needs to be installed
+//! on machines that actually use the transport: `PCANBasic.dll` on Windows,
+//! `libpcanbasic.so` on Linux and `libPCBUSB.dylib` on macOS.
+//!
+//! [`Pcan`] implements the async [`Transport`](super::Transport) for tokio,
+//! which the feature enables, and the blocking
+//! [`Transport`](crate::blocking::Transport) when the `blocking` feature is
+//! enabled.
+//!
+//! ```no_run
+//! # tokio_test::block_on(async {
+//! use odrive::{can::ODrive, transport::pcan::{Pcan, USB_BUS_1}};
+//!
+//! let transport = Pcan::open(USB_BUS_1, 250_000).unwrap();
+//! let odrive = ODrive::new(transport, 1).unwrap();
+//! # });
+//! ```
+
+use super::Frame;
+use embedded_can::{ExtendedId, Id, StandardId};
+use futures::{StreamExt, channel::mpsc, lock::Mutex};
+use std::{
+    io,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+/// PCAN-USB interface, channel 1.
+pub const USB_BUS_1: u16 = 0x51;
+/// PCAN-USB interface, channel 2.
+pub const USB_BUS_2: u16 = 0x52;
+/// PCAN-USB interface, channel 3.
+pub const USB_BUS_3: u16 = 0x53;
+/// PCAN-USB interface, channel 4.
+pub const USB_BUS_4: u16 = 0x54;
+
+#[cfg(target_os = "windows")]
+const LIBRARY: &str = "PCANBasic.dll";
+#[cfg(target_os = "macos")]
+const LIBRARY: &str = "libPCBUSB.dylib";
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+const LIBRARY: &str = "libpcanbasic.so";
+
+const ERROR_OK: u32 = 0x00000;
+const ERROR_QRCVEMPTY: u32 = 0x00020;
+
+const MESSAGE_STANDARD: u8 = 0x00;
+const MESSAGE_RTR: u8 = 0x01;
+const MESSAGE_EXTENDED: u8 = 0x02;
+const MESSAGE_FD: u8 = 0x04;
+const MESSAGE_ERRFRAME: u8 = 0x40;
+const MESSAGE_STATUS: u8 = 0x80;
+
+/// Interval at which the receive queue is polled when empty.
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct Message {
+    id: u32,
+    message_type: u8,
+    len: u8,
+    data: [u8; 8],
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct Timestamp {
+    millis: u32,
+    millis_overflow: u16,
+    micros: u16,
+}
+
+type Initialize = unsafe extern "system" fn(u16, u16, u8, u32, u16) -> u32;
+type Uninitialize = unsafe extern "system" fn(u16) -> u32;
+type Read = unsafe extern "system" fn(u16, *mut Message, *mut Timestamp) -> u32;
+type Write = unsafe extern "system" fn(u16, *mut Message) -> u32;
+
+/// Transport using a PCAN adapter.
+pub struct Pcan {
+    device: Arc<Device>,
+    frames: Mutex<mpsc::UnboundedReceiver<io::Result<Frame>>>,
+}
+
+struct Device {
+    channel: u16,
+    running: AtomicBool,
+    uninitialize: Uninitialize,
+    read: Read,
+    write: Write,
+    // keeps the function pointers valid
+    _library: libloading::Library,
+}
+
+impl Pcan {
+    /// Open a PCAN `channel`, such as [`USB_BUS_1`], at `bitrate`.
+    pub fn open(channel: u16, bitrate: u32) -> io::Result<Self> {
+        let baudrate = baudrate(bitrate).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Bitrate {bitrate} not supported by PCAN-Basic"),
+            )
+        })?;
+
+        // Safety: PCAN-Basic has no initialisation routines with
+        // preconditions and the symbols match the documented signatures.
+        let device = unsafe {
+            let library = libloading::Library::new(LIBRARY).map_err(io::Error::other)?;
+            let initialize = *library
+                .get::<Initialize>(b"CAN_Initialize\0")
+                .map_err(io::Error::other)?;
+            let uninitialize = *library
+                .get::<Uninitialize>(b"CAN_Uninitialize\0")
+                .map_err(io::Error::other)?;
+            let read = *library
+                .get::<Read>(b"CAN_Read\0")
+                .map_err(io::Error::other)?;
+            let write = *library
+                .get::<Write>(b"CAN_Write\0")
+                .map_err(io::Error::other)?;
+
+            // the device uninitializes the channel on drop, which must not
+            // happen if it is already in use by someone else
+            check(initialize(channel, baudrate, 0, 0, 0))?;
+            Device {
+                channel,
+                running: AtomicBool::new(true),
+                uninitialize,
+                read,
+                write,
+                _library: library,
+            }
+        };
+
+        let device = Arc::new(device);
+        let (sender, receiver) = mpsc::unbounded();
+
+        let reader = device.clone();
+        thread::spawn(move || reader.read_loop(sender));
+
+        Ok(Self {
+            device,
+            frames: Mutex::new(receiver),
+        })
+    }
+
+    fn write(&self, frame: &Frame) -> io::Result<()> {
+        if frame.is_fd() {
+            return Err(super::fd_unsupported());
+        }
+        let mut message = encode(frame);
+        // Safety: `message` is a valid `TPCANMsg` for the duration of the call.
+        check(unsafe { (self.device.write)(self.device.channel, &mut message) })
+    }
+
+    async fn read(&self) -> io::Result<Frame> {
+        let mut frames = self.frames.lock().await;
+        match frames.next().await {
+            Some(frame) => frame,
+            None => Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "PCAN channel closed",
+            )),
+        }
+    }
+}
+
+impl Drop for Pcan {
+    fn drop(&mut self) {
+        self.device.running.store(false, Ordering::Relaxed);
+    }
+}
+
+impl Drop for Device {
+    fn drop(&mut self) {
+        // Safety: the channel was initialised when the device was created.
+        unsafe { (self.uninitialize)(self.channel) };
+    }
+}
+
+impl Device {
+    /// Forward received frames until the transport is dropped or the channel
+    /// fails.
+    fn read_loop(&self, sender: mpsc::UnboundedSender<io::Result<Frame>>) {
+        while self.running.load(Ordering::Relaxed) {
+            let mut message = Message::default();
+            let mut timestamp = Timestamp::default();
+            // Safety: both buffers are valid for the duration of the call.
+            let status = unsafe { (self.read)(self.channel, &mut message, &mut timestamp) };
+
+            let result = match status {
+                ERROR_OK => match decode(&message) {
+                    Some(frame) => Ok(frame),
+                    None => continue,
+                },
+                ERROR_QRCVEMPTY => {
+                    thread::sleep(POLL_INTERVAL);
+                    continue;
+                }
+                status => Err(status_error(status)),
+            };
+
+            let failed = result.is_err();
+            if sender.unbounded_send(result).is_err() || failed {
+                return;
+            }
+        }
+    }
+}
+
+impl super::Transport for Pcan {
+    type Timer = super::TokioTimer;
+
+    async fn write_frame(&self, frame: &Frame) -> io::Result<()> {
+        self.write(frame)
+    }
+
+    async fn read_frame(&self) -> io::Result<Frame> {
+        self.read().await
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl crate::blocking::Transport for Pcan {
+    fn write_frame(&self, frame: &Frame) -> io::Result<()> {
+        self.write(frame)
+    }
+
+    fn read_frame(&self) -> io::Result<Frame> {
+        futures::executor::block_on(self.read())
+    }
+}
+
+fn check(status: u32) -> io::Result<()> {
+    if status == ERROR_OK {
+        Ok(())
+    } else {
+        Err(status_error(status))
+    }
+}
+
+fn status_error(status: u32) -> io::Error {
+    io::Error::other(format!("PCAN-Basic error: {status:#07x}"))
+}
+
+/// Map a bitrate to the PCAN-Basic BTR0/BTR1 constant.
+fn baudrate(bitrate: u32) -> Option<u16> {
+    Some(match bitrate {
+        1_000_000 => 0x0014,
+        800_000 => 0x0016,
+        500_000 => 0x001c,
+        250_000 => 0x011c,
+        125_000 => 0x031c,
+        100_000 => 0x432f,
+        50_000 => 0x472f,
+        20_000 => 0x532f,
+        10_000 => 0x672f,
+        5_000 => 0x7f7f,
+        _ => return None,
+    })
+}
+
+fn encode(frame: &Frame) -> Message {
+    let (id, mut message_type) = match frame.id() {
+        Id::Standard(id) => (id.as_raw() as u32, MESSAGE_STANDARD),
+        Id::Extended(id) => (id.as_raw(), MESSAGE_EXTENDED),
+    };
+    if frame.is_remote_frame() {
+        message_type |= MESSAGE_RTR;
+    }
+
+    let mut data = [0; 8];
+    data[..frame.data().len()].copy_from_slice(frame.data());
+
+    Message {
+        id,
+        message_type,
+        len: frame.dlc() as u8,
+        data,
+    }
+}
+
+/// Decode a received message.
+///
+/// Returns [`None`] for status, error and CAN FD messages.
+fn decode(message: &Message) -> Option<Frame> {
+    if message.message_type & (MESSAGE_FD | MESSAGE_ERRFRAME | MESSAGE_STATUS) != 0 {
+        return None;
+    }
+
+    let id = if message.message_type & MESSAGE_EXTENDED != 0 {
+        Id::Extended(ExtendedId::new(message.id)?)
+    } else {
+        Id::Standard(StandardId::new(message.id as u16)?)
+    };
+    let len = (message.len as usize).min(8);
+
+    if message.message_type & MESSAGE_RTR != 0 {
+        Frame::new_remote(id, len)
+    } else {
+        Frame::new(id, &message.data[..len])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_round_trip() {
+        let frames = [
+            Frame::new(StandardId::new(0x029).unwrap(), &[1, 2, 3]).unwrap(),
+            Frame::new_remote(StandardId::new(0x029).unwrap(), 8).unwrap(),
+            Frame::new(ExtendedId::new(0x1234567).unwrap(), &[4]).unwrap(),
+        ];
+
+        for frame in frames {
+            assert_eq!(decode(&encode(&frame)), Some(frame));
+        }
+    }
+
+    #[test]
+    fn status_messages_ignored() {
+        let message = Message {
+            message_type: MESSAGE_STATUS,
+            ..Default::default()
+        };
+        assert!(decode(&message).is_none());
+    }
+
+    #[test]
+    fn message_layout() {
+        // TPCANMsg is 16 bytes including padding
+        assert_eq!(std::mem::size_of::<Message>(), 16);
+        assert_eq!(std::mem::size_of::<Timestamp>(), 8);
+    }
+}
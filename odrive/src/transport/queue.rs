@@ -0,0 +1,526 @@
+//! # Transmit queue
+//!
+//! [`TxQueue`] wraps another transport and queues outgoing frames. While a
+//! frame is being written further frames wait in the queue and are sent by
+//! [`Priority`], so an `estop` is never stuck behind a burst of SDO writes.
+//! Frames of the same priority are sent in order. A setpoint
+//! (`set_input_position`, `set_input_velocity` or `set_input_torque`) that
+//! is still queued is replaced by a newer setpoint of the same kind for the
+//! same node. When setpoints are produced faster than the bus can carry them
+//! only the latest value is sent instead of an ever growing backlog.
+//!
+//! ```no_run
+//! # tokio_test::block_on(async {
+//! use odrive::{can::ODrive, transport::queue::TxQueue};
+//!
+//! let socket = socketcan::tokio::CanSocket::open("can0").unwrap();
+//! let odrive = ODrive::new(TxQueue::new(socket), 1).unwrap();
+//! # });
+//! ```
+//!
+//! Every caller waits until its frame has been written and gets the result
+//! of that write, whichever caller wrote it. A setpoint replaced by a newer
+//! one gets the result of the newer one. When the transmitting caller is
+//! done or dropped, a waiting caller takes over. Frames whose callers were
+//! all dropped are removed from the queue.
+
+use super::{Filter, Frame, Transport};
+use crate::protocol::Priority;
+use std::{
+    collections::{HashMap, VecDeque},
+    future::poll_fn,
+    io,
+    sync::{Condvar, Mutex, MutexGuard, PoisonError},
+    task::{Context, Poll, Waker},
+};
+
+/// Transport queueing outgoing frames and coalescing setpoints.
+pub struct TxQueue<T> {
+    transport: T,
+    state: Mutex<State>,
+    /// Notified when a frame was written or transmitting stopped.
+    ready: Condvar,
+}
+
+impl<T> TxQueue<T> {
+    /// Queue frames written to `transport`.
+    pub fn new(transport: T) -> Self {
+        Self {
+            transport,
+            state: Mutex::new(State::default()),
+            ready: Condvar::new(),
+        }
+    }
+
+    /// Wrapped transport.
+    pub fn get_ref(&self) -> &T {
+        &self.transport
+    }
+
+    /// Number of frames waiting to be written.
+    pub fn len(&self) -> usize {
+        self.lock().queues.0.iter().map(VecDeque::len).sum()
+    }
+
+    /// Returns true if no frames are waiting to be written.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Queue `frame` for a new caller.
+    fn enqueue(&self, frame: Frame) -> Ticket<'_, T> {
+        let mut state = self.lock();
+        let ticket = state.next;
+        state.next += 1;
+        state.queues.push(frame, ticket);
+        Ticket(self, ticket)
+    }
+
+    /// Next frame to write, marked as in flight.
+    fn pop(&self) -> Option<InFlight<'_, T>> {
+        let entry = self.lock().queues.pop()?;
+        Some(InFlight {
+            queue: self,
+            frame: entry.frame,
+            tickets: entry.tickets,
+        })
+    }
+
+    /// Hand out the result of the write of `tickets`.
+    fn complete(&self, tickets: Vec<u64>, result: io::Result<()>) {
+        let mut state = self.lock();
+        let mut result = Some(result);
+        for (i, &ticket) in tickets.iter().enumerate() {
+            // io::Error is not Clone, the last caller gets the original
+            let result = if i + 1 == tickets.len() {
+                result.take().unwrap_or(Ok(()))
+            } else {
+                match &result {
+                    Some(Err(err)) => Err(io::Error::new(err.kind(), err.to_string())),
+                    _ => Ok(()),
+                }
+            };
+            state.done.insert(ticket, result);
+            if let Some(waker) = state.wakers.remove(&ticket) {
+                waker.wake();
+            }
+        }
+        self.ready.notify_all();
+    }
+
+    /// Result of `ticket` or the transmitting role, if either is available.
+    fn turn(&self, state: &mut State, ticket: u64) -> Option<Turn<'_, T>> {
+        if let Some(result) = state.done.remove(&ticket) {
+            return Some(Turn::Done(result));
+        }
+        if !state.transmitting {
+            state.transmitting = true;
+            return Some(Turn::Transmit(Transmitting(self)));
+        }
+        None
+    }
+
+    fn poll_turn(&self, ticket: u64, cx: &mut Context<'_>) -> Poll<Turn<'_, T>> {
+        let mut state = self.lock();
+        match self.turn(&mut state, ticket) {
+            Some(turn) => Poll::Ready(turn),
+            None => {
+                state.wakers.insert(ticket, cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+
+    #[cfg(feature = "blocking")]
+    fn wait_turn(&self, ticket: u64) -> Turn<'_, T> {
+        let mut state = self.lock();
+        loop {
+            if let Some(turn) = self.turn(&mut state, ticket) {
+                return turn;
+            }
+            state = self
+                .ready
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    fn is_done(&self, ticket: u64) -> bool {
+        self.lock().done.contains_key(&ticket)
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    queues: Queues,
+    transmitting: bool,
+    /// Results of written frames not yet picked up by their callers.
+    done: HashMap<u64, io::Result<()>>,
+    /// Wakers of async callers waiting for their frame.
+    wakers: HashMap<u64, Waker>,
+    next: u64,
+}
+
+/// Queued frame and the callers waiting for it.
+#[derive(Debug)]
+struct Entry {
+    frame: Frame,
+    tickets: Vec<u64>,
+}
+
+/// One queue per [`Priority`].
+#[derive(Debug, Default)]
+struct Queues([VecDeque<Entry>; Priority::COUNT]);
+
+impl Queues {
+    fn push(&mut self, frame: Frame, ticket: u64) {
+        push(&mut self.0[Priority::of(&frame) as usize], frame, ticket);
+    }
+
+    /// Next frame of the highest priority.
+    fn pop(&mut self) -> Option<Entry> {
+        self.0.iter_mut().find_map(VecDeque::pop_front)
+    }
+
+    /// Stop waiting for `ticket`, removing its frame if no one else waits.
+    fn remove(&mut self, ticket: u64) {
+        for queue in &mut self.0 {
+            for entry in queue.iter_mut() {
+                entry.tickets.retain(|&t| t != ticket);
+            }
+            queue.retain(|entry| !entry.tickets.is_empty());
+        }
+    }
+}
+
+enum Turn<'a, T> {
+    /// The frame was written, by this or another caller.
+    Done(io::Result<()>),
+    /// No one is transmitting, the caller writes queued frames.
+    Transmit(Transmitting<'a, T>),
+}
+
+/// Frame queued by a caller, removed when the caller is dropped.
+struct Ticket<'a, T>(&'a TxQueue<T>, u64);
+
+impl<T> Drop for Ticket<'_, T> {
+    fn drop(&mut self) {
+        let mut state = self.0.lock();
+        state.queues.remove(self.1);
+        state.done.remove(&self.1);
+        state.wakers.remove(&self.1);
+    }
+}
+
+/// Releases the transmitting role when dropped and hands it to a waiting
+/// caller.
+struct Transmitting<'a, T>(&'a TxQueue<T>);
+
+impl<T> Drop for Transmitting<'_, T> {
+    fn drop(&mut self) {
+        let mut state = self.0.lock();
+        state.transmitting = false;
+        let next = state.wakers.keys().next().copied();
+        if let Some(waker) = next.and_then(|ticket| state.wakers.remove(&ticket)) {
+            waker.wake();
+        }
+        self.0.ready.notify_all();
+    }
+}
+
+/// Frame being written, fails its callers if the write is dropped.
+struct InFlight<'a, T> {
+    queue: &'a TxQueue<T>,
+    frame: Frame,
+    tickets: Vec<u64>,
+}
+
+impl<T> InFlight<'_, T> {
+    fn complete(mut self, result: io::Result<()>) {
+        self.queue
+            .complete(std::mem::take(&mut self.tickets), result);
+    }
+}
+
+impl<T> Drop for InFlight<'_, T> {
+    fn drop(&mut self) {
+        if !self.tickets.is_empty() {
+            let err = io::Error::new(io::ErrorKind::Interrupted, "write was cancelled");
+            self.queue
+                .complete(std::mem::take(&mut self.tickets), Err(err));
+        }
+    }
+}
+
+/// Returns true if `frame` is a setpoint that may be replaced by a newer one.
+fn is_setpoint(frame: &Frame) -> bool {
+    Priority::of(frame) == Priority::Setpoint
+}
+
+/// Queue `frame`, replacing a queued setpoint with the same identifier.
+fn push(queue: &mut VecDeque<Entry>, frame: Frame, ticket: u64) {
+    if is_setpoint(&frame)
+        && let Some(queued) = queue
+            .iter_mut()
+            .find(|queued| queued.frame.id() == frame.id())
+    {
+        queued.frame = frame;
+        queued.tickets.push(ticket);
+        return;
+    }
+    queue.push_back(Entry {
+        frame,
+        tickets: vec![ticket],
+    });
+}
+
+impl<T: Transport + Sync> Transport for TxQueue<T> {
+    type Timer = T::Timer;
+
+    async fn write_frame(&self, frame: &Frame) -> io::Result<()> {
+        let ticket = self.enqueue(*frame);
+
+        loop {
+            match poll_fn(|cx| self.poll_turn(ticket.1, cx)).await {
+                Turn::Done(result) => return result,
+                Turn::Transmit(_transmitting) => {
+                    // write until the own frame is written, then hand over
+                    while let Some(in_flight) = self.pop() {
+                        let result = self.transport.write_frame(&in_flight.frame).await;
+                        in_flight.complete(result);
+                        if self.is_done(ticket.1) {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn read_frame(&self) -> impl Future<Output = io::Result<Frame>> + Send {
+        self.transport.read_frame()
+    }
+
+    fn set_filters(&self, filters: &[Filter]) -> io::Result<()> {
+        self.transport.set_filters(filters)
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl<T: crate::blocking::Transport> crate::blocking::Transport for TxQueue<T> {
+    fn write_frame(&self, frame: &Frame) -> io::Result<()> {
+        let ticket = self.enqueue(*frame);
+
+        loop {
+            match self.wait_turn(ticket.1) {
+                Turn::Done(result) => return result,
+                Turn::Transmit(_transmitting) => {
+                    while let Some(in_flight) = self.pop() {
+                        let result = self.transport.write_frame(&in_flight.frame);
+                        in_flight.complete(result);
+                        if self.is_done(ticket.1) {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn read_frame(&self) -> io::Result<Frame> {
+        self.transport.read_frame()
+    }
+
+    fn read_frame_timeout(&self, timeout: std::time::Duration) -> io::Result<Option<Frame>> {
+        self.transport.read_frame_timeout(timeout)
+    }
+
+    fn set_filters(&self, filters: &[Filter]) -> io::Result<()> {
+        self.transport.set_filters(filters)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol;
+    #[cfg(any(feature = "tokio", feature = "blocking"))]
+    use std::time::Duration;
+
+    /// Slow transport that fails to write one frame.
+    #[cfg(any(feature = "tokio", feature = "blocking"))]
+    struct Failing {
+        fail: Frame,
+        written: Mutex<Vec<Frame>>,
+    }
+
+    #[cfg(any(feature = "tokio", feature = "blocking"))]
+    impl Failing {
+        fn new(fail: Frame) -> Self {
+            Self {
+                fail,
+                written: Mutex::default(),
+            }
+        }
+
+        fn write(&self, frame: &Frame) -> io::Result<()> {
+            if *frame == self.fail {
+                return Err(io::ErrorKind::NetworkDown.into());
+            }
+            self.written.lock().unwrap().push(*frame);
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    impl Transport for Failing {
+        type Timer = crate::transport::TokioTimer;
+
+        async fn write_frame(&self, frame: &Frame) -> io::Result<()> {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            self.write(frame)
+        }
+
+        async fn read_frame(&self) -> io::Result<Frame> {
+            std::future::pending().await
+        }
+    }
+
+    #[cfg(feature = "blocking")]
+    impl crate::blocking::Transport for Failing {
+        fn write_frame(&self, frame: &Frame) -> io::Result<()> {
+            std::thread::sleep(Duration::from_millis(10));
+            self.write(frame)
+        }
+
+        fn read_frame(&self) -> io::Result<Frame> {
+            Err(io::ErrorKind::WouldBlock.into())
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(start_paused = true)]
+    async fn errors_reach_their_caller() {
+        let ok = protocol::clear_errors(1, false).unwrap();
+        let fail = protocol::estop(2).unwrap();
+        let queue = TxQueue::new(Failing::new(fail));
+
+        // the second frame is queued and written by the first caller
+        let (first, second) = tokio::join!(queue.write_frame(&ok), queue.write_frame(&fail));
+        assert!(first.is_ok());
+        assert_eq!(second.unwrap_err().kind(), io::ErrorKind::NetworkDown);
+
+        // the queued caller takes over when the transmitting one is dropped
+        let other = protocol::estop(3).unwrap();
+        let (first, second) = tokio::join!(
+            tokio::time::timeout(Duration::from_millis(5), queue.write_frame(&ok)),
+            queue.write_frame(&other)
+        );
+        assert!(first.is_err());
+        assert!(second.is_ok());
+        assert_eq!(*queue.get_ref().written.lock().unwrap(), [ok, other]);
+        assert!(queue.is_empty());
+    }
+
+    #[cfg(feature = "blocking")]
+    #[test]
+    fn blocking_errors_reach_their_caller() {
+        let write =
+            |queue: &TxQueue<Failing>, frame| crate::blocking::Transport::write_frame(queue, frame);
+        let ok = protocol::clear_errors(1, false).unwrap();
+        let fail = protocol::estop(2).unwrap();
+        let queue = TxQueue::new(Failing::new(fail));
+
+        std::thread::scope(|scope| {
+            let first = scope.spawn(|| write(&queue, &ok));
+            std::thread::sleep(Duration::from_millis(5));
+            assert_eq!(
+                write(&queue, &fail).unwrap_err().kind(),
+                io::ErrorKind::NetworkDown
+            );
+            assert!(first.join().unwrap().is_ok());
+        });
+        assert_eq!(*queue.get_ref().written.lock().unwrap(), [ok]);
+    }
+
+    #[test]
+    fn setpoints_coalesce() {
+        let mut queue = VecDeque::new();
+        push(
+            &mut queue,
+            protocol::set_input_position(1, 1.0, 0, 0).unwrap(),
+            0,
+        );
+        push(
+            &mut queue,
+            protocol::set_input_velocity(1, 2.0, 0.0).unwrap(),
+            1,
+        );
+        push(
+            &mut queue,
+            protocol::set_input_position(2, 3.0, 0, 0).unwrap(),
+            2,
+        );
+        push(
+            &mut queue,
+            protocol::set_input_position(1, 4.0, 0, 0).unwrap(),
+            3,
+        );
+
+        assert_eq!(queue.len(), 3);
+        assert_eq!(
+            queue[0].frame,
+            protocol::set_input_position(1, 4.0, 0, 0).unwrap()
+        );
+        assert_eq!(queue[0].tickets, [0, 3]);
+        assert_eq!(
+            queue[2].frame,
+            protocol::set_input_position(2, 3.0, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn priority_order() {
+        let mut queues = Queues::default();
+        queues.push(
+            protocol::sdo_write(1, 2, protocol::Value::Bool(true)).unwrap(),
+            0,
+        );
+        queues.push(*protocol::get_iq(1).unwrap().frame(), 1);
+        queues.push(protocol::set_input_torque(1, 0.5).unwrap(), 2);
+        queues.push(
+            protocol::sdo_write(1, 3, protocol::Value::Bool(true)).unwrap(),
+            3,
+        );
+        queues.push(protocol::estop(1).unwrap(), 4);
+        let mut pop = || queues.pop().map(|entry| entry.frame);
+
+        assert_eq!(pop(), Some(protocol::estop(1).unwrap()));
+        assert_eq!(pop(), Some(protocol::set_input_torque(1, 0.5).unwrap()));
+        assert_eq!(pop(), Some(*protocol::get_iq(1).unwrap().frame()));
+        assert_eq!(
+            pop(),
+            Some(protocol::sdo_write(1, 2, protocol::Value::Bool(true)).unwrap())
+        );
+        assert_eq!(
+            pop(),
+            Some(protocol::sdo_write(1, 3, protocol::Value::Bool(true)).unwrap())
+        );
+        assert_eq!(pop(), None);
+    }
+
+    #[test]
+    fn commands_do_not_coalesce() {
+        let mut queue = VecDeque::new();
+        push(&mut queue, protocol::clear_errors(1, false).unwrap(), 0);
+        push(&mut queue, protocol::clear_errors(1, false).unwrap(), 1);
+        push(&mut queue, *protocol::get_iq(1).unwrap().frame(), 2);
+        push(&mut queue, *protocol::get_iq(1).unwrap().frame(), 3);
+
+        assert_eq!(queue.len(), 4);
+    }
+}
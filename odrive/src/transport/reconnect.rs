@@ -0,0 +1,406 @@
+//! # Reconnecting transport
+//!
+//! [`Reconnect`] wraps another transport and reopens it when it fails, for
+//! example when a USB adapter is unplugged or the interface is taken down.
+//! Opening is retried with exponential backoff and operation resumes once the
+//! transport is back. Frames in flight while disconnected are lost.
+//!
+//! ```no_run
+//! # tokio_test::block_on(async {
+//! use odrive::{can::ODrive, transport::reconnect::Reconnect};
+//!
+//! let transport = Reconnect::new(|| socketcan::tokio::CanSocket::open("can0"))
+//!     .on_connection_state(|state| println!("{state:?}"));
+//! let odrive = ODrive::new(transport, 1).unwrap();
+//! # });
+//! ```
+
+use super::{BusError, BusState, Filter, Frame};
+#[cfg(feature = "blocking")]
+use std::time::Instant;
+use std::{
+    io,
+    sync::{Arc, Mutex, PoisonError, RwLock},
+    time::Duration,
+};
+
+/// Connection state of a [`Reconnect`] transport.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// The transport is open.
+    Connected,
+    /// The transport failed and will be reopened on next use.
+    Disconnected,
+    /// Opening the transport failed, retrying after a delay.
+    Reconnecting { attempt: u32, delay: Duration },
+}
+
+type StateCallback = Arc<dyn Fn(ConnectionState) + Send + Sync>;
+
+/// Transport that reopens the wrapped transport when it fails.
+pub struct Reconnect<T, C> {
+    connect: C,
+    transport: RwLock<Option<Arc<T>>>,
+    filters: Mutex<Option<Vec<Filter>>>,
+    initial_delay: Duration,
+    max_delay: Duration,
+    on_state: Option<StateCallback>,
+}
+
+impl<T, C> Reconnect<T, C>
+where
+    C: Fn() -> io::Result<T>,
+{
+    /// Create a transport opened by `connect`.
+    ///
+    /// The transport is opened on first use.
+    pub fn new(connect: C) -> Self {
+        Self {
+            connect,
+            transport: RwLock::new(None),
+            filters: Mutex::new(None),
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(5),
+            on_state: None,
+        }
+    }
+
+    /// Retry opening after `initial` doubling up to `max`.
+    ///
+    /// Defaults to 100 ms up to 5 s.
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_delay = initial;
+        self.max_delay = max;
+        self
+    }
+
+    /// Call `callback` when the connection state changes.
+    pub fn on_connection_state(
+        mut self,
+        callback: impl Fn(ConnectionState) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_state = Some(Arc::new(callback));
+        self
+    }
+
+    fn emit(&self, state: ConnectionState) {
+        trace_event!(info, ?state, "connection state changed");
+        if let Some(callback) = &self.on_state {
+            callback(state);
+        }
+    }
+
+    fn current(&self) -> Option<Arc<T>> {
+        self.transport
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Try to open the transport once.
+    fn open(&self, install: impl Fn(&T, &[Filter]) -> io::Result<()>) -> io::Result<Arc<T>> {
+        let transport = (self.connect)()?;
+        if let Some(filters) = &*self.filters.lock().unwrap_or_else(PoisonError::into_inner) {
+            install(&transport, filters)?;
+        }
+
+        let mut slot = self
+            .transport
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        // another caller may have reconnected in the meantime
+        let transport = slot.get_or_insert_with(|| Arc::new(transport)).clone();
+        drop(slot);
+
+        self.emit(ConnectionState::Connected);
+        Ok(transport)
+    }
+
+    /// Drop `failed` if it is still the current transport.
+    fn disconnect(&self, failed: &Arc<T>) {
+        let mut slot = self
+            .transport
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        if slot
+            .as_ref()
+            .is_some_and(|current| Arc::ptr_eq(current, failed))
+        {
+            *slot = None;
+            drop(slot);
+            self.emit(ConnectionState::Disconnected);
+        }
+    }
+
+    /// Delay before the given retry attempt.
+    fn delay(&self, attempt: u32) -> Duration {
+        self.initial_delay
+            .saturating_mul(1 << attempt.min(16))
+            .min(self.max_delay)
+    }
+
+    fn set_filters_with(
+        &self,
+        filters: &[Filter],
+        install: impl Fn(&T, &[Filter]) -> io::Result<()>,
+    ) -> io::Result<()> {
+        *self.filters.lock().unwrap_or_else(PoisonError::into_inner) = Some(filters.to_vec());
+        match self.current() {
+            Some(transport) => install(&transport, filters),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Returns true if `err` means the transport has to be reopened.
+fn is_disconnect(err: &io::Error) -> bool {
+    if BusState::from_error(err).is_some() || BusError::from_error(err).is_some() {
+        return false;
+    }
+
+    #[cfg(target_os = "linux")]
+    if err.raw_os_error() == Some(19) {
+        // ENODEV, the interface was removed
+        return true;
+    }
+
+    matches!(
+        err.kind(),
+        io::ErrorKind::NotConnected
+            | io::ErrorKind::BrokenPipe
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::NetworkDown
+            | io::ErrorKind::UnexpectedEof
+    )
+}
+
+impl<T, C> super::Transport for Reconnect<T, C>
+where
+    T: super::Transport + Send + Sync,
+    C: Fn() -> io::Result<T> + Send + Sync,
+{
+    type Timer = T::Timer;
+
+    async fn write_frame(&self, frame: &Frame) -> io::Result<()> {
+        loop {
+            let transport = self.connected().await;
+            match transport.write_frame(frame).await {
+                Err(err) if is_disconnect(&err) => self.disconnect(&transport),
+                result => return result,
+            }
+        }
+    }
+
+    async fn read_frame(&self) -> io::Result<Frame> {
+        loop {
+            let transport = self.connected().await;
+            match transport.read_frame().await {
+                Err(err) if is_disconnect(&err) => self.disconnect(&transport),
+                result => return result,
+            }
+        }
+    }
+
+    fn set_filters(&self, filters: &[Filter]) -> io::Result<()> {
+        self.set_filters_with(filters, |transport, filters| transport.set_filters(filters))
+    }
+}
+
+impl<T, C> Reconnect<T, C>
+where
+    T: super::Transport + Send + Sync,
+    C: Fn() -> io::Result<T> + Send + Sync,
+{
+    /// Current transport, reopening it until it succeeds.
+    async fn connected(&self) -> Arc<T> {
+        use super::Timer as _;
+
+        let mut attempt = 0;
+        loop {
+            if let Some(transport) = self.current() {
+                return transport;
+            }
+            match self.open(|transport, filters| transport.set_filters(filters)) {
+                Ok(transport) => return transport,
+                Err(_) => {
+                    let delay = self.delay(attempt);
+                    attempt += 1;
+                    self.emit(ConnectionState::Reconnecting { attempt, delay });
+                    T::Timer::sleep(delay).await;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl<T, C> crate::blocking::Transport for Reconnect<T, C>
+where
+    T: crate::blocking::Transport,
+    C: Fn() -> io::Result<T>,
+{
+    fn write_frame(&self, frame: &Frame) -> io::Result<()> {
+        loop {
+            // without a deadline reopening only returns once connected
+            let Some(transport) = self.connected_blocking(None) else {
+                continue;
+            };
+            match transport.write_frame(frame) {
+                Err(err) if is_disconnect(&err) => self.disconnect(&transport),
+                result => return result,
+            }
+        }
+    }
+
+    fn read_frame(&self) -> io::Result<Frame> {
+        loop {
+            // without a deadline reopening only returns once connected
+            let Some(transport) = self.connected_blocking(None) else {
+                continue;
+            };
+            match transport.read_frame() {
+                Err(err) if is_disconnect(&err) => self.disconnect(&transport),
+                result => return result,
+            }
+        }
+    }
+
+    fn read_frame_timeout(&self, timeout: Duration) -> io::Result<Option<Frame>> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let Some(transport) = self.connected_blocking(Some(deadline)) else {
+                return Ok(None);
+            };
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match transport.read_frame_timeout(remaining) {
+                Err(err) if is_disconnect(&err) => self.disconnect(&transport),
+                result => return result,
+            }
+        }
+    }
+
+    fn set_filters(&self, filters: &[Filter]) -> io::Result<()> {
+        self.set_filters_with(filters, |transport, filters| transport.set_filters(filters))
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl<T, C> Reconnect<T, C>
+where
+    T: crate::blocking::Transport,
+    C: Fn() -> io::Result<T>,
+{
+    /// Current transport, reopening it until it succeeds or `deadline`
+    /// passes.
+    fn connected_blocking(&self, deadline: Option<Instant>) -> Option<Arc<T>> {
+        let mut attempt = 0;
+        loop {
+            if let Some(transport) = self.current() {
+                return Some(transport);
+            }
+            match self.open(|transport, filters| transport.set_filters(filters)) {
+                Ok(transport) => return Some(transport),
+                Err(_) => {
+                    let mut delay = self.delay(attempt);
+                    if let Some(deadline) = deadline {
+                        let remaining = deadline.saturating_duration_since(Instant::now());
+                        if remaining.is_zero() {
+                            return None;
+                        }
+                        delay = delay.min(remaining);
+                    }
+                    attempt += 1;
+                    self.emit(ConnectionState::Reconnecting { attempt, delay });
+                    std::thread::sleep(delay);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::{Timer, Transport};
+    use embedded_can::StandardId;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    struct NoDelay;
+
+    impl Timer for NoDelay {
+        async fn sleep(_: Duration) {}
+    }
+
+    /// Transport that fails after delivering one frame.
+    struct Flaky(Mutex<u32>);
+
+    impl Transport for Flaky {
+        type Timer = NoDelay;
+
+        async fn write_frame(&self, _: &Frame) -> io::Result<()> {
+            Ok(())
+        }
+
+        async fn read_frame(&self) -> io::Result<Frame> {
+            let mut reads = self.0.lock().unwrap();
+            *reads += 1;
+            if *reads > 1 {
+                return Err(io::ErrorKind::NetworkDown.into());
+            }
+            Ok(Frame::new(StandardId::new(0x029).unwrap(), &[1]).unwrap())
+        }
+    }
+
+    #[test]
+    fn reconnects_after_failure() {
+        let opened = AtomicU32::new(0);
+        let states = Arc::new(Mutex::new(vec![]));
+        let log = states.clone();
+
+        let transport = Reconnect::new(|| {
+            // the first two attempts fail to open
+            if opened.fetch_add(1, Ordering::Relaxed) % 3 < 2 {
+                return Err(io::ErrorKind::NotFound.into());
+            }
+            Ok(Flaky(Mutex::new(0)))
+        })
+        .on_connection_state(move |state| log.lock().unwrap().push(state));
+
+        tokio_test::block_on(async {
+            transport.read_frame().await.unwrap();
+            transport.read_frame().await.unwrap();
+        });
+
+        assert_eq!(opened.load(Ordering::Relaxed), 6);
+        let states = states.lock().unwrap();
+        assert_eq!(
+            states.first(),
+            Some(&ConnectionState::Reconnecting {
+                attempt: 1,
+                delay: Duration::from_millis(100),
+            })
+        );
+        assert!(states.contains(&ConnectionState::Disconnected));
+        assert_eq!(states.last(), Some(&ConnectionState::Connected));
+    }
+
+    #[cfg(feature = "blocking")]
+    #[test]
+    fn blocking_read_timeout_bounds_reopening() {
+        let transport = Reconnect::new(|| -> io::Result<crate::mock::MockBus> {
+            Err(io::ErrorKind::NotFound.into())
+        });
+        let start = Instant::now();
+        let read =
+            crate::blocking::Transport::read_frame_timeout(&transport, Duration::from_millis(50));
+        assert!(read.unwrap().is_none());
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn bus_errors_do_not_reconnect() {
+        assert!(!is_disconnect(&BusState::BusOff.into_error()));
+        assert!(is_disconnect(&io::ErrorKind::BrokenPipe.into()));
+    }
+}
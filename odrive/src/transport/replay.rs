@@ -0,0 +1,194 @@
+//! # Session replay
+//!
+//! [`Replay`] plays a recorded [`Session`] back to the driver. Frames the
+//! session read are returned by [`Transport::read_frame`] in order, and
+//! frames it wrote are expected to be written again. Timestamps are ignored,
+//! a frame read after a write is only returned once the driver repeated
+//! that write, so replays are deterministic and fast:
+//!
+//! ```
+//! # tokio_test::block_on(async {
+//! use odrive::{can::ODrive, middleware::session::Session, transport::replay::Replay};
+//!
+//! let session: Session = "\
+//!     (1718000000.000000) tx 034#R\n\
+//!     (1718000000.000250) rx 034#0000803F00000040\n"
+//!     .parse()
+//!     .unwrap();
+//! let odrive = ODrive::new(Replay::new(session), 1).unwrap();
+//! assert_eq!(odrive.get_iq().await.unwrap(), (1.0, 2.0));
+//! # });
+//! ```
+//!
+//! Writing a frame that differs from the next one the session wrote fails
+//! with [`io::ErrorKind::InvalidData`], reading or writing past the end of
+//! the session fails with [`io::ErrorKind::UnexpectedEof`]. Together with
+//! the [`NoTimeout`] timer a diverging driver fails instead of waiting for
+//! frames that will never come, which turns field sessions into regression
+//! tests.
+
+use super::{Frame, Timer, Transport};
+use crate::middleware::session::Session;
+use crate::middleware::{blackbox::Direction, candump::format_frame};
+use std::{
+    collections::VecDeque,
+    future::poll_fn,
+    io,
+    sync::{Mutex, PoisonError},
+    task::{Poll, Waker},
+    time::Duration,
+};
+
+/// Timer that never expires, requests never time out.
+#[derive(Debug, Clone, Copy)]
+pub struct NoTimeout;
+
+impl Timer for NoTimeout {
+    async fn sleep(_: Duration) {
+        std::future::pending().await
+    }
+}
+
+#[derive(Default)]
+struct State {
+    entries: VecDeque<(Direction, Frame)>,
+    wakers: Vec<Waker>,
+}
+
+/// Transport replaying a [`Session`].
+pub struct Replay {
+    state: Mutex<State>,
+}
+
+impl Replay {
+    pub fn new(session: Session) -> Self {
+        Self {
+            state: Mutex::new(State {
+                entries: session
+                    .entries
+                    .into_iter()
+                    .map(|entry| (entry.direction, entry.frame))
+                    .collect(),
+                wakers: Vec::new(),
+            }),
+        }
+    }
+
+    /// Number of frames not yet read or written.
+    pub fn remaining(&self) -> usize {
+        self.lock().entries.len()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Consume the next written frame of the session if it is `frame`.
+    ///
+    /// Frames read before it stay to be read.
+    fn write(&self, frame: &Frame) -> io::Result<()> {
+        let mut state = self.lock();
+        let Some(next) = state
+            .entries
+            .iter()
+            .position(|(direction, _)| *direction == Direction::Tx)
+        else {
+            return Err(eof());
+        };
+        if state.entries[next].1 != *frame {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "replay wrote {}, session wrote {}",
+                    format_frame(frame),
+                    format_frame(&state.entries[next].1)
+                ),
+            ));
+        }
+        state.entries.remove(next);
+        // a read may be waiting for this write
+        state.wakers.drain(..).for_each(Waker::wake);
+        Ok(())
+    }
+
+    /// Next read frame if it is not preceded by a write.
+    fn read(&self, waker: Option<&Waker>) -> Poll<io::Result<Frame>> {
+        let mut state = self.lock();
+        match state.entries.front() {
+            None => Poll::Ready(Err(eof())),
+            Some((Direction::Rx, frame)) => {
+                let frame = *frame;
+                state.entries.pop_front();
+                Poll::Ready(Ok(frame))
+            }
+            Some((Direction::Tx, _)) => {
+                if let Some(waker) = waker {
+                    state.wakers.push(waker.clone());
+                }
+                Poll::Pending
+            }
+        }
+    }
+}
+
+fn eof() -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "end of replayed session")
+}
+
+impl Transport for Replay {
+    type Timer = NoTimeout;
+
+    async fn write_frame(&self, frame: &Frame) -> io::Result<()> {
+        self.write(frame)
+    }
+
+    async fn read_frame(&self) -> io::Result<Frame> {
+        poll_fn(|cx| self.read(Some(cx.waker()))).await
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl crate::blocking::Transport for Replay {
+    fn write_frame(&self, frame: &Frame) -> io::Result<()> {
+        self.write(frame)
+    }
+
+    /// Fails with [`io::ErrorKind::WouldBlock`] instead of waiting for a
+    /// write from another thread.
+    fn read_frame(&self) -> io::Result<Frame> {
+        match self.read(None) {
+            Poll::Ready(result) => result,
+            Poll::Pending => Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                "replayed session writes before reading",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::can::ODrive;
+
+    #[test]
+    fn diverging_driver() {
+        let session: Session = "\
+            (0.000000) rx 021#0000000001000000\n\
+            (0.000100) tx 037#R\n\
+            (0.000200) rx 037#0000C0410000A040\n"
+            .parse()
+            .unwrap();
+        let odrive = ODrive::new(Replay::new(session.clone()), 1).unwrap();
+        let bus = tokio_test::block_on(odrive.get_bus_voltage_current()).unwrap();
+        assert_eq!((bus.voltage, bus.current), (24.0, 5.0));
+        assert!(odrive.cache().latest_heartbeat().is_some());
+        assert!(tokio_test::block_on(odrive.get_iq()).is_err());
+
+        let odrive = ODrive::new(Replay::new(session), 1).unwrap();
+        let err = tokio_test::block_on(odrive.get_iq()).unwrap_err();
+        assert!(
+            matches!(err, crate::Error::Transport(err) if err.kind() == io::ErrorKind::InvalidData)
+        );
+    }
+}
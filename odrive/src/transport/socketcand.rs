@@ -0,0 +1,195 @@
+//! # socketcand transport
+//!
+//! Access a CAN bus on a remote machine through a
+//! [`socketcand`](https://github.com/linux-can/socketcand) daemon over TCP.
+//!
+//! The daemon is used in raw mode. socketcand has no notation for remote
+//! frames so they are sent as zero-length data frames, which ODrives treat as
+//! a request in the same way.
+//!
+//! ```no_run
+//! # tokio_test::block_on(async {
+//! use odrive::{can::ODrive, transport::socketcand::SocketCand};
+//!
+//! let transport = SocketCand::connect("192.168.1.10:29536", "can0").await.unwrap();
+//! let odrive = ODrive::new(transport, 1).unwrap();
+//! # });
+//! ```
+
+use super::{Frame, TokioTimer, Transport};
+use embedded_can::{ExtendedId, Id, StandardId};
+use std::io;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{
+        TcpStream, ToSocketAddrs,
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+    },
+    sync::Mutex,
+};
+
+/// Transport using a remote socketcand daemon.
+pub struct SocketCand {
+    reader: Mutex<BufReader<OwnedReadHalf>>,
+    writer: Mutex<OwnedWriteHalf>,
+}
+
+impl SocketCand {
+    /// Connect to a socketcand daemon and open `interface` in raw mode.
+    pub async fn connect(addr: impl ToSocketAddrs, interface: &str) -> io::Result<Self> {
+        let stream = TcpStream::connect(addr).await?;
+        stream.set_nodelay(true)?;
+        let (reader, writer) = stream.into_split();
+
+        let transport = Self {
+            reader: Mutex::new(BufReader::new(reader)),
+            writer: Mutex::new(writer),
+        };
+
+        transport.expect("hi").await?;
+        transport.command(&format!("open {interface}")).await?;
+        transport.expect("ok").await?;
+        transport.command("rawmode").await?;
+        transport.expect("ok").await?;
+
+        Ok(transport)
+    }
+
+    async fn command(&self, command: &str) -> io::Result<()> {
+        let mut writer = self.writer.lock().await;
+        writer.write_all(format!("< {command} >").as_bytes()).await
+    }
+
+    /// Read the next `< ... >` element without the angle brackets.
+    async fn element(&self) -> io::Result<String> {
+        let mut reader = self.reader.lock().await;
+
+        loop {
+            let mut buf = vec![];
+            if reader.read_until(b'>', &mut buf).await? == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+
+            let text = String::from_utf8_lossy(&buf);
+            if let Some((_, element)) = text.rsplit_once('<') {
+                return Ok(element.trim_end_matches('>').trim().to_owned());
+            }
+        }
+    }
+
+    async fn expect(&self, expected: &str) -> io::Result<()> {
+        let element = self.element().await?;
+        if element == expected {
+            Ok(())
+        } else {
+            Err(io::Error::other(format!(
+                "Unexpected socketcand response: < {element} >"
+            )))
+        }
+    }
+}
+
+impl Transport for SocketCand {
+    type Timer = TokioTimer;
+
+    async fn write_frame(&self, frame: &Frame) -> io::Result<()> {
+        if frame.is_fd() {
+            return Err(super::fd_unsupported());
+        }
+        self.command(&format_send(frame)).await
+    }
+
+    async fn read_frame(&self) -> io::Result<Frame> {
+        loop {
+            let element = self.element().await?;
+            if let Some(frame) = parse_frame(&element) {
+                return Ok(frame);
+            }
+            if let Some(error) = element.strip_prefix("error") {
+                return Err(io::Error::other(format!(
+                    "socketcand error: {}",
+                    error.trim()
+                )));
+            }
+        }
+    }
+}
+
+/// Format a frame as a socketcand `send` command.
+fn format_send(frame: &Frame) -> String {
+    let mut command = match frame.id() {
+        Id::Standard(id) => format!("send {:03X} {}", id.as_raw(), frame.data().len()),
+        Id::Extended(id) => format!("send {:08X} {}", id.as_raw(), frame.data().len()),
+    };
+
+    for byte in frame.data() {
+        command.push_str(&format!(" {byte:02X}"));
+    }
+
+    command
+}
+
+/// Parse a socketcand `frame` element.
+///
+/// Data bytes are accepted both space separated and concatenated.
+fn parse_frame(element: &str) -> Option<Frame> {
+    let mut parts = element.split_whitespace();
+    if parts.next()? != "frame" {
+        return None;
+    }
+
+    let id = parts.next()?;
+    let raw = u32::from_str_radix(id, 16).ok()?;
+    let id = if id.len() > 3 {
+        Id::Extended(ExtendedId::new(raw)?)
+    } else {
+        Id::Standard(StandardId::new(raw as u16)?)
+    };
+
+    // timestamp
+    parts.next()?;
+
+    let hex: String = parts.collect();
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+
+    let mut data = vec![];
+    for i in (0..hex.len()).step_by(2) {
+        data.push(u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()?);
+    }
+
+    Frame::new(id, &data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_send_command() {
+        let id = StandardId::new(0x029).unwrap();
+        let frame = Frame::new(id, &[0x01, 0xab]).unwrap();
+        assert_eq!(format_send(&frame), "send 029 2 01 AB");
+
+        let frame = Frame::new_remote(id, 8).unwrap();
+        assert_eq!(format_send(&frame), "send 029 0");
+    }
+
+    #[test]
+    fn parse_frame_element() {
+        let frame = parse_frame("frame 029 23.424242 11223344").unwrap();
+        assert_eq!(frame.id(), StandardId::new(0x029).unwrap().into());
+        assert_eq!(frame.data(), &[0x11, 0x22, 0x33, 0x44]);
+
+        let frame = parse_frame("frame 1AAAAAAA 23.424242 11 22").unwrap();
+        assert_eq!(frame.id(), ExtendedId::new(0x1AAAAAAA).unwrap().into());
+        assert_eq!(frame.data(), &[0x11, 0x22]);
+
+        let frame = parse_frame("frame 123 1.0").unwrap();
+        assert!(frame.data().is_empty());
+
+        assert!(parse_frame("ok").is_none());
+        assert!(parse_frame("frame 029 1.0 123").is_none());
+    }
+}
@@ -0,0 +1,42 @@
+//! Compiles and runs the accessors generated from
+//! `codegen/flat_endpoints.json`, a `flat_endpoints::codegen` unit test
+//! checks that `codegen/endpoints.rs` is current.
+
+use odrive::{
+    can::ODrive,
+    mock::{MockBus, MockODrive},
+};
+use std::sync::Arc;
+
+#[allow(dead_code)]
+mod endpoints {
+    include!("codegen/endpoints.rs");
+}
+
+#[test]
+fn generated_accessors() {
+    let node = Arc::new(MockODrive::new(1));
+    node.update(|state| {
+        state.endpoints.insert(1, 24.0f32.to_le_bytes());
+    });
+    let odrive = ODrive::new(MockBus::new().with_node(node.clone()), 1).unwrap();
+    let device = endpoints::Device(&odrive);
+
+    tokio_test::block_on(async {
+        assert_eq!(device.vbus_voltage().get().await.unwrap(), 24.0);
+        let vel_limit = device.axis0().controller().config().vel_limit();
+        vel_limit.set(10.0).await.unwrap();
+        device.axis0().config().r#type().set(2).await.unwrap();
+        device.self_().r#move().set(true).await.unwrap();
+        device.self__().set(-1).await.unwrap();
+        // the read-only endpoint named like the `controller` namespace
+        assert!(device.axis0().controller_().set(1).await.is_err());
+    });
+
+    let endpoints = node.state().endpoints;
+    assert_eq!(endpoints[&2], 10.0f32.to_le_bytes());
+    assert_eq!(endpoints[&3], [2, 0, 0, 0]);
+    assert_eq!(endpoints[&7], [1, 0, 0, 0]);
+    assert_eq!(endpoints[&8], [0xff, 0xff, 0, 0]);
+    assert_eq!(device.axis0().controller_().endpoint().id(), 4);
+}
@@ -0,0 +1,96 @@
+// Generated by odrive::flat_endpoints::codegen, do not edit.
+
+use ::odrive::can::ODrive;
+use ::odrive::flat_endpoints::{Access, Endpoint, codegen::Bound};
+use ::odrive::transport::Transport;
+
+/// Root of the endpoints tree.
+#[allow(non_camel_case_types)]
+pub struct Device<'a, T>(pub &'a ODrive<T>);
+
+impl<'a, T: Transport> Device<'a, T> {
+    pub fn axis0(&self) -> Axis0<'a, T> {
+        Axis0(self.0)
+    }
+    pub fn device(&self) -> Device_<'a, T> {
+        Device_(self.0)
+    }
+    pub fn self_(&self) -> Self_<'a, T> {
+        Self_(self.0)
+    }
+    pub fn self__(&self) -> Bound<'a, T, i16> {
+        Bound::new(self.0, Endpoint::new(8, Access::ReadWrite))
+    }
+    pub fn vbus_voltage(&self) -> Bound<'a, T, f32> {
+        Bound::new(self.0, Endpoint::new(1, Access::Read))
+    }
+}
+
+/// `axis0`
+#[allow(non_camel_case_types)]
+pub struct Axis0<'a, T>(&'a ODrive<T>);
+
+impl<'a, T: Transport> Axis0<'a, T> {
+    pub fn config(&self) -> Axis0_Config<'a, T> {
+        Axis0_Config(self.0)
+    }
+    pub fn controller(&self) -> Axis0_Controller<'a, T> {
+        Axis0_Controller(self.0)
+    }
+    pub fn controller_(&self) -> Bound<'a, T, u32> {
+        Bound::new(self.0, Endpoint::new(4, Access::Read))
+    }
+    pub fn requested_state(&self) -> Bound<'a, T, u32> {
+        Bound::new(self.0, Endpoint::new(5, Access::Write))
+    }
+}
+
+/// `axis0.config`
+#[allow(non_camel_case_types)]
+pub struct Axis0_Config<'a, T>(&'a ODrive<T>);
+
+impl<'a, T: Transport> Axis0_Config<'a, T> {
+    pub fn r#type(&self) -> Bound<'a, T, u8> {
+        Bound::new(self.0, Endpoint::new(3, Access::ReadWrite))
+    }
+}
+
+/// `axis0.controller`
+#[allow(non_camel_case_types)]
+pub struct Axis0_Controller<'a, T>(&'a ODrive<T>);
+
+impl<'a, T: Transport> Axis0_Controller<'a, T> {
+    pub fn config(&self) -> Axis0_Controller_Config<'a, T> {
+        Axis0_Controller_Config(self.0)
+    }
+}
+
+/// `axis0.controller.config`
+#[allow(non_camel_case_types)]
+pub struct Axis0_Controller_Config<'a, T>(&'a ODrive<T>);
+
+impl<'a, T: Transport> Axis0_Controller_Config<'a, T> {
+    pub fn vel_limit(&self) -> Bound<'a, T, f32> {
+        Bound::new(self.0, Endpoint::new(2, Access::ReadWrite))
+    }
+}
+
+/// `device`
+#[allow(non_camel_case_types)]
+pub struct Device_<'a, T>(&'a ODrive<T>);
+
+impl<'a, T: Transport> Device_<'a, T> {
+    pub fn serial_number(&self) -> Bound<'a, T, u32> {
+        Bound::new(self.0, Endpoint::new(6, Access::Read))
+    }
+}
+
+/// `self`
+#[allow(non_camel_case_types)]
+pub struct Self_<'a, T>(&'a ODrive<T>);
+
+impl<'a, T: Transport> Self_<'a, T> {
+    pub fn r#move(&self) -> Bound<'a, T, bool> {
+        Bound::new(self.0, Endpoint::new(7, Access::ReadWrite))
+    }
+}
@@ -0,0 +1,40 @@
+//! Conformance suite on real hardware.
+//!
+//! Ignored by default, run with:
+//!
+//! ```sh
+//! ODRIVE_INTERFACE=can0 ODRIVE_NODE=1 cargo test -p odrive --features hw-tests --test hw -- --ignored --nocapture
+//! ```
+//!
+//! `ODRIVE_SDO_ENDPOINT` additionally reads an endpoint with SDO.
+
+use odrive::{can::ODrive, conformance::Conformance, protocol::ValueKind};
+
+fn var<T: std::str::FromStr>(name: &str, default: Option<T>) -> Option<T> {
+    match std::env::var(name) {
+        Ok(value) => Some(
+            value
+                .parse()
+                .unwrap_or_else(|_| panic!("invalid {name}: {value}")),
+        ),
+        Err(_) => default,
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs an ODrive"]
+async fn conformance() {
+    let interface = var("ODRIVE_INTERFACE", Some("can0".to_string())).unwrap();
+    let node = var("ODRIVE_NODE", Some(0)).unwrap();
+
+    let socket = socketcan::tokio::CanSocket::open(&interface).unwrap();
+    let odrive = ODrive::new(socket, node).unwrap();
+    let mut conformance = Conformance::new();
+    if let Some(endpoint) = var("ODRIVE_SDO_ENDPOINT", None) {
+        conformance = conformance.with_sdo(endpoint, ValueKind::U32);
+    }
+
+    let report = conformance.run(&odrive).await;
+    print!("{report}");
+    assert!(report.is_ok(), "commands failed on {interface} node {node}");
+}